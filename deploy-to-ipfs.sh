@@ -8,13 +8,10 @@ echo "🚀 Deploying KPGB to IPFS..."
 echo "📦 Building static site..."
 cargo run generate
 
-# Add to IPFS
+# Add to IPFS, pin and publish to IPNS (recorded in `kpgb deploy history`)
 echo "📤 Uploading to IPFS..."
-IPFS_HASH=$(ipfs add -r ./public --quieter)
-
-echo "✅ Site deployed to IPFS!"
-echo "📌 IPFS Hash: $IPFS_HASH"
-echo "🌐 IPFS Gateway URL: https://ipfs.io/ipfs/$IPFS_HASH"
+cargo run deploy ipfs || exit 1
+IPFS_HASH=$(cargo run -q deploy history --limit 1 | grep '^#' | awk '{print $4}')
 echo ""
 echo "🔗 To set this as your .sol domain's IPFS record:"
 echo "   1. Go to https://v1.sns.id"
@@ -0,0 +1,9 @@
+-- Storage IDs a post was previously stored under, so old links keep resolving
+CREATE TABLE IF NOT EXISTS post_storage_aliases (
+    storage_id TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_post_storage_aliases_post_id ON post_storage_aliases(post_id);
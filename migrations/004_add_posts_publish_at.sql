@@ -0,0 +1,4 @@
+-- Scheduled publishing: a post counts as published once publish_at has passed
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMP;
+
+CREATE INDEX idx_posts_publish_at ON posts(publish_at);
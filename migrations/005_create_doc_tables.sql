@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS doc_categories (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    description TEXT,
+    order_index INTEGER DEFAULT 0,
+    icon TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS doc_sections (
+    id TEXT PRIMARY KEY,
+    category_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    content TEXT NOT NULL,
+    parent_id TEXT,
+    order_index INTEGER DEFAULT 0,
+    source_url TEXT,
+    is_translated BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (category_id) REFERENCES doc_categories(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_id) REFERENCES doc_sections(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_doc_sections_category ON doc_sections(category_id);
+CREATE INDEX idx_doc_sections_parent ON doc_sections(parent_id);
@@ -0,0 +1,6 @@
+-- Translation tracking: the upstream text a translation follows, and whether
+-- it changed since the translation was last updated
+ALTER TABLE doc_sections ADD COLUMN source_text TEXT;
+ALTER TABLE doc_sections ADD COLUMN source_hash TEXT;
+ALTER TABLE doc_sections ADD COLUMN source_synced_at TIMESTAMP;
+ALTER TABLE doc_sections ADD COLUMN is_stale BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- Multilingual posts: the post language and the slug of the post it translates
+ALTER TABLE posts ADD COLUMN lang TEXT;
+ALTER TABLE posts ADD COLUMN translation_of TEXT;
+
+CREATE INDEX idx_posts_translation_of ON posts(translation_of);
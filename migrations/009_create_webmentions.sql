@@ -0,0 +1,34 @@
+-- Webmentions received for our posts
+CREATE TABLE IF NOT EXISTS webmentions (
+    id TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    post_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    kind TEXT NOT NULL DEFAULT 'mention',
+    author_name TEXT,
+    author_url TEXT,
+    author_photo TEXT,
+    content TEXT,
+    created_at TIMESTAMP NOT NULL,
+    verified_at TIMESTAMP,
+    UNIQUE(source, target),
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_webmentions_post_id ON webmentions(post_id, status);
+
+-- Outbound links of published posts, collected by the generator and
+-- notified by `kpgb webmention send`
+CREATE TABLE IF NOT EXISTS webmention_links (
+    post_id TEXT NOT NULL,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    endpoint TEXT,
+    error TEXT,
+    collected_at TIMESTAMP NOT NULL,
+    sent_at TIMESTAMP,
+    PRIMARY KEY (post_id, target),
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,19 @@
+-- Comments left on posts through the built-in comment form. Replies point
+-- at their parent; new comments wait in the moderation queue as 'pending'.
+CREATE TABLE IF NOT EXISTS comments (
+    id TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL,
+    parent_id TEXT,
+    author_name TEXT NOT NULL,
+    author_email TEXT,
+    author_url TEXT,
+    content TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL,
+    approved_at TIMESTAMP,
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_id) REFERENCES comments(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_comments_post_id ON comments(post_id, status);
+CREATE INDEX idx_comments_parent_id ON comments(parent_id);
@@ -0,0 +1,19 @@
+-- The key pair the blog's ActivityPub actor signs its requests with
+CREATE TABLE IF NOT EXISTS activitypub_keys (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    private_key_pem TEXT NOT NULL,
+    public_key_pem TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- Fediverse accounts following the blog
+CREATE TABLE IF NOT EXISTS activitypub_followers (
+    actor_id TEXT PRIMARY KEY,
+    inbox TEXT NOT NULL,
+    shared_inbox TEXT,
+    followed_at TIMESTAMP NOT NULL
+);
+
+-- Replies that arrived through the inbox keep the ID of their Note
+ALTER TABLE comments ADD COLUMN remote_id TEXT;
+CREATE UNIQUE INDEX idx_comments_remote_id ON comments(remote_id);
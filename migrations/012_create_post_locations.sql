@@ -0,0 +1,17 @@
+-- Every backend a stored post version was written to, so reads can fall
+-- back to a mirror and `kpgb storage verify` can re-check the copies
+CREATE TABLE IF NOT EXISTS post_locations (
+    storage_id TEXT NOT NULL,
+    post_id TEXT NOT NULL,
+    backend TEXT NOT NULL,
+    location TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'ok',
+    created_at TIMESTAMP NOT NULL,
+    verified_at TIMESTAMP,
+    PRIMARY KEY (storage_id, backend),
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_post_locations_post_id ON post_locations(post_id);
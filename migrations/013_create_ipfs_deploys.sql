@@ -0,0 +1,14 @@
+-- Every root CID the generated site was deployed to IPFS as, newest last
+CREATE TABLE IF NOT EXISTS ipfs_deploys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    cid TEXT NOT NULL,
+    ipns_key TEXT,
+    ipns_name TEXT,
+    files INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    -- The deploy this one went back to, for rollbacks
+    rollback_of INTEGER REFERENCES ipfs_deploys(id),
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_ipfs_deploys_cid ON ipfs_deploys(cid);
@@ -0,0 +1,20 @@
+-- posts_fts takes its content from posts, so removing a row has to pass the
+-- old values through the 'delete' command; a plain DELETE reads the already
+-- updated row and corrupts the index
+DROP TRIGGER IF EXISTS posts_ad;
+DROP TRIGGER IF EXISTS posts_au;
+
+CREATE TRIGGER posts_ad AFTER DELETE ON posts BEGIN
+    INSERT INTO posts_fts(posts_fts, rowid, title, content, excerpt, author)
+    VALUES ('delete', old.rowid, old.title, old.content, old.excerpt, old.author);
+END;
+
+CREATE TRIGGER posts_au AFTER UPDATE ON posts BEGIN
+    INSERT INTO posts_fts(posts_fts, rowid, title, content, excerpt, author)
+    VALUES ('delete', old.rowid, old.title, old.content, old.excerpt, old.author);
+    INSERT INTO posts_fts(rowid, title, content, excerpt, author)
+    VALUES (new.rowid, new.title, new.content, new.excerpt, new.author);
+END;
+
+-- Indexes damaged by the old triggers
+INSERT INTO posts_fts(posts_fts) VALUES ('rebuild');
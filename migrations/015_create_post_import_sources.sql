@@ -0,0 +1,8 @@
+-- Hashes of imported markdown files, front matter included, so re-importing
+-- an unchanged file is skipped before its images are uploaded again
+CREATE TABLE IF NOT EXISTS post_import_sources (
+    source_hash TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL,
+    imported_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (post_id) REFERENCES posts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- The file a post was imported from, so re-importing it after its slug
+-- changed updates the post instead of creating another one
+ALTER TABLE post_import_sources ADD COLUMN source_path TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_post_import_sources_path ON post_import_sources(source_path);
@@ -0,0 +1,536 @@
+pub mod objects;
+pub mod signature;
+
+use anyhow::{anyhow, bail, Result};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::models::BlogPost;
+use crate::site::SiteConfig;
+use crate::webmention::receive::is_public_url;
+use objects::{Actor, CONTENT_TYPE};
+use signature::{KeyPair, SignatureHeader};
+
+/// An account following the blog
+#[derive(Debug, Clone, Serialize)]
+pub struct Follower {
+    pub actor_id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+    pub followed_at: DateTime<Utc>,
+}
+
+/// Followers of the blog actor, in the blog database
+#[derive(Clone)]
+pub struct FollowerStore {
+    pool: SqlitePool,
+}
+
+impl FollowerStore {
+    /// Tables are created by the migrations run in `Database::new`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Add a follower, or update its inboxes when it follows again
+    pub async fn add(&self, actor_id: &str, inbox: &str, shared_inbox: Option<&str>) -> Result<()> {
+        query(
+            r#"
+            INSERT INTO activitypub_followers (actor_id, inbox, shared_inbox, followed_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(actor_id) DO UPDATE SET
+                inbox = excluded.inbox, shared_inbox = excluded.shared_inbox
+            "#,
+        )
+        .bind(actor_id)
+        .bind(inbox)
+        .bind(shared_inbox)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns whether the actor was following
+    pub async fn remove(&self, actor_id: &str) -> Result<bool> {
+        let result = query("DELETE FROM activitypub_followers WHERE actor_id = ?1")
+            .bind(actor_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list(&self) -> Result<Vec<Follower>> {
+        let rows = query("SELECT * FROM activitypub_followers ORDER BY followed_at")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(Self::row_to_follower).collect())
+    }
+
+    /// Inboxes to deliver to: one per server when it has a shared inbox
+    pub async fn delivery_inboxes(&self) -> Result<Vec<String>> {
+        let mut inboxes: Vec<String> = Vec::new();
+        for follower in self.list().await? {
+            let inbox = follower.shared_inbox.unwrap_or(follower.inbox);
+            if !inboxes.contains(&inbox) {
+                inboxes.push(inbox);
+            }
+        }
+        Ok(inboxes)
+    }
+
+    fn row_to_follower(row: &SqliteRow) -> Follower {
+        Follower {
+            actor_id: row.get("actor_id"),
+            inbox: row.get("inbox"),
+            shared_inbox: row.get("shared_inbox"),
+            followed_at: row.get("followed_at"),
+        }
+    }
+}
+
+/// The blog's presence on the fediverse: its actor, signing key and
+/// followers
+#[derive(Clone)]
+pub struct Federation {
+    actor: Actor,
+    keys: Arc<KeyPair>,
+    public_key_pem: String,
+    followers: FollowerStore,
+    client: reqwest::Client,
+}
+
+impl Federation {
+    /// Load the actor's key pair, creating it the first time
+    pub async fn new(pool: SqlitePool, config: &SiteConfig) -> Result<Self> {
+        let (keys, public_key_pem) = load_or_create_keys(&pool).await?;
+        let redirect = reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= 5 || !is_public_url(attempt.url()) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(redirect)
+            .user_agent(concat!(
+                "kpgb/",
+                env!("CARGO_PKG_VERSION"),
+                " (activitypub)"
+            ))
+            .build()?;
+
+        Ok(Self {
+            actor: Actor::from_config(config),
+            keys: Arc::new(keys),
+            public_key_pem,
+            followers: FollowerStore::new(pool),
+            client,
+        })
+    }
+
+    /// Talk to other servers with `client`, e.g. one resolving test hosts to a local stub
+    #[cfg(test)]
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn actor(&self) -> &Actor {
+        &self.actor
+    }
+
+    pub fn actor_json(&self) -> Value {
+        self.actor.to_json(&self.public_key_pem)
+    }
+
+    pub fn followers(&self) -> &FollowerStore {
+        &self.followers
+    }
+
+    /// Sign an activity and POST it to an inbox
+    pub async fn send(&self, inbox: &str, activity: &Value) -> Result<()> {
+        let url = Url::parse(inbox)?;
+        if !is_public_url(&url) {
+            bail!("Inbox is not a public URL: {}", inbox);
+        }
+        let body = serde_json::to_vec(activity)?;
+        let headers =
+            signature::sign_request(&self.keys, &self.actor.key_id(), "POST", &url, Some(&body))?;
+
+        let mut request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Send an activity to every follower. Returns how many inboxes took it;
+    /// failures are logged.
+    pub async fn deliver(&self, activity: &Value) -> Result<usize> {
+        let inboxes = self.followers.delivery_inboxes().await?;
+        let results = join_all(inboxes.iter().map(|inbox| self.send(inbox, activity))).await;
+
+        let mut delivered = 0;
+        for (inbox, result) in inboxes.iter().zip(results) {
+            match result {
+                Ok(()) => delivered += 1,
+                Err(e) => warn!("Delivery to {} failed: {}", inbox, e),
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Deliver `Create{Article}` for a newly published post
+    pub async fn announce_post(&self, post: &BlogPost) -> Result<usize> {
+        self.deliver(&self.actor.create(post)).await
+    }
+
+    /// Fetch a remote object with a signed GET, for servers that require one
+    pub async fn fetch(&self, id: &str) -> Result<Value> {
+        let url = Url::parse(id)?;
+        if !is_public_url(&url) {
+            bail!("Not a public URL: {}", id);
+        }
+        let headers = signature::sign_request(&self.keys, &self.actor.key_id(), "GET", &url, None)?;
+
+        let mut request = self.client.get(url).header(
+            reqwest::header::ACCEPT,
+            r#"application/activity+json, application/ld+json; profile="https://www.w3.org/ns/activitystreams""#,
+        );
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    /// Check the HTTP signature of a request to the inbox. Returns the
+    /// document of the actor that signed it.
+    pub async fn verify_request(
+        &self,
+        method: &str,
+        path_and_query: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Value> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let signature = SignatureHeader::parse(
+            header("signature").ok_or_else(|| anyhow!("Request is not signed"))?,
+        )?;
+        signature::check_request(&signature, header("date"), header("digest"), body)?;
+        let data = signature::signing_string(method, path_and_query, &signature.headers, header)?;
+
+        // Documents must be served from their own ID, or anyone could host
+        // a copy of someone else's actor carrying their own key
+        let key_url = without_fragment(&signature.key_id);
+        let mut actor = self.fetch(key_url).await?;
+        if actor["id"].as_str().map(without_fragment) != Some(key_url) {
+            bail!("{} isn't the document of key {}", key_url, signature.key_id);
+        }
+        // The key may be published on its own, pointing at its owner
+        if actor.get("publicKeyPem").is_some() {
+            let owner = without_fragment(
+                actor["owner"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("Key has no owner"))?,
+            )
+            .to_string();
+            actor = self.fetch(&owner).await?;
+            if actor["id"] != owner.as_str() {
+                bail!("Actor fetched from {} has another ID", owner);
+            }
+        }
+        let actor_id = actor["id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Actor has no ID"))?;
+        if host_of(actor_id)? != host_of(&signature.key_id)? {
+            bail!(
+                "Key {} is on another host than {}",
+                signature.key_id,
+                actor_id
+            );
+        }
+
+        let keys = match &actor["publicKey"] {
+            Value::Array(keys) => keys.clone(),
+            key => vec![key.clone()],
+        };
+        let key = keys
+            .iter()
+            .find(|key| key["id"] == signature.key_id.as_str())
+            .ok_or_else(|| anyhow!("Actor has no key {}", signature.key_id))?;
+        if key["owner"] != actor_id {
+            bail!("Key {} isn't owned by {}", signature.key_id, actor_id);
+        }
+        let pem = key["publicKeyPem"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Key has no PEM"))?;
+
+        if !signature::verify(pem, &data, &signature.signature)? {
+            bail!("Signature doesn't match");
+        }
+        Ok(actor)
+    }
+}
+
+fn without_fragment(url: &str) -> &str {
+    url.split('#').next().unwrap_or(url)
+}
+
+fn host_of(url: &str) -> Result<String> {
+    Url::parse(url)?
+        .host_str()
+        .map(str::to_ascii_lowercase)
+        .ok_or_else(|| anyhow!("{} has no host", url))
+}
+
+async fn load_or_create_keys(pool: &SqlitePool) -> Result<(KeyPair, String)> {
+    let select = "SELECT private_key_pem, public_key_pem FROM activitypub_keys WHERE id = 1";
+    if let Some(row) = query(select).fetch_optional(pool).await? {
+        let keys = KeyPair::from_pem(&row.get::<String, _>("private_key_pem"))?;
+        return Ok((keys, row.get("public_key_pem")));
+    }
+
+    let keys = KeyPair::generate()?;
+    let public_key_pem = keys.public_key_pem()?;
+    query(
+        r#"
+        INSERT OR IGNORE INTO activitypub_keys (id, private_key_pem, public_key_pem, created_at)
+        VALUES (1, ?1, ?2, ?3)
+        "#,
+    )
+    .bind(keys.private_key_pem()?)
+    .bind(&public_key_pem)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    // Another process may have created the keys first
+    let row = query(select).fetch_one(pool).await?;
+    let keys = KeyPair::from_pem(&row.get::<String, _>("private_key_pem"))?;
+    Ok((keys, row.get("public_key_pem")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    #[tokio::test]
+    async fn test_keys_and_followers() {
+        let path =
+            std::env::temp_dir().join(format!("kpgb-activitypub-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let database = Database::new(&url).await.unwrap();
+        let pool = database.pool().clone();
+
+        let federation = Federation::new(pool.clone(), &SiteConfig::default())
+            .await
+            .unwrap();
+        let again = Federation::new(pool.clone(), &SiteConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(federation.public_key_pem, again.public_key_pem);
+        assert_eq!(
+            federation.actor_json()["publicKey"]["publicKeyPem"],
+            federation.public_key_pem.as_str()
+        );
+
+        let followers = federation.followers();
+        followers
+            .add(
+                "https://a.example/users/1",
+                "https://a.example/users/1/inbox",
+                Some("https://a.example/inbox"),
+            )
+            .await
+            .unwrap();
+        followers
+            .add(
+                "https://a.example/users/2",
+                "https://a.example/users/2/inbox",
+                Some("https://a.example/inbox"),
+            )
+            .await
+            .unwrap();
+        followers
+            .add("https://b.example/u", "https://b.example/u/inbox", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            followers.delivery_inboxes().await.unwrap(),
+            vec!["https://a.example/inbox", "https://b.example/u/inbox"]
+        );
+
+        assert!(followers.remove("https://b.example/u").await.unwrap());
+        assert!(!followers.remove("https://b.example/u").await.unwrap());
+        assert_eq!(followers.list().await.unwrap().len(), 2);
+        std::fs::remove_file(&path).ok();
+    }
+
+    type Documents = Arc<std::sync::Mutex<std::collections::HashMap<String, Value>>>;
+
+    /// Serve JSON documents by path on a local port, for hosts `good.example`
+    /// and `evil.example`. Returns the documents, a client resolving both
+    /// hosts to the port, and a function making URLs on it.
+    async fn document_server() -> (Documents, reqwest::Client, impl Fn(&str, &str) -> String) {
+        use axum::response::IntoResponse;
+
+        let documents = Documents::default();
+        let served = documents.clone();
+        let app = axum::Router::new().fallback(move |uri: axum::http::Uri| {
+            let document = served.lock().unwrap().get(uri.path()).cloned();
+            async move {
+                match document {
+                    Some(document) => axum::Json(document).into_response(),
+                    None => axum::http::StatusCode::NOT_FOUND.into_response(),
+                }
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .resolve("good.example", addr)
+            .resolve("evil.example", addr)
+            .build()
+            .unwrap();
+        let url = move |host: &str, path: &str| format!("http://{}:{}{}", host, addr.port(), path);
+        (documents, client, url)
+    }
+
+    fn actor(id: &str, key_id: &str, owner: &str, keys: &KeyPair) -> Value {
+        serde_json::json!({
+            "id": id,
+            "type": "Person",
+            "inbox": format!("{}/inbox", id),
+            "publicKey": {
+                "id": key_id,
+                "owner": owner,
+                "publicKeyPem": keys.public_key_pem().unwrap(),
+            }
+        })
+    }
+
+    /// Sign a POST to the inbox with `keys` and check it
+    async fn verify(federation: &Federation, keys: &KeyPair, key_id: &str) -> Result<Value> {
+        let body = br#"{"type":"Follow"}"#;
+        let url = Url::parse("https://blog.example/activitypub/inbox").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "blog.example".parse().unwrap());
+        for (name, value) in
+            signature::sign_request(keys, key_id, "POST", &url, Some(body)).unwrap()
+        {
+            headers.insert(name, value.parse().unwrap());
+        }
+        federation
+            .verify_request("POST", "/activitypub/inbox", &headers, body)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_verify_request_checks_actor() {
+        let path = std::env::temp_dir().join(format!("kpgb-verify-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let pool = crate::database::Database::new(&url)
+            .await
+            .unwrap()
+            .pool()
+            .clone();
+        let (documents, client, at) = document_server().await;
+        let federation = Federation::new(pool, &SiteConfig::default())
+            .await
+            .unwrap()
+            .with_client(client);
+        let alice_keys = KeyPair::generate().unwrap();
+        let evil_keys = KeyPair::generate().unwrap();
+
+        // Both hosts share one server, so paths tell the documents apart
+        let alice = at("good.example", "/users/alice");
+        let alice_key = format!("{}#main-key", alice);
+        let fake = at("evil.example", "/fake");
+        let mallory = at("evil.example", "/mallory");
+        let evil_key = at("evil.example", "/keys/1");
+        let alice_standalone_key = at("good.example", "/keys/alice");
+        documents.lock().unwrap().extend([
+            (
+                "/users/alice".to_string(),
+                actor(&alice, &alice_key, &alice, &alice_keys),
+            ),
+            // A copy of alice with the attacker's key, served from elsewhere
+            (
+                "/fake".to_string(),
+                actor(&alice, &format!("{}#key", fake), &alice, &evil_keys),
+            ),
+            // The attacker's own actor, claiming its key belongs to alice
+            (
+                "/mallory".to_string(),
+                actor(&mallory, &format!("{}#key", mallory), &alice, &evil_keys),
+            ),
+            // A key on the attacker's host naming alice as its owner
+            (
+                "/keys/1".to_string(),
+                serde_json::json!({
+                    "id": evil_key,
+                    "owner": alice,
+                    "publicKeyPem": evil_keys.public_key_pem().unwrap(),
+                }),
+            ),
+            // Alice's key published on its own
+            (
+                "/keys/alice".to_string(),
+                serde_json::json!({
+                    "id": alice_standalone_key,
+                    "owner": alice,
+                    "publicKeyPem": alice_keys.public_key_pem().unwrap(),
+                }),
+            ),
+        ]);
+
+        let signer = verify(&federation, &alice_keys, &alice_key).await.unwrap();
+        assert_eq!(signer["id"], alice.as_str());
+
+        let forged = [
+            format!("{}#key", fake),
+            format!("{}#key", mallory),
+            evil_key.clone(),
+        ];
+        for key_id in forged {
+            assert!(
+                verify(&federation, &evil_keys, &key_id).await.is_err(),
+                "accepted {}",
+                key_id
+            );
+        }
+
+        // A standalone key must be listed by its owner, on the same host
+        assert!(verify(&federation, &alice_keys, &alice_standalone_key)
+            .await
+            .is_err());
+        documents.lock().unwrap().get_mut("/users/alice").unwrap()["publicKey"] = serde_json::json!([
+            {"id": alice_key, "owner": alice, "publicKeyPem": alice_keys.public_key_pem().unwrap()},
+            {"id": alice_standalone_key, "owner": alice, "publicKeyPem": alice_keys.public_key_pem().unwrap()},
+        ]);
+        let signer = verify(&federation, &alice_keys, &alice_standalone_key)
+            .await
+            .unwrap();
+        assert_eq!(signer["id"], alice.as_str());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,282 @@
+use serde_json::{json, Value};
+
+use crate::models::BlogPost;
+use crate::site::generator::{sanitize_slug, sanitize_tag_for_url};
+use crate::site::SiteConfig;
+use crate::web::handlers_helpers::markdown_to_html;
+
+pub const ACTIVITY_STREAMS: &str = "https://www.w3.org/ns/activitystreams";
+pub const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+pub const CONTENT_TYPE: &str = "application/activity+json";
+
+/// The blog as an ActivityPub actor: its URLs and how posts look to the
+/// fediverse
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub base_url: String,
+    pub username: String,
+    pub name: String,
+    pub summary: String,
+}
+
+impl Actor {
+    pub fn from_config(config: &SiteConfig) -> Self {
+        Self {
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            username: config.activitypub.username.clone(),
+            name: config.title.clone(),
+            summary: config.description.clone(),
+        }
+    }
+
+    pub fn id(&self) -> String {
+        format!("{}/activitypub/actor", self.base_url)
+    }
+
+    pub fn key_id(&self) -> String {
+        format!("{}#main-key", self.id())
+    }
+
+    pub fn inbox(&self) -> String {
+        format!("{}/activitypub/inbox", self.base_url)
+    }
+
+    pub fn outbox(&self) -> String {
+        format!("{}/activitypub/outbox", self.base_url)
+    }
+
+    pub fn followers(&self) -> String {
+        format!("{}/activitypub/followers", self.base_url)
+    }
+
+    /// Host part of `@username@host`
+    pub fn host(&self) -> String {
+        reqwest::Url::parse(&self.base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    /// The `acct:` URI WebFinger looks the actor up by
+    pub fn acct(&self) -> String {
+        format!("acct:{}@{}", self.username, self.host())
+    }
+
+    /// ID of the Article for a post
+    pub fn object_id(&self, post: &BlogPost) -> String {
+        format!("{}/activitypub/posts/{}", self.base_url, post.id)
+    }
+
+    /// The post's page on the site
+    pub fn post_url(&self, post: &BlogPost) -> String {
+        format!("{}/posts/{}.html", self.base_url, sanitize_slug(&post.slug))
+    }
+
+    pub fn to_json(&self, public_key_pem: &str) -> Value {
+        json!({
+            "@context": [ACTIVITY_STREAMS, "https://w3id.org/security/v1"],
+            "id": self.id(),
+            "type": "Person",
+            "preferredUsername": self.username,
+            "name": self.name,
+            "summary": self.summary,
+            "url": self.base_url,
+            "inbox": self.inbox(),
+            "outbox": self.outbox(),
+            "followers": self.followers(),
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "endpoints": { "sharedInbox": self.inbox() },
+            "publicKey": {
+                "id": self.key_id(),
+                "owner": self.id(),
+                "publicKeyPem": public_key_pem,
+            },
+        })
+    }
+
+    pub fn webfinger(&self) -> Value {
+        json!({
+            "subject": self.acct(),
+            "aliases": [self.id()],
+            "links": [
+                {
+                    "rel": "self",
+                    "type": CONTENT_TYPE,
+                    "href": self.id(),
+                },
+                {
+                    "rel": "http://webfinger.net/rel/profile-page",
+                    "type": "text/html",
+                    "href": self.base_url,
+                },
+            ],
+        })
+    }
+
+    pub fn article(&self, post: &BlogPost) -> Value {
+        let tags: Vec<Value> = post
+            .tags
+            .iter()
+            .map(|tag| {
+                json!({
+                    "type": "Hashtag",
+                    "name": format!("#{}", tag.replace(' ', "")),
+                    "href": format!("{}/tags/{}/", self.base_url, sanitize_tag_for_url(tag)),
+                })
+            })
+            .collect();
+        let published = post.publish_at.unwrap_or(post.created_at);
+
+        let mut article = json!({
+            "id": self.object_id(post),
+            "type": "Article",
+            "attributedTo": self.id(),
+            "name": post.title,
+            "content": markdown_to_html(&post.content),
+            "mediaType": "text/html",
+            "url": self.post_url(post),
+            "published": published.to_rfc3339(),
+            "to": [PUBLIC],
+            "cc": [self.followers()],
+            "tag": tags,
+        });
+        if let Some(excerpt) = &post.excerpt {
+            article["summary"] = Value::String(excerpt.clone());
+        }
+        if post.updated_at > published {
+            article["updated"] = Value::String(post.updated_at.to_rfc3339());
+        }
+        if let Some(lang) = &post.lang {
+            article["contentMap"] = json!({ lang.as_str(): article["content"].clone() });
+        }
+        article
+    }
+
+    /// The `Create` activity announcing a post
+    pub fn create(&self, post: &BlogPost) -> Value {
+        let article = self.article(post);
+        json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}/activity", self.object_id(post)),
+            "type": "Create",
+            "actor": self.id(),
+            "published": article["published"],
+            "to": article["to"],
+            "cc": article["cc"],
+            "object": article,
+        })
+    }
+
+    /// Accept a `Follow` activity
+    pub fn accept(&self, follow: &Value) -> Value {
+        json!({
+            "@context": ACTIVITY_STREAMS,
+            "id": format!("{}/activitypub/accept/{}", self.base_url, uuid::Uuid::new_v4()),
+            "type": "Accept",
+            "actor": self.id(),
+            "object": follow,
+        })
+    }
+
+    /// Whether `id` names one of our posts: its Article or its page. Returns
+    /// the post ID or slug to look the post up by.
+    pub fn post_reference<'a>(&self, id: &'a str) -> Option<PostReference<'a>> {
+        let objects = format!("{}/activitypub/posts/", self.base_url);
+        if let Some(post_id) = id.strip_prefix(&objects) {
+            return Some(PostReference::Id(post_id.trim_end_matches('/')));
+        }
+        let pages = format!("{}/posts/", self.base_url);
+        let slug = id.strip_prefix(&pages)?.split(['?', '#']).next()?;
+        let slug = slug.strip_suffix(".html").unwrap_or(slug);
+        (!slug.is_empty() && !slug.contains('/')).then_some(PostReference::Slug(slug))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostReference<'a> {
+    Id(&'a str),
+    Slug(&'a str),
+}
+
+/// An `OrderedCollection` with all its items inline
+pub fn ordered_collection(id: &str, items: Vec<Value>) -> Value {
+    json!({
+        "@context": ACTIVITY_STREAMS,
+        "id": id,
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    })
+}
+
+/// ID of an object that may be given inline or as a bare ID
+pub fn id_of(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(id) => Some(id),
+        Value::Object(object) => object.get("id").and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor() -> Actor {
+        Actor {
+            base_url: "https://blog.example".to_string(),
+            username: "blog".to_string(),
+            name: "Blog".to_string(),
+            summary: "A blog".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_create_article() {
+        let actor = actor();
+        let mut post = BlogPost::new(
+            "Hello World".to_string(),
+            "Some **bold** text".to_string(),
+            "me".to_string(),
+        );
+        post.tags = vec!["rust".to_string()];
+
+        let create = actor.create(&post);
+        assert_eq!(create["type"], "Create");
+        assert_eq!(create["actor"], "https://blog.example/activitypub/actor");
+        let article = &create["object"];
+        assert_eq!(article["type"], "Article");
+        assert_eq!(article["name"], "Hello World");
+        assert_eq!(
+            article["url"],
+            "https://blog.example/posts/hello-world.html"
+        );
+        assert!(article["content"]
+            .as_str()
+            .unwrap()
+            .contains("<strong>bold</strong>"));
+        assert_eq!(article["tag"][0]["name"], "#rust");
+        assert_eq!(article["to"][0], PUBLIC);
+        assert_eq!(actor.acct(), "acct:blog@blog.example");
+    }
+
+    #[test]
+    fn test_post_reference() {
+        let actor = actor();
+        assert_eq!(
+            actor.post_reference("https://blog.example/activitypub/posts/abc"),
+            Some(PostReference::Id("abc"))
+        );
+        assert_eq!(
+            actor.post_reference("https://blog.example/posts/hello.html#x"),
+            Some(PostReference::Slug("hello"))
+        );
+        assert_eq!(
+            actor.post_reference("https://other.example/posts/hello"),
+            None
+        );
+        assert_eq!(id_of(&json!({"id": "x"})), Some("x"));
+        assert_eq!(id_of(&json!("y")), Some("y"));
+    }
+}
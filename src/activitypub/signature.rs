@@ -0,0 +1,301 @@
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::{Signer, Verifier};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Signed requests older or further in the future than this are refused
+const MAX_CLOCK_SKEW_HOURS: i64 = 12;
+
+/// The actor's RSA key pair, for HTTP Signatures with `rsa-sha256`
+pub struct KeyPair {
+    private_key: PKey<Private>,
+}
+
+impl KeyPair {
+    pub fn generate() -> Result<Self> {
+        let rsa = Rsa::generate(2048)?;
+        Ok(Self {
+            private_key: PKey::from_rsa(rsa)?,
+        })
+    }
+
+    pub fn from_pem(private_pem: &str) -> Result<Self> {
+        Ok(Self {
+            private_key: PKey::private_key_from_pem(private_pem.as_bytes())?,
+        })
+    }
+
+    pub fn private_key_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(
+            self.private_key.private_key_to_pem_pkcs8()?,
+        )?)
+    }
+
+    pub fn public_key_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(self.private_key.public_key_to_pem()?)?)
+    }
+
+    pub fn sign(&self, data: &str) -> Result<String> {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.private_key)?;
+        signer.update(data.as_bytes())?;
+        Ok(STANDARD.encode(signer.sign_to_vec()?))
+    }
+}
+
+/// Check a base64 `rsa-sha256` signature against a PEM public key
+pub fn verify(public_pem: &str, data: &str, signature: &str) -> Result<bool> {
+    let key: PKey<Public> = PKey::public_key_from_pem(public_pem.as_bytes())?;
+    let signature = STANDARD.decode(signature.trim())?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verifier.update(data.as_bytes())?;
+    Ok(verifier.verify(&signature)?)
+}
+
+/// Value of the `Digest` header for a body
+pub fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(Sha256::digest(body)))
+}
+
+/// Value of the `Date` header, in the format HTTP wants
+pub fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// The parameters of a `Signature` header
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHeader {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    /// Lowercase names of the signed headers, in order
+    pub headers: Vec<String>,
+    pub signature: String,
+}
+
+impl SignatureHeader {
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut params = HashMap::new();
+        for param in split_params(value) {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Malformed signature parameter: {}", param))?;
+            params.insert(
+                name.trim().to_string(),
+                value.trim().trim_matches('"').to_string(),
+            );
+        }
+
+        let key_id = params
+            .remove("keyId")
+            .ok_or_else(|| anyhow!("Signature has no keyId"))?;
+        let signature = params
+            .remove("signature")
+            .ok_or_else(|| anyhow!("Signature has no signature"))?;
+        // Without a list only the date is signed
+        let headers = params
+            .remove("headers")
+            .unwrap_or_else(|| "date".to_string())
+            .split_whitespace()
+            .map(str::to_ascii_lowercase)
+            .collect();
+
+        Ok(Self {
+            key_id,
+            algorithm: params.remove("algorithm"),
+            headers,
+            signature,
+        })
+    }
+}
+
+/// Split on the commas between parameters, not those inside quotes
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                params.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(value[start..].trim());
+    params.retain(|param| !param.is_empty());
+    params
+}
+
+/// The string that is signed: one `name: value` line per signed header.
+/// `header` looks up a request header by lowercase name.
+pub fn signing_string<'a>(
+    method: &str,
+    path_and_query: &str,
+    signed_headers: &[String],
+    header: impl Fn(&str) -> Option<&'a str>,
+) -> Result<String> {
+    let lines = signed_headers
+        .iter()
+        .map(|name| match name.as_str() {
+            "(request-target)" => Ok(format!(
+                "(request-target): {} {}",
+                method.to_ascii_lowercase(),
+                path_and_query
+            )),
+            name => header(name)
+                .map(|value| format!("{}: {}", name, value.trim()))
+                .ok_or_else(|| anyhow!("Signed header missing: {}", name)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(lines.join("\n"))
+}
+
+/// Headers to add to a request to sign it: `Date`, `Digest` when there is a
+/// body, and `Signature`
+pub fn sign_request(
+    keys: &KeyPair,
+    key_id: &str,
+    method: &str,
+    url: &reqwest::Url,
+    body: Option<&[u8]>,
+) -> Result<Vec<(&'static str, String)>> {
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let mut headers = vec![("host", host), ("date", http_date(Utc::now()))];
+    if let Some(body) = body {
+        headers.push(("digest", digest(body)));
+    }
+
+    let mut signed: Vec<String> = vec!["(request-target)".to_string()];
+    signed.extend(headers.iter().map(|(name, _)| name.to_string()));
+    let data = signing_string(method, &path, &signed, |name| {
+        headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.as_str())
+    })?;
+
+    let signature = format!(
+        r#"keyId="{}",algorithm="rsa-sha256",headers="{}",signature="{}""#,
+        key_id,
+        signed.join(" "),
+        keys.sign(&data)?
+    );
+    headers.retain(|(name, _)| *name != "host");
+    headers.push(("signature", signature));
+    Ok(headers)
+}
+
+/// Check the parts of a signed request that don't need the sender's key:
+/// the date is recent, the digest matches the body, and what must be
+/// signed is signed
+pub fn check_request(
+    signature: &SignatureHeader,
+    date: Option<&str>,
+    digest_header: Option<&str>,
+    body: &[u8],
+) -> Result<()> {
+    if let Some(algorithm) = &signature.algorithm {
+        if !matches!(algorithm.as_str(), "rsa-sha256" | "hs2019") {
+            bail!("Unsupported signature algorithm: {}", algorithm);
+        }
+    }
+    for required in ["(request-target)", "date", "digest"] {
+        if !signature.headers.iter().any(|h| h == required) {
+            bail!("Signature doesn't cover {}", required);
+        }
+    }
+
+    let date = date.ok_or_else(|| anyhow!("Request has no Date header"))?;
+    let date = DateTime::parse_from_rfc2822(date)?.with_timezone(&Utc);
+    if (Utc::now() - date).abs() > Duration::hours(MAX_CLOCK_SKEW_HOURS) {
+        bail!("Request date is too far off: {}", date);
+    }
+
+    let expected = STANDARD.encode(Sha256::digest(body));
+    let matches = digest_header.is_some_and(|value| {
+        value.split(',').any(|d| {
+            d.trim().split_once('=').is_some_and(|(algorithm, hash)| {
+                algorithm.eq_ignore_ascii_case("SHA-256") && hash == expected
+            })
+        })
+    });
+    if !matches {
+        bail!("Digest doesn't match the body");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signature_header() {
+        let header = SignatureHeader::parse(
+            r#"keyId="https://x.example/users/a#main-key",algorithm="rsa-sha256",headers="(request-target) host date digest",signature="YWJj,ZA==""#,
+        )
+        .unwrap();
+        assert_eq!(header.key_id, "https://x.example/users/a#main-key");
+        assert_eq!(header.algorithm.as_deref(), Some("rsa-sha256"));
+        assert_eq!(
+            header.headers,
+            vec!["(request-target)", "host", "date", "digest"]
+        );
+        assert_eq!(header.signature, "YWJj,ZA==");
+        assert!(SignatureHeader::parse(r#"signature="x""#).is_err());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keys = KeyPair::generate().unwrap();
+        let public_pem = keys.public_key_pem().unwrap();
+        let url = reqwest::Url::parse("https://them.example/inbox").unwrap();
+        let body = br#"{"type":"Follow"}"#;
+
+        let headers = sign_request(
+            &keys,
+            "https://me.example/actor#main-key",
+            "POST",
+            &url,
+            Some(body),
+        )
+        .unwrap();
+        let get = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let signature = SignatureHeader::parse(get("signature").unwrap()).unwrap();
+        check_request(&signature, get("date"), get("digest"), body).unwrap();
+        assert!(check_request(&signature, get("date"), get("digest"), b"other").is_err());
+
+        let lookup = |name: &str| match name {
+            "host" => Some("them.example"),
+            name => get(name),
+        };
+        let data = signing_string("POST", "/inbox", &signature.headers, lookup).unwrap();
+        assert!(data.starts_with("(request-target): post /inbox\nhost: them.example\ndate: "));
+        assert!(verify(&public_pem, &data, &signature.signature).unwrap());
+
+        let tampered = signing_string("POST", "/other", &signature.headers, lookup).unwrap();
+        assert!(!verify(&public_pem, &tampered, &signature.signature).unwrap());
+
+        let reloaded = KeyPair::from_pem(&keys.private_key_pem().unwrap()).unwrap();
+        assert_eq!(reloaded.public_key_pem().unwrap(), public_pem);
+    }
+}
@@ -0,0 +1,614 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::blog::BlogManager;
+use crate::docs::{DocCategory, DocFrontMatter, DocSection};
+use crate::frontmatter::{self, FrontMatter};
+use crate::models::BlogPost;
+
+/// Version 2 keeps content byte for byte and the stored `published` flag
+const ARCHIVE_VERSION: u32 = 2;
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Dir,
+    Tar,
+    Json,
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dir" => Ok(Self::Dir),
+            "tar" => Ok(Self::Tar),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!(
+                "Unknown export format: {} (use dir, tar or json)",
+                s
+            )),
+        }
+    }
+}
+
+impl ArchiveFormat {
+    pub fn default_output(&self) -> &'static str {
+        match self {
+            Self::Dir => "./export",
+            Self::Tar => "./kpgb-export.tar",
+            Self::Json => "./kpgb-export.json",
+        }
+    }
+}
+
+/// Index of an export: which file holds which post or doc section, plus the
+/// database fields that don't live in the markdown frontmatter
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub posts: Vec<PostEntry>,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    pub doc_categories: Vec<DocCategory>,
+    pub doc_sections: Vec<SectionEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostEntry {
+    pub storage_id: String,
+    pub id: String,
+    pub file: String,
+    pub content_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub published: bool,
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SectionEntry {
+    pub id: String,
+    pub category_id: String,
+    pub parent_id: Option<String>,
+    pub file: String,
+    pub title: String,
+    pub slug: String,
+    pub order: i32,
+    pub source_url: Option<String>,
+    pub is_translated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_synced_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub is_stale: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A complete export: the manifest and the markdown files it points to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub manifest: Manifest,
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub posts_restored: usize,
+    pub posts_skipped: usize,
+    pub doc_categories: usize,
+    pub doc_sections: usize,
+}
+
+impl RestoreReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} posts restored, {} already present, {} doc categories, {} doc sections",
+            self.posts_restored, self.posts_skipped, self.doc_categories, self.doc_sections
+        )
+    }
+}
+
+/// Collect every post and doc section into an archive
+pub async fn build_archive(blog_manager: &BlogManager) -> Result<Archive> {
+    let mut posts = blog_manager.list_posts(false).await?;
+    posts.sort_by_key(|(_, post)| post.created_at);
+    // `published` on a post also counts scheduled posts that are due
+    let stored_published = blog_manager.stored_published_flags().await?;
+
+    let mut files = BTreeMap::new();
+    let mut post_entries = Vec::new();
+    let mut tags = BTreeSet::new();
+    let mut categories = BTreeSet::new();
+
+    for (storage_id, post) in posts {
+        let name = if post.slug.is_empty() {
+            post.id.clone()
+        } else {
+            post.slug.clone()
+        };
+        let file = unique_file_name(&files, "posts", &name);
+        files.insert(
+            file.clone(),
+            frontmatter::render_frontmatter(&FrontMatter::from_post(&post), &post.content)?,
+        );
+
+        tags.extend(post.tags.iter().cloned());
+        categories.extend(post.category.iter().cloned());
+        let published = stored_published
+            .get(&post.id)
+            .copied()
+            .unwrap_or(post.published);
+        post_entries.push(PostEntry {
+            storage_id,
+            id: post.id,
+            file,
+            content_hash: post.content_hash,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+            published,
+            publish_at: post.publish_at,
+        });
+    }
+
+    let docs_db = blog_manager.docs_db();
+    let doc_categories = docs_db.get_all_categories().await?;
+    let sections = docs_db.get_all_sections().await?;
+
+    let category_slugs: HashMap<&str, &str> = doc_categories
+        .iter()
+        .map(|c| (c.id.as_str(), c.slug.as_str()))
+        .collect();
+    let section_slugs: HashMap<&str, &str> = sections
+        .iter()
+        .map(|s| (s.id.as_str(), s.slug.as_str()))
+        .collect();
+
+    let mut section_entries = Vec::new();
+    for section in &sections {
+        let category_slug = category_slugs
+            .get(section.category_id.as_str())
+            .copied()
+            .unwrap_or("uncategorized");
+        let file = unique_file_name(&files, &format!("docs/{}", category_slug), &section.slug);
+
+        let meta = DocFrontMatter {
+            title: section.title.clone(),
+            slug: Some(section.slug.clone()),
+            order: Some(section.order),
+            parent: section
+                .parent_id
+                .as_deref()
+                .and_then(|id| section_slugs.get(id))
+                .map(|slug| slug.to_string()),
+            source_url: section.source_url.clone(),
+            icon: None,
+        };
+        files.insert(
+            file.clone(),
+            frontmatter::render_frontmatter(&meta, &section.content)?,
+        );
+
+        section_entries.push(SectionEntry {
+            id: section.id.clone(),
+            category_id: section.category_id.clone(),
+            parent_id: section.parent_id.clone(),
+            file,
+            title: section.title.clone(),
+            slug: section.slug.clone(),
+            order: section.order,
+            source_url: section.source_url.clone(),
+            is_translated: section.is_translated,
+            source_text: section.source_text.clone(),
+            source_hash: section.source_hash.clone(),
+            source_synced_at: section.source_synced_at,
+            is_stale: section.is_stale,
+            created_at: section.created_at,
+            updated_at: section.updated_at,
+        });
+    }
+
+    Ok(Archive {
+        manifest: Manifest {
+            version: ARCHIVE_VERSION,
+            exported_at: Utc::now(),
+            posts: post_entries,
+            tags: tags.into_iter().collect(),
+            categories: categories.into_iter().collect(),
+            doc_categories,
+            doc_sections: section_entries,
+        },
+        files,
+    })
+}
+
+/// Pick `dir/name.md`, adding a numeric suffix if the name is already taken
+fn unique_file_name(files: &BTreeMap<String, String>, dir: &str, name: &str) -> String {
+    let name = name.replace(['/', '\\'], "-");
+    let mut file = format!("{}/{}.md", dir, name);
+    let mut n = 2;
+    while files.contains_key(&file) {
+        file = format!("{}/{}-{}.md", dir, name, n);
+        n += 1;
+    }
+    file
+}
+
+pub fn write_archive(archive: &Archive, format: ArchiveFormat, output: &Path) -> Result<()> {
+    let manifest = serde_json::to_string_pretty(&archive.manifest)?;
+
+    match format {
+        ArchiveFormat::Dir => {
+            for (name, content) in &archive.files {
+                let path = output.join(name);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(path, content)?;
+            }
+            fs::create_dir_all(output)?;
+            fs::write(output.join(MANIFEST_FILE), manifest)?;
+        }
+        ArchiveFormat::Tar => {
+            let mut builder = tar::Builder::new(fs::File::create(output)?);
+            let mtime = archive.manifest.exported_at.timestamp().max(0) as u64;
+
+            let entries = std::iter::once((MANIFEST_FILE, &manifest))
+                .chain(archive.files.iter().map(|(k, v)| (k.as_str(), v)));
+            for (name, content) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                builder.append_data(&mut header, name, content.as_bytes())?;
+            }
+            builder.finish()?;
+        }
+        ArchiveFormat::Json => {
+            fs::write(output, serde_json::to_string_pretty(archive)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read an export written by `write_archive`. The format is picked from the path.
+pub fn read_archive(path: &Path) -> Result<Archive> {
+    if path.is_dir() {
+        let manifest: Manifest =
+            serde_json::from_str(&fs::read_to_string(path.join(MANIFEST_FILE))?)?;
+
+        let mut files = BTreeMap::new();
+        let names = manifest
+            .posts
+            .iter()
+            .map(|p| &p.file)
+            .chain(manifest.doc_sections.iter().map(|s| &s.file));
+        for name in names {
+            files.insert(name.clone(), fs::read_to_string(path.join(name))?);
+        }
+
+        return Ok(Archive { manifest, files });
+    }
+
+    if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        return Ok(serde_json::from_str(&fs::read_to_string(path)?)?);
+    }
+
+    let mut tar = tar::Archive::new(fs::File::open(path)?);
+    let mut manifest = None;
+    let mut files = BTreeMap::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+
+        if name == MANIFEST_FILE {
+            manifest = Some(serde_json::from_str(&content)?);
+        } else {
+            files.insert(name, content);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow!("{} missing from archive", MANIFEST_FILE))?;
+    Ok(Archive { manifest, files })
+}
+
+/// Restore an archive into the database. Posts whose content is already
+/// present and doc categories or sections with an existing slug are skipped.
+pub async fn restore_archive(
+    blog_manager: &mut BlogManager,
+    archive: &Archive,
+) -> Result<RestoreReport> {
+    let manifest = &archive.manifest;
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(anyhow!(
+            "Archive version {} is newer than supported version {}",
+            manifest.version,
+            ARCHIVE_VERSION
+        ));
+    }
+
+    let file = |name: &str| {
+        archive
+            .files
+            .get(name)
+            .ok_or_else(|| anyhow!("{} listed in manifest but missing from archive", name))
+    };
+
+    let mut report = RestoreReport::default();
+    let mut existing: HashSet<String> = blog_manager
+        .list_posts(false)
+        .await?
+        .into_iter()
+        .map(|(_, post)| post.content_hash)
+        .collect();
+
+    // Version 1 archives trimmed the content, so hashes may not match
+    let exact = manifest.version >= 2;
+    for entry in &manifest.posts {
+        let (fm, content) = if exact {
+            frontmatter::parse_rendered_as::<FrontMatter>(file(&entry.file)?)?
+        } else {
+            let (fm, content) = frontmatter::parse_frontmatter(file(&entry.file)?)?;
+            let fm = fm.ok_or_else(|| anyhow!("{} has no frontmatter", entry.file))?;
+            (fm, content)
+        };
+
+        let mut post = BlogPost::new(fm.title.clone(), content, fm.author.clone());
+        if exact && post.content_hash != entry.content_hash {
+            return Err(anyhow!(
+                "{} doesn't match its content hash in the manifest",
+                entry.file
+            ));
+        }
+        if existing.contains(&entry.content_hash) || !existing.insert(post.content_hash.clone()) {
+            report.posts_skipped += 1;
+            continue;
+        }
+
+        post.id = entry.id.clone();
+        if let Some(slug) = &fm.slug {
+            post.slug = slug.clone();
+        }
+        post.tags = fm.tags.clone();
+        post.category = fm.category();
+        post.excerpt = fm.excerpt.clone();
+        post.lang = fm.lang.clone();
+        post.translation_of = fm.translation_of.clone();
+        post.created_at = entry.created_at;
+        post.updated_at = entry.updated_at;
+        post.published = entry.published;
+        post.publish_at = entry.publish_at;
+
+        blog_manager.restore_post(post, &entry.storage_id).await?;
+        report.posts_restored += 1;
+    }
+
+    let docs_db = blog_manager.docs_db();
+
+    // Map archived IDs to the IDs of rows that already exist under the same slug
+    let mut category_ids: HashMap<String, String> = HashMap::new();
+    let current: HashMap<String, String> = docs_db
+        .get_all_categories()
+        .await?
+        .into_iter()
+        .map(|c| (c.slug, c.id))
+        .collect();
+    for category in &manifest.doc_categories {
+        if let Some(id) = current.get(&category.slug) {
+            category_ids.insert(category.id.clone(), id.clone());
+        } else {
+            docs_db.create_category(category).await?;
+            category_ids.insert(category.id.clone(), category.id.clone());
+            report.doc_categories += 1;
+        }
+    }
+
+    let current: HashMap<String, String> = docs_db
+        .get_all_sections()
+        .await?
+        .into_iter()
+        .map(|s| (s.slug, s.id))
+        .collect();
+    let mut section_ids: HashMap<String, String> = HashMap::new();
+    for entry in &manifest.doc_sections {
+        if let Some(id) = current.get(&entry.slug) {
+            section_ids.insert(entry.id.clone(), id.clone());
+        }
+    }
+
+    // Parents have to exist before their children reference them
+    let mut pending: Vec<&SectionEntry> = manifest
+        .doc_sections
+        .iter()
+        .filter(|entry| !section_ids.contains_key(&entry.id))
+        .collect();
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut waiting = Vec::new();
+
+        for entry in pending {
+            let parent_id = match &entry.parent_id {
+                Some(parent) => match section_ids.get(parent) {
+                    Some(id) => Some(id.clone()),
+                    None => {
+                        waiting.push(entry);
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let category_id = category_ids
+                .get(&entry.category_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("Doc section {} has an unknown category", entry.slug))?;
+            let content = if exact {
+                frontmatter::parse_rendered_as::<DocFrontMatter>(file(&entry.file)?)?.1
+            } else {
+                frontmatter::parse_frontmatter_as::<DocFrontMatter>(file(&entry.file)?)?.1
+            };
+
+            docs_db
+                .create_section(&DocSection {
+                    id: entry.id.clone(),
+                    category_id,
+                    title: entry.title.clone(),
+                    slug: entry.slug.clone(),
+                    content,
+                    parent_id,
+                    order: entry.order,
+                    created_at: entry.created_at,
+                    updated_at: entry.updated_at,
+                    source_url: entry.source_url.clone(),
+                    is_translated: entry.is_translated,
+                    source_text: entry.source_text.clone(),
+                    source_hash: entry.source_hash.clone(),
+                    source_synced_at: entry.source_synced_at,
+                    is_stale: entry.is_stale,
+                })
+                .await?;
+            section_ids.insert(entry.id.clone(), entry.id.clone());
+            report.doc_sections += 1;
+        }
+
+        if waiting.len() == before {
+            return Err(anyhow!(
+                "Doc sections reference missing parents: {}",
+                waiting
+                    .iter()
+                    .map(|e| e.slug.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        pending = waiting;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{blog, TempDir};
+
+    fn sample_archive() -> Archive {
+        let mut files = BTreeMap::new();
+        files.insert(
+            "posts/hello.md".to_string(),
+            "---\ntitle: Hello\n---\n\nBody\n".to_string(),
+        );
+
+        Archive {
+            manifest: Manifest {
+                version: ARCHIVE_VERSION,
+                exported_at: Utc::now(),
+                posts: vec![PostEntry {
+                    storage_id: "abc".to_string(),
+                    id: "1".to_string(),
+                    file: "posts/hello.md".to_string(),
+                    content_hash: "hash".to_string(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                    published: true,
+                    publish_at: None,
+                }],
+                tags: vec![],
+                categories: vec![],
+                doc_categories: vec![],
+                doc_sections: vec![],
+            },
+            files,
+        }
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = TempDir::new("archive");
+        let dir = dir.path();
+        let archive = sample_archive();
+
+        for (format, output) in [
+            (ArchiveFormat::Dir, dir.join("out")),
+            (ArchiveFormat::Tar, dir.join("out.tar")),
+            (ArchiveFormat::Json, dir.join("out.json")),
+        ] {
+            write_archive(&archive, format, &output).unwrap();
+            let restored = read_archive(&output).unwrap();
+            assert_eq!(restored.files, archive.files);
+            assert_eq!(restored.manifest.posts[0].storage_id, "abc");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_and_restore() {
+        let (_source_dir, mut source) = blog("archive").await;
+        let mut post = BlogPost::new(
+            "Spaced".to_string(),
+            "\n  Body with whitespace\r\n\n".to_string(),
+            "Me".to_string(),
+        );
+        post.published = true;
+        source.create_post(post.clone()).await.unwrap();
+        let mut scheduled = BlogPost::new("Due".to_string(), "Later".to_string(), "Me".to_string());
+        scheduled.publish_at = Some(Utc::now() - chrono::Duration::minutes(1));
+        source.create_post(scheduled.clone()).await.unwrap();
+
+        let archive = build_archive(&source).await.unwrap();
+        let entry = |id: &str| {
+            archive
+                .manifest
+                .posts
+                .iter()
+                .find(|entry| entry.id == id)
+                .unwrap()
+        };
+        assert!(entry(&post.id).published);
+        // Due but never published: restoring it keeps it scheduled
+        assert!(!entry(&scheduled.id).published);
+
+        let (_target_dir, mut target) = blog("restore").await;
+        let report = restore_archive(&mut target, &archive).await.unwrap();
+        assert_eq!(report.posts_restored, 2);
+        let restored = target.list_posts(false).await.unwrap();
+        let restored_post = &restored.iter().find(|(_, p)| p.id == post.id).unwrap().1;
+        assert_eq!(restored_post.content, post.content);
+        assert_eq!(restored_post.content_hash, post.content_hash);
+        let flags = target.stored_published_flags().await.unwrap();
+        assert!(!flags[&scheduled.id]);
+
+        // Restoring again finds everything already there
+        let report = restore_archive(&mut target, &archive).await.unwrap();
+        assert_eq!((report.posts_restored, report.posts_skipped), (0, 2));
+
+        let mut tampered = build_archive(&source).await.unwrap();
+        let file = tampered.manifest.posts[0].file.clone();
+        tampered.files.get_mut(&file).unwrap().push('!');
+        let error = restore_archive(&mut blog("tampered").await.1, &tampered)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("content hash"));
+    }
+
+    #[test]
+    fn test_unique_file_name() {
+        let mut files = BTreeMap::new();
+        assert_eq!(unique_file_name(&files, "posts", "a/b"), "posts/a-b.md");
+        files.insert("posts/a-b.md".to_string(), String::new());
+        assert_eq!(unique_file_name(&files, "posts", "a/b"), "posts/a-b-2.md");
+    }
+}
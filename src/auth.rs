@@ -0,0 +1,243 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
+
+/// Prefix of every token, so leaked tokens are easy to recognise
+const TOKEN_PREFIX: &str = "kpgb_";
+
+/// Shortest ID prefix `revoke` accepts
+const MIN_ID_PREFIX: usize = 8;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Bearer tokens for the write API, stored as SHA-256 hashes
+#[derive(Clone)]
+pub struct TokenStore {
+    pool: SqlitePool,
+}
+
+impl TokenStore {
+    /// Tables are created by the migrations run in `Database::new`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Create a token. The plain token is only returned here; it can't be
+    /// recovered later.
+    pub async fn create(&self, name: &str) -> Result<(ApiToken, String)> {
+        let secret = generate_token();
+        let token = ApiToken {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        query(
+            r#"
+            INSERT INTO api_tokens (id, name, token_hash, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+        .bind(&token.id)
+        .bind(&token.name)
+        .bind(hash_token(&secret))
+        .bind(token.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok((token, secret))
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiToken>> {
+        let rows = query(
+            r#"
+            SELECT id, name, created_at, last_used_at, revoked_at
+            FROM api_tokens
+            ORDER BY created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_token).collect())
+    }
+
+    /// Revoke the tokens with this name, or the token with this ID or an ID
+    /// prefix of at least `MIN_ID_PREFIX` characters. A prefix matching
+    /// several tokens is an error. Returns the revoked tokens.
+    pub async fn revoke(&self, id_or_name: &str) -> Result<Vec<ApiToken>> {
+        let active: Vec<ApiToken> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|t| t.revoked_at.is_none())
+            .collect();
+
+        let mut matching: Vec<ApiToken> = active
+            .iter()
+            .filter(|t| t.name == id_or_name || t.id == id_or_name)
+            .cloned()
+            .collect();
+        if matching.is_empty() && id_or_name.len() >= MIN_ID_PREFIX {
+            matching = active
+                .into_iter()
+                .filter(|t| t.id.starts_with(id_or_name))
+                .collect();
+            if matching.len() > 1 {
+                anyhow::bail!(
+                    "{} tokens have IDs starting with {}, give more of the ID",
+                    matching.len(),
+                    id_or_name
+                );
+            }
+        }
+
+        for token in &matching {
+            query("UPDATE api_tokens SET revoked_at = ?1 WHERE id = ?2")
+                .bind(Utc::now())
+                .bind(&token.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(matching)
+    }
+
+    /// Look up an active token by its plain value, recording the use
+    pub async fn verify(&self, secret: &str) -> Result<Option<ApiToken>> {
+        if !secret.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+
+        let row = query(
+            r#"
+            SELECT id, name, created_at, last_used_at, revoked_at
+            FROM api_tokens
+            WHERE token_hash = ?1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(hash_token(secret))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(token) = row.as_ref().map(Self::row_to_token) else {
+            return Ok(None);
+        };
+
+        query("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(&token.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(Some(token))
+    }
+
+    fn row_to_token(row: &SqliteRow) -> ApiToken {
+        ApiToken {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+            last_used_at: row.get("last_used_at"),
+            revoked_at: row.get("revoked_at"),
+        }
+    }
+}
+
+pub fn generate_token() -> String {
+    format!("{}{}", TOKEN_PREFIX, generate_secret())
+}
+
+/// 32 random bytes, hex encoded
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn store() -> TokenStore {
+        // A single connection keeps the in-memory database alive and shared
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        TokenStore::new(pool)
+    }
+
+    #[tokio::test]
+    async fn test_token_lifecycle() {
+        let store = store().await;
+        let (token, secret) = store.create("deploy").await.unwrap();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        assert_ne!(hash_token(&secret), secret);
+
+        let verified = store.verify(&secret).await.unwrap().unwrap();
+        assert_eq!(verified.id, token.id);
+        assert!(store.verify("kpgb_wrong").await.unwrap().is_none());
+        assert!(store.verify("").await.unwrap().is_none());
+        assert!(store.list().await.unwrap()[0].last_used_at.is_some());
+
+        let revoked = store.revoke("deploy").await.unwrap();
+        assert_eq!(revoked.len(), 1);
+        assert!(store.verify(&secret).await.unwrap().is_none());
+        assert!(store.revoke("deploy").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_by_id_prefix() {
+        let store = store().await;
+        for (name, id) in [("one", "abcdef12-0001"), ("two", "abcdef12-0002")] {
+            let (token, _) = store.create(name).await.unwrap();
+            query("UPDATE api_tokens SET id = ?1 WHERE id = ?2")
+                .bind(id)
+                .bind(&token.id)
+                .execute(&store.pool)
+                .await
+                .unwrap();
+        }
+
+        // Short prefixes never match, so they can't sweep up every token
+        assert!(store.revoke("").await.unwrap().is_empty());
+        assert!(store.revoke("a").await.unwrap().is_empty());
+        assert!(store.revoke("abcdef12").await.is_err());
+        assert_eq!(store.list().await.unwrap().len(), 2);
+        assert!(store
+            .list()
+            .await
+            .unwrap()
+            .iter()
+            .all(|t| t.revoked_at.is_none()));
+
+        let revoked = store.revoke("abcdef12-0002").await.unwrap();
+        assert_eq!(revoked.len(), 1);
+        assert_eq!(revoked[0].name, "two");
+        // With the other one revoked the prefix is no longer ambiguous
+        let revoked = store.revoke("abcdef12").await.unwrap();
+        assert_eq!(revoked.len(), 1);
+        assert_eq!(revoked[0].name, "one");
+    }
+}
@@ -1,12 +1,27 @@
+use crate::activitypub::Federation;
+use crate::auth::TokenStore;
+use crate::comments::CommentStore;
 use crate::database::Database;
-use crate::models::BlogPost;
-use crate::storage::StorageManager;
+use crate::deploy::DeployStore;
+use crate::docs::DocsDatabase;
+use crate::models::{BlogPost, PostLocation, PostRevision};
+use crate::site::SiteConfig;
+use crate::storage::local::StorageReferences;
+use crate::storage::{Replica, ReplicaStatus, StorageBackend, StorageManager};
+use crate::webmention::WebmentionStore;
 use anyhow::Result;
 use std::collections::HashMap;
+use tracing::{info, warn};
 
+#[cfg(test)]
+pub mod test_support;
+
+#[derive(Clone)]
 pub struct BlogManager {
     storage_manager: StorageManager,
     database: Database,
+    /// Set when the blog federates; published posts are delivered to followers
+    federation: Option<Federation>,
 }
 
 impl BlogManager {
@@ -15,9 +30,21 @@ impl BlogManager {
         Ok(Self {
             storage_manager,
             database,
+            federation: None,
         })
     }
 
+    /// Act as the ActivityPub actor described by `[activitypub]` in the config
+    pub async fn enable_federation(&mut self, config: &SiteConfig) -> Result<()> {
+        let federation = Federation::new(self.database.pool().clone(), config).await?;
+        self.federation = Some(federation);
+        Ok(())
+    }
+
+    pub fn federation(&self) -> Option<&Federation> {
+        self.federation.as_ref()
+    }
+
     pub async fn create_post(&mut self, post: BlogPost) -> Result<String> {
         // Check for duplicate content
         if let Some(existing_storage_id) = self
@@ -28,21 +55,199 @@ impl BlogManager {
             return Ok(existing_storage_id);
         }
 
-        // Store in default storage backend
-        let storage = self.storage_manager.default_backend();
+        let storage_id = self.insert_post(&post).await?;
+        if post.published {
+            self.went_live(&post).await;
+        }
+        Ok(storage_id)
+    }
+
+    /// Store a post that isn't in the database yet and record it
+    async fn insert_post(&mut self, post: &BlogPost) -> Result<String> {
+        // Store in the primary backend and its mirrors
+        let replicas = self.store_post(post).await?;
+        let storage_id = replicas[0].result.id.clone();
+
+        // Save to database
+        self.database.insert_post(post, &storage_id).await?;
+        self.database.insert_revision(post, &storage_id).await?;
+        self.record_locations(&post.id, &replicas).await?;
+
+        Ok(storage_id)
+    }
+
+    /// Re-create a post from a backup. The storage ID it had before is kept
+    /// as an alias when the storage backend hands out a different one.
+    /// Followers aren't told again; they heard of the post when it first went out.
+    pub async fn restore_post(
+        &mut self,
+        post: BlogPost,
+        original_storage_id: &str,
+    ) -> Result<String> {
+        if let Some(existing_storage_id) = self
+            .database
+            .get_post_by_content_hash(&post.content_hash)
+            .await?
+        {
+            return Ok(existing_storage_id);
+        }
+
+        let storage_id = self.insert_post(&post).await?;
+        if storage_id != original_storage_id {
+            self.database
+                .replace_storage_id(&post.id, original_storage_id, &storage_id)
+                .await?;
+        }
+        Ok(storage_id)
+    }
+
+    /// Deliver a post that just went from unpublished to published to the
+    /// followers. Every change that publishes a post ends here, so followers
+    /// hear about each post once whichever way it went out.
+    async fn went_live(&self, post: &BlogPost) {
+        let Some(federation) = &self.federation else {
+            return;
+        };
+        match federation.announce_post(post).await {
+            Ok(delivered) => info!("Delivered {} to {} inboxes", post.slug, delivered),
+            Err(e) => warn!("Failed to deliver {}: {}", post.slug, e),
+        }
+    }
+
+    pub fn docs_db(&self) -> DocsDatabase {
+        DocsDatabase::new(self.database.pool().clone())
+    }
+
+    pub fn token_store(&self) -> TokenStore {
+        TokenStore::new(self.database.pool().clone())
+    }
+
+    pub fn comments(&self) -> CommentStore {
+        CommentStore::new(self.database.pool().clone())
+    }
+
+    pub fn webmentions(&self) -> WebmentionStore {
+        WebmentionStore::new(self.database.pool().clone())
+    }
+
+    pub fn deploys(&self) -> DeployStore {
+        DeployStore::new(self.database.pool().clone())
+    }
+
+    pub fn storage_manager(&self) -> &StorageManager {
+        &self.storage_manager
+    }
 
-        let post_json = serde_json::to_string_pretty(&post)?;
+    async fn store_post(&self, post: &BlogPost) -> Result<Vec<Replica>> {
+        let post_json = serde_json::to_string_pretty(post)?;
         let mut metadata = HashMap::new();
         metadata.insert("content_type".to_string(), "application/json".to_string());
         metadata.insert("post_id".to_string(), post.id.clone());
         metadata.insert("slug".to_string(), post.slug.clone());
+        metadata.insert("created_at".to_string(), post.created_at.to_rfc3339());
 
-        let result = storage.store(post_json.as_bytes(), metadata).await?;
+        self.storage_manager
+            .replicate(post_json.as_bytes(), metadata)
+            .await
+    }
 
-        // Save to database
-        self.database.insert_post(&post, &result.id).await?;
+    /// Remember where each copy of a post version went, keyed by the
+    /// primary's storage ID
+    async fn record_locations(&self, post_id: &str, replicas: &[Replica]) -> Result<()> {
+        let storage_id = &replicas[0].result.id;
+        let locations: Vec<PostLocation> = replicas
+            .iter()
+            .map(|replica| PostLocation {
+                storage_id: storage_id.clone(),
+                post_id: post_id.to_string(),
+                backend: replica.backend.name().to_string(),
+                location: replica.result.id.clone(),
+                hash: replica.result.metadata.hash.clone(),
+                size: replica.result.metadata.size as i64,
+                status: ReplicaStatus::Ok.as_str().to_string(),
+                created_at: replica.result.metadata.created_at,
+                verified_at: None,
+            })
+            .collect();
+        self.database.insert_post_locations(&locations).await
+    }
+
+    /// Where the current version of each post, or of one post, is stored
+    pub async fn post_locations(&self, post_id: Option<&str>) -> Result<Vec<PostLocation>> {
+        self.database.list_current_locations(post_id).await
+    }
+
+    /// Re-read every copy of the current post versions and record whether
+    /// it still has its hash
+    pub async fn verify_locations(
+        &self,
+        post_id: Option<&str>,
+    ) -> Result<Vec<(PostLocation, ReplicaStatus)>> {
+        let mut results = Vec::new();
+        for location in self.post_locations(post_id).await? {
+            let status = match location.backend.parse::<StorageBackend>() {
+                Ok(backend) => {
+                    self.storage_manager
+                        .verify(&backend, &location.location, &location.hash)
+                        .await
+                }
+                Err(_) => ReplicaStatus::Unavailable,
+            };
+            // A backend that isn't configured here says nothing about the copy
+            if status != ReplicaStatus::Unavailable {
+                self.database
+                    .update_location_status(
+                        &location.storage_id,
+                        &location.backend,
+                        status.as_str(),
+                    )
+                    .await?;
+            }
+            results.push((location, status));
+        }
+        Ok(results)
+    }
+
+    /// Whether each post was published explicitly, by post ID
+    pub async fn stored_published_flags(&self) -> Result<HashMap<String, bool>> {
+        self.database.list_stored_published().await
+    }
 
-        Ok(result.id)
+    /// Hashes of the markdown files posts were imported from
+    pub async fn import_sources(&self) -> Result<Vec<String>> {
+        self.database.list_import_sources().await
+    }
+
+    /// Imported file paths and the IDs of the posts they became
+    pub async fn import_paths(&self) -> Result<HashMap<String, String>> {
+        self.database.list_import_paths().await
+    }
+
+    /// Remember that the markdown file at `source_path` with this hash was
+    /// imported as a post
+    pub async fn record_import_source(
+        &self,
+        source_hash: &str,
+        post_id: &str,
+        source_path: &str,
+    ) -> Result<()> {
+        self.database
+            .insert_import_source(source_hash, post_id, source_path)
+            .await
+    }
+
+    /// What may still point at stored content, for `storage gc`
+    pub async fn storage_references(&self) -> Result<StorageReferences> {
+        self.database.storage_references().await
+    }
+
+    /// Resolve a storage ID the post was previously stored under to its current one
+    pub async fn resolve_storage_id(&self, storage_id: &str) -> Result<String> {
+        Ok(self
+            .database
+            .resolve_storage_alias(storage_id)
+            .await?
+            .unwrap_or_else(|| storage_id.to_string()))
     }
 
     pub async fn get_post(&self, storage_id: &str) -> Result<BlogPost> {
@@ -51,17 +256,130 @@ impl BlogManager {
             return Ok(post);
         }
 
-        // Fallback to storage
-        let storage = self.storage_manager.default_backend();
-        let content = storage.retrieve(storage_id).await?;
+        // Old storage IDs of edited posts
+        if let Some(current_id) = self.database.resolve_storage_alias(storage_id).await? {
+            if let Some(post) = self.database.get_post_by_storage_id(&current_id).await? {
+                return Ok(post);
+            }
+        }
+
+        // Fallback to storage, trying each copy the post was written to
+        let locations = self.database.list_post_locations(storage_id).await?;
+        let content = match locations.first() {
+            Some(first) => {
+                let copies: Vec<(StorageBackend, String)> = locations
+                    .iter()
+                    .filter_map(|l| Some((l.backend.parse().ok()?, l.location.clone())))
+                    .collect();
+                self.storage_manager
+                    .retrieve_replica(&copies, &first.hash)
+                    .await?
+            }
+            None => {
+                self.storage_manager
+                    .default_backend()
+                    .retrieve(storage_id)
+                    .await?
+            }
+        };
         let post: BlogPost = serde_json::from_slice(&content)?;
         Ok(post)
     }
 
-    pub async fn update_post(&mut self, post: &BlogPost) -> Result<()> {
+    pub async fn find_post(&self, id: &str) -> Result<Option<(String, BlogPost)>> {
+        let id = self.resolve_storage_id(id.trim()).await?;
+        if id.is_empty() {
+            anyhow::bail!("Give the storage ID of a post");
+        }
+        let posts = self.database.list_posts(false).await?;
+        let mut matching: Vec<(String, BlogPost)> = posts
+            .into_iter()
+            .filter(|(storage_id, _)| storage_id.starts_with(&id))
+            .collect();
+        if let Some(exact) = matching
+            .iter()
+            .position(|(storage_id, _)| *storage_id == id)
+        {
+            return Ok(Some(matching.swap_remove(exact)));
+        }
+        if matching.len() > 1 {
+            anyhow::bail!(
+                "{} is the start of {} posts' storage IDs, give more of it",
+                id,
+                matching.len()
+            );
+        }
+        Ok(matching.pop())
+    }
+
+    /// Find a post by slug, the newest one if several share it
+    pub async fn find_post_by_slug(&self, slug: &str) -> Result<Option<(String, BlogPost)>> {
+        let posts = self.database.list_posts(false).await?;
+        Ok(posts
+            .into_iter()
+            .filter(|(_, post)| post.slug == slug)
+            .max_by_key(|(_, post)| post.created_at))
+    }
+
+    /// Save an edited post, re-storing it in the storage backend. Returns the new storage ID.
+    pub async fn update_post(&mut self, post: &BlogPost) -> Result<String> {
+        let storage_id = post.storage_id.clone().unwrap_or_default();
+
+        // Posts created before revisions were tracked get their current state
+        // recorded first, so the edit can still be diffed and rolled back
+        if self.database.list_revisions(&post.id).await?.is_empty() {
+            if let Some(current) = self.database.get_post_by_storage_id(&storage_id).await? {
+                self.database.insert_revision(&current, &storage_id).await?;
+            }
+        }
+
+        let mut updated = post.clone();
+        updated.content_hash = BlogPost::calculate_hash(&updated.content);
+        updated.updated_at = chrono::Utc::now();
+
+        // Store the new version so the storage copies don't go stale
+        let replicas = self.store_post(&updated).await?;
+        let new_storage_id = replicas[0].result.id.clone();
+        if new_storage_id != storage_id {
+            self.database
+                .replace_storage_id(&post.id, &storage_id, &new_storage_id)
+                .await?;
+        }
+        updated.storage_id = Some(new_storage_id.clone());
+
         // Update the post in database
-        self.database.update_post(post).await?;
-        Ok(())
+        let published = self.database.update_post(&updated).await?;
+        self.database
+            .insert_revision(&updated, &new_storage_id)
+            .await?;
+        self.record_locations(&post.id, &replicas).await?;
+        if published {
+            self.went_live(&updated).await;
+        }
+        Ok(new_storage_id)
+    }
+
+    pub async fn list_revisions(&self, post_id: &str) -> Result<Vec<PostRevision>> {
+        self.database.list_revisions(post_id).await
+    }
+
+    pub async fn get_revision(&self, post_id: &str, revision: i64) -> Result<PostRevision> {
+        self.database
+            .get_revision(post_id, revision)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Revision {} not found for post {}", revision, post_id))
+    }
+
+    pub async fn rollback_post(&mut self, storage_id: &str, revision: i64) -> Result<BlogPost> {
+        let mut post = self.get_post(storage_id).await?;
+        let target = self.get_revision(&post.id, revision).await?;
+
+        post.title = target.title;
+        post.update_content(target.content);
+        let new_storage_id = self.update_post(&post).await?;
+        post.storage_id = Some(new_storage_id);
+
+        Ok(post)
     }
 
     pub async fn delete_post(&mut self, post_id: &str) -> Result<()> {
@@ -71,12 +389,42 @@ impl BlogManager {
     }
 
     pub async fn publish_post(&mut self, storage_id: &str) -> Result<()> {
+        let storage_id = self.resolve_storage_id(storage_id).await?;
+        if self
+            .database
+            .update_post_published(&storage_id, true)
+            .await?
+        {
+            if let Some(post) = self.database.get_post_by_storage_id(&storage_id).await? {
+                self.went_live(&post).await;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn schedule_post(
+        &mut self,
+        storage_id: &str,
+        publish_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let storage_id = self.resolve_storage_id(storage_id).await?;
         self.database
-            .update_post_published(storage_id, true)
+            .update_post_publish_at(&storage_id, Some(publish_at))
             .await?;
         Ok(())
     }
 
+    /// Publish scheduled posts whose time has come. Returns how many there were.
+    pub async fn publish_due_posts(&mut self) -> Result<u64> {
+        let storage_ids = self.database.publish_due_posts().await?;
+        for storage_id in &storage_ids {
+            if let Some(post) = self.database.get_post_by_storage_id(storage_id).await? {
+                self.went_live(&post).await;
+            }
+        }
+        Ok(storage_ids.len() as u64)
+    }
+
     pub async fn list_posts(&self, published_only: bool) -> Result<Vec<(String, BlogPost)>> {
         self.database.list_posts(published_only).await
     }
@@ -109,3 +457,227 @@ impl BlogManager {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::Json;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+    use test_support::blog;
+
+    #[tokio::test]
+    async fn test_update_keeps_revisions_and_aliases() {
+        let (_dir, mut blog_manager) = blog("blog").await;
+        let post = BlogPost::new("Hello".to_string(), "First".to_string(), "me".to_string());
+        let old_storage_id = blog_manager.create_post(post).await.unwrap();
+
+        let mut post = blog_manager.get_post(&old_storage_id).await.unwrap();
+        post.title = "Hello again".to_string();
+        post.update_content("Second".to_string());
+        let new_storage_id = blog_manager.update_post(&post).await.unwrap();
+        assert_ne!(new_storage_id, old_storage_id);
+
+        let revisions = blog_manager.list_revisions(&post.id).await.unwrap();
+        let contents: Vec<(i64, &str, &str)> = revisions
+            .iter()
+            .map(|r| (r.revision, r.content.as_str(), r.storage_id.as_str()))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                (1, "First", old_storage_id.as_str()),
+                (2, "Second", new_storage_id.as_str())
+            ]
+        );
+
+        // Links to the old storage ID still find the post
+        assert_eq!(
+            blog_manager
+                .resolve_storage_id(&old_storage_id)
+                .await
+                .unwrap(),
+            new_storage_id
+        );
+        let resolved = blog_manager.get_post(&old_storage_id).await.unwrap();
+        assert_eq!(resolved.id, post.id);
+        assert_eq!(resolved.content, "Second");
+
+        // Rolling back is another revision, not a rewrite of history
+        let rolled_back = blog_manager
+            .rollback_post(&old_storage_id, 1)
+            .await
+            .unwrap();
+        assert_eq!(rolled_back.content, "First");
+        assert_eq!(
+            blog_manager.list_revisions(&post.id).await.unwrap().len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_post_by_prefix() {
+        let (_dir, mut blog_manager) = blog("find").await;
+        for (title, storage_id) in [("One", "shared-1"), ("Two", "shared-2")] {
+            let post = BlogPost::new(title.to_string(), title.to_string(), "me".to_string());
+            blog_manager.create_post(post.clone()).await.unwrap();
+            sqlx::query("UPDATE posts SET storage_id = ?1 WHERE id = ?2")
+                .bind(storage_id)
+                .bind(&post.id)
+                .execute(blog_manager.database.pool())
+                .await
+                .unwrap();
+        }
+
+        let title = |found: Option<(String, BlogPost)>| found.unwrap().1.title;
+        assert_eq!(
+            title(blog_manager.find_post("shared-2").await.unwrap()),
+            "Two"
+        );
+        assert_eq!(
+            title(blog_manager.find_post("shared-1").await.unwrap()),
+            "One"
+        );
+        assert!(blog_manager.find_post("shared").await.is_err());
+        assert!(blog_manager.find_post("").await.is_err());
+        assert!(blog_manager.find_post("other").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_publishing() {
+        let (_dir, mut blog_manager) = blog("blog").await;
+        let now = chrono::Utc::now();
+        let mut ids = Vec::new();
+        for (title, publish_at) in [
+            ("Later", Some(now + chrono::Duration::hours(1))),
+            ("Due", Some(now - chrono::Duration::minutes(1))),
+            ("Draft", None),
+        ] {
+            let mut post = BlogPost::new(title.to_string(), title.to_string(), "me".to_string());
+            post.publish_at = publish_at;
+            ids.push(post.id.clone());
+            blog_manager.create_post(post).await.unwrap();
+        }
+        let published_titles = |posts: Vec<(String, BlogPost)>| {
+            let mut titles: Vec<String> = posts.into_iter().map(|(_, p)| p.title).collect();
+            titles.sort();
+            titles
+        };
+
+        // Due posts show up before the scheduler has run, future ones don't
+        let published = blog_manager.list_posts(true).await.unwrap();
+        assert_eq!(published_titles(published), vec!["Due"]);
+
+        assert_eq!(blog_manager.publish_due_posts().await.unwrap(), 1);
+        assert_eq!(blog_manager.publish_due_posts().await.unwrap(), 0);
+        let stored: Vec<(String, bool)> =
+            sqlx::query_as("SELECT title, published FROM posts ORDER BY title")
+                .fetch_all(blog_manager.database.pool())
+                .await
+                .unwrap();
+        assert_eq!(
+            stored,
+            vec![
+                ("Draft".to_string(), false),
+                ("Due".to_string(), true),
+                ("Later".to_string(), false)
+            ]
+        );
+
+        // Once its time comes, the scheduled post is visible and gets published
+        sqlx::query("UPDATE posts SET publish_at = ?1 WHERE id = ?2")
+            .bind(now - chrono::Duration::seconds(1))
+            .bind(&ids[0])
+            .execute(blog_manager.database.pool())
+            .await
+            .unwrap();
+        let published = blog_manager.list_posts(true).await.unwrap();
+        assert_eq!(published_titles(published), vec!["Due", "Later"]);
+        assert_eq!(blog_manager.publish_due_posts().await.unwrap(), 1);
+    }
+
+    /// A federating blog whose one follower's inbox records what it's sent
+    async fn federating_blog() -> (test_support::TempDir, BlogManager, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let inbox = {
+            let received = received.clone();
+            axum::routing::post(move |Json(activity): Json<Value>| async move {
+                received.lock().unwrap().push(activity);
+                StatusCode::ACCEPTED
+            })
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route("/inbox", inbox);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (dir, mut blog_manager) = blog("federation").await;
+        let client = reqwest::Client::builder()
+            .resolve("follower.example", addr)
+            .no_proxy()
+            .build()
+            .unwrap();
+        let federation =
+            Federation::new(blog_manager.database.pool().clone(), &SiteConfig::default())
+                .await
+                .unwrap()
+                .with_client(client);
+        federation
+            .followers()
+            .add(
+                "http://follower.example/actor",
+                &format!("http://follower.example:{}/inbox", addr.port()),
+                None,
+            )
+            .await
+            .unwrap();
+        blog_manager.federation = Some(federation);
+        (dir, blog_manager, received)
+    }
+
+    #[tokio::test]
+    async fn test_every_way_of_publishing_federates() {
+        let (_dir, mut blog_manager, received) = federating_blog().await;
+        let delivered = |received: &Arc<Mutex<Vec<Value>>>| -> Vec<String> {
+            received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|activity| {
+                    assert_eq!(activity["type"], "Create");
+                    activity["object"]["name"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+
+        // Created published, as `kpgb new --publish` and the write APIs do
+        let mut post = BlogPost::new("Live".to_string(), "Live".to_string(), "me".to_string());
+        post.published = true;
+        blog_manager.create_post(post).await.unwrap();
+        assert_eq!(delivered(&received), vec!["Live"]);
+
+        // Drafts wait until an update publishes them, and go out once
+        let draft = BlogPost::new("Draft".to_string(), "Draft".to_string(), "me".to_string());
+        let storage_id = blog_manager.create_post(draft).await.unwrap();
+        assert_eq!(delivered(&received).len(), 1);
+        let mut draft = blog_manager.get_post(&storage_id).await.unwrap();
+        draft.published = true;
+        let storage_id = blog_manager.update_post(&draft).await.unwrap();
+        blog_manager.publish_post(&storage_id).await.unwrap();
+        draft.update_content("Edited".to_string());
+        blog_manager.update_post(&draft).await.unwrap();
+        assert_eq!(delivered(&received), vec!["Live", "Draft"]);
+
+        // Scheduled posts go out when the scheduler publishes them
+        let mut scheduled = BlogPost::new(
+            "Scheduled".to_string(),
+            "Scheduled".to_string(),
+            "me".to_string(),
+        );
+        scheduled.publish_at = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+        blog_manager.create_post(scheduled).await.unwrap();
+        assert_eq!(blog_manager.publish_due_posts().await.unwrap(), 1);
+        assert_eq!(delivered(&received), vec!["Live", "Draft", "Scheduled"]);
+    }
+}
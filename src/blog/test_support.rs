@@ -0,0 +1,70 @@
+//! Fixtures for tests that need a blog backed by a real database and storage
+
+use super::BlogManager;
+use crate::storage::local::LocalStorage;
+use crate::storage::{StorageBackend, StorageManager};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir, removed again on drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("kpgb-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A blog in `dir`: local storage in `storage/` and the database in `blog.db`
+pub async fn blog_in(dir: &TempDir) -> BlogManager {
+    let mut storage_manager = StorageManager::new(StorageBackend::Local);
+    storage_manager.add_backend(
+        StorageBackend::Local,
+        Box::new(LocalStorage::new(dir.path().join("storage")).unwrap()),
+    );
+    let url = format!("sqlite:{}?mode=rwc", dir.path().join("blog.db").display());
+    BlogManager::new(storage_manager, &url).await.unwrap()
+}
+
+/// A blog in a new temp dir, which lives as long as the returned guard
+pub async fn blog(name: &str) -> (TempDir, BlogManager) {
+    let dir = TempDir::new(name);
+    let blog_manager = blog_in(&dir).await;
+    (dir, blog_manager)
+}
+
+/// A post's row as stored, before scheduled posts count as published
+#[derive(Debug, PartialEq)]
+pub struct StoredPost {
+    pub title: String,
+    pub slug: String,
+    pub published: bool,
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+pub async fn stored_post(blog_manager: &BlogManager, post_id: &str) -> StoredPost {
+    let (title, slug, published, publish_at) =
+        sqlx::query_as("SELECT title, slug, published, publish_at FROM posts WHERE id = ?1")
+            .bind(post_id)
+            .fetch_one(blog_manager.database.pool())
+            .await
+            .unwrap();
+    StoredPost {
+        title,
+        slug,
+        published,
+        publish_at,
+    }
+}
@@ -0,0 +1,308 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
+
+/// Replies nested deeper than this are shown at this depth
+pub const MAX_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comment {
+    pub id: String,
+    pub post_id: String,
+    pub parent_id: Option<String>,
+    pub author_name: String,
+    /// Only shown to the moderator, never rendered
+    #[serde(skip_serializing)]
+    pub author_email: Option<String>,
+    pub author_url: Option<String>,
+    pub content: String,
+    /// `pending` until approved
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub approved_at: Option<DateTime<Utc>>,
+    /// ID of the ActivityPub object for replies from the fediverse
+    pub remote_id: Option<String>,
+}
+
+/// A comment as submitted through the form or received as a reply
+#[derive(Debug, Clone, Default)]
+pub struct NewComment {
+    pub post_id: String,
+    pub parent_id: Option<String>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub author_url: Option<String>,
+    pub content: String,
+    pub remote_id: Option<String>,
+}
+
+/// A comment in thread order, with how deeply it is nested
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadedComment {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub depth: usize,
+}
+
+/// Comments of posts, in the blog database
+#[derive(Clone)]
+pub struct CommentStore {
+    pool: SqlitePool,
+}
+
+impl CommentStore {
+    /// Tables are created by the migrations run in `Database::new`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a comment, approved right away or queued for moderation.
+    /// Replies must answer an approved comment on the same post.
+    pub async fn add(&self, new: &NewComment, approved: bool) -> Result<Comment> {
+        if let Some(parent_id) = &new.parent_id {
+            let parent = self.get(parent_id).await?;
+            if !parent.is_some_and(|p| p.post_id == new.post_id && p.status == "approved") {
+                bail!("Replied to comment not found: {}", parent_id);
+            }
+        }
+
+        let now = Utc::now();
+        let comment = Comment {
+            id: uuid::Uuid::new_v4().to_string(),
+            post_id: new.post_id.clone(),
+            parent_id: new.parent_id.clone(),
+            author_name: new.author_name.clone(),
+            author_email: new.author_email.clone(),
+            author_url: new.author_url.clone(),
+            content: new.content.clone(),
+            status: if approved { "approved" } else { "pending" }.to_string(),
+            created_at: now,
+            approved_at: approved.then_some(now),
+            remote_id: new.remote_id.clone(),
+        };
+
+        query(
+            r#"
+            INSERT INTO comments (id, post_id, parent_id, author_name, author_email,
+                                  author_url, content, status, created_at, approved_at,
+                                  remote_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            "#,
+        )
+        .bind(&comment.id)
+        .bind(&comment.post_id)
+        .bind(&comment.parent_id)
+        .bind(&comment.author_name)
+        .bind(&comment.author_email)
+        .bind(&comment.author_url)
+        .bind(&comment.content)
+        .bind(&comment.status)
+        .bind(comment.created_at)
+        .bind(comment.approved_at)
+        .bind(&comment.remote_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(comment)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Comment>> {
+        let row = query("SELECT * FROM comments WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Self::row_to_comment))
+    }
+
+    pub async fn get_remote(&self, remote_id: &str) -> Result<Option<Comment>> {
+        let row = query("SELECT * FROM comments WHERE remote_id = ?1")
+            .bind(remote_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Self::row_to_comment))
+    }
+
+    /// All comments, newest first, optionally only those with `status`
+    pub async fn list(&self, status: Option<&str>) -> Result<Vec<Comment>> {
+        let rows = query(
+            r#"
+            SELECT * FROM comments
+            WHERE ?1 IS NULL OR status = ?1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_comment).collect())
+    }
+
+    /// Approved comments of a post, oldest first
+    pub async fn approved_for_post(&self, post_id: &str) -> Result<Vec<Comment>> {
+        let rows = query(
+            r#"
+            SELECT * FROM comments
+            WHERE post_id = ?1 AND status = 'approved'
+            ORDER BY created_at
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_comment).collect())
+    }
+
+    /// Approve pending comments with this ID (or ID prefix). Returns the
+    /// approved comments.
+    pub async fn approve(&self, id: &str) -> Result<Vec<Comment>> {
+        let matching: Vec<Comment> = self
+            .list(Some("pending"))
+            .await?
+            .into_iter()
+            .filter(|c| c.id.starts_with(id))
+            .collect();
+
+        for comment in &matching {
+            query("UPDATE comments SET status = 'approved', approved_at = ?1 WHERE id = ?2")
+                .bind(Utc::now())
+                .bind(&comment.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(matching)
+    }
+
+    /// Delete comments with this ID (or ID prefix), and their replies.
+    /// Returns the deleted comments, not counting replies.
+    pub async fn delete(&self, id: &str) -> Result<Vec<Comment>> {
+        let matching: Vec<Comment> = self
+            .list(None)
+            .await?
+            .into_iter()
+            .filter(|c| c.id.starts_with(id))
+            .collect();
+
+        for comment in &matching {
+            query("DELETE FROM comments WHERE id = ?1")
+                .bind(&comment.id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(matching)
+    }
+
+    fn row_to_comment(row: &SqliteRow) -> Comment {
+        Comment {
+            id: row.get("id"),
+            post_id: row.get("post_id"),
+            parent_id: row.get("parent_id"),
+            author_name: row.get("author_name"),
+            author_email: row.get("author_email"),
+            author_url: row.get("author_url"),
+            content: row.get("content"),
+            status: row.get("status"),
+            created_at: row.get("created_at"),
+            approved_at: row.get("approved_at"),
+            remote_id: row.get("remote_id"),
+        }
+    }
+}
+
+/// Order comments for display: every comment followed by its replies.
+/// Replies whose parent isn't in `comments` are shown at the top level.
+pub fn thread(comments: Vec<Comment>) -> Vec<ThreadedComment> {
+    fn push_replies(
+        parent: Option<&str>,
+        depth: usize,
+        comments: &[Comment],
+        roots: &[bool],
+        threaded: &mut Vec<ThreadedComment>,
+    ) {
+        for (i, comment) in comments.iter().enumerate() {
+            let is_child = match parent {
+                Some(parent) => comment.parent_id.as_deref() == Some(parent),
+                None => roots[i],
+            };
+            if is_child {
+                threaded.push(ThreadedComment {
+                    comment: comment.clone(),
+                    depth: depth.min(MAX_DEPTH),
+                });
+                push_replies(Some(&comment.id), depth + 1, comments, roots, threaded);
+            }
+        }
+    }
+
+    let roots: Vec<bool> = comments
+        .iter()
+        .map(|c| match &c.parent_id {
+            Some(parent) => !comments.iter().any(|p| &p.id == parent),
+            None => true,
+        })
+        .collect();
+
+    let mut threaded = Vec::with_capacity(comments.len());
+    push_replies(None, 0, &comments, &roots, &mut threaded);
+    threaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::BlogPost;
+
+    #[tokio::test]
+    async fn test_comments() {
+        let path = std::env::temp_dir().join(format!("kpgb-comments-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let database = Database::new(&url).await.unwrap();
+        let post = BlogPost::new("Hello".to_string(), "Hi".to_string(), "me".to_string());
+        database.insert_post(&post, "storage-1").await.unwrap();
+        let store = CommentStore::new(database.pool().clone());
+
+        let new = |parent_id: Option<&str>, content: &str| NewComment {
+            post_id: post.id.clone(),
+            parent_id: parent_id.map(str::to_string),
+            author_name: "Ann".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        };
+
+        let first = store.add(&new(None, "first"), false).await.unwrap();
+        assert!(store.approved_for_post(&post.id).await.unwrap().is_empty());
+        // Can't reply to a comment that isn't approved yet
+        assert!(store.add(&new(Some(&first.id), "re"), true).await.is_err());
+
+        assert_eq!(store.approve(&first.id[..8]).await.unwrap().len(), 1);
+        let reply = store.add(&new(Some(&first.id), "re"), true).await.unwrap();
+        let second = store.add(&new(None, "second"), true).await.unwrap();
+        store
+            .add(&new(Some(&reply.id), "re re"), true)
+            .await
+            .unwrap();
+
+        let threaded = thread(store.approved_for_post(&post.id).await.unwrap());
+        let order: Vec<(&str, usize)> = threaded
+            .iter()
+            .map(|t| (t.comment.content.as_str(), t.depth))
+            .collect();
+        assert_eq!(
+            order,
+            vec![("first", 0), ("re", 1), ("re re", 2), ("second", 0)]
+        );
+
+        // Deleting a comment takes its replies along
+        assert_eq!(store.delete(&first.id).await.unwrap().len(), 1);
+        let left = store.list(None).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, second.id);
+        std::fs::remove_file(&path).ok();
+    }
+}
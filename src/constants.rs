@@ -10,6 +10,11 @@ pub const STORAGE_GITHUB: &str = "github";
 
 // Default values
 pub const DEFAULT_IPFS_API_URL: &str = "http://localhost:5001";
+/// The node's own key, which `kpgb deploy ipfs` publishes to by default
+pub const DEFAULT_IPNS_KEY: &str = "self";
+pub const LOCAL_STORAGE_PATH: &str = "./storage/local";
+/// Content stored this recently is never garbage-collected
+pub const GC_GRACE_PERIOD_MINUTES: i64 = 60;
 pub const DEFAULT_POSTS_PER_PAGE: usize = 10;
 pub const DEFAULT_EXCERPT_LENGTH: usize = 300;
 
@@ -1,8 +1,13 @@
-use crate::models::BlogPost;
+use crate::models::{BlogPost, PostLocation, PostRevision};
+use crate::storage::local::StorageReferences;
 use anyhow::Result;
-use sqlx::{sqlite::SqlitePoolOptions, Row, SqlitePool};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqlitePoolOptions, SqliteRow};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
 use std::time::Duration;
 
+#[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
 }
@@ -21,6 +26,10 @@ impl Database {
         Ok(Self { pool })
     }
 
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
     async fn load_tags_for_post(&self, post_id: &str) -> Result<Vec<String>> {
         let tags: Vec<String> = sqlx::query_scalar(
             r#"
@@ -44,8 +53,9 @@ impl Database {
         sqlx::query(
             r#"
             INSERT INTO posts (id, storage_id, title, slug, content, excerpt, author, 
-                             content_hash, created_at, updated_at, published, category)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                             content_hash, created_at, updated_at, published, category,
+                             publish_at, lang, translation_of)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
             "#,
         )
         .bind(&post.id)
@@ -60,6 +70,9 @@ impl Database {
         .bind(post.updated_at)
         .bind(post.published)
         .bind(&post.category)
+        .bind(post.publish_at)
+        .bind(&post.lang)
+        .bind(&post.translation_of)
         .execute(&mut *tx)
         .await?;
 
@@ -93,7 +106,8 @@ impl Database {
         let row = sqlx::query(
             r#"
             SELECT id, title, slug, content, excerpt, author,
-                   created_at, updated_at, published, category, storage_id, content_hash
+                   created_at, updated_at, published, category, storage_id, content_hash, publish_at,
+                   lang, translation_of
             FROM posts
             WHERE storage_id = ?1
             "#,
@@ -103,21 +117,7 @@ impl Database {
         .await?;
 
         if let Some(row) = row {
-            let mut post = BlogPost {
-                id: row.get("id"),
-                title: row.get("title"),
-                slug: row.get("slug"),
-                content: row.get("content"),
-                excerpt: row.get("excerpt"),
-                author: row.get("author"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                published: row.get("published"),
-                tags: Vec::new(),
-                category: row.get("category"),
-                storage_id: row.get("storage_id"),
-                content_hash: row.get("content_hash"),
-            };
+            let mut post = Self::row_to_post(&row);
 
             // Load tags
             post.tags = self.load_tags_for_post(&post.id).await?;
@@ -136,10 +136,28 @@ impl Database {
         Ok(storage_id)
     }
 
-    pub async fn update_post_published(&self, storage_id: &str, published: bool) -> Result<()> {
-        sqlx::query("UPDATE posts SET published = ?1, updated_at = ?2 WHERE storage_id = ?3")
-            .bind(published)
-            .bind(chrono::Utc::now())
+    /// Set a post's `published` flag. Returns false if it already had that value.
+    pub async fn update_post_published(&self, storage_id: &str, published: bool) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE posts SET published = ?1, updated_at = ?2 WHERE storage_id = ?3 AND published != ?1",
+        )
+        .bind(published)
+        .bind(chrono::Utc::now())
+        .bind(storage_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn update_post_publish_at(
+        &self,
+        storage_id: &str,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE posts SET publish_at = ?1, updated_at = ?2 WHERE storage_id = ?3")
+            .bind(publish_at)
+            .bind(Utc::now())
             .bind(storage_id)
             .execute(&self.pool)
             .await?;
@@ -147,16 +165,93 @@ impl Database {
         Ok(())
     }
 
-    pub async fn update_post(&self, post: &BlogPost) -> Result<()> {
+    /// Mark scheduled posts whose publish time has passed as published.
+    /// Returns their storage IDs.
+    pub async fn publish_due_posts(&self) -> Result<Vec<String>> {
+        let now = Utc::now();
+        let storage_ids = sqlx::query_scalar(
+            r#"
+            UPDATE posts
+            SET published = 1, updated_at = ?1
+            WHERE published = 0 AND publish_at <= ?1
+            RETURNING storage_id
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(storage_ids)
+    }
+
+    pub async fn replace_storage_id(
+        &self,
+        post_id: &str,
+        old_storage_id: &str,
+        new_storage_id: &str,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE posts SET storage_id = ?1 WHERE id = ?2")
+            .bind(new_storage_id)
+            .bind(post_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Keep the previous ID resolvable so published links don't break
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO post_storage_aliases (storage_id, post_id, created_at)
+            VALUES (?1, ?2, ?3)
+            "#,
+        )
+        .bind(old_storage_id)
+        .bind(post_id)
+        .bind(chrono::Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Map a storage ID that a post was previously stored under to its current one
+    pub async fn resolve_storage_alias(&self, storage_id: &str) -> Result<Option<String>> {
+        let current = sqlx::query_scalar(
+            r#"
+            SELECT p.storage_id
+            FROM post_storage_aliases a
+            JOIN posts p ON p.id = a.post_id
+            WHERE a.storage_id = ?1
+            "#,
+        )
+        .bind(storage_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(current)
+    }
+
+    /// Write a post's fields and tags. Returns whether this published a post
+    /// that was stored as unpublished.
+    pub async fn update_post(&self, post: &BlogPost) -> Result<bool> {
         let mut tx = self.pool.begin().await?;
 
+        let was_published: Option<bool> =
+            sqlx::query_scalar("SELECT published FROM posts WHERE id = ?1")
+                .bind(&post.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
         // Update post content and metadata
         sqlx::query(
             r#"
             UPDATE posts 
             SET title = ?1, content = ?2, excerpt = ?3, author = ?4, 
-                category = ?5, updated_at = ?6, content_hash = ?7
-            WHERE id = ?8
+                category = ?5, updated_at = ?6, content_hash = ?7,
+                lang = ?8, translation_of = ?9, slug = ?10, published = ?11,
+                publish_at = ?12
+            WHERE id = ?13
             "#,
         )
         .bind(&post.title)
@@ -166,6 +261,11 @@ impl Database {
         .bind(&post.category)
         .bind(chrono::Utc::now())
         .bind(BlogPost::calculate_hash(&post.content))
+        .bind(&post.lang)
+        .bind(&post.translation_of)
+        .bind(&post.slug)
+        .bind(post.published)
+        .bind(post.publish_at)
         .bind(&post.id)
         .execute(&mut *tx)
         .await?;
@@ -199,7 +299,7 @@ impl Database {
         }
 
         tx.commit().await?;
-        Ok(())
+        Ok(post.published && was_published == Some(false))
     }
 
     pub async fn delete_post(&self, post_id: &str) -> Result<()> {
@@ -221,43 +321,310 @@ impl Database {
         Ok(())
     }
 
+    pub async fn insert_revision(&self, post: &BlogPost, storage_id: &str) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+
+        let revision: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(revision), 0) + 1 FROM post_revisions WHERE post_id = ?1",
+        )
+        .bind(&post.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO post_revisions (post_id, revision, title, content, content_hash,
+                                        storage_id, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+        )
+        .bind(&post.id)
+        .bind(revision)
+        .bind(&post.title)
+        .bind(&post.content)
+        .bind(BlogPost::calculate_hash(&post.content))
+        .bind(storage_id)
+        .bind(chrono::Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(revision)
+    }
+
+    pub async fn list_revisions(&self, post_id: &str) -> Result<Vec<PostRevision>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT post_id, revision, title, content, content_hash, storage_id, created_at
+            FROM post_revisions
+            WHERE post_id = ?1
+            ORDER BY revision ASC
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_revision).collect())
+    }
+
+    pub async fn get_revision(&self, post_id: &str, revision: i64) -> Result<Option<PostRevision>> {
+        let row = sqlx::query(
+            r#"
+            SELECT post_id, revision, title, content, content_hash, storage_id, created_at
+            FROM post_revisions
+            WHERE post_id = ?1 AND revision = ?2
+            "#,
+        )
+        .bind(post_id)
+        .bind(revision)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(Self::row_to_revision))
+    }
+
+    pub async fn insert_post_locations(&self, locations: &[PostLocation]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for location in locations {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO post_locations
+                    (storage_id, post_id, backend, location, hash, size, status, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )
+            .bind(&location.storage_id)
+            .bind(&location.post_id)
+            .bind(&location.backend)
+            .bind(&location.location)
+            .bind(&location.hash)
+            .bind(location.size)
+            .bind(&location.status)
+            .bind(location.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Copies of a post version, those last found intact first
+    pub async fn list_post_locations(&self, storage_id: &str) -> Result<Vec<PostLocation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM post_locations
+            WHERE storage_id = ?1
+            ORDER BY status != 'ok', rowid
+            "#,
+        )
+        .bind(storage_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_location).collect())
+    }
+
+    /// Copies of the current version of every post, or of one post
+    pub async fn list_current_locations(&self, post_id: Option<&str>) -> Result<Vec<PostLocation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT l.* FROM post_locations l
+            JOIN posts p ON p.storage_id = l.storage_id
+            WHERE ?1 IS NULL OR l.post_id = ?1
+            ORDER BY p.created_at, l.rowid
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_location).collect())
+    }
+
+    pub async fn update_location_status(
+        &self,
+        storage_id: &str,
+        backend: &str,
+        status: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE post_locations SET status = ?1, verified_at = ?2
+            WHERE storage_id = ?3 AND backend = ?4
+            "#,
+        )
+        .bind(status)
+        .bind(Utc::now())
+        .bind(storage_id)
+        .bind(backend)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The `published` column of every post, before due scheduled posts are
+    /// counted as published
+    pub async fn list_stored_published(&self) -> Result<HashMap<String, bool>> {
+        let rows: Vec<(String, bool)> = sqlx::query_as("SELECT id, published FROM posts")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Hashes of the markdown files posts were imported from
+    pub async fn list_import_sources(&self) -> Result<Vec<String>> {
+        let hashes = sqlx::query_scalar("SELECT source_hash FROM post_import_sources")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(hashes)
+    }
+
+    /// The post each imported file path was last imported as
+    pub async fn list_import_paths(&self) -> Result<HashMap<String, String>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT source_path, post_id FROM post_import_sources
+            WHERE source_path IS NOT NULL
+            ORDER BY imported_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    pub async fn insert_import_source(
+        &self,
+        source_hash: &str,
+        post_id: &str,
+        source_path: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO post_import_sources
+                (source_hash, post_id, imported_at, source_path)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+        .bind(source_hash)
+        .bind(post_id)
+        .bind(Utc::now())
+        .bind(source_path)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Everything that may point at local storage: the storage IDs of posts,
+    /// revisions, aliases and local copies, and the markdown that links images
+    pub async fn storage_references(&self) -> Result<StorageReferences> {
+        let ids = sqlx::query(
+            r#"
+            SELECT storage_id AS id FROM posts
+            UNION SELECT storage_id FROM post_revisions
+            UNION SELECT storage_id FROM post_storage_aliases
+            UNION SELECT location FROM post_locations WHERE backend = 'local'
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let texts = sqlx::query(
+            r#"
+            SELECT content FROM posts
+            UNION SELECT content FROM post_revisions
+            UNION SELECT content FROM doc_sections
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(StorageReferences {
+            ids: ids.iter().map(|row| row.get("id")).collect(),
+            texts: texts.iter().map(|row| row.get("content")).collect(),
+        })
+    }
+
+    fn row_to_post(row: &SqliteRow) -> BlogPost {
+        let published: bool = row.get("published");
+        let publish_at: Option<DateTime<Utc>> = row.get("publish_at");
+
+        BlogPost {
+            id: row.get("id"),
+            title: row.get("title"),
+            slug: row.get("slug"),
+            content: row.get("content"),
+            excerpt: row.get("excerpt"),
+            author: row.get("author"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            // Scheduled posts count as published once their time has passed
+            published: published || publish_at.is_some_and(|at| at <= Utc::now()),
+            publish_at,
+            tags: Vec::new(),
+            category: row.get("category"),
+            storage_id: row.get("storage_id"),
+            content_hash: row.get("content_hash"),
+            lang: row.get("lang"),
+            translation_of: row.get("translation_of"),
+        }
+    }
+
+    fn row_to_location(row: &SqliteRow) -> PostLocation {
+        PostLocation {
+            storage_id: row.get("storage_id"),
+            post_id: row.get("post_id"),
+            backend: row.get("backend"),
+            location: row.get("location"),
+            hash: row.get("hash"),
+            size: row.get("size"),
+            status: row.get("status"),
+            created_at: row.get("created_at"),
+            verified_at: row.get("verified_at"),
+        }
+    }
+
+    fn row_to_revision(row: &SqliteRow) -> PostRevision {
+        PostRevision {
+            post_id: row.get("post_id"),
+            revision: row.get("revision"),
+            title: row.get("title"),
+            content: row.get("content"),
+            content_hash: row.get("content_hash"),
+            storage_id: row.get("storage_id"),
+            created_at: row.get("created_at"),
+        }
+    }
+
     pub async fn list_posts(&self, published_only: bool) -> Result<Vec<(String, BlogPost)>> {
         let query = if published_only {
             r#"
             SELECT id, title, slug, content, excerpt, author,
-                   created_at, updated_at, published, category, storage_id, content_hash
+                   created_at, updated_at, published, category, storage_id, content_hash, publish_at,
+                   lang, translation_of
             FROM posts
-            WHERE published = 1
+            WHERE published = 1 OR publish_at <= ?1
             ORDER BY created_at DESC
             "#
         } else {
             r#"
             SELECT id, title, slug, content, excerpt, author,
-                   created_at, updated_at, published, category, storage_id, content_hash
+                   created_at, updated_at, published, category, storage_id, content_hash, publish_at,
+                   lang, translation_of
             FROM posts
             ORDER BY created_at DESC
             "#
         };
 
-        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+        let mut rows_query = sqlx::query(query);
+        if published_only {
+            rows_query = rows_query.bind(chrono::Utc::now());
+        }
+        let rows = rows_query.fetch_all(&self.pool).await?;
 
         let mut results = Vec::new();
         for row in rows {
-            let mut post = BlogPost {
-                id: row.get("id"),
-                title: row.get("title"),
-                slug: row.get("slug"),
-                content: row.get("content"),
-                excerpt: row.get("excerpt"),
-                author: row.get("author"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                published: row.get("published"),
-                tags: Vec::new(),
-                category: row.get("category"),
-                storage_id: Some(row.get("storage_id")),
-                content_hash: row.get("content_hash"),
-            };
+            let mut post = Self::row_to_post(&row);
 
             let storage_id = post.storage_id.clone().unwrap_or_default();
 
@@ -274,7 +641,8 @@ impl Database {
         let rows = sqlx::query(
             r#"
             SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.author,
-                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash
+                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash, p.publish_at,
+                   p.lang, p.translation_of
             FROM posts p
             JOIN posts_fts ON p.rowid = posts_fts.rowid
             WHERE posts_fts MATCH ?1
@@ -287,21 +655,7 @@ impl Database {
 
         let mut results = Vec::new();
         for row in rows {
-            let mut post = BlogPost {
-                id: row.get("id"),
-                title: row.get("title"),
-                slug: row.get("slug"),
-                content: row.get("content"),
-                excerpt: row.get("excerpt"),
-                author: row.get("author"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                published: row.get("published"),
-                tags: Vec::new(),
-                category: row.get("category"),
-                storage_id: Some(row.get("storage_id")),
-                content_hash: row.get("content_hash"),
-            };
+            let mut post = Self::row_to_post(&row);
 
             let storage_id = post.storage_id.clone().unwrap_or_default();
 
@@ -320,11 +674,12 @@ impl Database {
             FROM tags t
             LEFT JOIN post_tags pt ON t.id = pt.tag_id
             LEFT JOIN posts p ON pt.post_id = p.id
-            WHERE p.published = 1 OR p.published IS NULL
+            WHERE p.published = 1 OR p.publish_at <= ?1 OR p.published IS NULL
             GROUP BY t.id, t.name
             ORDER BY post_count DESC, t.name ASC
             "#,
         )
+        .bind(chrono::Utc::now())
         .fetch_all(&self.pool)
         .await?;
 
@@ -346,17 +701,19 @@ impl Database {
         let query = if published_only {
             r#"
             SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.author,
-                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash
+                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash, p.publish_at,
+                   p.lang, p.translation_of
             FROM posts p
             JOIN post_tags pt ON p.id = pt.post_id
             JOIN tags t ON pt.tag_id = t.id
-            WHERE t.name = ?1 AND p.published = 1
+            WHERE t.name = ?1 AND (p.published = 1 OR p.publish_at <= ?2)
             ORDER BY p.created_at DESC
             "#
         } else {
             r#"
             SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.author,
-                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash
+                   p.created_at, p.updated_at, p.published, p.category, p.storage_id, p.content_hash, p.publish_at,
+                   p.lang, p.translation_of
             FROM posts p
             JOIN post_tags pt ON p.id = pt.post_id
             JOIN tags t ON pt.tag_id = t.id
@@ -365,25 +722,15 @@ impl Database {
             "#
         };
 
-        let rows = sqlx::query(query).bind(tag).fetch_all(&self.pool).await?;
+        let mut rows_query = sqlx::query(query).bind(tag);
+        if published_only {
+            rows_query = rows_query.bind(chrono::Utc::now());
+        }
+        let rows = rows_query.fetch_all(&self.pool).await?;
 
         let mut results = Vec::new();
         for row in rows {
-            let mut post = BlogPost {
-                id: row.get("id"),
-                title: row.get("title"),
-                slug: row.get("slug"),
-                content: row.get("content"),
-                excerpt: row.get("excerpt"),
-                author: row.get("author"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                published: row.get("published"),
-                tags: Vec::new(),
-                category: row.get("category"),
-                storage_id: Some(row.get("storage_id")),
-                content_hash: row.get("content_hash"),
-            };
+            let mut post = Self::row_to_post(&row);
 
             let storage_id = post.storage_id.clone().unwrap_or_default();
 
@@ -434,6 +781,9 @@ impl Database {
                 p.category,
                 p.storage_id,
                 p.content_hash,
+                p.publish_at,
+                p.lang,
+                p.translation_of,
                 (
                     -- Count shared tags
                     SELECT COUNT(DISTINCT t2.name)
@@ -446,7 +796,7 @@ impl Database {
                 -- Same category bonus
                 CASE WHEN p.category = ?2 AND p.category IS NOT NULL THEN 1 ELSE 0 END AS relevance_score
             FROM posts p
-            WHERE p.id != ?1 AND p.published = 1
+            WHERE p.id != ?1 AND (p.published = 1 OR p.publish_at <= ?4)
             GROUP BY p.id
             HAVING relevance_score > 0
             ORDER BY relevance_score DESC, p.created_at DESC
@@ -457,26 +807,13 @@ impl Database {
             .bind(post_id)
             .bind(category)
             .bind(limit as i64)
+            .bind(chrono::Utc::now())
             .fetch_all(&self.pool)
             .await?;
 
         let mut results = Vec::new();
         for row in rows {
-            let mut post = BlogPost {
-                id: row.get("id"),
-                title: row.get("title"),
-                slug: row.get("slug"),
-                content: row.get("content"),
-                excerpt: row.get("excerpt"),
-                author: row.get("author"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                published: row.get("published"),
-                tags: Vec::new(),
-                category: row.get("category"),
-                storage_id: Some(row.get("storage_id")),
-                content_hash: row.get("content_hash"),
-            };
+            let mut post = Self::row_to_post(&row);
 
             let storage_id = post.storage_id.clone().unwrap_or_default();
 
@@ -501,13 +838,14 @@ impl Database {
         if results.len() < limit {
             let additional_query = r#"
                 SELECT id, title, slug, content, excerpt, author,
-                       created_at, updated_at, published, category, storage_id, content_hash
+                       created_at, updated_at, published, category, storage_id, content_hash, publish_at,
+                       lang, translation_of
                 FROM posts
-                WHERE id != ?1 AND published = 1
+                WHERE id != ?1 AND (published = 1 OR publish_at <= ?4)
                   AND id NOT IN (
                       SELECT p2.id
                       FROM posts p2
-                      WHERE p2.id != ?1 AND p2.published = 1
+                      WHERE p2.id != ?1 AND (p2.published = 1 OR p2.publish_at <= ?4)
                       AND (
                           EXISTS (
                               SELECT 1 FROM post_tags pt1
@@ -525,25 +863,12 @@ impl Database {
                 .bind(post_id)
                 .bind(category)
                 .bind((limit - results.len()) as i64)
+                .bind(chrono::Utc::now())
                 .fetch_all(&self.pool)
                 .await?;
 
             for row in additional_rows {
-                let mut post = BlogPost {
-                    id: row.get("id"),
-                    title: row.get("title"),
-                    slug: row.get("slug"),
-                    content: row.get("content"),
-                    excerpt: row.get("excerpt"),
-                    author: row.get("author"),
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                    published: row.get("published"),
-                    tags: Vec::new(),
-                    category: row.get("category"),
-                    storage_id: Some(row.get("storage_id")),
-                    content_hash: row.get("content_hash"),
-                };
+                let mut post = Self::row_to_post(&row);
 
                 let storage_id = post.storage_id.clone().unwrap_or_default();
 
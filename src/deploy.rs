@@ -0,0 +1,383 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
+use std::path::Path;
+use tracing::info;
+
+use crate::constants::DEFAULT_IPNS_KEY;
+use crate::storage::ipfs::IpfsStorage;
+
+/// One deploy of the generated site to IPFS
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    pub id: i64,
+    /// Root CID of the site
+    pub cid: String,
+    pub ipns_key: Option<String>,
+    /// What `ipns_key` resolved to when published
+    pub ipns_name: Option<String>,
+    pub files: i64,
+    pub size: i64,
+    /// The earlier deploy this one published again
+    pub rollback_of: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The IPNS key from the command line, `IPFS_IPNS_KEY`, or the node's own key
+pub fn ipns_key(key: Option<String>) -> String {
+    key.or_else(|| std::env::var("IPFS_IPNS_KEY").ok())
+        .filter(|key| !key.is_empty())
+        .unwrap_or_else(|| DEFAULT_IPNS_KEY.to_string())
+}
+
+/// History of IPFS deploys, in the blog database
+#[derive(Clone)]
+pub struct DeployStore {
+    pool: SqlitePool,
+}
+
+impl DeployStore {
+    /// Tables are created by the migrations run in `Database::new`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a deploy, returning it with its ID
+    pub async fn record(&self, mut deployment: Deployment) -> Result<Deployment> {
+        let result = query(
+            r#"
+            INSERT INTO ipfs_deploys
+                (cid, ipns_key, ipns_name, files, size, rollback_of, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+        )
+        .bind(&deployment.cid)
+        .bind(&deployment.ipns_key)
+        .bind(&deployment.ipns_name)
+        .bind(deployment.files)
+        .bind(deployment.size)
+        .bind(deployment.rollback_of)
+        .bind(deployment.created_at)
+        .execute(&self.pool)
+        .await?;
+        deployment.id = result.last_insert_rowid();
+        Ok(deployment)
+    }
+
+    /// The most recent deploys, newest first
+    pub async fn list(&self, limit: i64) -> Result<Vec<Deployment>> {
+        let rows = query("SELECT * FROM ipfs_deploys ORDER BY id DESC LIMIT ?1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(Self::row_to_deployment).collect())
+    }
+
+    /// A deploy by its ID, or the newest one with a CID starting with
+    /// `target`. A prefix of several different CIDs is an error.
+    pub async fn find(&self, target: &str) -> Result<Option<Deployment>> {
+        let target = target.trim();
+        if target.is_empty() {
+            bail!("Give a deploy ID or CID");
+        }
+
+        let by_id = query("SELECT * FROM ipfs_deploys WHERE CAST(id AS TEXT) = ?1")
+            .bind(target)
+            .fetch_optional(&self.pool)
+            .await?;
+        if let Some(row) = by_id {
+            return Ok(Some(Self::row_to_deployment(&row)));
+        }
+
+        let matching: Vec<Deployment> = query(
+            r#"
+            SELECT * FROM ipfs_deploys
+            WHERE substr(cid, 1, length(?1)) = ?1
+            ORDER BY id DESC
+            "#,
+        )
+        .bind(target)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(Self::row_to_deployment)
+        .collect();
+        if let Some(other) = matching.iter().find(|d| d.cid != matching[0].cid) {
+            bail!(
+                "{} is the start of several CIDs ({}, {}), give more of it",
+                target,
+                matching[0].cid,
+                other.cid
+            );
+        }
+        Ok(matching.into_iter().next())
+    }
+
+    /// The newest deploy of a different site than the current one
+    pub async fn previous(&self) -> Result<Option<Deployment>> {
+        let row = query(
+            r#"
+            SELECT * FROM ipfs_deploys
+            WHERE cid != (SELECT cid FROM ipfs_deploys ORDER BY id DESC LIMIT 1)
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.as_ref().map(Self::row_to_deployment))
+    }
+
+    fn row_to_deployment(row: &SqliteRow) -> Deployment {
+        Deployment {
+            id: row.get("id"),
+            cid: row.get("cid"),
+            ipns_key: row.get("ipns_key"),
+            ipns_name: row.get("ipns_name"),
+            files: row.get("files"),
+            size: row.get("size"),
+            rollback_of: row.get("rollback_of"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// Add the generated site to IPFS, pin its root and publish it to `key`,
+/// or only add and pin it without a key
+pub async fn deploy(
+    ipfs: &IpfsStorage,
+    store: &DeployStore,
+    dir: &Path,
+    key: Option<&str>,
+) -> Result<Deployment> {
+    if !dir.join("index.html").exists() {
+        return Err(anyhow!(
+            "No index.html in {}; run `kpgb generate` first",
+            dir.display()
+        ));
+    }
+
+    let added = ipfs.add_directory(dir).await?;
+    info!("Added {} files as {}", added.files, added.cid);
+    ipfs.ipfs_pin(&added.cid).await?;
+
+    let ipns_name = match key {
+        Some(key) => Some(ipfs.publish_name(&added.cid, key).await?),
+        None => None,
+    };
+
+    store
+        .record(Deployment {
+            id: 0,
+            cid: added.cid,
+            ipns_key: key.map(str::to_string),
+            ipns_name,
+            files: added.files as i64,
+            size: added.size as i64,
+            rollback_of: None,
+            created_at: Utc::now(),
+        })
+        .await
+}
+
+/// Pin and publish an earlier deploy again: `target` (an ID or CID prefix),
+/// or the one before the current deploy
+pub async fn rollback(
+    ipfs: &IpfsStorage,
+    store: &DeployStore,
+    target: Option<&str>,
+    key: &str,
+) -> Result<Deployment> {
+    let earlier = match target {
+        Some(target) => store
+            .find(target)
+            .await?
+            .ok_or_else(|| anyhow!("Deploy not found: {}", target))?,
+        None => store
+            .previous()
+            .await?
+            .ok_or_else(|| anyhow!("No earlier deploy to roll back to"))?,
+    };
+
+    // It may have been unpinned and collected since
+    ipfs.ipfs_pin(&earlier.cid).await?;
+    let ipns_name = ipfs.publish_name(&earlier.cid, key).await?;
+
+    store
+        .record(Deployment {
+            id: 0,
+            cid: earlier.cid.clone(),
+            ipns_key: Some(key.to_string()),
+            ipns_name: Some(ipns_name),
+            files: earlier.files,
+            size: earlier.size,
+            rollback_of: Some(earlier.id),
+            created_at: Utc::now(),
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use axum::extract::{Multipart, Query, State};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Query parameters of an `add` call and the file name and content type
+    /// of each part it uploaded
+    type Add = (HashMap<String, String>, Vec<(String, String)>);
+
+    /// What the stubbed Kubo API was asked to do
+    #[derive(Default)]
+    struct FakeKubo {
+        adds: Vec<Add>,
+        pins: Vec<String>,
+        published: Vec<(String, String)>,
+    }
+
+    type Kubo = State<Arc<Mutex<FakeKubo>>>;
+
+    async fn add(
+        State(kubo): Kubo,
+        Query(params): Query<HashMap<String, String>>,
+        mut multipart: Multipart,
+    ) -> String {
+        let mut parts = Vec::new();
+        let mut hasher = Sha256::new();
+        let mut lines = Vec::new();
+        while let Some(field) = multipart.next_field().await.unwrap() {
+            let name = field.file_name().unwrap().to_string();
+            let content_type = field.content_type().unwrap().to_string();
+            let bytes = field.bytes().await.unwrap();
+            hasher.update(name.as_bytes());
+            hasher.update(&bytes);
+            let cid = format!("bafy{}", hex::encode(Sha256::digest(&bytes)));
+            lines.push(json!({"Name": name, "Hash": cid, "Size": "1"}).to_string());
+            parts.push((name, content_type));
+        }
+        let root = format!("bafyroot{}", &hex::encode(hasher.finalize())[..16]);
+        lines.push(json!({"Name": "", "Hash": root, "Size": "1"}).to_string());
+        kubo.lock().unwrap().adds.push((params, parts));
+        lines.join("\n")
+    }
+
+    async fn pin(State(kubo): Kubo, Query(params): Query<HashMap<String, String>>) -> Json<Value> {
+        kubo.lock().unwrap().pins.push(params["arg"].clone());
+        Json(json!({"Pins": [params["arg"]]}))
+    }
+
+    async fn publish(
+        State(kubo): Kubo,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Json<Value> {
+        let (path, key) = (params["arg"].clone(), params["key"].clone());
+        kubo.lock().unwrap().published.push((path.clone(), key));
+        Json(json!({"Name": "k51qzi5uqu5dtest", "Value": path}))
+    }
+
+    async fn start_kubo() -> (String, Arc<Mutex<FakeKubo>>) {
+        let kubo = Arc::new(Mutex::new(FakeKubo::default()));
+        let app = Router::new()
+            .route("/api/v0/add", post(add))
+            .route("/api/v0/pin/add", post(pin))
+            .route("/api/v0/name/publish", post(publish))
+            .with_state(kubo.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), kubo)
+    }
+
+    #[tokio::test]
+    async fn test_deploy_and_rollback() {
+        let (api_url, kubo) = start_kubo().await;
+        let ipfs = IpfsStorage::new(&api_url).unwrap();
+        let path = std::env::temp_dir().join(format!("kpgb-deploys-{}.db", uuid::Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        let store = DeployStore::new(database.pool().clone());
+
+        let site = std::env::temp_dir().join(format!("kpgb-site-{}", uuid::Uuid::new_v4()));
+        assert!(deploy(&ipfs, &store, &site, Some("blog")).await.is_err());
+        std::fs::create_dir_all(site.join("posts")).unwrap();
+        std::fs::write(site.join("index.html"), "<h1>v1</h1>").unwrap();
+        std::fs::write(site.join("posts").join("hello world.html"), "hi").unwrap();
+        std::fs::write(site.join(".kpgb-build.json"), "{}").unwrap();
+
+        let first = deploy(&ipfs, &store, &site, Some("blog")).await.unwrap();
+        assert_eq!(first.files, 2);
+        assert_eq!(first.size, 13);
+        assert_eq!(first.ipns_name.as_deref(), Some("k51qzi5uqu5dtest"));
+        {
+            let kubo = kubo.lock().unwrap();
+            let (params, parts) = &kubo.adds[0];
+            assert_eq!(params["wrap-with-directory"], "true");
+            assert_eq!(params["recursive"], "true");
+            let names: Vec<(&str, &str)> = parts
+                .iter()
+                .map(|(name, content_type)| (name.as_str(), content_type.as_str()))
+                .collect();
+            assert_eq!(
+                names,
+                vec![
+                    ("index.html", "application/octet-stream"),
+                    ("posts", "application/x-directory"),
+                    ("posts/hello%20world.html", "application/octet-stream"),
+                ]
+            );
+            assert_eq!(kubo.pins, vec![first.cid.clone()]);
+            assert_eq!(
+                kubo.published,
+                vec![(format!("/ipfs/{}", first.cid), "blog".to_string())]
+            );
+        }
+
+        // Nothing to roll back to while every deploy is the same site
+        assert!(rollback(&ipfs, &store, None, "blog").await.is_err());
+
+        std::fs::write(site.join("index.html"), "<h1>v2</h1>").unwrap();
+        let second = deploy(&ipfs, &store, &site, None).await.unwrap();
+        assert_ne!(second.cid, first.cid);
+        assert!(second.ipns_name.is_none());
+        assert_eq!(kubo.lock().unwrap().published.len(), 1);
+
+        let rolled_back = rollback(&ipfs, &store, None, "blog").await.unwrap();
+        assert_eq!(rolled_back.cid, first.cid);
+        assert_eq!(rolled_back.rollback_of, Some(first.id));
+        assert_eq!(
+            kubo.lock().unwrap().published.last().unwrap().0,
+            format!("/ipfs/{}", first.cid)
+        );
+
+        // Forward again, by CID prefix
+        let target = &second.cid[..12];
+        let forward = rollback(&ipfs, &store, Some(target), "blog").await.unwrap();
+        assert_eq!(forward.cid, second.cid);
+        assert!(rollback(&ipfs, &store, Some("bafynothing"), "blog")
+            .await
+            .is_err());
+        // Prefixes must pick out a single site
+        for target in ["", " ", "bafyroot"] {
+            assert!(store.find(target).await.is_err(), "{:?}", target);
+        }
+        assert_eq!(
+            store.find(&first.id.to_string()).await.unwrap().unwrap().id,
+            first.id
+        );
+
+        let history: Vec<i64> = store.list(10).await.unwrap().iter().map(|d| d.id).collect();
+        assert_eq!(
+            history,
+            vec![forward.id, rolled_back.id, second.id, first.id]
+        );
+        std::fs::remove_dir_all(&site).ok();
+    }
+}
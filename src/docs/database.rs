@@ -1,69 +1,20 @@
+use super::{DocCategory, DocSection};
 use anyhow::Result;
-use sqlx::{query, query_as, SqlitePool};
-use super::{DocSection, DocCategory};
+use chrono::Utc;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
 
+#[derive(Clone)]
 pub struct DocsDatabase {
     pool: SqlitePool,
 }
 
 impl DocsDatabase {
+    /// Tables are created by the migrations run in `Database::new`
     pub fn new(pool: SqlitePool) -> Self {
         Self { pool }
     }
 
-    pub async fn init_tables(&self) -> Result<()> {
-        // 创建文档分类表
-        query(
-            r#"
-            CREATE TABLE IF NOT EXISTS doc_categories (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                slug TEXT NOT NULL UNIQUE,
-                description TEXT,
-                order_index INTEGER DEFAULT 0,
-                icon TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        // 创建文档章节表
-        query(
-            r#"
-            CREATE TABLE IF NOT EXISTS doc_sections (
-                id TEXT PRIMARY KEY,
-                category_id TEXT NOT NULL,
-                title TEXT NOT NULL,
-                slug TEXT NOT NULL UNIQUE,
-                content TEXT NOT NULL,
-                parent_id TEXT,
-                order_index INTEGER DEFAULT 0,
-                source_url TEXT,
-                is_translated BOOLEAN DEFAULT FALSE,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (category_id) REFERENCES doc_categories(id),
-                FOREIGN KEY (parent_id) REFERENCES doc_sections(id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        // 创建索引
-        query("CREATE INDEX IF NOT EXISTS idx_doc_sections_category ON doc_sections(category_id)")
-            .execute(&self.pool)
-            .await?;
-
-        query("CREATE INDEX IF NOT EXISTS idx_doc_sections_slug ON doc_sections(slug)")
-            .execute(&self.pool)
-            .await?;
-
-        Ok(())
-    }
-
     pub async fn create_category(&self, category: &DocCategory) -> Result<()> {
         query(
             r#"
@@ -87,14 +38,15 @@ impl DocsDatabase {
         query(
             r#"
             INSERT INTO doc_sections (
-                id, category_id, title, slug, content, parent_id, 
-                order_index, source_url, is_translated, created_at, updated_at
+                id, category_id, title, slug, content, parent_id,
+                order_index, source_url, is_translated, created_at, updated_at,
+                source_text, source_hash, source_synced_at, is_stale
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
             "#,
         )
         .bind(&section.id)
-        .bind(&section.parent_id.as_ref().unwrap_or(&section.id)) // category_id
+        .bind(&section.category_id)
         .bind(&section.title)
         .bind(&section.slug)
         .bind(&section.content)
@@ -104,6 +56,10 @@ impl DocsDatabase {
         .bind(section.is_translated)
         .bind(section.created_at)
         .bind(section.updated_at)
+        .bind(&section.source_text)
+        .bind(&section.source_hash)
+        .bind(section.source_synced_at)
+        .bind(section.is_stale)
         .execute(&self.pool)
         .await?;
 
@@ -111,99 +67,249 @@ impl DocsDatabase {
     }
 
     pub async fn get_all_categories(&self) -> Result<Vec<DocCategory>> {
-        let categories = query_as!(
-            DocCategory,
+        let rows = query(
             r#"
-            SELECT 
-                id, name, slug, description, 
-                order_index as "order: i32", 
-                icon
+            SELECT id, name, slug, description, order_index, icon
             FROM doc_categories
             ORDER BY order_index, name
-            "#
+            "#,
         )
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(categories)
+        Ok(rows
+            .into_iter()
+            .map(|row| DocCategory {
+                id: row.get("id"),
+                name: row.get("name"),
+                slug: row.get("slug"),
+                description: row
+                    .get::<Option<String>, _>("description")
+                    .unwrap_or_default(),
+                order: row.get("order_index"),
+                icon: row.get("icon"),
+            })
+            .collect())
+    }
+
+    pub async fn get_all_sections(&self) -> Result<Vec<DocSection>> {
+        let rows = query(
+            r#"
+            SELECT
+                id, category_id, title, slug, content, parent_id,
+                order_index, source_url, is_translated,
+                created_at, updated_at,
+                source_text, source_hash, source_synced_at, is_stale
+            FROM doc_sections
+            ORDER BY category_id, order_index, title
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_section).collect())
     }
 
     pub async fn get_sections_by_category(&self, category_id: &str) -> Result<Vec<DocSection>> {
-        let sections = sqlx::query!(
+        let rows = query(
             r#"
-            SELECT 
-                id, title, slug, content, parent_id, 
+            SELECT
+                id, category_id, title, slug, content, parent_id,
                 order_index, source_url, is_translated,
-                created_at, updated_at
+                created_at, updated_at,
+                source_text, source_hash, source_synced_at, is_stale
             FROM doc_sections
-            WHERE category_id = ?1 OR parent_id = ?1
+            WHERE category_id = ?1
             ORDER BY order_index, title
             "#,
-            category_id
         )
+        .bind(category_id)
         .fetch_all(&self.pool)
-        .await?
-        .into_iter()
-        .map(|row| DocSection {
-            id: row.id,
-            title: row.title,
-            slug: row.slug,
-            content: row.content,
-            parent_id: row.parent_id,
-            order: row.order_index,
-            created_at: row.created_at,
-            updated_at: row.updated_at,
-            source_url: row.source_url,
-            is_translated: row.is_translated,
-        })
-        .collect();
-
-        Ok(sections)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_section).collect())
+    }
+
+    pub async fn get_section(&self, id: &str) -> Result<Option<DocSection>> {
+        let row = query(
+            r#"
+            SELECT
+                id, category_id, title, slug, content, parent_id,
+                order_index, source_url, is_translated,
+                created_at, updated_at,
+                source_text, source_hash, source_synced_at, is_stale
+            FROM doc_sections
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(Self::row_to_section))
     }
 
     pub async fn get_section_by_slug(&self, slug: &str) -> Result<Option<DocSection>> {
-        let section = sqlx::query!(
+        let row = query(
             r#"
-            SELECT 
-                id, title, slug, content, parent_id, 
+            SELECT
+                id, category_id, title, slug, content, parent_id,
                 order_index, source_url, is_translated,
-                created_at, updated_at
+                created_at, updated_at,
+                source_text, source_hash, source_synced_at, is_stale
             FROM doc_sections
             WHERE slug = ?1
             "#,
-            slug
         )
+        .bind(slug)
         .fetch_optional(&self.pool)
-        .await?
-        .map(|row| DocSection {
-            id: row.id,
-            title: row.title,
-            slug: row.slug,
-            content: row.content,
-            parent_id: row.parent_id,
-            order: row.order_index,
-            created_at: row.created_at,
-            updated_at: row.updated_at,
-            source_url: row.source_url,
-            is_translated: row.is_translated,
-        });
-
-        Ok(section)
-    }
-
-    pub async fn update_section_content(&self, id: &str, content: &str) -> Result<()> {
-        query(
+        .await?;
+
+        Ok(row.as_ref().map(Self::row_to_section))
+    }
+
+    /// Store a translation and clear the outdated flag. False if there is no
+    /// section `id`.
+    pub async fn update_section_content(&self, id: &str, content: &str) -> Result<bool> {
+        let result = query(
             r#"
-            UPDATE doc_sections 
-            SET content = ?1, is_translated = TRUE, updated_at = CURRENT_TIMESTAMP
-            WHERE id = ?2
+            UPDATE doc_sections
+            SET content = ?1, is_translated = TRUE, is_stale = FALSE, updated_at = ?2
+            WHERE id = ?3
             "#,
         )
         .bind(content)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_category_by_slug(&self, slug: &str) -> Result<Option<DocCategory>> {
+        Ok(self
+            .get_all_categories()
+            .await?
+            .into_iter()
+            .find(|c| c.slug == slug))
+    }
+
+    pub async fn update_category(&self, category: &DocCategory) -> Result<()> {
+        query(
+            r#"
+            UPDATE doc_categories
+            SET name = ?1, slug = ?2, description = ?3, order_index = ?4, icon = ?5
+            WHERE id = ?6
+            "#,
+        )
+        .bind(&category.name)
+        .bind(&category.slug)
+        .bind(&category.description)
+        .bind(category.order)
+        .bind(&category.icon)
+        .bind(&category.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete a category together with its sections
+    pub async fn delete_category(&self, id: &str) -> Result<bool> {
+        let result = query("DELETE FROM doc_categories WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Write every field of a section back, stamping `updated_at`
+    pub async fn update_section(&self, section: &DocSection) -> Result<()> {
+        query(
+            r#"
+            UPDATE doc_sections
+            SET category_id = ?1, title = ?2, slug = ?3, content = ?4, parent_id = ?5,
+                order_index = ?6, source_url = ?7, is_translated = ?8, updated_at = ?9,
+                source_text = ?10, source_hash = ?11, source_synced_at = ?12, is_stale = ?13
+            WHERE id = ?14
+            "#,
+        )
+        .bind(&section.category_id)
+        .bind(&section.title)
+        .bind(&section.slug)
+        .bind(&section.content)
+        .bind(&section.parent_id)
+        .bind(section.order)
+        .bind(&section.source_url)
+        .bind(section.is_translated)
+        .bind(Utc::now())
+        .bind(&section.source_text)
+        .bind(&section.source_hash)
+        .bind(section.source_synced_at)
+        .bind(section.is_stale)
+        .bind(&section.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record the upstream text fetched by a sync. Leaves `updated_at` alone,
+    /// which tracks the translated content.
+    pub async fn update_section_source(
+        &self,
+        id: &str,
+        source_text: &str,
+        source_hash: &str,
+        is_stale: bool,
+    ) -> Result<()> {
+        query(
+            r#"
+            UPDATE doc_sections
+            SET source_text = ?1, source_hash = ?2, source_synced_at = ?3, is_stale = ?4
+            WHERE id = ?5
+            "#,
+        )
+        .bind(source_text)
+        .bind(source_hash)
+        .bind(Utc::now())
+        .bind(is_stale)
         .bind(id)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Delete a section together with its subsections
+    pub async fn delete_section(&self, id: &str) -> Result<bool> {
+        let result = query("DELETE FROM doc_sections WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    fn row_to_section(row: &SqliteRow) -> DocSection {
+        DocSection {
+            id: row.get("id"),
+            category_id: row.get("category_id"),
+            title: row.get("title"),
+            slug: row.get("slug"),
+            content: row.get("content"),
+            parent_id: row.get("parent_id"),
+            order: row.get("order_index"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            source_url: row.get("source_url"),
+            is_translated: row.get("is_translated"),
+            source_text: row.get("source_text"),
+            source_hash: row.get("source_hash"),
+            source_synced_at: row.get("source_synced_at"),
+            is_stale: row.get("is_stale"),
+        }
+    }
+}
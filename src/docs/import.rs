@@ -0,0 +1,395 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::{generate_doc_slug, DocCategory, DocFrontMatter, DocSection, DocsDatabase};
+use crate::frontmatter::parse_frontmatter_as;
+use crate::import::{collect_markdown_files, ImportReport};
+
+/// File holding a category's name, order and icon; its body is the description
+pub const CATEGORY_INDEX: &str = "_index.md";
+
+/// A category directory of a docs tree
+#[derive(Debug)]
+pub struct CategoryDir {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub order: i32,
+    pub icon: Option<String>,
+    pub sections: Vec<SectionFile>,
+}
+
+/// A markdown file of a category directory
+#[derive(Debug, Clone)]
+pub struct SectionFile {
+    pub path: PathBuf,
+    pub title: String,
+    pub slug: String,
+    pub order: i32,
+    /// Parent from the frontmatter, or the directory the file sits in
+    pub parent: Option<String>,
+    pub source_url: Option<String>,
+    pub content: String,
+}
+
+/// Read a docs tree: each directory under `dir` is a category, each markdown
+/// file in it a section. Files in a subdirectory are children of the section
+/// named like the directory (`usage.md` or `usage/index.md`). A numeric
+/// prefix such as `02-usage.md` sets the order when the frontmatter has none.
+pub fn read_doc_tree(dir: &Path) -> Result<Vec<CategoryDir>> {
+    let mut categories = Vec::new();
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+
+    for category_dir in dirs {
+        let dir_name = file_name(&category_dir);
+        let (dir_order, dir_slug) = split_order_prefix(&dir_name);
+        let mut category = CategoryDir {
+            slug: generate_doc_slug(dir_slug),
+            name: dir_slug.to_string(),
+            description: String::new(),
+            order: dir_order.unwrap_or(0),
+            icon: None,
+            sections: Vec::new(),
+        };
+
+        let index = category_dir.join(CATEGORY_INDEX);
+        if index.is_file() {
+            let (meta, body) =
+                parse_frontmatter_as::<DocFrontMatter>(&fs::read_to_string(&index)?)?;
+            if let Some(meta) = meta {
+                category.name = meta.title;
+                category.slug = meta.slug.unwrap_or(category.slug);
+                category.order = meta.order.unwrap_or(category.order);
+                category.icon = meta.icon;
+            }
+            category.description = body.trim().to_string();
+        }
+
+        let mut files = Vec::new();
+        collect_markdown_files(&category_dir, &mut files)?;
+        files.sort();
+        for path in files.into_iter().filter(|path| *path != index) {
+            category
+                .sections
+                .push(read_section_file(&category_dir, &path)?);
+        }
+
+        categories.push(category);
+    }
+
+    Ok(categories)
+}
+
+fn read_section_file(category_dir: &Path, path: &Path) -> Result<SectionFile> {
+    let text = fs::read_to_string(path)?;
+    let (meta, content) = parse_frontmatter_as::<DocFrontMatter>(&text)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+    // Directories between the category and the file, e.g. ["usage"] for usage/install.md
+    let relative = path.strip_prefix(category_dir).unwrap_or(path);
+    let mut dirs: Vec<String> = relative
+        .parent()
+        .map(|p| p.iter().map(|c| c.to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // usage/index.md is the section for the usage directory itself
+    let name = if stem == "index" || stem == "README" {
+        dirs.pop().unwrap_or(stem)
+    } else {
+        stem
+    };
+    let (name_order, name) = split_order_prefix(&name);
+    let dir_parent = dirs
+        .last()
+        .map(|dir| generate_doc_slug(split_order_prefix(dir).1));
+
+    let meta = meta.unwrap_or_else(|| DocFrontMatter {
+        title: first_heading(&content).unwrap_or_else(|| name.to_string()),
+        slug: None,
+        order: None,
+        parent: None,
+        source_url: None,
+        icon: None,
+    });
+
+    Ok(SectionFile {
+        path: path.to_path_buf(),
+        slug: meta.slug.unwrap_or_else(|| generate_doc_slug(name)),
+        title: meta.title,
+        order: meta.order.or(name_order).unwrap_or(0),
+        parent: meta.parent.or(dir_parent),
+        source_url: meta.source_url,
+        content,
+    })
+}
+
+/// Import a docs tree into the database. Categories and sections are matched
+/// by slug: new ones are created, changed ones updated, identical ones skipped.
+pub async fn import_doc_tree(docs_db: &DocsDatabase, dir: &Path) -> Result<ImportReport> {
+    let tree = read_doc_tree(dir)?;
+    let mut report = ImportReport::default();
+
+    let mut categories: HashMap<String, DocCategory> = docs_db
+        .get_all_categories()
+        .await?
+        .into_iter()
+        .map(|c| (c.slug.clone(), c))
+        .collect();
+    let mut sections: HashMap<String, DocSection> = docs_db
+        .get_all_sections()
+        .await?
+        .into_iter()
+        .map(|s| (s.slug.clone(), s))
+        .collect();
+
+    for dir in &tree {
+        let category = DocCategory {
+            id: categories
+                .get(&dir.slug)
+                .map(|c| c.id.clone())
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            name: dir.name.clone(),
+            slug: dir.slug.clone(),
+            description: dir.description.clone(),
+            order: dir.order,
+            icon: dir.icon.clone(),
+        };
+        match categories.get(&dir.slug) {
+            None => docs_db.create_category(&category).await?,
+            Some(existing) if !same_category(existing, &category) => {
+                docs_db.update_category(&category).await?
+            }
+            Some(_) => {}
+        }
+        categories.insert(category.slug.clone(), category);
+    }
+
+    // Parents are written before their children reference them
+    let mut pending: Vec<(&CategoryDir, &SectionFile)> = tree
+        .iter()
+        .flat_map(|dir| dir.sections.iter().map(move |file| (dir, file)))
+        .collect();
+    let pending_slugs: HashSet<&str> = pending.iter().map(|(_, f)| f.slug.as_str()).collect();
+    let mut written: HashSet<String> = HashSet::new();
+
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut waiting = Vec::new();
+
+        for (dir, file) in pending {
+            if let Some(parent) = &file.parent {
+                if pending_slugs.contains(parent.as_str()) && !written.contains(parent) {
+                    waiting.push((dir, file));
+                    continue;
+                }
+            }
+
+            match write_section(docs_db, &categories[&dir.slug], file, &mut sections).await {
+                Ok(Some(true)) => report.created.push(file.path.clone()),
+                Ok(Some(false)) => report.updated.push(file.path.clone()),
+                Ok(None) => report.skipped.push(file.path.clone()),
+                Err(e) => report.failed.push((file.path.clone(), e.to_string())),
+            }
+            written.insert(file.slug.clone());
+        }
+
+        // Whatever is left waits on a parent cycle; write it without parents
+        if waiting.len() == before {
+            for (dir, file) in waiting {
+                warn!(
+                    "Parent cycle at {}, importing it at the top level",
+                    file.path.display()
+                );
+                let section = SectionFile {
+                    parent: None,
+                    ..file.clone()
+                };
+                match write_section(docs_db, &categories[&dir.slug], &section, &mut sections).await
+                {
+                    Ok(_) => report.updated.push(file.path.clone()),
+                    Err(e) => report.failed.push((file.path.clone(), e.to_string())),
+                }
+            }
+            break;
+        }
+        pending = waiting;
+    }
+
+    Ok(report)
+}
+
+/// Create or update one section. Returns `Some(true)` when created,
+/// `Some(false)` when updated and `None` when nothing changed.
+async fn write_section(
+    docs_db: &DocsDatabase,
+    category: &DocCategory,
+    file: &SectionFile,
+    sections: &mut HashMap<String, DocSection>,
+) -> Result<Option<bool>> {
+    let parent_id = match &file.parent {
+        Some(parent) => match sections.get(parent) {
+            Some(parent) if parent.category_id == category.id => Some(parent.id.clone()),
+            Some(_) => return Err(anyhow!("Parent {} is in another category", parent)),
+            None => {
+                warn!("Unknown parent {} of {}", parent, file.path.display());
+                None
+            }
+        },
+        None => None,
+    };
+
+    let result = match sections.get(&file.slug) {
+        Some(existing) => {
+            let mut section = existing.clone();
+            section.category_id = category.id.clone();
+            section.title = file.title.clone();
+            section.content = file.content.clone();
+            section.parent_id = parent_id;
+            section.order = file.order;
+            section.source_url = file.source_url.clone();
+
+            if same_section(existing, &section) {
+                return Ok(None);
+            }
+            docs_db.update_section(&section).await?;
+            (section, false)
+        }
+        None => {
+            let now = Utc::now();
+            let section = DocSection {
+                id: uuid::Uuid::new_v4().to_string(),
+                category_id: category.id.clone(),
+                title: file.title.clone(),
+                slug: file.slug.clone(),
+                content: file.content.clone(),
+                parent_id,
+                order: file.order,
+                created_at: now,
+                updated_at: now,
+                source_url: file.source_url.clone(),
+                // Only sections with a source are translations waiting for work
+                is_translated: file.source_url.is_none(),
+                source_text: None,
+                source_hash: None,
+                source_synced_at: None,
+                is_stale: false,
+            };
+            docs_db.create_section(&section).await?;
+            (section, true)
+        }
+    };
+
+    let (section, created) = result;
+    sections.insert(section.slug.clone(), section);
+    Ok(Some(created))
+}
+
+fn same_category(a: &DocCategory, b: &DocCategory) -> bool {
+    a.name == b.name && a.description == b.description && a.order == b.order && a.icon == b.icon
+}
+
+fn same_section(a: &DocSection, b: &DocSection) -> bool {
+    a.category_id == b.category_id
+        && a.title == b.title
+        && a.content == b.content
+        && a.parent_id == b.parent_id
+        && a.order == b.order
+        && a.source_url == b.source_url
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Split `02-usage` into `(Some(2), "usage")`
+fn split_order_prefix(name: &str) -> (Option<i32>, &str) {
+    let digits = name.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return (None, name);
+    }
+    match name[digits..].strip_prefix(['-', '_']) {
+        Some(rest) if !rest.is_empty() => (name[..digits].parse().ok(), rest),
+        _ => (None, name),
+    }
+}
+
+fn first_heading(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_doc_tree() {
+        let root = std::env::temp_dir().join(format!("kpgb-docs-{}", uuid::Uuid::new_v4()));
+        let guide = root.join("01-guide");
+        fs::create_dir_all(guide.join("02-usage")).unwrap();
+        fs::write(
+            guide.join(CATEGORY_INDEX),
+            "---\ntitle: User Guide\nicon: \"📘\"\n---\n\nHow to use it\n",
+        )
+        .unwrap();
+        fs::write(guide.join("01-intro.md"), "# Introduction\n\nHello").unwrap();
+        fs::write(
+            guide.join("02-usage").join("index.md"),
+            "---\ntitle: Usage\nsource_url: https://example.com/usage\n---\n\nUse it",
+        )
+        .unwrap();
+        fs::write(
+            guide.join("02-usage").join("install.md"),
+            "---\ntitle: Install\norder: 5\n---\n\nInstall it",
+        )
+        .unwrap();
+
+        let tree = read_doc_tree(&root).unwrap();
+        assert_eq!(tree.len(), 1);
+        let category = &tree[0];
+        assert_eq!(category.slug, "guide");
+        assert_eq!(category.name, "User Guide");
+        assert_eq!(category.order, 1);
+        assert_eq!(category.icon.as_deref(), Some("📘"));
+        assert_eq!(category.description, "How to use it");
+
+        let by_slug: HashMap<_, _> = category
+            .sections
+            .iter()
+            .map(|s| (s.slug.as_str(), s))
+            .collect();
+        assert_eq!(by_slug.len(), 3);
+        assert_eq!(by_slug["intro"].title, "Introduction");
+        assert_eq!(by_slug["intro"].order, 1);
+        assert_eq!(by_slug["usage"].order, 2);
+        assert!(by_slug["usage"].parent.is_none());
+        assert_eq!(by_slug["install"].parent.as_deref(), Some("usage"));
+        assert_eq!(by_slug["install"].order, 5);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_split_order_prefix() {
+        assert_eq!(split_order_prefix("02-usage"), (Some(2), "usage"));
+        assert_eq!(split_order_prefix("usage"), (None, "usage"));
+        assert_eq!(split_order_prefix("2024"), (None, "2024"));
+    }
+}
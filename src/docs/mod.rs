@@ -2,9 +2,17 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod database;
+pub mod import;
+pub mod translation;
+pub mod tree;
+
+pub use database::DocsDatabase;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocSection {
     pub id: String,
+    pub category_id: String,
     pub title: String,
     pub slug: String,
     pub content: String,
@@ -14,6 +22,16 @@ pub struct DocSection {
     pub updated_at: DateTime<Utc>,
     pub source_url: Option<String>,
     pub is_translated: bool,
+    /// Upstream text at `source_url` as of the last `kpgb docs sync`
+    #[serde(default)]
+    pub source_text: Option<String>,
+    #[serde(default)]
+    pub source_hash: Option<String>,
+    #[serde(default)]
+    pub source_synced_at: Option<DateTime<Utc>>,
+    /// The source changed after the section was translated
+    #[serde(default)]
+    pub is_stale: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +44,24 @@ pub struct DocCategory {
     pub icon: Option<String>,
 }
 
+/// Frontmatter of a documentation section written as markdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocFrontMatter {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    /// Slug of the parent section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// Icon of a category, set in its `_index.md`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Documentation {
     pub project_name: String,
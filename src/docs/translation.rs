@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::tree::SectionTree;
+use super::{DocCategory, DocSection, DocsDatabase};
+
+/// Where a section stands relative to its upstream source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationState {
+    /// Written here, no source to follow
+    Original,
+    Untranslated,
+    /// Translated, but the source changed since
+    Stale,
+    Translated,
+}
+
+impl TranslationState {
+    pub fn of(section: &DocSection) -> Self {
+        if section.source_url.is_none() {
+            Self::Original
+        } else if !section.is_translated {
+            Self::Untranslated
+        } else if section.is_stale {
+            Self::Stale
+        } else {
+            Self::Translated
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Original => "original",
+            Self::Untranslated => "untranslated",
+            Self::Stale => "outdated",
+            Self::Translated => "translated",
+        }
+    }
+}
+
+pub fn source_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// Fetch the upstream text of a section. Besides http(s) URLs, `file://`
+/// URLs and plain paths are read from disk.
+pub async fn fetch_source(client: &reqwest::Client, url: &str) -> Result<String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let response = client.get(url).send().await?.error_for_status()?;
+        return Ok(response.text().await?);
+    }
+
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))
+}
+
+/// Result of syncing one section against its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOutcome {
+    Unchanged,
+    /// First sync, the source text is recorded as the baseline
+    Recorded,
+    Changed {
+        stale: bool,
+    },
+}
+
+impl SyncOutcome {
+    /// Compare a freshly fetched source hash with what the section recorded.
+    /// Only translated sections go stale; untranslated ones still need work
+    /// either way.
+    pub fn compare(section: &DocSection, hash: &str) -> Self {
+        match section.source_hash.as_deref() {
+            None => Self::Recorded,
+            Some(known) if known == hash => Self::Unchanged,
+            Some(_) => Self::Changed {
+                stale: section.is_translated,
+            },
+        }
+    }
+}
+
+/// Fetch a section's source and record it when it changed
+pub async fn sync_section(
+    docs_db: &DocsDatabase,
+    client: &reqwest::Client,
+    section: &DocSection,
+) -> Result<SyncOutcome> {
+    let url = section
+        .source_url
+        .as_deref()
+        .ok_or_else(|| anyhow!("Section {} has no source URL", section.slug))?;
+    let text = fetch_source(client, url).await?;
+    let hash = source_hash(&text);
+
+    let outcome = SyncOutcome::compare(section, &hash);
+    let is_stale = match outcome {
+        SyncOutcome::Unchanged => return Ok(outcome),
+        SyncOutcome::Recorded => section.is_stale,
+        SyncOutcome::Changed { stale } => section.is_stale || stale,
+    };
+    docs_db
+        .update_section_source(&section.id, &text, &hash, is_stale)
+        .await?;
+
+    Ok(outcome)
+}
+
+/// Translation progress of one category
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryStatus {
+    pub category: DocCategory,
+    pub total: usize,
+    pub original: usize,
+    pub translated: usize,
+    pub untranslated: usize,
+    pub stale: usize,
+    /// Sections needing work, in reading order
+    pub pending: Vec<(TranslationState, DocSection)>,
+}
+
+impl CategoryStatus {
+    pub fn build(category: DocCategory, sections: Vec<DocSection>) -> Self {
+        let tree = SectionTree::build(sections);
+        let mut status = Self {
+            category,
+            total: tree.sections().len(),
+            original: 0,
+            translated: 0,
+            untranslated: 0,
+            stale: 0,
+            pending: Vec::new(),
+        };
+
+        for section in tree.sections() {
+            let state = TranslationState::of(section);
+            match state {
+                TranslationState::Original => status.original += 1,
+                TranslationState::Translated => status.translated += 1,
+                TranslationState::Untranslated => status.untranslated += 1,
+                TranslationState::Stale => status.stale += 1,
+            }
+            if matches!(
+                state,
+                TranslationState::Untranslated | TranslationState::Stale
+            ) {
+                status.pending.push((state, section.clone()));
+            }
+        }
+
+        status
+    }
+
+    /// Share of the sourced sections that are translated and up to date
+    pub fn progress(&self) -> f64 {
+        let sourced = self.total - self.original;
+        if sourced == 0 {
+            return 100.0;
+        }
+        self.translated as f64 * 100.0 / sourced as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn section(slug: &str, source: Option<&str>, translated: bool, stale: bool) -> DocSection {
+        DocSection {
+            id: slug.to_string(),
+            category_id: "cat".to_string(),
+            title: slug.to_string(),
+            slug: slug.to_string(),
+            content: String::new(),
+            parent_id: None,
+            order: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            source_url: source.map(str::to_string),
+            is_translated: translated,
+            source_text: None,
+            source_hash: None,
+            source_synced_at: None,
+            is_stale: stale,
+        }
+    }
+
+    #[test]
+    fn test_sync_outcome() {
+        let mut translated = section("a", Some("https://example.com/a"), true, false);
+        assert_eq!(
+            SyncOutcome::compare(&translated, "abc"),
+            SyncOutcome::Recorded
+        );
+
+        translated.source_hash = Some("abc".to_string());
+        assert_eq!(
+            SyncOutcome::compare(&translated, "abc"),
+            SyncOutcome::Unchanged
+        );
+        assert_eq!(
+            SyncOutcome::compare(&translated, "def"),
+            SyncOutcome::Changed { stale: true }
+        );
+
+        let mut pending = section("b", Some("https://example.com/b"), false, false);
+        pending.source_hash = Some("abc".to_string());
+        assert_eq!(
+            SyncOutcome::compare(&pending, "def"),
+            SyncOutcome::Changed { stale: false }
+        );
+    }
+
+    #[test]
+    fn test_category_status() {
+        let category = DocCategory {
+            id: "cat".to_string(),
+            name: "Guide".to_string(),
+            slug: "guide".to_string(),
+            description: String::new(),
+            order: 0,
+            icon: None,
+        };
+        let status = CategoryStatus::build(
+            category,
+            vec![
+                section("own", None, true, false),
+                section("done", Some("u"), true, false),
+                section("old", Some("u"), true, true),
+                section("todo", Some("u"), false, false),
+            ],
+        );
+
+        assert_eq!(status.total, 4);
+        assert_eq!(
+            (
+                status.original,
+                status.translated,
+                status.stale,
+                status.untranslated
+            ),
+            (1, 1, 1, 1)
+        );
+        let pending: Vec<_> = status
+            .pending
+            .iter()
+            .map(|(state, s)| (*state, s.slug.as_str()))
+            .collect();
+        assert_eq!(
+            pending,
+            vec![
+                (TranslationState::Stale, "old"),
+                (TranslationState::Untranslated, "todo")
+            ]
+        );
+        assert!((status.progress() - 100.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fetch_source_from_file() {
+        let path = std::env::temp_dir().join(format!("kpgb-source-{}.md", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "# Upstream").unwrap();
+
+        let client = reqwest::Client::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let url = format!("file://{}", path.display());
+        let text = runtime.block_on(fetch_source(&client, &url)).unwrap();
+        assert_eq!(text, "# Upstream");
+        assert_eq!(source_hash(&text), source_hash("# Upstream"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use super::DocSection;
+
+/// Entry of a category's table of contents
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub id: String,
+    pub title: String,
+    pub slug: String,
+    pub depth: usize,
+    pub is_translated: bool,
+    pub is_stale: bool,
+    pub source_url: Option<String>,
+}
+
+/// Link to another section, for breadcrumbs and prev/next navigation
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionLink {
+    pub title: String,
+    pub slug: String,
+}
+
+impl From<&DocSection> for SectionLink {
+    fn from(section: &DocSection) -> Self {
+        Self {
+            title: section.title.clone(),
+            slug: section.slug.clone(),
+        }
+    }
+}
+
+/// The sections of one category as a tree, built from `parent_id` and
+/// `order`. Sections are kept in reading order: each parent is followed by
+/// its children, siblings sorted by order, then title.
+#[derive(Debug, Clone)]
+pub struct SectionTree {
+    sections: Vec<DocSection>,
+    depths: Vec<usize>,
+}
+
+impl SectionTree {
+    /// Sections whose parent is missing are treated as top-level sections
+    pub fn build(sections: Vec<DocSection>) -> Self {
+        let ids: HashSet<&str> = sections.iter().map(|s| s.id.as_str()).collect();
+        let mut children: HashMap<Option<&str>, Vec<&DocSection>> = HashMap::new();
+        for section in &sections {
+            let parent = section
+                .parent_id
+                .as_deref()
+                .filter(|id| ids.contains(id) && *id != section.id);
+            children.entry(parent).or_default().push(section);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.title.cmp(&b.title)));
+        }
+
+        let mut ordered = Vec::with_capacity(sections.len());
+        let mut depths = Vec::with_capacity(sections.len());
+        let mut visited = HashSet::new();
+        let mut stack: Vec<(&DocSection, usize)> = children
+            .get(&None)
+            .map(|roots| roots.iter().rev().map(|s| (*s, 0)).collect())
+            .unwrap_or_default();
+
+        while let Some((section, depth)) = stack.pop() {
+            // A parent cycle would otherwise loop forever
+            if !visited.insert(section.id.as_str()) {
+                continue;
+            }
+            ordered.push(section.clone());
+            depths.push(depth);
+            if let Some(kids) = children.get(&Some(section.id.as_str())) {
+                stack.extend(kids.iter().rev().map(|s| (*s, depth + 1)));
+            }
+        }
+
+        // Sections caught in a parent cycle are unreachable from the roots
+        for section in &sections {
+            if !visited.contains(section.id.as_str()) {
+                ordered.push(section.clone());
+                depths.push(0);
+            }
+        }
+
+        Self {
+            sections: ordered,
+            depths,
+        }
+    }
+
+    /// Sections in reading order
+    pub fn sections(&self) -> &[DocSection] {
+        &self.sections
+    }
+
+    pub fn toc(&self) -> Vec<TocEntry> {
+        self.sections
+            .iter()
+            .zip(&self.depths)
+            .map(|(section, depth)| TocEntry {
+                id: section.id.clone(),
+                title: section.title.clone(),
+                slug: section.slug.clone(),
+                depth: *depth,
+                is_translated: section.is_translated,
+                is_stale: section.is_stale,
+                source_url: section.source_url.clone(),
+            })
+            .collect()
+    }
+
+    fn position(&self, slug: &str) -> Option<usize> {
+        self.sections.iter().position(|s| s.slug == slug)
+    }
+
+    pub fn prev(&self, slug: &str) -> Option<&DocSection> {
+        let index = self.position(slug)?;
+        index.checked_sub(1).map(|i| &self.sections[i])
+    }
+
+    pub fn next(&self, slug: &str) -> Option<&DocSection> {
+        self.sections.get(self.position(slug)? + 1)
+    }
+
+    /// Ancestors of a section, outermost first
+    pub fn breadcrumbs(&self, slug: &str) -> Vec<SectionLink> {
+        let mut trail = Vec::new();
+        let Some(index) = self.position(slug) else {
+            return trail;
+        };
+
+        // Walking back in reading order, each shallower section is an ancestor
+        let mut depth = self.depths[index];
+        for i in (0..index).rev() {
+            if depth == 0 {
+                break;
+            }
+            if self.depths[i] < depth {
+                trail.push(SectionLink::from(&self.sections[i]));
+                depth = self.depths[i];
+            }
+        }
+        trail.reverse();
+        trail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn section(id: &str, parent: Option<&str>, order: i32) -> DocSection {
+        DocSection {
+            id: id.to_string(),
+            category_id: "cat".to_string(),
+            title: id.to_uppercase(),
+            slug: id.to_string(),
+            content: String::new(),
+            parent_id: parent.map(str::to_string),
+            order,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            source_url: None,
+            is_translated: false,
+            source_text: None,
+            source_hash: None,
+            source_synced_at: None,
+            is_stale: false,
+        }
+    }
+
+    #[test]
+    fn test_tree_order_and_navigation() {
+        let tree = SectionTree::build(vec![
+            section("usage", None, 2),
+            section("intro", None, 1),
+            section("config", Some("usage"), 2),
+            section("install", Some("usage"), 1),
+            section("env", Some("config"), 1),
+            section("orphan", Some("missing"), 3),
+        ]);
+
+        let slugs: Vec<_> = tree.sections().iter().map(|s| s.slug.as_str()).collect();
+        assert_eq!(
+            slugs,
+            vec!["intro", "usage", "install", "config", "env", "orphan"]
+        );
+        let depths: Vec<_> = tree.toc().iter().map(|e| e.depth).collect();
+        assert_eq!(depths, vec![0, 0, 1, 1, 2, 0]);
+
+        assert_eq!(tree.prev("install").unwrap().slug, "usage");
+        assert_eq!(tree.next("env").unwrap().slug, "orphan");
+        assert!(tree.prev("intro").is_none());
+        assert!(tree.next("orphan").is_none());
+
+        let trail: Vec<_> = tree
+            .breadcrumbs("env")
+            .into_iter()
+            .map(|link| link.slug)
+            .collect();
+        assert_eq!(trail, vec!["usage", "config"]);
+        assert!(tree.breadcrumbs("intro").is_empty());
+    }
+
+    #[test]
+    fn test_parent_cycle_keeps_all_sections() {
+        let tree = SectionTree::build(vec![section("a", Some("b"), 0), section("b", Some("a"), 0)]);
+        assert_eq!(tree.sections().len(), 2);
+    }
+}
@@ -1,22 +1,104 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use crate::models::BlogPost;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FrontMatter {
     pub title: String,
+    #[serde(default)]
     pub author: String,
     #[serde(default)]
     pub slug: Option<String>,
-    #[serde(default)]
+    /// A string, or a TOML datetime like Hugo writes (`date = 2023-01-01T00:00:00Z`)
+    #[serde(default, deserialize_with = "deserialize_date")]
     pub date: Option<String>,
     #[serde(default, deserialize_with = "deserialize_tags")]
     pub tags: Vec<String>,
     #[serde(default)]
     pub category: Option<String>,
-    #[serde(default)]
+    /// Hugo, Jekyll and Hexo list categories; the first one becomes the post category
+    #[serde(
+        default,
+        deserialize_with = "deserialize_tags",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub categories: Vec<String>,
+    #[serde(default, alias = "description")]
     pub excerpt: Option<String>,
     #[serde(default)]
     pub published: Option<bool>,
+    /// Hugo's draft flag, the inverse of `published`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft: Option<bool>,
+    /// Language of the post, e.g. `en` or `zh-CN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Slug of the post this one translates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation_of: Option<String>,
+}
+
+impl FrontMatter {
+    pub fn from_post(post: &BlogPost) -> Self {
+        Self {
+            title: post.title.clone(),
+            author: post.author.clone(),
+            slug: Some(post.slug.clone()),
+            date: Some(post.created_at.to_rfc3339()),
+            tags: post.tags.clone(),
+            category: post.category.clone(),
+            categories: Vec::new(),
+            excerpt: post.excerpt.clone(),
+            published: Some(post.published),
+            draft: None,
+            lang: post.lang.clone(),
+            translation_of: post.translation_of.clone(),
+        }
+    }
+
+    pub fn category(&self) -> Option<String> {
+        self.category
+            .clone()
+            .or_else(|| self.categories.first().cloned())
+    }
+
+    pub fn published(&self) -> Option<bool> {
+        self.published.or(self.draft.map(|draft| !draft))
+    }
+
+    /// Copy slug, tags, category, excerpt, language and publishing state onto a post.
+    /// A future `date` schedules the post, a past one backdates it.
+    pub fn apply_to(&self, post: &mut BlogPost) -> Result<()> {
+        if let Some(slug) = &self.slug {
+            post.slug = slug.clone();
+        }
+        post.tags = self.tags.clone();
+        post.category = self.category();
+        if let Some(excerpt) = &self.excerpt {
+            post.excerpt = Some(excerpt.clone());
+        }
+        post.lang = self.lang.clone();
+        post.translation_of = self.translation_of.clone();
+
+        let date = self.date.as_deref().map(parse_date).transpose()?;
+        match date {
+            Some(date) if date > Utc::now() => {
+                if self.published() != Some(false) {
+                    post.publish_at = Some(date);
+                }
+            }
+            Some(date) => {
+                post.created_at = date;
+                post.published = self.published().unwrap_or(false);
+            }
+            None => post.published = self.published().unwrap_or(false),
+        }
+
+        Ok(())
+    }
 }
 
 // Custom deserializer for tags that handles both array and comma-separated string
@@ -62,13 +144,71 @@ where
     deserializer.deserialize_any(TagsVisitor)
 }
 
+// Dates arrive as strings from YAML and quoted TOML, as datetimes from bare TOML
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DateValue {
+        Text(String),
+        Toml(toml::value::Datetime),
+    }
+
+    Ok(
+        Option::<DateValue>::deserialize(deserializer)?.map(|date| match date {
+            DateValue::Text(text) => text,
+            DateValue::Toml(datetime) => datetime.to_string(),
+        }),
+    )
+}
+
+/// Prepend `meta` as a YAML frontmatter block to markdown content. The
+/// content is kept byte for byte; `parse_rendered_as` gets it back.
+pub fn render_frontmatter<T: Serialize>(meta: &T, content: &str) -> Result<String> {
+    let yaml = serde_yaml::to_string(meta)?;
+    Ok(format!("---\n{}---\n\n{}", yaml, content))
+}
+
+/// Split markdown written by `render_frontmatter`. Unlike `parse_frontmatter_as`
+/// the content is returned exactly as it was rendered, whitespace included.
+pub fn parse_rendered_as<T: DeserializeOwned>(markdown: &str) -> Result<(T, String)> {
+    let rest = markdown
+        .strip_prefix("---\n")
+        .ok_or_else(|| anyhow!("Missing frontmatter block"))?;
+    let (yaml, body) = if let Some(body) = rest.strip_prefix("---\n") {
+        ("", body)
+    } else {
+        let end = rest
+            .find("\n---\n")
+            .ok_or_else(|| anyhow!("Unclosed frontmatter block"))?;
+        (&rest[..end + 1], &rest[end + 5..])
+    };
+
+    let meta =
+        serde_yaml::from_str(yaml).map_err(|e| anyhow!("Failed to parse frontmatter: {}", e))?;
+    // The blank line after the block belongs to the layout, not the content
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    Ok((meta, body.to_string()))
+}
+
 pub fn parse_frontmatter(content: &str) -> Result<(Option<FrontMatter>, String)> {
+    parse_frontmatter_as(content)
+}
+
+/// Split off a YAML or TOML frontmatter block and deserialize it into `T`
+pub fn parse_frontmatter_as<T: DeserializeOwned>(content: &str) -> Result<(Option<T>, String)> {
     let content = content.trim_start();
 
-    // Check if content starts with frontmatter delimiter
-    if !content.starts_with("---") {
+    // YAML frontmatter uses ---, Hugo's TOML frontmatter uses +++
+    let delimiter = if content.starts_with("---") {
+        "---"
+    } else if content.starts_with("+++") {
+        "+++"
+    } else {
         return Ok((None, content.to_string()));
-    }
+    };
 
     // Find the end of frontmatter
     let mut lines = content.lines();
@@ -79,7 +219,7 @@ pub fn parse_frontmatter(content: &str) -> Result<(Option<FrontMatter>, String)>
     let mut in_frontmatter = true;
 
     for line in lines {
-        if in_frontmatter && line.trim() == "---" {
+        if in_frontmatter && line.trim() == delimiter {
             in_frontmatter = false;
             continue;
         }
@@ -99,13 +239,48 @@ pub fn parse_frontmatter(content: &str) -> Result<(Option<FrontMatter>, String)>
     let frontmatter_str = frontmatter_lines.join("\n");
     let content_str = content_lines.join("\n").trim().to_string();
 
-    // Parse YAML frontmatter
-    let frontmatter: FrontMatter = serde_yaml::from_str(&frontmatter_str)
-        .map_err(|e| anyhow!("Failed to parse frontmatter: {}", e))?;
+    let frontmatter: T = if delimiter == "+++" {
+        toml::from_str(&frontmatter_str)
+            .map_err(|e| anyhow!("Failed to parse frontmatter: {}", e))?
+    } else {
+        serde_yaml::from_str(&frontmatter_str)
+            .map_err(|e| anyhow!("Failed to parse frontmatter: {}", e))?
+    };
 
     Ok((Some(frontmatter), content_str))
 }
 
+/// Parse a frontmatter or CLI date. Accepts RFC 3339 as well as
+/// `YYYY-MM-DD HH:MM[:SS]` and `YYYY-MM-DD`, which are read as local time.
+pub fn parse_date(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| anyhow!("Unrecognized date: {}", value))?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid local time: {}", value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +312,120 @@ This is the post content."#;
         assert!(content.starts_with("# Test Content"));
     }
 
+    #[test]
+    fn test_hugo_frontmatter() {
+        let content = r#"+++
+title = "Hugo Post"
+date = "2023-05-01T10:00:00+08:00"
+draft = true
+categories = ["Programming", "Rust"]
+tags = ["rust"]
++++
+
+Body"#;
+
+        let (frontmatter, content) = parse_frontmatter(content).unwrap();
+        let fm = frontmatter.unwrap();
+        assert_eq!(fm.title, "Hugo Post");
+        assert_eq!(fm.author, "");
+        assert_eq!(fm.category(), Some("Programming".to_string()));
+        assert_eq!(fm.published(), Some(false));
+        assert_eq!(content, "Body");
+
+        let mut post = BlogPost::new(fm.title.clone(), content, "Me".to_string());
+        fm.apply_to(&mut post).unwrap();
+        assert!(!post.published);
+        assert_eq!(post.created_at.to_rfc3339(), "2023-05-01T02:00:00+00:00");
+    }
+
+    #[test]
+    fn test_hugo_bare_datetime() {
+        for (date, expected) in [
+            ("2023-01-01T00:00:00Z", "2023-01-01T00:00:00+00:00"),
+            ("2023-05-01T10:00:00+08:00", "2023-05-01T02:00:00+00:00"),
+        ] {
+            let content = format!("+++\ntitle = \"Hugo\"\ndate = {}\n+++\nBody", date);
+            let (frontmatter, _) = parse_frontmatter(&content).unwrap();
+            let fm = frontmatter.unwrap();
+
+            let mut post = BlogPost::new(fm.title.clone(), "Body".to_string(), "Me".to_string());
+            fm.apply_to(&mut post).unwrap();
+            assert_eq!(post.created_at.to_rfc3339(), expected);
+        }
+
+        // Local dates and datetimes have no offset and are read as local time
+        let content = "+++\ntitle = \"Hugo\"\ndate = 2023-01-01\n+++\nBody";
+        let fm = parse_frontmatter(content).unwrap().0.unwrap();
+        assert_eq!(fm.date.as_deref(), Some("2023-01-01"));
+        let content = "+++\ntitle = \"Hugo\"\ndate = 2023-01-01T08:30:00\n+++\nBody";
+        let fm = parse_frontmatter(content).unwrap().0.unwrap();
+        assert!(parse_date(fm.date.as_deref().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_jekyll_frontmatter() {
+        let content = r#"---
+title: Jekyll Post
+categories: [notes]
+description: A short summary
+published: true
+---
+Body"#;
+
+        let (frontmatter, _) = parse_frontmatter(content).unwrap();
+        let fm = frontmatter.unwrap();
+        assert_eq!(fm.category(), Some("notes".to_string()));
+        assert_eq!(fm.excerpt, Some("A short summary".to_string()));
+        assert_eq!(fm.published(), Some(true));
+    }
+
+    #[test]
+    fn test_render_frontmatter_roundtrip() {
+        let mut post = BlogPost::new(
+            "Round Trip".to_string(),
+            "Body text".to_string(),
+            "Me".to_string(),
+        );
+        post.tags = vec!["rust".to_string()];
+        post.published = true;
+
+        let markdown = render_frontmatter(&FrontMatter::from_post(&post), &post.content).unwrap();
+        assert!(!markdown.contains("draft"));
+
+        // Whitespace around the content survives, so its hash does too
+        let content = "\n  indented\r\nlast line\n\n";
+        let rendered = render_frontmatter(&FrontMatter::from_post(&post), content).unwrap();
+        let (fm, body) = parse_rendered_as::<FrontMatter>(&rendered).unwrap();
+        assert_eq!(fm.title, "Round Trip");
+        assert_eq!(body, content);
+
+        let (frontmatter, content) = parse_frontmatter(&markdown).unwrap();
+        let fm = frontmatter.unwrap();
+        assert_eq!(fm.title, "Round Trip");
+        assert_eq!(fm.slug, Some(post.slug.clone()));
+        assert_eq!(fm.tags, post.tags);
+        assert_eq!(fm.published, Some(true));
+        assert_eq!(content, "Body text");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let utc = parse_date("2024-03-01T08:30:00+02:00").unwrap();
+        assert_eq!(utc.to_rfc3339(), "2024-03-01T06:30:00+00:00");
+
+        let local = parse_date("2024-03-01 08:30").unwrap();
+        let expected = Local
+            .with_ymd_and_hms(2024, 3, 1, 8, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(local, expected);
+        // As sent by <input type="datetime-local">
+        assert_eq!(parse_date("2024-03-01T08:30").unwrap(), expected);
+
+        assert!(parse_date("2024-03-01").is_ok());
+        assert!(parse_date("next tuesday").is_err());
+    }
+
     #[test]
     fn test_no_frontmatter() {
         let content = "# Just a regular markdown file\n\nNo frontmatter here.";
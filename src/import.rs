@@ -0,0 +1,422 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::blog::BlogManager;
+use crate::frontmatter;
+use crate::models::BlogPost;
+use crate::storage::StorageManager;
+
+/// Outcome of a directory import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} created, {} updated, {} skipped, {} failed",
+            self.created.len(),
+            self.updated.len(),
+            self.skipped.len(),
+            self.failed.len()
+        )
+    }
+}
+
+enum ImportOutcome {
+    Created,
+    Updated,
+    Skipped,
+}
+
+/// Posts already in the database, indexed for idempotent re-imports
+struct ExistingPosts {
+    /// Storage ID and post by post ID
+    posts: HashMap<String, (String, BlogPost)>,
+    /// Post IDs by slug
+    by_slug: HashMap<String, String>,
+    /// Post IDs by the file they were imported from
+    by_path: HashMap<String, String>,
+    hashes: HashSet<String>,
+    /// Hashes of files imported before, front matter included
+    sources: HashSet<String>,
+}
+
+impl ExistingPosts {
+    /// The post imported from `path` before, or else the one with `slug`
+    fn matching(&self, path: &str, slug: &str) -> Option<&(String, BlogPost)> {
+        self.by_path
+            .get(path)
+            .and_then(|id| self.posts.get(id))
+            .or_else(|| self.by_slug.get(slug).and_then(|id| self.posts.get(id)))
+    }
+
+    fn insert(&mut self, storage_id: String, post: BlogPost) {
+        self.by_slug.retain(|_, id| *id != post.id);
+        self.by_slug.insert(post.slug.clone(), post.id.clone());
+        self.hashes.insert(post.content_hash.clone());
+        self.posts.insert(post.id.clone(), (storage_id, post));
+    }
+}
+
+/// Import every markdown file under `dir`. Unchanged files are skipped, files
+/// matching an existing post's slug update it.
+pub async fn import_directory(
+    blog_manager: &mut BlogManager,
+    storage_manager: &StorageManager,
+    dir: &Path,
+    default_author: &str,
+) -> Result<ImportReport> {
+    let mut files = Vec::new();
+    collect_markdown_files(dir, &mut files)?;
+    files.sort();
+
+    import_files(blog_manager, storage_manager, files, default_author).await
+}
+
+/// Import the given markdown files, with the same skip and update rules as `import_directory`
+pub async fn import_files(
+    blog_manager: &mut BlogManager,
+    storage_manager: &StorageManager,
+    files: Vec<PathBuf>,
+    default_author: &str,
+) -> Result<ImportReport> {
+    let mut existing = ExistingPosts {
+        posts: HashMap::new(),
+        by_slug: HashMap::new(),
+        by_path: blog_manager.import_paths().await?,
+        hashes: HashSet::new(),
+        sources: blog_manager.import_sources().await?.into_iter().collect(),
+    };
+    for (storage_id, post) in blog_manager.list_posts(false).await? {
+        existing.insert(storage_id, post);
+    }
+
+    let mut report = ImportReport::default();
+    for path in files {
+        match import_file(
+            blog_manager,
+            storage_manager,
+            &path,
+            default_author,
+            &mut existing,
+        )
+        .await
+        {
+            Ok(ImportOutcome::Created) => {
+                println!("   ✨ Created: {}", path.display());
+                report.created.push(path);
+            }
+            Ok(ImportOutcome::Updated) => {
+                println!("   📝 Updated: {}", path.display());
+                report.updated.push(path);
+            }
+            Ok(ImportOutcome::Skipped) => report.skipped.push(path),
+            Err(e) => {
+                println!("   ❌ Failed: {} ({})", path.display(), e);
+                report.failed.push((path, e.to_string()));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+async fn import_file(
+    blog_manager: &mut BlogManager,
+    storage_manager: &StorageManager,
+    path: &Path,
+    default_author: &str,
+    existing: &mut ExistingPosts,
+) -> Result<ImportOutcome> {
+    let raw = fs::read_to_string(path)?;
+    // Checked before images are uploaded, so unchanged files cost nothing
+    let source_hash = BlogPost::calculate_hash(&raw);
+    if existing.sources.contains(&source_hash) {
+        return Ok(ImportOutcome::Skipped);
+    }
+    let source_path = fs::canonicalize(path)?.display().to_string();
+    let (frontmatter, content) = frontmatter::parse_frontmatter(&raw)?;
+
+    // Upload images referenced relative to the markdown file
+    let (content, _) =
+        crate::utils::process_images_in_markdown(&content, path.parent(), storage_manager).await?;
+
+    let (file_date, file_slug) = parse_file_name(path);
+    let title = frontmatter
+        .as_ref()
+        .map(|fm| fm.title.clone())
+        .or_else(|| file_slug.clone())
+        .unwrap_or_else(|| "Untitled".to_string());
+    let author = frontmatter
+        .as_ref()
+        .map(|fm| fm.author.clone())
+        .filter(|author| !author.is_empty())
+        .unwrap_or_else(|| default_author.to_string());
+
+    let mut post = BlogPost::new(title, content, author);
+    let import_time = post.created_at;
+    if let Some(slug) = file_slug {
+        post.slug = slug;
+    }
+    if let Some(fm) = &frontmatter {
+        fm.apply_to(&mut post)?;
+    }
+    // Jekyll keeps the date in the file name
+    if frontmatter.as_ref().is_none_or(|fm| fm.date.is_none()) {
+        if let Some(date) = file_date {
+            post.created_at = date;
+        }
+    }
+    if post.excerpt.is_none() {
+        post.excerpt = Some(crate::utils::generate_excerpt(&post.content, 50));
+    }
+
+    if let Some((storage_id, current)) = existing.matching(&source_path, &post.slug).cloned() {
+        // Everything a fresh import would set is taken from the file
+        let mut updated = post;
+        updated.id = current.id.clone();
+        updated.storage_id = Some(storage_id.clone());
+        if updated.created_at == import_time {
+            updated.created_at = current.created_at;
+        }
+        // A schedule that has come due reads as a past date now. The post
+        // stays scheduled rather than turning into a draft.
+        if updated.publish_at.is_none() && current.publish_at == Some(updated.created_at) {
+            updated.publish_at = current.publish_at;
+        }
+
+        if !differs(&current, &updated) {
+            blog_manager
+                .record_import_source(&source_hash, &current.id, &source_path)
+                .await?;
+            existing.sources.insert(source_hash);
+            return Ok(ImportOutcome::Skipped);
+        }
+
+        let new_storage_id = blog_manager.update_post(&updated).await?;
+        blog_manager
+            .record_import_source(&source_hash, &updated.id, &source_path)
+            .await?;
+        existing.sources.insert(source_hash);
+        existing.by_path.insert(source_path, updated.id.clone());
+        existing.insert(new_storage_id, updated);
+        return Ok(ImportOutcome::Updated);
+    }
+
+    if existing.hashes.contains(&post.content_hash) {
+        return Ok(ImportOutcome::Skipped);
+    }
+
+    existing.sources.insert(source_hash.clone());
+    let storage_id = blog_manager.create_post(post.clone()).await?;
+    blog_manager
+        .record_import_source(&source_hash, &post.id, &source_path)
+        .await?;
+    existing.by_path.insert(source_path, post.id.clone());
+    existing.insert(storage_id, post);
+    Ok(ImportOutcome::Created)
+}
+
+/// Whether importing `file` would change `current`, front matter included
+fn differs(current: &BlogPost, file: &BlogPost) -> bool {
+    let now = chrono::Utc::now();
+    let is_live = |post: &BlogPost| post.published || post.publish_at.is_some_and(|at| at <= now);
+    current.content_hash != file.content_hash
+        || current.title != file.title
+        || current.slug != file.slug
+        || current.author != file.author
+        || current.tags != file.tags
+        || current.category != file.category
+        || current.excerpt != file.excerpt
+        || current.lang != file.lang
+        || current.translation_of != file.translation_of
+        || current.created_at != file.created_at
+        || current.publish_at != file.publish_at
+        || is_live(current) != is_live(file)
+}
+
+pub fn is_markdown_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md") | Some("markdown")
+    )
+}
+
+pub(crate) fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_markdown_files(&path, files)?;
+        } else if is_markdown_file(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Derive the date and slug encoded in a file name. Jekyll names posts
+/// `YYYY-MM-DD-slug.md`, Hugo page bundles use the directory name.
+fn parse_file_name(path: &Path) -> (Option<chrono::DateTime<chrono::Utc>>, Option<String>) {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let stem = if stem == "index" || stem == "_index" {
+        path.parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or(stem)
+    } else {
+        stem
+    };
+
+    let (date, name) = match (stem.get(..10), stem.get(10..11)) {
+        (Some(prefix), Some("-")) => match frontmatter::parse_date(prefix) {
+            Ok(date) => (Some(date), &stem[11..]),
+            Err(_) => (None, stem),
+        },
+        _ => (None, stem),
+    };
+
+    // Non-ASCII names would only produce a timestamp slug
+    let slug = if name.chars().any(|c| c.is_ascii_alphanumeric()) {
+        Some(BlogPost::generate_slug(name))
+    } else {
+        None
+    };
+
+    (date, slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{blog, stored_post};
+
+    #[tokio::test]
+    async fn test_reimport() {
+        let (dir, mut blog_manager) = blog("import").await;
+        let storage_manager = blog_manager.storage_manager().clone();
+        let posts = dir.path().join("posts");
+        std::fs::create_dir_all(&posts).unwrap();
+        let post = posts.join("hello.md");
+        std::fs::write(&post, "---\ntitle: Hello\n---\n![cat](cat.png)").unwrap();
+        std::fs::write(posts.join("cat.png"), b"meow").unwrap();
+        assert_eq!(
+            import_directory(&mut blog_manager, &storage_manager, &posts, "Me")
+                .await
+                .unwrap()
+                .created
+                .len(),
+            1
+        );
+
+        // Unchanged files are skipped before their images are looked at:
+        // with the image gone, processing it would change the content
+        std::fs::remove_file(posts.join("cat.png")).unwrap();
+        assert_eq!(
+            import_directory(&mut blog_manager, &storage_manager, &posts, "Me")
+                .await
+                .unwrap()
+                .skipped
+                .len(),
+            1
+        );
+
+        // A change in the front matter alone updates the post
+        std::fs::write(
+            &post,
+            "---\ntitle: Hello again\ntags: [cats]\n---\n![cat](cat.png)",
+        )
+        .unwrap();
+        std::fs::write(posts.join("cat.png"), b"meow").unwrap();
+        let report = import_directory(&mut blog_manager, &storage_manager, &posts, "Me")
+            .await
+            .unwrap();
+        assert_eq!(report.updated.len(), 1, "{}", report.summary());
+        let (_, updated) = blog_manager.list_posts(false).await.unwrap().remove(0);
+        assert_eq!(updated.title, "Hello again");
+        assert_eq!(updated.tags, vec!["cats"]);
+        assert!(!updated.content.contains("(cat.png)"));
+        assert_eq!(
+            import_directory(&mut blog_manager, &storage_manager, &posts, "Me")
+                .await
+                .unwrap()
+                .skipped
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reimport_front_matter() {
+        let (dir, blog_manager) = blog("reimport").await;
+        let storage_manager = blog_manager.storage_manager().clone();
+        let posts = dir.path().join("posts");
+        std::fs::create_dir_all(&posts).unwrap();
+        let file = posts.join("hello.md");
+        let import = |content: &str| {
+            std::fs::write(&file, content).unwrap();
+            let mut blog_manager = blog_manager.clone();
+            let storage_manager = storage_manager.clone();
+            let posts = posts.clone();
+            async move {
+                import_directory(&mut blog_manager, &storage_manager, &posts, "Me")
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(
+            import("---\ntitle: Hello\n---\nBody").await.created.len(),
+            1
+        );
+        let (_, post) = blog_manager.list_posts(false).await.unwrap().remove(0);
+        assert!(!stored_post(&blog_manager, &post.id).await.published);
+
+        let report = import("---\ntitle: Hello\npublished: true\nlang: de\n---\nBody").await;
+        assert_eq!(report.updated.len(), 1, "{}", report.summary());
+        let stored = stored_post(&blog_manager, &post.id).await;
+        assert!(stored.published);
+        let (_, updated) = blog_manager.list_posts(false).await.unwrap().remove(0);
+        assert_eq!(updated.lang.as_deref(), Some("de"));
+
+        // The file is still the same post after its slug changes
+        let report = import("---\ntitle: Hello\nslug: renamed\n---\nBody").await;
+        assert_eq!(report.updated.len(), 1, "{}", report.summary());
+        let all = blog_manager.list_posts(false).await.unwrap();
+        assert_eq!(all.len(), 1);
+        let stored = stored_post(&blog_manager, &post.id).await;
+        assert_eq!(stored.slug, "renamed");
+        assert!(!stored.published);
+
+        let report = import("---\ntitle: Hello\nslug: renamed\ndate: 2999-01-01\n---\nBody").await;
+        assert_eq!(report.updated.len(), 1, "{}", report.summary());
+        let stored = stored_post(&blog_manager, &post.id).await;
+        assert_eq!(
+            stored.publish_at,
+            Some(crate::frontmatter::parse_date("2999-01-01").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        let (date, slug) = parse_file_name(Path::new("_posts/2023-04-05-Hello_World.md"));
+        assert!(date.is_some());
+        assert_eq!(slug, Some("hello-world".to_string()));
+
+        let (date, slug) = parse_file_name(Path::new("content/posts/my-bundle/index.md"));
+        assert!(date.is_none());
+        assert_eq!(slug, Some("my-bundle".to_string()));
+
+        let (_, slug) = parse_file_name(Path::new("source/_posts/中文.md"));
+        assert_eq!(slug, None);
+    }
+}
@@ -9,16 +9,23 @@
 #![allow(clippy::if_same_then_else)]
 #![allow(clippy::needless_borrows_for_generic_args)]
 
+mod activitypub;
+mod archive;
+mod auth;
 mod blog;
+mod comments;
 mod constants;
 mod database;
+mod deploy;
 mod docs;
 mod frontmatter;
+mod import;
 mod models;
 mod site;
 mod storage;
 mod utils;
 mod web;
+mod webmention;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -27,7 +34,7 @@ use tracing::info;
 
 use crate::blog::BlogManager;
 use crate::models::BlogPost;
-use crate::storage::{StorageBackend, StorageManager};
+use crate::storage::{ReplicaStatus, ReplicationPolicy, StorageBackend, StorageManager};
 
 #[derive(Parser)]
 #[command(author, version, about = "Decentralized Personal Blog System", long_about = None)]
@@ -53,6 +60,33 @@ enum Commands {
         content: Option<String>,
     },
 
+    /// Import markdown posts from a directory (Hugo, Jekyll, Hexo layouts)
+    Import {
+        /// Directory containing markdown files
+        dir: String,
+
+        /// Author for posts without one in their frontmatter
+        #[arg(short, long, default_value = "Anonymous")]
+        author: String,
+    },
+
+    /// Export all posts and docs with a manifest
+    Export {
+        /// Output format: dir, tar, json
+        #[arg(short, long, default_value = "dir")]
+        format: String,
+
+        /// Output path (defaults to ./export, ./kpgb-export.tar or ./kpgb-export.json)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Restore posts and docs from an export
+    ImportArchive {
+        /// Export directory, tar archive or JSON file
+        path: String,
+    },
+
     /// List all posts
     List {
         /// Show only published posts
@@ -64,8 +98,15 @@ enum Commands {
     Publish {
         /// Storage ID of the post
         id: String,
+
+        /// Schedule publishing for a later time (RFC 3339 or "YYYY-MM-DD HH:MM")
+        #[arg(long)]
+        at: Option<String>,
     },
 
+    /// Mark scheduled posts whose publish time has passed as published
+    PublishDue,
+
     /// Read a specific post
     Read {
         /// Storage ID of the post
@@ -96,7 +137,7 @@ enum Commands {
 
     /// Test storage backends
     TestStorage {
-        /// Backend type: ipfs, github, local
+        /// Backend type: ipfs, github, s3, local
         #[arg(short, long, default_value = "local")]
         backend: String,
     },
@@ -110,6 +151,10 @@ enum Commands {
         /// Configuration file to use
         #[arg(short, long, default_value = "site.toml")]
         config: String,
+
+        /// Re-render every page, ignoring the previous build manifest
+        #[arg(long)]
+        full: bool,
     },
 
     /// Initialize site configuration
@@ -124,6 +169,23 @@ enum Commands {
         /// Configuration file to use
         #[arg(short, long, default_value = "site.toml")]
         config: String,
+
+        /// Re-import changed content and reload templates and open pages
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Content directory to import and watch
+        #[arg(long, default_value = "content")]
+        content: String,
+
+        /// Directory with template overrides to watch (defaults to the
+        /// theme's templates, or ./templates without a theme directory)
+        #[arg(long)]
+        templates: Option<String>,
+
+        /// Author for watched posts without one in their frontmatter
+        #[arg(long, default_value = "Anonymous")]
+        author: String,
     },
 
     /// Edit an existing post
@@ -166,6 +228,33 @@ enum Commands {
         force: bool,
     },
 
+    /// Show the revision history of a post
+    History {
+        /// Storage ID of the post
+        id: String,
+    },
+
+    /// Show the changes between two revisions of a post
+    Diff {
+        /// Storage ID of the post
+        id: String,
+
+        /// Revision to compare from
+        rev_a: i64,
+
+        /// Revision to compare to
+        rev_b: i64,
+    },
+
+    /// Restore a post to an earlier revision
+    Rollback {
+        /// Storage ID of the post
+        id: String,
+
+        /// Revision to restore
+        rev: i64,
+    },
+
     /// Show blog statistics
     Stats {
         /// Show detailed statistics
@@ -177,6 +266,48 @@ enum Commands {
         json: bool,
     },
 
+    /// Manage themes
+    Theme {
+        #[command(subcommand)]
+        action: ThemeCommands,
+    },
+
+    /// Manage bearer tokens for the write API
+    Token {
+        #[command(subcommand)]
+        action: TokenCommands,
+    },
+
+    /// Moderate comments
+    Comments {
+        #[command(subcommand)]
+        action: CommentsCommands,
+    },
+
+    /// Check the copies of posts and clean up local storage
+    Storage {
+        #[command(subcommand)]
+        action: StorageCommands,
+    },
+
+    /// Publish the generated site
+    Deploy {
+        #[command(subcommand)]
+        action: DeployCommands,
+    },
+
+    /// Send webmentions and manage received ones
+    Webmention {
+        #[command(subcommand)]
+        action: WebmentionCommands,
+    },
+
+    /// Manage documentation categories and sections
+    Docs {
+        #[command(subcommand)]
+        action: DocsCommands,
+    },
+
     /// Read a random post
     Random {
         /// Only from published posts
@@ -189,6 +320,306 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum ThemeCommands {
+    /// List built-in themes and theme directories
+    List {
+        /// Configuration file to use
+        #[arg(short, long, default_value = "site.toml")]
+        config: String,
+    },
+
+    /// Create a theme directory from the built-in templates
+    New {
+        /// Theme name
+        name: String,
+
+        /// Built-in theme to copy the stylesheet from
+        #[arg(long, default_value = "default")]
+        from: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommands {
+    /// Create a token; it is shown once and stored hashed
+    Create {
+        /// Name to recognise the token by, e.g. the tool using it
+        name: String,
+    },
+
+    /// List tokens
+    List,
+
+    /// Revoke a token
+    Revoke {
+        /// Token name, ID or ID prefix of at least 8 characters
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CommentsCommands {
+    /// List comments, newest first
+    List {
+        /// Only comments with this status (pending, approved)
+        #[arg(short, long)]
+        status: Option<String>,
+    },
+
+    /// Approve a pending comment
+    Approve {
+        /// Comment ID (or a prefix of it)
+        id: String,
+    },
+
+    /// Delete a comment and its replies
+    Delete {
+        /// Comment ID (or a prefix of it)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// Re-read every copy of the posts and check it against its hash
+    Verify {
+        /// Only this post (storage ID or a prefix of it)
+        #[arg(short, long)]
+        post: Option<String>,
+    },
+    /// Delete local blobs no post, revision or image link refers to
+    Gc {
+        /// Only show what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeployCommands {
+    /// Add the generated site to IPFS, pin it and publish it to IPNS
+    Ipfs {
+        /// Directory of the generated site
+        #[arg(short, long, default_value = "./public")]
+        dir: String,
+
+        /// IPNS key to publish to (default: IPFS_IPNS_KEY, or `self`)
+        #[arg(short, long)]
+        key: Option<String>,
+
+        /// Only add and pin the site
+        #[arg(long)]
+        no_publish: bool,
+    },
+    /// List earlier IPFS deploys, newest first
+    History {
+        #[arg(short, long, default_value = "10")]
+        limit: i64,
+    },
+    /// Publish an earlier deploy again
+    Rollback {
+        /// Deploy ID or CID (prefix); the deploy before the current one by default
+        target: Option<String>,
+
+        /// IPNS key to publish to (default: IPFS_IPNS_KEY, or `self`)
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WebmentionCommands {
+    /// Send webmentions for links collected by `kpgb generate`
+    Send {
+        /// Only links of this post (ID or prefix)
+        #[arg(short, long)]
+        post: Option<String>,
+
+        /// Also retry links that failed or had no endpoint
+        #[arg(long)]
+        retry: bool,
+
+        /// Discover endpoints but don't send anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List received webmentions
+    List {
+        /// Only mentions with this status (pending, verified, rejected)
+        #[arg(short, long)]
+        status: Option<String>,
+    },
+
+    /// Delete a received webmention
+    Delete {
+        /// Webmention ID (or a prefix of it)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DocsCommands {
+    /// Manage documentation categories
+    Category {
+        #[command(subcommand)]
+        action: DocCategoryCommands,
+    },
+
+    /// Manage documentation sections
+    Section {
+        #[command(subcommand)]
+        action: DocSectionCommands,
+    },
+
+    /// Import docs from a markdown tree, one directory per category
+    Import {
+        /// Directory containing the category directories
+        dir: String,
+    },
+
+    /// Fetch the source of translated sections and flag outdated translations
+    Sync {
+        /// Only sync sections of this category
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+
+    /// Report untranslated and outdated sections per category
+    Status {
+        /// Only report this category
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DocCategoryCommands {
+    /// Add a category
+    Add {
+        /// Category name
+        name: String,
+
+        /// URL slug (derived from the name by default)
+        #[arg(short, long)]
+        slug: Option<String>,
+
+        /// Short description
+        #[arg(short, long, default_value = "")]
+        description: String,
+
+        /// Icon shown on the docs index, e.g. an emoji
+        #[arg(short, long)]
+        icon: Option<String>,
+
+        /// Position among the categories
+        #[arg(short, long, default_value = "0")]
+        order: i32,
+    },
+
+    /// List categories
+    List,
+
+    /// Remove a category and all of its sections
+    Remove {
+        /// Category slug
+        slug: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DocSectionCommands {
+    /// Add a section to a category
+    Add {
+        /// Category slug
+        category: String,
+
+        /// Section title
+        title: String,
+
+        /// URL slug (derived from the title by default)
+        #[arg(short, long)]
+        slug: Option<String>,
+
+        /// Content file path (markdown)
+        #[arg(short, long)]
+        content: Option<String>,
+
+        /// Slug of the parent section
+        #[arg(short, long)]
+        parent: Option<String>,
+
+        /// Position among its siblings
+        #[arg(short, long, default_value = "0")]
+        order: i32,
+
+        /// URL of the original document
+        #[arg(long)]
+        source_url: Option<String>,
+    },
+
+    /// List the sections of a category as a tree
+    List {
+        /// Category slug (all categories by default)
+        category: Option<String>,
+    },
+
+    /// Edit a section
+    Edit {
+        /// Section slug
+        slug: String,
+
+        /// New title
+        #[arg(short, long)]
+        title: Option<String>,
+
+        /// Content file path for new content
+        #[arg(short, long)]
+        content: Option<String>,
+
+        /// New URL slug
+        #[arg(long)]
+        new_slug: Option<String>,
+
+        /// URL of the original document
+        #[arg(long)]
+        source_url: Option<String>,
+
+        /// Mark the translation as up to date with its source
+        #[arg(long)]
+        translated: bool,
+    },
+
+    /// Move a section under another parent, to the top level or to another category
+    Move {
+        /// Section slug
+        slug: String,
+
+        /// Slug of the new parent section
+        #[arg(short, long, conflicts_with = "root")]
+        parent: Option<String>,
+
+        /// Move to the top level of its category
+        #[arg(long)]
+        root: bool,
+
+        /// New position among its siblings
+        #[arg(short, long)]
+        order: Option<i32>,
+
+        /// Move the section and its subsections to another category
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+
+    /// Remove a section and its subsections
+    Remove {
+        /// Section slug
+        slug: String,
+    },
+}
+
 fn wrap_text(text: &str, width: usize) -> String {
     // Simple text wrapping for now
     let mut result = String::new();
@@ -228,17 +659,13 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    // Default to IPFS if available, otherwise use local
-    let default_backend = if std::env::var("IPFS_API_URL").is_ok() {
-        StorageBackend::Ipfs
-    } else {
-        StorageBackend::Local
-    };
-
-    let mut storage_manager = StorageManager::new(default_backend.clone());
+    // IPFS if available, otherwise local, unless STORAGE_PRIMARY says
+    // otherwise; STORAGE_MIRRORS get copies
+    let policy = ReplicationPolicy::from_env()?;
+    let mut storage_manager = StorageManager::with_policy(policy);
 
     // Always add local storage as fallback
-    let local_storage = storage::local::LocalStorage::new("./storage/local")?;
+    let local_storage = storage::local::LocalStorage::new(constants::LOCAL_STORAGE_PATH)?;
     storage_manager.add_backend(StorageBackend::Local, Box::new(local_storage));
 
     // Add IPFS if configured
@@ -253,6 +680,12 @@ async fn main() -> Result<()> {
         storage_manager.add_backend(StorageBackend::GitHub, Box::new(github_storage));
     }
 
+    // Add S3 if configured
+    if let Ok(s3_storage) = storage::s3::S3Storage::from_env() {
+        info!("S3 storage backend configured");
+        storage_manager.add_backend(StorageBackend::S3, Box::new(s3_storage));
+    }
+
     // Database URL
     let database_url =
         std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:./kpgb.db".to_string());
@@ -306,12 +739,12 @@ async fn main() -> Result<()> {
                 let final_title = if title != "Untitled" {
                     title.clone()
                 } else {
-                    fm.title
+                    fm.title.clone()
                 };
-                let final_author = if author != "Anonymous" {
+                let final_author = if author != "Anonymous" || fm.author.is_empty() {
                     author
                 } else {
-                    fm.author
+                    fm.author.clone()
                 };
 
                 let mut post = BlogPost::new(
@@ -319,15 +752,10 @@ async fn main() -> Result<()> {
                     clean_content.clone(),
                     final_author.clone(),
                 );
-                // Use slug from frontmatter if provided
-                if let Some(slug) = fm.slug {
-                    post.slug = slug;
+                fm.apply_to(&mut post)?;
+                if post.excerpt.is_none() {
+                    post.excerpt = Some(crate::utils::generate_excerpt(&clean_content, 50));
                 }
-                post.tags = fm.tags;
-                post.category = fm.category;
-                post.excerpt = fm
-                    .excerpt
-                    .or_else(|| Some(crate::utils::generate_excerpt(&clean_content, 50)));
 
                 post
             } else {
@@ -343,6 +771,49 @@ async fn main() -> Result<()> {
             println!("Storage ID: {storage_id}");
         }
 
+        Commands::Import { dir, author } => {
+            let dir = std::path::Path::new(&dir);
+            if !dir.is_dir() {
+                return Err(anyhow::anyhow!("Not a directory: {}", dir.display()));
+            }
+
+            println!("📥 Importing posts from {}", dir.display());
+            // Written even when the import stops early, since the database
+            // already points at what was stored
+            storage_manager.begin_batch().await?;
+            let report =
+                import::import_directory(&mut blog_manager, &storage_manager, dir, &author).await;
+            storage_manager
+                .commit_batch(&format!("Import posts from {}", dir.display()))
+                .await?;
+            let report = report?;
+            println!("✅ Import finished: {}", report.summary());
+        }
+        Commands::Export { format, output } => {
+            let format: archive::ArchiveFormat = format.parse()?;
+            let output = output.unwrap_or_else(|| format.default_output().to_string());
+
+            let export = archive::build_archive(&blog_manager).await?;
+            archive::write_archive(&export, format, std::path::Path::new(&output))?;
+
+            println!(
+                "✅ Exported {} posts and {} doc sections to {}",
+                export.manifest.posts.len(),
+                export.manifest.doc_sections.len(),
+                output
+            );
+        }
+        Commands::ImportArchive { path } => {
+            println!("📦 Restoring from {}", path);
+            let export = archive::read_archive(std::path::Path::new(&path))?;
+            storage_manager.begin_batch().await?;
+            let report = archive::restore_archive(&mut blog_manager, &export).await;
+            storage_manager
+                .commit_batch(&format!("Restore posts from {}", path))
+                .await?;
+            let report = report?;
+            println!("✅ Restore finished: {}", report.summary());
+        }
         Commands::List { published } => {
             let posts = blog_manager.list_posts(published).await?;
 
@@ -358,6 +829,14 @@ async fn main() -> Result<()> {
                     println!("Author: {}", post.author);
                     println!("Created: {}", post.created_at.format("%Y-%m-%d %H:%M"));
                     println!("Published: {}", if post.published { "Yes" } else { "No" });
+                    if let (false, Some(publish_at)) = (post.published, post.publish_at) {
+                        println!(
+                            "Scheduled: {}",
+                            publish_at
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                        );
+                    }
                     println!("Reading: {}", reading_time.to_string());
                     if !post.tags.is_empty() {
                         println!("Tags: {}", post.tags.join(", "));
@@ -367,9 +846,33 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Publish { id } => {
-            blog_manager.publish_post(&id).await?;
-            println!("✅ Post published successfully!");
+        Commands::Publish { id, at } => {
+            if let Some(at) = at {
+                let publish_at = frontmatter::parse_date(&at)?;
+                blog_manager.schedule_post(&id, publish_at).await?;
+                println!(
+                    "⏰ Post scheduled for {}",
+                    publish_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                );
+            } else {
+                let site_config = site::SiteConfig::load().unwrap_or_default();
+                if site_config.activitypub.enabled {
+                    blog_manager.enable_federation(&site_config).await?;
+                }
+                blog_manager.publish_post(&id).await?;
+                println!("✅ Post published successfully!");
+            }
+        }
+
+        Commands::PublishDue => {
+            let count = blog_manager.publish_due_posts().await?;
+            if count == 0 {
+                println!("No scheduled posts are due");
+            } else {
+                println!("✅ Published {} scheduled post(s)", count);
+            }
         }
 
         Commands::Read {
@@ -543,6 +1046,7 @@ async fn main() -> Result<()> {
             let storage_backend = match backend.as_str() {
                 "ipfs" => StorageBackend::Ipfs,
                 "github" => StorageBackend::GitHub,
+                "s3" => StorageBackend::S3,
                 _ => StorageBackend::Local,
             };
 
@@ -567,10 +1071,15 @@ async fn main() -> Result<()> {
             println!("✅ Exists check: {exists}");
         }
 
-        Commands::Generate { output, config } => {
+        Commands::Generate {
+            output,
+            config,
+            full,
+        } => {
             let site_config = site::SiteConfig::load_from(&config).unwrap_or_default();
-            let generator =
+            let mut generator =
                 site::generator::SiteGenerator::new(blog_manager, site_config, &output).await?;
+            generator.set_full_rebuild(full);
 
             generator.generate().await?;
         }
@@ -582,9 +1091,49 @@ async fn main() -> Result<()> {
             println!("📝 Edit site.toml to customize your blog settings");
         }
 
-        Commands::Serve { port, config } => {
+        Commands::Serve {
+            port,
+            config,
+            watch,
+            content,
+            templates,
+            author,
+        } => {
             let site_config = site::SiteConfig::load_from(&config).unwrap_or_default();
-            let server = web::server::WebServer::new(blog_manager, site_config, port);
+            if site_config.activitypub.enabled {
+                blog_manager.enable_federation(&site_config).await?;
+            }
+            let server = if watch {
+                let templates_dir = templates
+                    .map(Into::into)
+                    .or_else(|| site_config.theme_templates_dir())
+                    .unwrap_or_else(|| "templates".into());
+                let templates = site::templates::Templates::load(Some(templates_dir), true)?;
+                let state = web::AppState::new(blog_manager, site_config, templates);
+                let server = web::server::WebServer::with_state(state, port);
+
+                // The watcher imports through its own connection to the database
+                let watch_manager =
+                    BlogManager::new(storage_manager.clone(), &database_url).await?;
+                let state = server.state();
+                tokio::spawn(async move {
+                    if let Err(e) = web::watch::watch(
+                        watch_manager,
+                        storage_manager,
+                        state,
+                        content.into(),
+                        author,
+                    )
+                    .await
+                    {
+                        tracing::error!("Watch mode stopped: {}", e);
+                    }
+                });
+
+                server
+            } else {
+                web::server::WebServer::new(blog_manager, site_config, port)?
+            };
 
             println!("🌐 Starting web server...");
             println!("🔗 Visit http://localhost:{port}");
@@ -596,6 +1145,292 @@ async fn main() -> Result<()> {
             server.run().await?;
         }
 
+        Commands::Token { action } => {
+            let tokens = blog_manager.token_store();
+            match action {
+                TokenCommands::Create { name } => {
+                    let (token, secret) = tokens.create(&name).await?;
+                    println!("✅ Token created: {} ({})", token.name, token.id);
+                    println!("🔑 {}", secret);
+                    println!("   Store it now, it can't be shown again.");
+                    println!("   Send it as: Authorization: Bearer <token>");
+                }
+                TokenCommands::List => {
+                    let list = tokens.list().await?;
+                    if list.is_empty() {
+                        println!("No tokens. Create one with: kpgb token create <name>");
+                    }
+                    for token in list {
+                        let last_used = token
+                            .last_used_at
+                            .map(|at| format!("last used {}", at.format("%Y-%m-%d %H:%M")))
+                            .unwrap_or_else(|| "never used".to_string());
+                        let state = if token.revoked_at.is_some() {
+                            " [revoked]"
+                        } else {
+                            ""
+                        };
+                        println!(
+                            "🔑 {} ({}) - created {}, {}{}",
+                            token.name,
+                            token.id,
+                            token.created_at.format("%Y-%m-%d"),
+                            last_used,
+                            state
+                        );
+                    }
+                }
+                TokenCommands::Revoke { id } => {
+                    let revoked = tokens.revoke(&id).await?;
+                    if revoked.is_empty() {
+                        return Err(anyhow::anyhow!("No active token matches: {}", id));
+                    }
+                    for token in revoked {
+                        println!("🗑️  Revoked token {} ({})", token.name, token.id);
+                    }
+                }
+            }
+        }
+
+        Commands::Comments { action } => {
+            let comments = blog_manager.comments();
+            match action {
+                CommentsCommands::List { status } => {
+                    let list = comments.list(status.as_deref()).await?;
+                    if list.is_empty() {
+                        println!("No comments.");
+                    }
+                    let posts = blog_manager.list_posts(false).await?;
+                    for comment in list {
+                        let icon = if comment.status == "approved" {
+                            "✅"
+                        } else {
+                            "⏳"
+                        };
+                        let title = posts
+                            .iter()
+                            .find(|(_, p)| p.id == comment.post_id)
+                            .map_or(comment.post_id.as_str(), |(_, p)| p.title.as_str());
+                        println!("{} {} on \"{}\"", icon, comment.id, title);
+                        let email = comment
+                            .author_email
+                            .as_ref()
+                            .map(|email| format!(" <{}>", email))
+                            .unwrap_or_default();
+                        println!(
+                            "   👤 {}{} - {}",
+                            comment.author_name,
+                            email,
+                            comment.created_at.format("%Y-%m-%d %H:%M")
+                        );
+                        if comment.parent_id.is_some() {
+                            println!("   ↩️  reply");
+                        }
+                        println!(
+                            "   {}",
+                            wrap_text(&comment.content, 76)
+                                .trim_end()
+                                .replace('\n', "\n   ")
+                        );
+                    }
+                }
+                CommentsCommands::Approve { id } => {
+                    let approved = comments.approve(&id).await?;
+                    if approved.is_empty() {
+                        return Err(anyhow::anyhow!("No pending comment matches: {}", id));
+                    }
+                    for comment in approved {
+                        println!(
+                            "✅ Approved comment {} by {}",
+                            comment.id, comment.author_name
+                        );
+                    }
+                    println!("   Run `kpgb generate` to add it to the static site.");
+                }
+                CommentsCommands::Delete { id } => {
+                    let deleted = comments.delete(&id).await?;
+                    if deleted.is_empty() {
+                        return Err(anyhow::anyhow!("No comment matches: {}", id));
+                    }
+                    for comment in deleted {
+                        println!(
+                            "🗑️  Deleted comment {} by {}",
+                            comment.id, comment.author_name
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::Storage { action } => match action {
+            StorageCommands::Verify { post } => storage_verify(&blog_manager, post).await?,
+            StorageCommands::Gc { dry_run } => storage_gc(&blog_manager, dry_run).await?,
+        },
+
+        Commands::Deploy { action } => {
+            let ipfs = storage::ipfs::IpfsStorage::from_env()?;
+            let deploys = blog_manager.deploys();
+            match action {
+                DeployCommands::Ipfs {
+                    dir,
+                    key,
+                    no_publish,
+                } => {
+                    let key = (!no_publish).then(|| deploy::ipns_key(key));
+                    println!("📤 Adding {} to IPFS...", dir);
+                    let deployment =
+                        deploy::deploy(&ipfs, &deploys, std::path::Path::new(&dir), key.as_deref())
+                            .await?;
+                    print_deployment("✅ Deployed", &deployment);
+                }
+                DeployCommands::History { limit } => {
+                    let history = deploys.list(limit).await?;
+                    if history.is_empty() {
+                        println!("No deploys yet");
+                    }
+                    for (i, deployment) in history.iter().enumerate() {
+                        let current = if i == 0 { " (current)" } else { "" };
+                        let rollback = deployment
+                            .rollback_of
+                            .map(|id| format!(", rollback to #{}", id))
+                            .unwrap_or_default();
+                        println!(
+                            "#{} {} {} - {} files, {} bytes{}{}",
+                            deployment.id,
+                            deployment.created_at.format("%Y-%m-%d %H:%M"),
+                            deployment.cid,
+                            deployment.files,
+                            deployment.size,
+                            rollback,
+                            current
+                        );
+                    }
+                }
+                DeployCommands::Rollback { target, key } => {
+                    let deployment = deploy::rollback(
+                        &ipfs,
+                        &deploys,
+                        target.as_deref(),
+                        &deploy::ipns_key(key),
+                    )
+                    .await?;
+                    print_deployment("⏪ Rolled back", &deployment);
+                }
+            }
+        }
+
+        Commands::Webmention { action } => {
+            let webmentions = blog_manager.webmentions();
+            match action {
+                WebmentionCommands::Send {
+                    post,
+                    retry,
+                    dry_run,
+                } => webmention_send(&webmentions, post, retry, dry_run).await?,
+                WebmentionCommands::List { status } => {
+                    let list = webmentions.list(status.as_deref()).await?;
+                    if list.is_empty() {
+                        println!("No webmentions received yet.");
+                    }
+                    for mention in list {
+                        let icon = match mention.status.as_str() {
+                            "verified" => "✅",
+                            "rejected" => "❌",
+                            _ => "⏳",
+                        };
+                        println!(
+                            "{} {} [{}] {} -> {}",
+                            icon, mention.id, mention.kind, mention.source, mention.target
+                        );
+                        if let Some(author) = &mention.author_name {
+                            println!("   👤 {}", author);
+                        }
+                        println!("   📅 {}", mention.created_at.format("%Y-%m-%d %H:%M"));
+                    }
+                }
+                WebmentionCommands::Delete { id } => {
+                    if !webmentions.delete(&id).await? {
+                        return Err(anyhow::anyhow!("No webmention matches: {}", id));
+                    }
+                    println!("🗑️  Deleted webmention {}", id);
+                }
+            }
+        }
+
+        Commands::Docs { action } => {
+            let docs_db = blog_manager.docs_db();
+            match action {
+                DocsCommands::Category { action } => {
+                    docs_category_command(&docs_db, action).await?
+                }
+                DocsCommands::Section { action } => docs_section_command(&docs_db, action).await?,
+                DocsCommands::Import { dir } => {
+                    let dir = std::path::Path::new(&dir);
+                    if !dir.is_dir() {
+                        return Err(anyhow::anyhow!("Not a directory: {}", dir.display()));
+                    }
+
+                    println!("📥 Importing docs from {}", dir.display());
+                    let report = docs::import::import_doc_tree(&docs_db, dir).await?;
+                    for (path, error) in &report.failed {
+                        println!("❌ {}: {}", path.display(), error);
+                    }
+                    println!("✅ Import finished: {}", report.summary());
+                }
+                DocsCommands::Sync { category } => docs_sync(&docs_db, category).await?,
+                DocsCommands::Status { category } => docs_status(&docs_db, category).await?,
+            }
+        }
+
+        Commands::Theme { action } => match action {
+            ThemeCommands::List { config } => {
+                let site_config = site::SiteConfig::load_from(&config).unwrap_or_default();
+                let active_dir = site_config.theme_dir.as_deref().map(std::path::Path::new);
+
+                println!("🎨 Built-in themes:");
+                for (name, _) in site::theme::BUILTIN_THEMES {
+                    let marker = if active_dir.is_none() && *name == site_config.theme {
+                        " (active)"
+                    } else {
+                        ""
+                    };
+                    println!("   {}{}", name, marker);
+                }
+
+                let themes_dir = std::path::Path::new(site::theme::THEMES_DIR);
+                let dirs = site::theme::list_theme_dirs(themes_dir)?;
+                if !dirs.is_empty() {
+                    println!("\n📁 Theme directories:");
+                    for name in dirs {
+                        let path = themes_dir.join(&name);
+                        let marker = if active_dir == Some(path.as_path()) {
+                            " (active)"
+                        } else {
+                            ""
+                        };
+                        println!("   {}{}", path.display(), marker);
+                    }
+                }
+
+                if let Some(dir) = active_dir.filter(|dir| !dir.starts_with(themes_dir)) {
+                    println!("\n📁 Active theme directory: {}", dir.display());
+                }
+            }
+
+            ThemeCommands::New { name, from } => {
+                let dir = site::theme::create_theme(
+                    std::path::Path::new(site::theme::THEMES_DIR),
+                    &name,
+                    &from,
+                )?;
+                println!("✅ Theme created: {}", dir.display());
+                println!(
+                    "📝 Set theme_dir = \"{}\" in site.toml to use it",
+                    dir.display()
+                );
+            }
+        },
+
         Commands::Edit {
             id,
             title,
@@ -606,14 +1441,11 @@ async fn main() -> Result<()> {
             editor,
         } => {
             // First, get the current post
-            let posts = blog_manager.list_posts(false).await?;
-            let post_data = posts
-                .iter()
-                .find(|(storage_id, _)| storage_id == &id || storage_id.starts_with(&id))
+            let (storage_id, mut post) = blog_manager
+                .find_post(&id)
+                .await?
                 .ok_or_else(|| anyhow::anyhow!("Post not found with ID: {}", id))?;
 
-            let (storage_id, mut post) = post_data.clone();
-
             println!("📝 Editing post: {}", post.title);
             println!("   ID: {}", storage_id);
 
@@ -694,10 +1526,14 @@ async fn main() -> Result<()> {
                 return Ok(());
             }
 
-            // Update the post in database
-            blog_manager.update_post(&post).await?;
+            // Update the post in database and storage
+            let new_storage_id = blog_manager.update_post(&post).await?;
 
             println!("✅ Post updated successfully!");
+            if new_storage_id != storage_id {
+                println!("   New Storage ID: {}", new_storage_id);
+                println!("   Old ID {} still resolves to this post", storage_id);
+            }
             println!("   Title: {}", post.title);
             println!("   Author: {}", post.author);
             if !post.tags.is_empty() {
@@ -710,14 +1546,11 @@ async fn main() -> Result<()> {
 
         Commands::Delete { id, force } => {
             // First, get the post to delete
-            let posts = blog_manager.list_posts(false).await?;
-            let post_data = posts
-                .iter()
-                .find(|(storage_id, _)| storage_id == &id || storage_id.starts_with(&id))
+            let (storage_id, post) = blog_manager
+                .find_post(&id)
+                .await?
                 .ok_or_else(|| anyhow::anyhow!("Post not found with ID: {}", id))?;
 
-            let (storage_id, post) = post_data;
-
             println!("🗑️  Post to delete:");
             println!("   Title: {}", post.title);
             println!("   Author: {}", post.author);
@@ -747,6 +1580,77 @@ async fn main() -> Result<()> {
             println!("   Note: The content may still exist in IPFS if pinned elsewhere");
         }
 
+        Commands::History { id } => {
+            let (storage_id, post) = blog_manager
+                .find_post(&id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Post not found with ID: {}", id))?;
+
+            let revisions = blog_manager.list_revisions(&post.id).await?;
+
+            println!("📜 History of: {}", post.title);
+            println!("   ID: {}", storage_id);
+            println!("{:-<80}", "");
+
+            if revisions.is_empty() {
+                println!("No revisions recorded yet.");
+            } else {
+                for revision in revisions.iter().rev() {
+                    println!(
+                        "#{:<4} {}  {}  {}",
+                        revision.revision,
+                        revision.created_at.format("%Y-%m-%d %H:%M"),
+                        &revision.content_hash[..12],
+                        revision.title
+                    );
+                    println!("      Storage ID: {}", revision.storage_id);
+                }
+            }
+        }
+
+        Commands::Diff { id, rev_a, rev_b } => {
+            let (_, post) = blog_manager
+                .find_post(&id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Post not found with ID: {}", id))?;
+
+            let old = blog_manager.get_revision(&post.id, rev_a).await?;
+            let new = blog_manager.get_revision(&post.id, rev_b).await?;
+
+            if old.title != new.title {
+                println!("Title: {} -> {}", old.title, new.title);
+            }
+
+            let diff = crate::utils::unified_diff(
+                &old.content,
+                &new.content,
+                &format!("revision {}", rev_a),
+                &format!("revision {}", rev_b),
+            );
+
+            if diff.is_empty() {
+                println!(
+                    "No content changes between revision {} and {}",
+                    rev_a, rev_b
+                );
+            } else {
+                print!("{}", diff);
+            }
+        }
+
+        Commands::Rollback { id, rev } => {
+            let (storage_id, _) = blog_manager
+                .find_post(&id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Post not found with ID: {}", id))?;
+
+            let post = blog_manager.rollback_post(&storage_id, rev).await?;
+
+            println!("✅ Post rolled back to revision {}", rev);
+            println!("   Title: {}", post.title);
+            println!("   Storage ID: {}", post.storage_id.unwrap_or_default());
+        }
+
         Commands::Stats { detailed, json } => {
             let posts = blog_manager.list_posts(false).await?;
             let tags = blog_manager.get_all_tags().await?;
@@ -971,3 +1875,621 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Read a section's markdown file, dropping its frontmatter
+fn read_doc_content(path: &str) -> Result<(Option<docs::DocFrontMatter>, String)> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read content file: {}", e))?;
+    frontmatter::parse_frontmatter_as::<docs::DocFrontMatter>(&text)
+}
+
+async fn docs_category_command(
+    docs_db: &docs::DocsDatabase,
+    action: DocCategoryCommands,
+) -> Result<()> {
+    match action {
+        DocCategoryCommands::Add {
+            name,
+            slug,
+            description,
+            icon,
+            order,
+        } => {
+            let slug = slug.unwrap_or_else(|| docs::generate_doc_slug(&name));
+            if slug.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Cannot derive a slug from {:?}, pass --slug",
+                    name
+                ));
+            }
+            if docs_db.get_category_by_slug(&slug).await?.is_some() {
+                return Err(anyhow::anyhow!("Category already exists: {}", slug));
+            }
+
+            docs_db
+                .create_category(&docs::DocCategory {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    name: name.clone(),
+                    slug: slug.clone(),
+                    description,
+                    order,
+                    icon,
+                })
+                .await?;
+            println!("✅ Category created: {} ({})", name, slug);
+        }
+
+        DocCategoryCommands::List => {
+            let categories = docs_db.get_all_categories().await?;
+            if categories.is_empty() {
+                println!("No documentation categories yet.");
+                return Ok(());
+            }
+
+            println!("📚 Documentation categories:");
+            for category in categories {
+                let sections = docs_db.get_sections_by_category(&category.id).await?;
+                println!(
+                    "   {}{} ({}) - {} sections",
+                    category
+                        .icon
+                        .as_ref()
+                        .map(|icon| format!("{} ", icon))
+                        .unwrap_or_default(),
+                    category.name,
+                    category.slug,
+                    sections.len()
+                );
+            }
+        }
+
+        DocCategoryCommands::Remove { slug } => {
+            let category = docs_db
+                .get_category_by_slug(&slug)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Category not found: {}", slug))?;
+            let sections = docs_db.get_sections_by_category(&category.id).await?;
+
+            docs_db.delete_category(&category.id).await?;
+            println!(
+                "🗑️  Removed category {} and {} sections",
+                category.name,
+                sections.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn docs_section_command(
+    docs_db: &docs::DocsDatabase,
+    action: DocSectionCommands,
+) -> Result<()> {
+    let find_section = |slug: String| async move {
+        docs_db
+            .get_section_by_slug(&slug)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Section not found: {}", slug))
+    };
+
+    match action {
+        DocSectionCommands::Add {
+            category,
+            title,
+            slug,
+            content,
+            parent,
+            order,
+            source_url,
+        } => {
+            let category = docs_db
+                .get_category_by_slug(&category)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Category not found: {}", category))?;
+
+            let (meta, content) = match content {
+                Some(path) => read_doc_content(&path)?,
+                None => (None, String::new()),
+            };
+            let meta = meta.as_ref();
+
+            let slug = slug
+                .or_else(|| meta.and_then(|m| m.slug.clone()))
+                .unwrap_or_else(|| docs::generate_doc_slug(&title));
+            if slug.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Cannot derive a slug from {:?}, pass --slug",
+                    title
+                ));
+            }
+            if docs_db.get_section_by_slug(&slug).await?.is_some() {
+                return Err(anyhow::anyhow!("Section already exists: {}", slug));
+            }
+
+            let parent_id = match parent.or_else(|| meta.and_then(|m| m.parent.clone())) {
+                Some(parent) => {
+                    let parent = find_section(parent).await?;
+                    if parent.category_id != category.id {
+                        return Err(anyhow::anyhow!(
+                            "Parent {} is in another category",
+                            parent.slug
+                        ));
+                    }
+                    Some(parent.id)
+                }
+                None => None,
+            };
+            let source_url = source_url.or_else(|| meta.and_then(|m| m.source_url.clone()));
+
+            let now = chrono::Utc::now();
+            docs_db
+                .create_section(&docs::DocSection {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    category_id: category.id,
+                    title: title.clone(),
+                    slug: slug.clone(),
+                    content,
+                    parent_id,
+                    order,
+                    created_at: now,
+                    updated_at: now,
+                    is_translated: source_url.is_none(),
+                    source_url,
+                    source_text: None,
+                    source_hash: None,
+                    source_synced_at: None,
+                    is_stale: false,
+                })
+                .await?;
+            println!("✅ Section created: {} ({})", title, slug);
+        }
+
+        DocSectionCommands::List { category } => {
+            for category in select_doc_categories(docs_db, category).await? {
+                let sections = docs_db.get_sections_by_category(&category.id).await?;
+                let tree = docs::tree::SectionTree::build(sections);
+
+                println!("📚 {} ({})", category.name, category.slug);
+                for entry in tree.toc() {
+                    println!(
+                        "   {}{} ({}){}",
+                        "  ".repeat(entry.depth),
+                        entry.title,
+                        entry.slug,
+                        if !entry.is_translated {
+                            " [untranslated]"
+                        } else if entry.is_stale {
+                            " [outdated]"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+        }
+
+        DocSectionCommands::Edit {
+            slug,
+            title,
+            content,
+            new_slug,
+            source_url,
+            translated,
+        } => {
+            let mut section = find_section(slug).await?;
+            let mut updated = false;
+
+            if let Some(title) = title {
+                section.title = title;
+                updated = true;
+            }
+            if let Some(path) = content {
+                section.content = read_doc_content(&path)?.1;
+                updated = true;
+            }
+            if let Some(new_slug) = new_slug {
+                if docs_db.get_section_by_slug(&new_slug).await?.is_some() {
+                    return Err(anyhow::anyhow!("Section already exists: {}", new_slug));
+                }
+                section.slug = new_slug;
+                updated = true;
+            }
+            if let Some(source_url) = source_url {
+                section.source_url = Some(source_url).filter(|url| !url.is_empty());
+                updated = true;
+            }
+            if translated {
+                section.is_translated = true;
+                section.is_stale = false;
+                updated = true;
+            }
+
+            if !updated {
+                println!("ℹ️  No changes specified");
+                return Ok(());
+            }
+
+            docs_db.update_section(&section).await?;
+            println!("✅ Section updated: {} ({})", section.title, section.slug);
+        }
+
+        DocSectionCommands::Move {
+            slug,
+            parent,
+            root,
+            order,
+            category,
+        } => {
+            let mut section = find_section(slug).await?;
+            let all_sections = docs_db.get_all_sections().await?;
+
+            // The section and everything below it move together
+            let mut subtree = vec![section.id.clone()];
+            let mut i = 0;
+            while i < subtree.len() {
+                let id = subtree[i].clone();
+                subtree.extend(
+                    all_sections
+                        .iter()
+                        .filter(|s| s.parent_id.as_deref() == Some(id.as_str()))
+                        .map(|s| s.id.clone()),
+                );
+                i += 1;
+            }
+
+            let category_id = match category {
+                Some(category) => {
+                    docs_db
+                        .get_category_by_slug(&category)
+                        .await?
+                        .ok_or_else(|| anyhow::anyhow!("Category not found: {}", category))?
+                        .id
+                }
+                None => section.category_id.clone(),
+            };
+
+            if let Some(parent) = parent {
+                let parent = find_section(parent).await?;
+                if subtree.contains(&parent.id) {
+                    return Err(anyhow::anyhow!(
+                        "Cannot move {} under itself or one of its subsections",
+                        section.slug
+                    ));
+                }
+                if parent.category_id != category_id {
+                    return Err(anyhow::anyhow!(
+                        "Parent {} is in another category",
+                        parent.slug
+                    ));
+                }
+                section.parent_id = Some(parent.id);
+            } else if root || category_id != section.category_id {
+                // A parent in the old category no longer applies
+                section.parent_id = None;
+            }
+
+            if category_id != section.category_id {
+                for mut moved in all_sections
+                    .iter()
+                    .filter(|s| subtree.contains(&s.id) && s.id != section.id)
+                    .cloned()
+                {
+                    moved.category_id = category_id.clone();
+                    docs_db.update_section(&moved).await?;
+                }
+                section.category_id = category_id;
+            }
+
+            if let Some(order) = order {
+                section.order = order;
+            }
+
+            docs_db.update_section(&section).await?;
+            println!("✅ Section moved: {} ({})", section.title, section.slug);
+        }
+
+        DocSectionCommands::Remove { slug } => {
+            let section = find_section(slug).await?;
+            docs_db.delete_section(&section.id).await?;
+            println!("🗑️  Removed section {} and its subsections", section.title);
+        }
+    }
+
+    Ok(())
+}
+
+/// Categories selected by an optional `--category` slug
+async fn select_doc_categories(
+    docs_db: &docs::DocsDatabase,
+    category: Option<String>,
+) -> Result<Vec<docs::DocCategory>> {
+    let categories = docs_db.get_all_categories().await?;
+    match category {
+        Some(slug) => {
+            let found: Vec<_> = categories.into_iter().filter(|c| c.slug == slug).collect();
+            if found.is_empty() {
+                return Err(anyhow::anyhow!("Category not found: {}", slug));
+            }
+            Ok(found)
+        }
+        None => Ok(categories),
+    }
+}
+
+async fn docs_sync(docs_db: &docs::DocsDatabase, category: Option<String>) -> Result<()> {
+    use docs::translation::SyncOutcome;
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let (mut recorded, mut changed, mut stale, mut unchanged, mut failed) = (0, 0, 0, 0, 0);
+
+    for category in select_doc_categories(docs_db, category).await? {
+        let sections = docs_db.get_sections_by_category(&category.id).await?;
+        for section in sections.iter().filter(|s| s.source_url.is_some()) {
+            match docs::translation::sync_section(docs_db, &client, section).await {
+                Ok(SyncOutcome::Unchanged) => unchanged += 1,
+                Ok(SyncOutcome::Recorded) => {
+                    println!("📥 {}: source recorded", section.slug);
+                    recorded += 1;
+                }
+                Ok(SyncOutcome::Changed { stale: true }) => {
+                    println!("⚠️  {}: source changed, translation outdated", section.slug);
+                    changed += 1;
+                    stale += 1;
+                }
+                Ok(SyncOutcome::Changed { stale: false }) => {
+                    println!("🔄 {}: source changed", section.slug);
+                    changed += 1;
+                }
+                Err(e) => {
+                    println!("❌ {}: {}", section.slug, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!(
+        "✅ Sync finished: {} recorded, {} changed ({} outdated), {} unchanged, {} failed",
+        recorded, changed, stale, unchanged, failed
+    );
+    Ok(())
+}
+
+async fn docs_status(docs_db: &docs::DocsDatabase, category: Option<String>) -> Result<()> {
+    for category in select_doc_categories(docs_db, category).await? {
+        let sections = docs_db.get_sections_by_category(&category.id).await?;
+        let status = docs::translation::CategoryStatus::build(category, sections);
+
+        println!(
+            "📚 {} ({}) - {:.0}% translated",
+            status.category.name,
+            status.category.slug,
+            status.progress()
+        );
+        println!(
+            "   {} sections: {} translated, {} outdated, {} untranslated, {} original",
+            status.total, status.translated, status.stale, status.untranslated, status.original
+        );
+        for (state, section) in &status.pending {
+            let synced = section
+                .source_synced_at
+                .map(|at| format!(", source synced {}", at.format("%Y-%m-%d")))
+                .unwrap_or_default();
+            println!(
+                "   - [{}] {} ({}){}",
+                state.label(),
+                section.title,
+                section.slug,
+                synced
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn webmention_send(
+    webmentions: &webmention::WebmentionStore,
+    post: Option<String>,
+    retry: bool,
+    dry_run: bool,
+) -> Result<()> {
+    use webmention::send::{discover_endpoint, send_webmention};
+
+    let links: Vec<_> = webmentions
+        .pending_links(retry)
+        .await?
+        .into_iter()
+        .filter(|link| {
+            post.as_ref()
+                .map_or(true, |id| link.post_id.starts_with(id))
+        })
+        .collect();
+    if links.is_empty() {
+        println!("No links waiting. Run `kpgb generate` to collect links from published posts.");
+        return Ok(());
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(15))
+        .user_agent(concat!("kpgb/", env!("CARGO_PKG_VERSION"), " (webmention)"))
+        .build()?;
+    let (mut sent, mut no_endpoint, mut failed) = (0, 0, 0);
+
+    for link in &links {
+        let endpoint = match discover_endpoint(&client, &link.target).await {
+            Ok(Some(endpoint)) => endpoint,
+            Ok(None) => {
+                println!("➖ {}: no webmention endpoint", link.target);
+                if !dry_run {
+                    webmentions
+                        .mark_link(link, "no_endpoint", None, None)
+                        .await?;
+                }
+                no_endpoint += 1;
+                continue;
+            }
+            Err(e) => {
+                println!("❌ {}: {}", link.target, e);
+                if !dry_run {
+                    webmentions
+                        .mark_link(link, "failed", None, Some(&e.to_string()))
+                        .await?;
+                }
+                failed += 1;
+                continue;
+            }
+        };
+
+        if dry_run {
+            println!(
+                "🔍 {} -> {} (endpoint {})",
+                link.source, link.target, endpoint
+            );
+            sent += 1;
+            continue;
+        }
+
+        match send_webmention(&client, &endpoint, &link.source, &link.target).await {
+            Ok(()) => {
+                println!("📤 {} -> {}", link.source, link.target);
+                webmentions
+                    .mark_link(link, "sent", Some(endpoint.as_str()), None)
+                    .await?;
+                sent += 1;
+            }
+            Err(e) => {
+                println!("❌ {}: {}", link.target, e);
+                webmentions
+                    .mark_link(
+                        link,
+                        "failed",
+                        Some(endpoint.as_str()),
+                        Some(&e.to_string()),
+                    )
+                    .await?;
+                failed += 1;
+            }
+        }
+    }
+
+    let verb = if dry_run { "would be sent" } else { "sent" };
+    println!(
+        "✅ Webmentions: {} {}, {} without endpoint, {} failed",
+        sent, verb, no_endpoint, failed
+    );
+    Ok(())
+}
+
+async fn storage_verify(blog_manager: &BlogManager, post: Option<String>) -> Result<()> {
+    let policy = blog_manager.storage_manager().policy();
+    let mirrors: Vec<&str> = policy.mirrors.iter().map(|b| b.name()).collect();
+    println!(
+        "📦 Primary: {}, mirrors: {}",
+        policy.primary.name(),
+        if mirrors.is_empty() {
+            "none".to_string()
+        } else {
+            mirrors.join(", ")
+        }
+    );
+
+    let post_id = match &post {
+        Some(id) => Some(
+            blog_manager
+                .find_post(id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Post not found: {}", id))?
+                .1
+                .id,
+        ),
+        None => None,
+    };
+
+    let posts = blog_manager.list_posts(false).await?;
+    let results = blog_manager.verify_locations(post_id.as_deref()).await?;
+    let mut failed = 0;
+    for (location, status) in &results {
+        let title = posts
+            .iter()
+            .find(|(_, p)| p.id == location.post_id)
+            .map_or(location.post_id.as_str(), |(_, p)| p.title.as_str());
+        let icon = match status {
+            ReplicaStatus::Ok => "✅",
+            ReplicaStatus::Unavailable => "⏭️ ",
+            _ => {
+                failed += 1;
+                "❌"
+            }
+        };
+        println!(
+            "{} {} [{}] {} - {}",
+            icon,
+            title,
+            location.backend,
+            location.location,
+            status.as_str()
+        );
+        if let ReplicaStatus::Missing(error) = status {
+            println!("   {}", error);
+        }
+    }
+
+    let recorded: std::collections::HashSet<&str> =
+        results.iter().map(|(l, _)| l.post_id.as_str()).collect();
+    let untracked = posts
+        .iter()
+        .filter(|(_, p)| post_id.as_ref().is_none_or(|id| &p.id == id))
+        .filter(|(_, p)| !recorded.contains(p.id.as_str()))
+        .count();
+    if untracked > 0 {
+        println!(
+            "ℹ️  {} posts were stored before locations were recorded; saving them again records them",
+            untracked
+        );
+    }
+
+    println!("\n{} copies checked, {} failed", results.len(), failed);
+    if failed > 0 {
+        anyhow::bail!("{} copies failed verification", failed);
+    }
+    Ok(())
+}
+
+fn print_deployment(heading: &str, deployment: &deploy::Deployment) {
+    println!(
+        "{} {} files ({} bytes)",
+        heading, deployment.files, deployment.size
+    );
+    println!("📌 CID: {}", deployment.cid);
+    println!("🌐 https://ipfs.io/ipfs/{}/", deployment.cid);
+    if let Some(name) = &deployment.ipns_name {
+        println!(
+            "🔗 IPNS: /ipns/{} (key {})",
+            name,
+            deployment.ipns_key.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+async fn storage_gc(blog_manager: &BlogManager, dry_run: bool) -> Result<()> {
+    let storage = storage::local::LocalStorage::new(constants::LOCAL_STORAGE_PATH)?;
+    let references = blog_manager.storage_references().await?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::minutes(constants::GC_GRACE_PERIOD_MINUTES);
+    let report = storage.gc(&references, cutoff, dry_run).await?;
+
+    for hash in &report.removed {
+        println!("{} {}", if dry_run { "🔍" } else { "🗑️ " }, hash);
+    }
+    println!(
+        "\n{} {} blobs ({} bytes), kept {}",
+        if dry_run { "Would delete" } else { "Deleted" },
+        report.removed.len(),
+        report.freed_bytes,
+        report.kept
+    );
+    Ok(())
+}
@@ -12,10 +12,18 @@ pub struct BlogPost {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub published: bool,
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>,
     pub tags: Vec<String>,
     pub category: Option<String>,
     pub storage_id: Option<String>,
     pub content_hash: String,
+    /// Language of the post, the site default when unset
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Slug of the post this one translates
+    #[serde(default)]
+    pub translation_of: Option<String>,
 }
 
 impl BlogPost {
@@ -34,10 +42,13 @@ impl BlogPost {
             created_at: now,
             updated_at: now,
             published: false,
+            publish_at: None,
             tags: Vec::new(),
             category: None,
             storage_id: None,
             content_hash,
+            lang: None,
+            translation_of: None,
         }
     }
 
@@ -77,7 +88,6 @@ impl BlogPost {
         hex::encode(hash)
     }
 
-    #[allow(dead_code)]
     pub fn update_content(&mut self, new_content: String) {
         self.content = new_content;
         self.content_hash = Self::calculate_hash(&self.content);
@@ -85,6 +95,35 @@ impl BlogPost {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostRevision {
+    pub post_id: String,
+    pub revision: i64,
+    pub title: String,
+    pub content: String,
+    pub content_hash: String,
+    pub storage_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A copy of a stored post version in one storage backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostLocation {
+    /// The version's ID in the primary backend, as in `posts.storage_id`
+    pub storage_id: String,
+    pub post_id: String,
+    pub backend: String,
+    /// ID of the copy in `backend`
+    pub location: String,
+    /// SHA-256 of the stored bytes
+    pub hash: String,
+    pub size: i64,
+    /// `ok`, or what the last verification found
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub verified_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlogMetadata {
     pub total_posts: usize,
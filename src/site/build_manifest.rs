@@ -0,0 +1,147 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// File name of the build manifest inside the output directory
+pub const MANIFEST_FILE: &str = ".kpgb-build.json";
+
+/// Record of what the previous build produced and from which inputs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildManifest {
+    pub config_hash: String,
+    /// Template name → hash of its source
+    pub templates: BTreeMap<String, String>,
+    /// Post storage ID → content hash of the rendered version
+    pub posts: BTreeMap<String, String>,
+    /// Output path → fingerprint of the inputs it was rendered from
+    pub pages: BTreeMap<String, String>,
+    /// Image path → hash of the optimized file
+    pub images: BTreeMap<String, String>,
+}
+
+impl BuildManifest {
+    /// Load the manifest of the previous build. A missing or unreadable
+    /// manifest means everything gets rebuilt.
+    pub fn load(output_dir: &Path) -> Self {
+        fs::read_to_string(output_dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        fs::write(
+            output_dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+pub fn hash_bytes(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Hash any serializable set of inputs
+pub fn fingerprint<T: Serialize + ?Sized>(inputs: &T) -> Result<String> {
+    Ok(hash_bytes(&serde_json::to_vec(inputs)?))
+}
+
+/// Counters and per-phase timings of a build
+#[derive(Debug)]
+pub struct BuildStats {
+    pub rendered: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    pub images_optimized: usize,
+    pub phases: Vec<(&'static str, Duration)>,
+    lap_started: Instant,
+}
+
+impl Default for BuildStats {
+    fn default() -> Self {
+        Self {
+            rendered: 0,
+            skipped: 0,
+            removed: 0,
+            images_optimized: 0,
+            phases: Vec::new(),
+            lap_started: Instant::now(),
+        }
+    }
+}
+
+impl BuildStats {
+    /// Record the time spent since the previous phase ended
+    pub fn lap(&mut self, phase: &'static str) {
+        self.phases.push((phase, self.lap_started.elapsed()));
+        self.lap_started = Instant::now();
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} pages rendered, {} unchanged, {} removed, {} images optimized",
+            self.rendered, self.skipped, self.removed, self.images_optimized
+        )
+    }
+
+    pub fn timings(&self) -> String {
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        let mut out = String::new();
+        for (phase, duration) in &self.phases {
+            out.push_str(&format!(
+                "   {:<10} {:>8.1}ms\n",
+                phase,
+                duration.as_secs_f64() * 1000.0
+            ));
+        }
+        out.push_str(&format!(
+            "   {:<10} {:>8.1}ms",
+            "total",
+            total.as_secs_f64() * 1000.0
+        ));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let a = fingerprint(&("post", 1, vec!["rust"])).unwrap();
+        let b = fingerprint(&("post", 1, vec!["rust"])).unwrap();
+        let c = fingerprint(&("post", 2, vec!["rust"])).unwrap();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        // Inputs that can't be serialized must not all hash alike
+        let unserializable = BTreeMap::from([((1, 2), "tuple keys aren't JSON")]);
+        assert!(fingerprint(&unserializable).is_err());
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("kpgb-build-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(BuildManifest::load(&dir).pages.is_empty());
+
+        let mut manifest = BuildManifest::default();
+        manifest
+            .pages
+            .insert("index.html".to_string(), "abc".to_string());
+        manifest.save(&dir).unwrap();
+
+        let loaded = BuildManifest::load(&dir);
+        assert_eq!(loaded.pages.get("index.html"), Some(&"abc".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
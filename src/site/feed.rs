@@ -0,0 +1,459 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::generator::markdown_to_html;
+use super::SiteConfig;
+use crate::models::BlogPost;
+
+/// Feed settings, the `[feed]` table of site.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    /// Number of most recent posts in each feed
+    pub limit: usize,
+    /// Include the full post; otherwise only the excerpt
+    pub full_content: bool,
+    /// Also publish a feed for each tag and category
+    pub taxonomy_feeds: bool,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            limit: 20,
+            full_content: true,
+            taxonomy_feeds: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub const ALL: [FeedFormat; 3] = [FeedFormat::Rss, FeedFormat::Atom, FeedFormat::Json];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "feed.xml",
+            FeedFormat::Atom => "atom.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
+
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.file_name() == name)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml",
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Json => "application/feed+json",
+        }
+    }
+}
+
+/// The posts of one feed: the whole blog, a tag or a category
+pub struct Feed<'a> {
+    pub title: String,
+    /// Absolute URL of the listing the feed belongs to; feed files live below it
+    pub url: String,
+    /// Language of every post in the feed, if they share one
+    pub lang: Option<String>,
+    pub posts: Vec<&'a (String, BlogPost)>,
+}
+
+impl<'a> Feed<'a> {
+    /// The latest posts of the blog. `posts` are expected newest first.
+    pub fn site(config: &SiteConfig, posts: &'a [(String, BlogPost)]) -> Self {
+        Self {
+            title: config.title.clone(),
+            url: config.base_url.trim_end_matches('/').to_string(),
+            lang: None,
+            posts: posts.iter().take(config.feed.limit).collect(),
+        }
+    }
+
+    /// The latest posts written in `lang`, at `/<lang>/`
+    pub fn language(config: &SiteConfig, lang: &str, posts: &'a [(String, BlogPost)]) -> Self {
+        Self {
+            title: format!("{} ({})", config.title, config.i18n.language_name(lang)),
+            url: format!("{}/{}", config.base_url.trim_end_matches('/'), lang),
+            lang: Some(lang.to_string()),
+            posts: posts
+                .iter()
+                .filter(|(_, post)| super::i18n::lang_matches(config.i18n.post_lang(post), lang))
+                .take(config.feed.limit)
+                .collect(),
+        }
+    }
+
+    /// Posts tagged `tag`, at `/tags/<tag_slug>/`
+    pub fn tag(
+        config: &SiteConfig,
+        tag: &str,
+        tag_slug: &str,
+        posts: &'a [(String, BlogPost)],
+    ) -> Self {
+        Self {
+            title: format!("{} - {}", config.title, tag),
+            url: format!(
+                "{}/tags/{}",
+                config.base_url.trim_end_matches('/'),
+                tag_slug
+            ),
+            lang: None,
+            posts: posts
+                .iter()
+                .filter(|(_, post)| post.tags.iter().any(|t| t == tag))
+                .take(config.feed.limit)
+                .collect(),
+        }
+    }
+
+    /// Posts in `category`, at `/categories/<category_slug>/`
+    pub fn category(
+        config: &SiteConfig,
+        category: &str,
+        category_slug: &str,
+        posts: &'a [(String, BlogPost)],
+    ) -> Self {
+        Self {
+            title: format!("{} - {}", config.title, category),
+            url: format!(
+                "{}/categories/{}",
+                config.base_url.trim_end_matches('/'),
+                category_slug
+            ),
+            lang: None,
+            posts: posts
+                .iter()
+                .filter(|(_, post)| post.category.as_deref() == Some(category))
+                .take(config.feed.limit)
+                .collect(),
+        }
+    }
+
+    pub fn feed_url(&self, format: FeedFormat) -> String {
+        format!("{}/{}", self.url, format.file_name())
+    }
+
+    /// Render the feed. `post_url` gives the absolute URL of a post, which
+    /// differs between the static site and the web server.
+    pub fn render(
+        &self,
+        config: &SiteConfig,
+        format: FeedFormat,
+        post_url: impl Fn(&BlogPost) -> String,
+    ) -> Result<String> {
+        match format {
+            FeedFormat::Rss => Ok(self.render_rss(config, &post_url)),
+            FeedFormat::Atom => Ok(self.render_atom(config, &post_url)),
+            FeedFormat::Json => self.render_json(config, &post_url),
+        }
+    }
+
+    fn updated(&self) -> DateTime<Utc> {
+        self.posts
+            .iter()
+            .map(|(_, post)| post.updated_at)
+            .max()
+            .unwrap_or_else(Utc::now)
+    }
+
+    fn render_rss(&self, config: &SiteConfig, post_url: &dyn Fn(&BlogPost) -> String) -> String {
+        use rss::{ChannelBuilder, ItemBuilder};
+
+        let items: Vec<_> = self
+            .posts
+            .iter()
+            .map(|(storage_id, post)| {
+                ItemBuilder::default()
+                    .title(Some(post.title.clone()))
+                    .link(Some(post_url(post)))
+                    .description(Some(entry_html(config, post)))
+                    .author(Some(post.author.clone()))
+                    .categories(
+                        post.tags
+                            .iter()
+                            .map(|tag| rss::Category {
+                                name: tag.clone(),
+                                domain: None,
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .pub_date(Some(post.created_at.to_rfc2822()))
+                    .guid(Some(rss::Guid {
+                        value: storage_id.clone(),
+                        permalink: false,
+                    }))
+                    .build()
+            })
+            .collect();
+
+        ChannelBuilder::default()
+            .title(&self.title)
+            .link(format!("{}/", self.url))
+            .description(&config.description)
+            .language(self.lang.clone())
+            .items(items)
+            .build()
+            .to_string()
+    }
+
+    fn render_atom(&self, config: &SiteConfig, post_url: &dyn Fn(&BlogPost) -> String) -> String {
+        use atom_syndication::{Category, Content, Entry, FeedBuilder, Link, Person, Text};
+
+        let link = |href: String, rel: &str, mime_type: Option<&str>| Link {
+            href,
+            rel: rel.to_string(),
+            mime_type: mime_type.map(str::to_string),
+            ..Default::default()
+        };
+        let person = |name: &str| Person {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        let entries: Vec<Entry> = self
+            .posts
+            .iter()
+            .map(|(_, post)| {
+                let url = post_url(post);
+                let html = entry_html(config, post);
+                let mut entry = Entry {
+                    title: Text::plain(post.title.clone()),
+                    id: format!("urn:uuid:{}", post.id),
+                    updated: post.updated_at.into(),
+                    published: Some(post.created_at.into()),
+                    authors: vec![person(&post.author)],
+                    links: vec![link(url, "alternate", Some("text/html"))],
+                    categories: post
+                        .tags
+                        .iter()
+                        .map(|tag| Category {
+                            term: tag.clone(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                };
+                if config.feed.full_content {
+                    entry.content = Some(Content {
+                        value: Some(html),
+                        content_type: Some("html".to_string()),
+                        ..Default::default()
+                    });
+                } else {
+                    entry.summary = Some(Text::html(html));
+                }
+                entry
+            })
+            .collect();
+
+        FeedBuilder::default()
+            .title(self.title.as_str())
+            .id(format!("{}/", self.url))
+            .updated(self.updated())
+            .subtitle(Some(Text::plain(config.description.clone())))
+            .authors(vec![person(&config.author)])
+            .links(vec![
+                link(format!("{}/", self.url), "alternate", Some("text/html")),
+                link(
+                    self.feed_url(FeedFormat::Atom),
+                    "self",
+                    Some(FeedFormat::Atom.content_type()),
+                ),
+            ])
+            .entries(entries)
+            .lang(self.lang.clone())
+            .build()
+            .to_string()
+    }
+
+    fn render_json(
+        &self,
+        config: &SiteConfig,
+        post_url: &dyn Fn(&BlogPost) -> String,
+    ) -> Result<String> {
+        let items: Vec<JsonFeedItem> = self
+            .posts
+            .iter()
+            .map(|(_, post)| {
+                let url = post_url(post);
+                let html = entry_html(config, post);
+                JsonFeedItem {
+                    id: format!("urn:uuid:{}", post.id),
+                    url,
+                    title: post.title.clone(),
+                    content_html: html,
+                    summary: post.excerpt.clone(),
+                    date_published: post.created_at.to_rfc3339(),
+                    date_modified: post.updated_at.to_rfc3339(),
+                    authors: vec![JsonFeedAuthor {
+                        name: post.author.clone(),
+                    }],
+                    tags: post.tags.clone(),
+                }
+            })
+            .collect();
+
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: self.title.clone(),
+            home_page_url: format!("{}/", self.url),
+            feed_url: self.feed_url(FeedFormat::Json),
+            description: config.description.clone(),
+            language: self.lang.clone(),
+            authors: vec![JsonFeedAuthor {
+                name: config.author.clone(),
+            }],
+            items,
+        };
+
+        Ok(serde_json::to_string_pretty(&feed)?)
+    }
+}
+
+/// HTML of a feed entry: the whole post, or its excerpt
+fn entry_html(config: &SiteConfig, post: &BlogPost) -> String {
+    if config.feed.full_content {
+        return markdown_to_html(&post.content);
+    }
+    let excerpt = match &post.excerpt {
+        Some(excerpt) => excerpt.clone(),
+        None => crate::utils::generate_formatted_excerpt(&post.content, 300),
+    };
+    markdown_to_html(&excerpt)
+}
+
+/// JSON Feed 1.1, https://www.jsonfeed.org/version/1.1/
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    content_html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonFeedAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn posts() -> Vec<(String, BlogPost)> {
+        let mut rust = BlogPost::new(
+            "Rust & Feeds".to_string(),
+            "# Hello\n\nFirst paragraph.\n\nSecond paragraph.".to_string(),
+            "alice".to_string(),
+        );
+        rust.tags = vec!["rust".to_string()];
+        rust.excerpt = Some("Just the excerpt".to_string());
+        let mut other = BlogPost::new("Other".to_string(), "Other post".to_string(), "bob".into());
+        other.category = Some("Notes".to_string());
+        vec![("id1".to_string(), rust), ("id2".to_string(), other)]
+    }
+
+    fn post_url(post: &BlogPost) -> String {
+        format!("http://localhost:8080/posts/{}.html", post.slug)
+    }
+
+    #[test]
+    fn test_feed_formats() {
+        let config = SiteConfig::default();
+        let posts = posts();
+        let feed = Feed::site(&config, &posts);
+
+        let rss = feed.render(&config, FeedFormat::Rss, post_url).unwrap();
+        assert!(rss.contains("<rss"));
+        assert!(rss.contains("Rust &amp; Feeds"));
+
+        let atom = feed.render(&config, FeedFormat::Atom, post_url).unwrap();
+        assert!(atom.contains("http://www.w3.org/2005/Atom"));
+        assert!(atom.contains("http://localhost:8080/atom.xml"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&feed.render(&config, FeedFormat::Json, post_url).unwrap())
+                .unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+        assert!(json["items"][0]["content_html"]
+            .as_str()
+            .unwrap()
+            .contains("Second paragraph"));
+    }
+
+    #[test]
+    fn test_taxonomy_feeds_and_excerpts() {
+        let mut config = SiteConfig::default();
+        config.feed.full_content = false;
+        config.feed.limit = 1;
+        let posts = posts();
+
+        let tag = Feed::tag(&config, "rust", "rust", &posts);
+        assert_eq!(tag.posts.len(), 1);
+        assert_eq!(
+            tag.feed_url(FeedFormat::Rss),
+            "http://localhost:8080/tags/rust/feed.xml"
+        );
+        let json = tag.render(&config, FeedFormat::Json, post_url).unwrap();
+        assert!(json.contains("Just the excerpt"));
+        assert!(!json.contains("Second paragraph"));
+
+        let category = Feed::category(&config, "Notes", "notes", &posts);
+        assert_eq!(category.posts[0].0, "id2");
+        assert_eq!(Feed::site(&config, &posts).posts.len(), 1);
+        assert_eq!(
+            FeedFormat::from_file_name("atom.xml"),
+            Some(FeedFormat::Atom)
+        );
+    }
+
+    #[test]
+    fn test_language_feed() {
+        let config = SiteConfig::default();
+        let mut posts = posts();
+        posts[1].1.lang = Some("en".to_string());
+
+        let feed = Feed::language(&config, "en", &posts);
+        assert_eq!(feed.posts.len(), 1);
+        assert_eq!(feed.url, "http://localhost:8080/en");
+        let rss = feed.render(&config, FeedFormat::Rss, post_url).unwrap();
+        assert!(rss.contains("<language>en</language>"));
+
+        // Posts without a language are in the default one
+        assert_eq!(Feed::language(&config, "zh-CN", &posts).posts.len(), 1);
+    }
+}
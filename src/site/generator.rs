@@ -1,19 +1,53 @@
 use anyhow::Result;
 use chrono::Datelike;
 use pulldown_cmark::{html, Options, Parser};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tera::{Context, Tera};
+use tracing::warn;
 
+use super::build_manifest::{self, BuildManifest, BuildStats};
+use super::feed::{Feed, FeedFormat};
+use super::i18n::Translation;
+use super::pagination::{page_output_path, page_url, paginate, total_pages, Pagination};
 use super::SiteConfig;
 use crate::blog::BlogManager;
+use crate::comments::{thread, ThreadedComment};
+use crate::docs::tree::{SectionLink, SectionTree};
 use crate::models::BlogPost;
+use crate::webmention::send::outbound_links;
+use crate::webmention::Webmention;
+
+/// Everything a post page is rendered from, also its build fingerprint
+#[derive(Serialize)]
+struct PostPage<'a> {
+    storage_id: &'a str,
+    post: &'a BlogPost,
+    prev_post: Option<&'a (String, BlogPost)>,
+    next_post: Option<&'a (String, BlogPost)>,
+    related_posts: Vec<(String, BlogPost)>,
+    translations: &'a [Translation],
+    webmentions: Vec<Webmention>,
+    comments: Vec<ThreadedComment>,
+}
 
 pub struct SiteGenerator {
     blog_manager: BlogManager,
     config: SiteConfig,
     tera: Tera,
     output_dir: PathBuf,
+    /// Manifest of the previous build, used to skip pages whose inputs are unchanged
+    previous: BuildManifest,
+    manifest: Mutex<BuildManifest>,
+    stats: Mutex<BuildStats>,
+    /// Hash of the config and templates, part of every page fingerprint
+    inputs_hash: String,
+    /// Static files of the theme directory, replacing built-in output of the same path
+    theme_assets: BTreeMap<String, PathBuf>,
+    full_rebuild: bool,
 }
 
 impl SiteGenerator {
@@ -28,38 +62,48 @@ impl SiteGenerator {
         fs::create_dir_all(&output_dir)?;
 
         // Load templates
-        let mut tera = Tera::default();
-        tera.add_raw_templates(vec![
-            ("base.html", include_str!("../../templates/base.html")),
-            ("index.html", include_str!("../../templates/index.html")),
-            ("post.html", include_str!("../../templates/post.html")),
-            ("archive.html", include_str!("../../templates/archive.html")),
-            ("tags.html", include_str!("../../templates/tags.html")),
-            (
-                "tag_posts.html",
-                include_str!("../../templates/tag_posts.html"),
-            ),
-            ("docs.html", include_str!("../../templates/docs.html")),
-        ])?;
-
-        // Add custom filters
-        tera.register_filter("url_safe_tag", crate::site::filters::url_safe_tag);
-        tera.register_filter("highlight_search", crate::site::filters::highlight_search);
-        tera.register_filter("escape", crate::site::filters::escape_html);
+        let sources = super::templates::template_sources(config.theme_templates_dir().as_deref())?;
+        let tera = super::templates::build_tera(&sources)?;
+
+        let templates: BTreeMap<String, String> = sources
+            .iter()
+            .map(|(name, source)| (name.clone(), build_manifest::hash_bytes(source.as_bytes())))
+            .collect();
+        let config_hash = build_manifest::fingerprint(&config)?;
+        let inputs_hash = build_manifest::fingerprint(&(&config_hash, &templates))?;
+
+        let theme_assets = config.theme_assets()?;
 
         Ok(Self {
             blog_manager,
             config,
+            theme_assets,
             tera,
+            previous: BuildManifest::load(&output_dir),
+            manifest: Mutex::new(BuildManifest {
+                config_hash,
+                templates,
+                ..Default::default()
+            }),
+            stats: Mutex::new(BuildStats::default()),
             output_dir,
+            inputs_hash,
+            full_rebuild: false,
         })
     }
 
+    /// Render every page even if the previous build manifest says it is up to date
+    pub fn set_full_rebuild(&mut self, full_rebuild: bool) {
+        self.full_rebuild = full_rebuild;
+    }
+
     pub async fn generate(&self) -> Result<()> {
         println!("🚀 Generating static site...");
+        self.report_changed_inputs();
 
         // Copy static assets
         self.copy_static_assets()?;
+        self.lap("assets");
 
         // Get all published posts
         let mut posts = self.blog_manager.list_posts(true).await?;
@@ -83,8 +127,28 @@ impl SiteGenerator {
         // Sort by created_at desc to maintain order
         posts.sort_by(|a, b| b.1.created_at.cmp(&a.1.created_at));
 
+        {
+            let mut manifest = self.manifest.lock().unwrap();
+            for (storage_id, post) in &posts {
+                manifest
+                    .posts
+                    .insert(storage_id.clone(), post.content_hash.clone());
+            }
+            let changed = manifest
+                .posts
+                .iter()
+                .filter(|(id, hash)| self.previous.posts.get(*id) != Some(*hash))
+                .count();
+            println!(
+                "📝 {} published posts, {} new or changed",
+                posts.len(),
+                changed
+            );
+        }
+
         // Generate index page
         self.generate_index(&posts).await?;
+        self.lap("index");
 
         // Generate individual post pages with navigation
         for (index, (storage_id, post)) in posts.iter().enumerate() {
@@ -101,36 +165,56 @@ impl SiteGenerator {
                 None
             };
 
-            self.generate_post_page(storage_id, post, prev_post, next_post)
+            let translations = self.post_translations(post, &posts);
+            self.generate_post_page(storage_id, post, prev_post, next_post, &translations)
                 .await?;
         }
+        self.lap("posts");
 
         // Generate archive page
         self.generate_archive(&posts).await?;
+        self.lap("archive");
 
-        // Generate RSS feed
+        // Generate RSS, Atom and JSON feeds
         if self.config.enable_rss {
-            self.generate_rss(&posts).await?;
+            self.generate_feeds(&posts)?;
         }
+        self.lap("feeds");
+
+        // Generate per-language index, feeds and sitemap
+        self.generate_language_pages(&posts)?;
+        self.lap("languages");
 
         // Generate tag pages
         self.generate_tag_pages(&posts).await?;
+        self.lap("tags");
 
         // Generate docs page
         self.generate_docs_page().await?;
+        self.lap("docs");
 
         // Generate SEO files
         self.generate_sitemap(&posts).await?;
         self.generate_robots_txt()?;
         self.generate_webmanifest()?;
         self.create_default_images()?;
+        self.copy_theme_assets()?;
+        self.lap("seo");
 
         // Optimize images
         self.optimize_all_images().await?;
+        self.lap("images");
 
-        // Minify HTML/CSS/JS
-        self.minify_resources().await?;
+        // Pages the previous build wrote but this one didn't belong to
+        // unpublished posts, removed tags or dropped pagination pages
+        self.remove_stale_pages()?;
+        self.lap("cleanup");
 
+        self.manifest.lock().unwrap().save(&self.output_dir)?;
+
+        let stats = self.stats.lock().unwrap();
+        println!("📊 {}", stats.summary());
+        println!("⏱️  Build timings:\n{}", stats.timings());
         println!(
             "✅ Site generated successfully in: {}",
             self.output_dir.display()
@@ -138,93 +222,207 @@ impl SiteGenerator {
         Ok(())
     }
 
-    async fn generate_index(&self, posts: &[(String, BlogPost)]) -> Result<()> {
-        let posts_per_page = self.config.posts_per_page;
-        let total_pages = posts.len().div_ceil(posts_per_page);
-
-        // Generate each page
-        for page in 1..=total_pages.max(1) {
-            let mut context = Context::new();
-            context.insert("site", &self.config);
-            let page_title = if page == 1 {
-                "Home".to_string()
-            } else {
-                format!("Page {}", page)
-            };
-            context.insert("page_title", &page_title);
+    fn lap(&self, phase: &'static str) {
+        self.stats.lock().unwrap().lap(phase);
+    }
 
-            // Calculate post range for this page
-            let start = (page - 1) * posts_per_page;
-            let end = (start + posts_per_page).min(posts.len());
+    fn report_changed_inputs(&self) {
+        let manifest = self.manifest.lock().unwrap();
+        if self.full_rebuild || self.previous.pages.is_empty() {
+            println!("🔨 Full build");
+        } else if self.previous.config_hash != manifest.config_hash {
+            println!("🔨 Site config changed, rebuilding all pages");
+        } else if self.previous.templates != manifest.templates {
+            println!("🔨 Templates changed, rebuilding all pages");
+        }
+    }
 
-            let page_posts: Vec<_> = posts[start..end]
-                .iter()
-                .map(|(id, post)| {
-                    let mut post_context = serde_json::to_value(post).unwrap();
-                    let base_path = self.config.base_path.as_deref().unwrap_or("");
-                    post_context["url"] = serde_json::Value::String(format!(
-                        "{}/posts/{}.html",
-                        base_path,
-                        sanitize_slug(&post.slug)
-                    ));
-                    post_context["content_html"] =
-                        serde_json::Value::String(markdown_to_html(&post.content));
-                    post_context["storage_id"] = serde_json::Value::String(id.clone());
+    /// Write a generated file unless the previous build produced it from the
+    /// same inputs. HTML, CSS and JS are minified as they are written.
+    fn write_page<T: Serialize + ?Sized>(
+        &self,
+        path: &Path,
+        inputs: &T,
+        render: impl FnOnce() -> Result<String>,
+    ) -> Result<()> {
+        // The theme ships its own version of this file
+        if self.theme_assets.contains_key(&self.output_key(path)) {
+            return Ok(());
+        }
+        self.write_output(path, inputs, || Ok(render()?.into_bytes()))
+    }
 
-                    // Add reading time
-                    let reading_time = crate::utils::calculate_reading_time(&post.content, false);
-                    post_context["reading_time"] =
-                        serde_json::Value::String(reading_time.to_string());
-                    post_context["reading_minutes"] =
-                        serde_json::Value::Number(reading_time.minutes.into());
-
-                    // Generate excerpt HTML if not provided
-                    if post.excerpt.is_none() {
-                        let excerpt_text =
-                            crate::utils::generate_formatted_excerpt(&post.content, 300);
-                        post_context["excerpt_html"] =
-                            serde_json::Value::String(markdown_to_html(&excerpt_text));
-                    } else {
-                        post_context["excerpt_html"] = serde_json::Value::String(markdown_to_html(
-                            post.excerpt.as_ref().unwrap(),
-                        ));
-                    }
+    fn write_output<T: Serialize + ?Sized>(
+        &self,
+        path: &Path,
+        inputs: &T,
+        render: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<()> {
+        let key = self.output_key(path);
+        let fingerprint = build_manifest::fingerprint(&(&self.inputs_hash, inputs))?;
 
-                    post_context
-                })
-                .collect();
+        let unchanged = !self.full_rebuild
+            && path.exists()
+            && self.previous.pages.get(&key) == Some(&fingerprint);
+        self.manifest.lock().unwrap().pages.insert(key, fingerprint);
 
-            context.insert("posts", &page_posts);
+        if unchanged {
+            self.stats.lock().unwrap().skipped += 1;
+            return Ok(());
+        }
 
-            // Pagination context
-            context.insert("current_page", &page);
-            context.insert("total_pages", &total_pages);
-            context.insert("has_prev", &(page > 1));
-            context.insert("has_next", &(page < total_pages));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, render()?)?;
+        if let Err(e) = crate::utils::minify_file(path, &crate::utils::MinifyConfig::default()) {
+            warn!("Failed to minify {}: {}", path.display(), e);
+        }
 
-            let rendered = self.tera.render("index.html", &context)?;
+        self.stats.lock().unwrap().rendered += 1;
+        Ok(())
+    }
 
-            // Determine output path
-            let output_path = if page == 1 {
-                self.output_dir.join("index.html")
-            } else {
-                let page_dir = self.output_dir.join("page").join(page.to_string());
-                fs::create_dir_all(&page_dir)?;
-                page_dir.join("index.html")
-            };
+    /// Path of an output file relative to the output directory, as stored in the manifest
+    fn output_key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.output_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
 
-            fs::write(output_path, rendered)?;
+    fn copy_theme_assets(&self) -> Result<()> {
+        for (key, source) in &self.theme_assets {
+            let data = fs::read(source)?;
+            let hash = build_manifest::hash_bytes(&data);
+            self.write_output(&self.output_dir.join(key), &hash, || Ok(data))?;
+        }
+        Ok(())
+    }
+
+    fn remove_stale_pages(&self) -> Result<()> {
+        let manifest = self.manifest.lock().unwrap();
+        let mut removed = 0;
+
+        for key in self.previous.pages.keys() {
+            if manifest.pages.contains_key(key) {
+                continue;
+            }
+
+            let path = self.output_dir.join(key);
+            if path.exists() {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+
+            // Drop directories left empty, e.g. tags/<tag>/page/2/
+            let mut dir = path.parent();
+            while let Some(current) = dir {
+                if current == self.output_dir || fs::remove_dir(current).is_err() {
+                    break;
+                }
+                dir = current.parent();
+            }
+        }
+
+        if removed > 0 {
+            println!("🗑️  Removed {} stale pages", removed);
+        }
+        self.stats.lock().unwrap().removed += removed;
+        Ok(())
+    }
+
+    async fn generate_index(&self, posts: &[(String, BlogPost)]) -> Result<()> {
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+        self.generate_index_pages(posts, &self.output_dir, base_path, None)
+    }
+
+    /// Paginated post listing in `dir`, linked at `base`. With `lang` set it is
+    /// the index of one language.
+    fn generate_index_pages(
+        &self,
+        posts: &[(String, BlogPost)],
+        dir: &Path,
+        base: &str,
+        lang: Option<&str>,
+    ) -> Result<()> {
+        let pages = paginate(posts, self.config.posts_per_page);
+        let total_pages = pages.len();
+
+        for (i, page_posts) in pages.into_iter().enumerate() {
+            let page = i + 1;
+            let output_path = page_output_path(dir, page);
+
+            self.write_page(&output_path, &(lang, page, total_pages, page_posts), || {
+                let mut context = Context::new();
+                context.insert("site", &self.config);
+                let home = match lang {
+                    Some(lang) => self.config.i18n.language_name(lang),
+                    None => "Home".to_string(),
+                };
+                let page_title = if page == 1 {
+                    home
+                } else {
+                    format!("{} - Page {}", home, page)
+                };
+                context.insert("page_title", &page_title);
+                if let Some(lang) = lang {
+                    context.insert("page_lang", lang);
+                    context.insert("current_lang", lang);
+                    if self.config.enable_rss {
+                        context.insert("lang_feed_url", &format!("{}/feed.xml", base));
+                    }
+                }
+
+                let page_posts: Vec<_> = page_posts
+                    .iter()
+                    .map(|(id, post)| self.post_summary_context(id, post))
+                    .collect();
+
+                context.insert("posts", &page_posts);
+                Pagination::new(base, page, total_pages).insert_into(&mut context);
+
+                Ok(self.tera.render("index.html", &context)?)
+            })?;
         }
 
         Ok(())
     }
 
+    /// Context for a post in a listing: URL, rendered content, reading time and excerpt
+    fn post_summary_context(&self, id: &str, post: &BlogPost) -> serde_json::Value {
+        let mut post_context = serde_json::to_value(post).unwrap();
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+        post_context["url"] = serde_json::Value::String(format!(
+            "{}/posts/{}.html",
+            base_path,
+            sanitize_slug(&post.slug)
+        ));
+        post_context["content_html"] = serde_json::Value::String(markdown_to_html(&post.content));
+        post_context["storage_id"] = serde_json::Value::String(id.to_string());
+
+        // Add reading time
+        let reading_time = crate::utils::calculate_reading_time(&post.content, false);
+        post_context["reading_time"] = serde_json::Value::String(reading_time.to_string());
+        post_context["reading_minutes"] = serde_json::Value::Number(reading_time.minutes.into());
+
+        // Generate excerpt HTML if not provided
+        let excerpt_text = match &post.excerpt {
+            Some(excerpt) => excerpt.clone(),
+            None => crate::utils::generate_formatted_excerpt(&post.content, 300),
+        };
+        post_context["excerpt_html"] = serde_json::Value::String(markdown_to_html(&excerpt_text));
+
+        post_context
+    }
+
     async fn generate_post_page(
         &self,
         storage_id: &str,
         post: &BlogPost,
         prev_post: Option<&(String, BlogPost)>,
         next_post: Option<&(String, BlogPost)>,
+        translations: &[Translation],
     ) -> Result<()> {
         // Get related posts
         let related_posts = self
@@ -232,6 +430,69 @@ impl SiteGenerator {
             .get_related_posts(&post.id, &post.tags, post.category.as_deref(), 5)
             .await?;
 
+        // Sanitize slug for file name (remove non-ASCII characters)
+        let safe_slug = sanitize_slug(&post.slug);
+        let output_path = self
+            .output_dir
+            .join("posts")
+            .join(format!("{}.html", safe_slug));
+
+        // Links to other sites, for `kpgb webmention send`
+        let webmentions = self.blog_manager.webmentions();
+        let source = format!("{}/posts/{}.html", self.config.base_url, safe_slug);
+        let links = outbound_links(&post.content, &self.config.base_url);
+        webmentions.record_links(&post.id, &source, &links).await?;
+
+        let page = PostPage {
+            storage_id,
+            post,
+            prev_post,
+            next_post,
+            related_posts,
+            translations,
+            webmentions: webmentions.verified_for_post(&post.id).await?,
+            comments: if self.config.comments.enabled {
+                thread(
+                    self.blog_manager
+                        .comments()
+                        .approved_for_post(&post.id)
+                        .await?,
+                )
+            } else {
+                Vec::new()
+            },
+        };
+        self.write_page(&output_path, &page, || self.render_post_page(&page))
+    }
+
+    /// Language variants of a post, empty unless it has translations
+    fn post_translations(&self, post: &BlogPost, posts: &[(String, BlogPost)]) -> Vec<Translation> {
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+        self.config.i18n.translations(
+            post,
+            posts,
+            |p| format!("{}/posts/{}.html", base_path, sanitize_slug(&p.slug)),
+            |p| {
+                format!(
+                    "{}/posts/{}.html",
+                    self.config.base_url,
+                    sanitize_slug(&p.slug)
+                )
+            },
+        )
+    }
+
+    fn render_post_page(&self, page: &PostPage) -> Result<String> {
+        let PostPage {
+            storage_id,
+            post,
+            prev_post,
+            next_post,
+            related_posts,
+            translations,
+            webmentions,
+            comments,
+        } = page;
         let related_posts_data: Vec<_> = related_posts
             .iter()
             .map(|(_, related_post)| {
@@ -256,12 +517,19 @@ impl SiteGenerator {
         context.insert("site", &self.config);
         context.insert("page_title", &post.title);
         context.insert("post", post);
+        context.insert("page_lang", self.config.i18n.post_lang(post));
+        context.insert("translations", translations);
 
         // Generate HTML content with heading IDs
         let content_html = markdown_to_html(&post.content);
         context.insert("content_html", &content_html);
         context.insert("storage_id", storage_id);
         context.insert("related_posts", &related_posts_data);
+        context.insert("webmentions", webmentions);
+        context.insert("comments", comments);
+        if let Some(endpoint) = &self.config.comments.endpoint {
+            context.insert("comment_endpoint", endpoint);
+        }
 
         // Generate table of contents
         let toc = crate::utils::generate_toc(&post.content);
@@ -336,100 +604,161 @@ impl SiteGenerator {
             context.insert("next_post", &next_context);
         }
 
-        let rendered = self.tera.render("post.html", &context)?;
-
-        // Create posts directory
-        let posts_dir = self.output_dir.join("posts");
-        fs::create_dir_all(&posts_dir)?;
-
-        // Sanitize slug for file name (remove non-ASCII characters)
-        let safe_slug = sanitize_slug(&post.slug);
-        let output_path = posts_dir.join(format!("{}.html", safe_slug));
-        fs::write(output_path, rendered)?;
-
-        Ok(())
+        Ok(self.tera.render("post.html", &context)?)
     }
 
     async fn generate_archive(&self, posts: &[(String, BlogPost)]) -> Result<()> {
-        let mut context = Context::new();
-        context.insert("site", &self.config);
-        context.insert("page_title", "Archive");
-
-        // Group posts by year
-        let mut posts_by_year: std::collections::HashMap<i32, Vec<_>> =
-            std::collections::HashMap::new();
-
-        for (id, post) in posts {
-            let year = post.created_at.year();
-            let mut post_context = serde_json::to_value(post).unwrap();
-            let base_path = self.config.base_path.as_deref().unwrap_or("");
-            post_context["url"] = serde_json::Value::String(format!(
-                "{}/posts/{}.html",
-                base_path,
-                sanitize_slug(&post.slug)
-            ));
-            post_context["storage_id"] = serde_json::Value::String(id.clone());
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+        let archive_base = format!("{}/archive", base_path);
+        let pages = paginate(posts, self.config.posts_per_page);
+        let total_pages = pages.len();
+
+        for (i, page_posts) in pages.into_iter().enumerate() {
+            let page = i + 1;
+            let render = || {
+                let mut context = Context::new();
+                context.insert("site", &self.config);
+                let page_title = if page == 1 {
+                    "Archive".to_string()
+                } else {
+                    format!("Archive - Page {}", page)
+                };
+                context.insert("page_title", &page_title);
+
+                // Group posts by year
+                let mut posts_by_year: std::collections::HashMap<i32, Vec<_>> =
+                    std::collections::HashMap::new();
+
+                for (id, post) in page_posts {
+                    let year = post.created_at.year();
+                    let mut post_context = serde_json::to_value(post).unwrap();
+                    post_context["url"] = serde_json::Value::String(format!(
+                        "{}/posts/{}.html",
+                        base_path,
+                        sanitize_slug(&post.slug)
+                    ));
+                    post_context["storage_id"] = serde_json::Value::String(id.clone());
 
-            posts_by_year
-                .entry(year)
-                .or_insert_with(Vec::new)
-                .push(post_context);
-        }
+                    posts_by_year
+                        .entry(year)
+                        .or_insert_with(Vec::new)
+                        .push(post_context);
+                }
 
-        let mut years: Vec<_> = posts_by_year.into_iter().collect();
-        years.sort_by(|a, b| b.0.cmp(&a.0)); // Sort years descending
+                let mut years: Vec<_> = posts_by_year.into_iter().collect();
+                years.sort_by(|a, b| b.0.cmp(&a.0)); // Sort years descending
 
-        context.insert("years", &years);
+                context.insert("years", &years);
+                Pagination::new(&archive_base, page, total_pages).insert_into(&mut context);
 
-        let rendered = self.tera.render("archive.html", &context)?;
+                Ok(self.tera.render("archive.html", &context)?)
+            };
 
-        // Create archive directory for clean URLs
-        let archive_dir = self.output_dir.join("archive");
-        fs::create_dir_all(&archive_dir)?;
-        let output_path = archive_dir.join("index.html");
-        fs::write(&output_path, &rendered)?;
+            let inputs = (page, total_pages, page_posts);
+            let archive_dir = self.output_dir.join("archive");
+            self.write_page(&page_output_path(&archive_dir, page), &inputs, render)?;
 
-        // Also create archive.html for backward compatibility
-        let archive_html_path = self.output_dir.join("archive.html");
-        fs::write(archive_html_path, rendered)?;
+            // archive.html for backward compatibility
+            if page == 1 {
+                self.write_page(&self.output_dir.join("archive.html"), &inputs, render)?;
+            }
+        }
 
         Ok(())
     }
 
-    async fn generate_rss(&self, posts: &[(String, BlogPost)]) -> Result<()> {
-        use rss::{ChannelBuilder, ItemBuilder};
-
-        let mut items = Vec::new();
+    fn generate_feeds(&self, posts: &[(String, BlogPost)]) -> Result<()> {
+        let mut feeds = vec![(self.output_dir.clone(), Feed::site(&self.config, posts))];
 
-        for (storage_id, post) in posts.iter().take(20) {
-            // Latest 20 posts
-            let link = format!("{}/posts/{}.html", self.config.base_url, post.slug);
-            let content_html = markdown_to_html(&post.content);
+        if self.config.feed.taxonomy_feeds {
+            let mut tags = BTreeSet::new();
+            let mut categories = BTreeSet::new();
+            for (_, post) in posts {
+                tags.extend(post.tags.iter());
+                categories.extend(post.category.iter());
+            }
 
-            let item = ItemBuilder::default()
-                .title(Some(post.title.clone()))
-                .link(Some(link))
-                .description(Some(content_html))
-                .author(Some(post.author.clone()))
-                .pub_date(Some(post.created_at.to_rfc2822()))
-                .guid(Some(rss::Guid {
-                    value: storage_id.clone(),
-                    permalink: false,
-                }))
-                .build();
+            for tag in tags {
+                let slug = sanitize_tag_for_url(tag);
+                let dir = self.output_dir.join("tags").join(&slug);
+                feeds.push((dir, Feed::tag(&self.config, tag, &slug, posts)));
+            }
+            for category in categories {
+                let slug = sanitize_tag_for_url(category);
+                let dir = self.output_dir.join("categories").join(&slug);
+                feeds.push((dir, Feed::category(&self.config, category, &slug, posts)));
+            }
+        }
 
-            items.push(item);
+        for (dir, feed) in &feeds {
+            for format in FeedFormat::ALL {
+                let path = dir.join(format.file_name());
+                self.write_page(&path, &(format.file_name(), &feed.posts), || {
+                    feed.render(&self.config, format, |post| {
+                        format!(
+                            "{}/posts/{}.html",
+                            self.config.base_url,
+                            sanitize_slug(&post.slug)
+                        )
+                    })
+                })?;
+            }
         }
 
-        let channel = ChannelBuilder::default()
-            .title(&self.config.title)
-            .link(&self.config.base_url)
-            .description(&self.config.description)
-            .items(items)
-            .build();
+        println!("📡 Generated {} feeds (RSS, Atom, JSON Feed)", feeds.len());
+        Ok(())
+    }
 
-        let output_path = self.output_dir.join("feed.xml");
-        fs::write(output_path, channel.to_string())?;
+    /// Index, feeds and sitemap of each configured language under `/<code>/`
+    fn generate_language_pages(&self, posts: &[(String, BlogPost)]) -> Result<()> {
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+
+        for language in &self.config.i18n.languages {
+            let lang = language.code.as_str();
+            let lang_posts = self.config.i18n.posts_in(lang, posts);
+            let dir = self.output_dir.join(lang);
+            let base = format!("{}/{}", base_path, lang);
+
+            self.generate_index_pages(&lang_posts, &dir, &base, Some(lang))?;
+
+            if self.config.enable_rss {
+                let feed = Feed::language(&self.config, lang, &lang_posts);
+                for format in FeedFormat::ALL {
+                    let path = dir.join(format.file_name());
+                    self.write_page(&path, &(lang, format.file_name(), &feed.posts), || {
+                        feed.render(&self.config, format, |post| {
+                            format!(
+                                "{}/posts/{}.html",
+                                self.config.base_url,
+                                sanitize_slug(&post.slug)
+                            )
+                        })
+                    })?;
+                }
+            }
+
+            let lang_base = format!("{}/{}", self.config.base_url, lang);
+            let mut sitemap = String::from(SITEMAP_HEADER);
+            for page in 1..=total_pages(lang_posts.len(), self.config.posts_per_page) {
+                sitemap.push_str(&format!(
+                    "  <url>\n    <loc>{}</loc>\n    <changefreq>daily</changefreq>\n    <priority>{}</priority>\n  </url>\n",
+                    page_url(&lang_base, page),
+                    if page == 1 { "1.0" } else { "0.4" }
+                ));
+            }
+            for (_, post) in &lang_posts {
+                sitemap.push_str(&self.sitemap_post_entry(post, posts));
+            }
+            sitemap.push_str("</urlset>");
+            self.write_page(&dir.join("sitemap.xml"), &sitemap, || Ok(sitemap.clone()))?;
+
+            println!(
+                "🌐 Generated {} pages for {} ({} posts)",
+                language.name,
+                lang,
+                lang_posts.len()
+            );
+        }
 
         Ok(())
     }
@@ -437,18 +766,13 @@ impl SiteGenerator {
     fn copy_static_assets(&self) -> Result<()> {
         // Create CSS
         let css_dir = self.output_dir.join("css");
-        fs::create_dir_all(&css_dir)?;
 
         // Load theme-specific CSS
-        let css_content = match self.config.theme.as_str() {
-            "hacker" => include_str!("../../templates/themes/hacker.css"),
-            "minimal" => include_str!("../../templates/themes/minimal.css"),
-            "dark" => include_str!("../../templates/themes/dark.css"),
-            "cyberpunk" => include_str!("../../templates/themes/cyberpunk.css"),
-            _ => include_str!("../../templates/style.css"), // default theme
-        };
+        let css_content = super::theme::builtin_css(&self.config.theme);
 
-        fs::write(css_dir.join("style.css"), css_content)?;
+        self.write_page(&css_dir.join("style.css"), &css_content, || {
+            Ok(css_content.to_string())
+        })?;
 
         // Create images directory
         let images_dir = self.output_dir.join("images");
@@ -457,9 +781,15 @@ impl SiteGenerator {
         Ok(())
     }
 
+    /// Tags with published posts; tags of deleted posts stay in the database
+    async fn published_tags(&self) -> Result<Vec<(String, i64)>> {
+        let mut tags = self.blog_manager.get_all_tags().await?;
+        tags.retain(|(_, count)| *count > 0);
+        Ok(tags)
+    }
+
     async fn generate_tag_pages(&self, posts: &[(String, BlogPost)]) -> Result<()> {
-        // Get all tags
-        let all_tags = self.blog_manager.get_all_tags().await?;
+        let all_tags = self.published_tags().await?;
 
         // Generate main tags page
         let mut context = Context::new();
@@ -487,12 +817,10 @@ impl SiteGenerator {
         context.insert("tags", &tag_data);
 
         // Create tags directory with index.html for clean URLs
-        let tags_dir = self.output_dir.join("tags");
-        fs::create_dir_all(&tags_dir)?;
-
-        let rendered = self.tera.render("tags.html", &context)?;
-        let output_path = tags_dir.join("index.html");
-        fs::write(output_path, rendered)?;
+        let output_path = self.output_dir.join("tags").join("index.html");
+        self.write_page(&output_path, &all_tags, || {
+            Ok(self.tera.render("tags.html", &context)?)
+        })?;
 
         // Generate individual tag pages
         for (tag_name, _count) in all_tags {
@@ -510,90 +838,52 @@ impl SiteGenerator {
             .filter(|(_, post)| post.tags.contains(&tag.to_string()))
             .collect();
 
-        let posts_per_page = self.config.posts_per_page;
-        let total_pages = tag_posts.len().div_ceil(posts_per_page);
+        let base_path = self.config.base_path.as_deref().unwrap_or("");
+        let tag_url_safe = sanitize_tag_for_url(tag);
+        let tag_base = format!("{}/tags/{}", base_path, tag_url_safe);
+        let tag_dir = self.output_dir.join("tags").join(&tag_url_safe);
+        let pages = paginate(&tag_posts, self.config.posts_per_page);
+        let total_pages = pages.len();
 
         // Generate each page for this tag
-        for page in 1..=total_pages.max(1) {
-            // Calculate post range for this page
-            let start = (page - 1) * posts_per_page;
-            let end = (start + posts_per_page).min(tag_posts.len());
-
-            let posts_data: Vec<_> = tag_posts[start..end]
-                .iter()
-                .map(|(id, post)| {
-                    let mut post_context = serde_json::to_value(post).unwrap();
-                    let base_path = self.config.base_path.as_deref().unwrap_or("");
-                    post_context["url"] = serde_json::Value::String(format!(
-                        "{}/posts/{}.html",
-                        base_path,
-                        sanitize_slug(&post.slug)
-                    ));
-                    post_context["content_html"] =
-                        serde_json::Value::String(markdown_to_html(&post.content));
-                    post_context["storage_id"] = serde_json::Value::String(id.clone());
-
-                    // Add reading time
-                    let reading_time = crate::utils::calculate_reading_time(&post.content, false);
-                    post_context["reading_time"] =
-                        serde_json::Value::String(reading_time.to_string());
-                    post_context["reading_minutes"] =
-                        serde_json::Value::Number(reading_time.minutes.into());
-
-                    // Generate excerpt HTML if not provided
-                    if post.excerpt.is_none() {
-                        let excerpt_text =
-                            crate::utils::generate_formatted_excerpt(&post.content, 300);
-                        post_context["excerpt_html"] =
-                            serde_json::Value::String(markdown_to_html(&excerpt_text));
-                    } else {
-                        post_context["excerpt_html"] = serde_json::Value::String(markdown_to_html(
-                            post.excerpt.as_ref().unwrap(),
-                        ));
-                    }
-
-                    post_context
-                })
-                .collect();
-
-            let mut context = Context::new();
-            context.insert("site", &self.config);
-            context.insert("posts", &posts_data);
-            context.insert("tag", tag);
-            context.insert("title", &format!("Posts tagged '{}'", tag));
-
-            // Pagination context
-            context.insert("current_page", &page);
-            context.insert("total_pages", &total_pages);
-            context.insert("has_prev", &(page > 1));
-            context.insert("has_next", &(page < total_pages));
-
-            // Create output directory
-            let tag_url_safe = sanitize_tag_for_url(tag);
-            let output_path = if page == 1 {
-                let tag_dir = self.output_dir.join("tags").join(&tag_url_safe);
-                fs::create_dir_all(&tag_dir)?;
-                tag_dir.join("index.html")
-            } else {
-                let page_dir = self
-                    .output_dir
-                    .join("tags")
-                    .join(&tag_url_safe)
-                    .join("page")
-                    .join(page.to_string());
-                fs::create_dir_all(&page_dir)?;
-                page_dir.join("index.html")
-            };
+        for (i, page_posts) in pages.into_iter().enumerate() {
+            let page = i + 1;
+            let output_path = page_output_path(&tag_dir, page);
+
+            let inputs = (tag, tag_posts.len(), page, total_pages, page_posts);
+            self.write_page(&output_path, &inputs, || {
+                let posts_data: Vec<_> = page_posts
+                    .iter()
+                    .map(|(id, post)| self.post_summary_context(id, post))
+                    .collect();
+
+                let mut context = Context::new();
+                context.insert("site", &self.config);
+                context.insert("posts", &posts_data);
+                context.insert("tag", tag);
+                context.insert("title", &format!("Posts tagged '{}'", tag));
+                context.insert("tag_post_count", &tag_posts.len());
+                if self.config.enable_rss && self.config.feed.taxonomy_feeds {
+                    context.insert("tag_feed_url", &format!("{}/feed.xml", tag_base));
+                }
+                Pagination::new(&tag_base, page, total_pages).insert_into(&mut context);
 
-            let rendered = self.tera.render("tag_posts.html", &context)?;
-            fs::write(output_path, rendered)?;
+                Ok(self.tera.render("tag_posts.html", &context)?)
+            })?;
         }
 
         Ok(())
     }
 }
 
-fn markdown_to_html(markdown: &str) -> String {
+const SITEMAP_HEADER: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" ",
+    "xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n"
+);
+
+/// Render post markdown the way the site's pages show it
+pub(super) fn markdown_to_html(markdown: &str) -> String {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
@@ -685,7 +975,7 @@ fn add_copy_buttons_to_code_blocks(html: &str) -> String {
 }
 
 /// Sanitize slug to only contain ASCII characters for file names
-fn sanitize_slug(slug: &str) -> String {
+pub(crate) fn sanitize_slug(slug: &str) -> String {
     let sanitized = slug
         .chars()
         .map(|c| {
@@ -709,7 +999,7 @@ fn sanitize_slug(slug: &str) -> String {
     }
 }
 
-fn sanitize_tag_for_url(tag: &str) -> String {
+pub(crate) fn sanitize_tag_for_url(tag: &str) -> String {
     // For tags, we'll use a simple approach: convert to lowercase and replace spaces with hyphens
     // Chinese characters and other non-ASCII will be preserved
     tag.to_lowercase().replace(' ', "-")
@@ -717,19 +1007,84 @@ fn sanitize_tag_for_url(tag: &str) -> String {
 
 impl SiteGenerator {
     async fn generate_docs_page(&self) -> Result<()> {
+        let docs_db = self.blog_manager.docs_db();
+        let categories = docs_db.get_all_categories().await?;
+
         let mut context = Context::new();
         context.insert("site", &self.config);
         context.insert("page_title", "技术文档中心");
+        context.insert("categories", &categories);
 
-        let html = self.tera.render("docs.html", &context)?;
-
-        let docs_path = self.output_dir.join("docs");
-        fs::create_dir_all(&docs_path)?;
-        fs::write(docs_path.join("index.html"), html)?;
+        // Without any categories the docs landing page is shown instead
+        let template = if categories.is_empty() {
+            "docs.html"
+        } else {
+            "docs_index.html"
+        };
 
         // Also create docs.html for compatibility
-        let html = self.tera.render("docs.html", &context)?;
-        fs::write(self.output_dir.join("docs.html"), html)?;
+        for output_path in [
+            self.output_dir.join("docs").join("index.html"),
+            self.output_dir.join("docs.html"),
+        ] {
+            self.write_page(&output_path, &categories, || {
+                Ok(self.tera.render(template, &context)?)
+            })?;
+        }
+
+        let docs_dir = self.output_dir.join("docs");
+        let mut sections_written = 0;
+        for category in &categories {
+            let tree = SectionTree::build(docs_db.get_sections_by_category(&category.id).await?);
+            let toc = tree.toc();
+
+            let output_path = docs_dir.join(&category.slug).join("index.html");
+            self.write_page(&output_path, &(&categories, category, &toc), || {
+                let mut context = Context::new();
+                context.insert("site", &self.config);
+                context.insert("page_title", &format!("{} - Documentation", category.name));
+                context.insert("category", category);
+                context.insert("sections", tree.sections());
+                context.insert("toc", &toc);
+                context.insert("categories", &categories);
+                Ok(self.tera.render("docs_category.html", &context)?)
+            })?;
+
+            for section in tree.sections() {
+                let breadcrumbs = tree.breadcrumbs(&section.slug);
+                let prev_section = tree.prev(&section.slug).map(SectionLink::from);
+                let next_section = tree.next(&section.slug).map(SectionLink::from);
+
+                let output_path = docs_dir
+                    .join("detail")
+                    .join(sanitize_slug(&section.slug))
+                    .join("index.html");
+                let inputs = (&categories, &toc, section, &prev_section, &next_section);
+                self.write_page(&output_path, &inputs, || {
+                    let mut context = Context::new();
+                    context.insert("site", &self.config);
+                    context.insert("page_title", &format!("{} - Documentation", section.title));
+                    context.insert("section", section);
+                    context.insert("content_html", &markdown_to_html(&section.content));
+                    context.insert("categories", &categories);
+                    context.insert("category", category);
+                    context.insert("toc", &toc);
+                    context.insert("breadcrumbs", &breadcrumbs);
+                    context.insert("prev_section", &prev_section);
+                    context.insert("next_section", &next_section);
+                    Ok(self.tera.render("docs_detail.html", &context)?)
+                })?;
+                sections_written += 1;
+            }
+        }
+
+        if !categories.is_empty() {
+            println!(
+                "📚 Generated docs: {} categories, {} sections",
+                categories.len(),
+                sections_written
+            );
+        }
 
         Ok(())
     }
@@ -737,8 +1092,7 @@ impl SiteGenerator {
     async fn generate_sitemap(&self, posts: &[(String, BlogPost)]) -> Result<()> {
         use chrono::SecondsFormat;
 
-        let mut sitemap = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
-        sitemap.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        let mut sitemap = String::from(SITEMAP_HEADER);
 
         // Add homepage
         sitemap.push_str(&format!(
@@ -764,47 +1118,106 @@ impl SiteGenerator {
             self.config.base_url
         ));
 
-        // Add all posts
-        for (_, post) in posts {
-            let safe_slug = sanitize_slug(&post.slug);
+        // Add docs categories and sections
+        let docs_db = self.blog_manager.docs_db();
+        for category in docs_db.get_all_categories().await? {
             sitemap.push_str(&format!(
-                "  <url>\n    <loc>{}/posts/{}.html</loc>\n    <lastmod>{}</lastmod>\n    <changefreq>monthly</changefreq>\n    <priority>0.6</priority>\n  </url>\n",
+                "  <url>\n    <loc>{}/docs/{}/</loc>\n    <changefreq>weekly</changefreq>\n    <priority>0.7</priority>\n  </url>\n",
                 self.config.base_url,
-                safe_slug,
-                post.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                category.slug
             ));
         }
-
-        // Add tag pages
-        let all_tags = self.blog_manager.get_all_tags().await?;
-        for (tag_name, _) in all_tags {
-            let tag_url_safe = sanitize_tag_for_url(&tag_name);
+        for section in docs_db.get_all_sections().await? {
             sitemap.push_str(&format!(
-                "  <url>\n    <loc>{}/tags/{}/</loc>\n    <changefreq>weekly</changefreq>\n    <priority>0.5</priority>\n  </url>\n",
+                "  <url>\n    <loc>{}/docs/detail/{}/</loc>\n    <lastmod>{}</lastmod>\n    <changefreq>monthly</changefreq>\n    <priority>0.6</priority>\n  </url>\n",
                 self.config.base_url,
-                tag_url_safe
+                sanitize_slug(&section.slug),
+                section.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
             ));
         }
 
-        // Add pagination pages
-        let total_pages = posts.len().div_ceil(self.config.posts_per_page);
-        for page in 2..=total_pages {
+        // Add language indexes
+        for language in &self.config.i18n.languages {
             sitemap.push_str(&format!(
-                "  <url>\n    <loc>{}/page/{}/</loc>\n    <changefreq>weekly</changefreq>\n    <priority>0.4</priority>\n  </url>\n",
+                "  <url>\n    <loc>{}/{}/</loc>\n    <changefreq>daily</changefreq>\n    <priority>0.9</priority>\n  </url>\n",
                 self.config.base_url,
-                page
+                language.code
             ));
         }
 
+        // Add all posts
+        for (_, post) in posts {
+            sitemap.push_str(&self.sitemap_post_entry(post, posts));
+        }
+
+        // Add tag pages, with their pagination
+        let per_page = self.config.posts_per_page;
+        let all_tags = self.published_tags().await?;
+        for (tag_name, _) in all_tags {
+            let tag_url_safe = sanitize_tag_for_url(&tag_name);
+            let tag_base = format!("{}/tags/{}", self.config.base_url, tag_url_safe);
+            let tag_posts = posts
+                .iter()
+                .filter(|(_, post)| post.tags.contains(&tag_name))
+                .count();
+            for page in 1..=total_pages(tag_posts, per_page) {
+                let priority = if page == 1 { "0.5" } else { "0.3" };
+                sitemap.push_str(&format!(
+                    "  <url>\n    <loc>{}</loc>\n    <changefreq>weekly</changefreq>\n    <priority>{}</priority>\n  </url>\n",
+                    page_url(&tag_base, page),
+                    priority
+                ));
+            }
+        }
+
+        // Add index and archive pagination pages
+        let pages = total_pages(posts.len(), per_page);
+        for base in ["", "/archive"] {
+            let base = format!("{}{}", self.config.base_url, base);
+            for page in 2..=pages {
+                sitemap.push_str(&format!(
+                    "  <url>\n    <loc>{}</loc>\n    <changefreq>weekly</changefreq>\n    <priority>0.4</priority>\n  </url>\n",
+                    page_url(&base, page)
+                ));
+            }
+        }
+
         sitemap.push_str("</urlset>");
 
         let output_path = self.output_dir.join("sitemap.xml");
-        fs::write(output_path, sitemap)?;
+        self.write_page(&output_path, &sitemap, || Ok(sitemap.clone()))?;
 
         println!("📄 Generated sitemap.xml");
         Ok(())
     }
 
+    /// Sitemap `<url>` of a post, listing its translations as hreflang alternates
+    fn sitemap_post_entry(&self, post: &BlogPost, posts: &[(String, BlogPost)]) -> String {
+        use chrono::SecondsFormat;
+
+        let mut alternates = String::new();
+        for translation in self.post_translations(post, posts) {
+            alternates.push_str(&format!(
+                "    <xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                translation.lang, translation.absolute_url
+            ));
+            if translation.original {
+                alternates.push_str(&format!(
+                    "    <xhtml:link rel=\"alternate\" hreflang=\"x-default\" href=\"{}\"/>\n",
+                    translation.absolute_url
+                ));
+            }
+        }
+
+        format!(
+            "  <url>\n    <loc>{}/posts/{}.html</loc>\n    <lastmod>{}</lastmod>\n    <changefreq>monthly</changefreq>\n    <priority>0.6</priority>\n{}  </url>\n",
+            self.config.base_url,
+            sanitize_slug(&post.slug),
+            post.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            alternates
+        )
+    }
+
     fn generate_robots_txt(&self) -> Result<()> {
         let mut robots = String::from("# Robots.txt for KPGB Blog\n\n");
 
@@ -825,7 +1238,7 @@ impl SiteGenerator {
         robots.push_str(&format!("\nHost: {}\n", self.config.base_url));
 
         let output_path = self.output_dir.join("robots.txt");
-        fs::write(output_path, robots)?;
+        self.write_page(&output_path, &robots, || Ok(robots.clone()))?;
 
         println!("🤖 Generated robots.txt");
         Ok(())
@@ -865,7 +1278,8 @@ impl SiteGenerator {
         });
 
         let output_path = self.output_dir.join("site.webmanifest");
-        fs::write(output_path, serde_json::to_string_pretty(&manifest)?)?;
+        let manifest = serde_json::to_string_pretty(&manifest)?;
+        self.write_page(&output_path, &manifest, || Ok(manifest.clone()))?;
 
         println!("📱 Generated site.webmanifest");
         Ok(())
@@ -885,7 +1299,9 @@ impl SiteGenerator {
             <text x="600" y="315" font-family="Arial, sans-serif" font-size="72" fill="white" text-anchor="middle" dominant-baseline="middle">KPGB Blog</text>
             <text x="600" y="400" font-family="Arial, sans-serif" font-size="36" fill="white" text-anchor="middle" opacity="0.8">Decentralized IPFS Blog</text>
         </svg>"##;
-        fs::write(images_dir.join("og-default.svg"), og_svg)?;
+        self.write_page(&images_dir.join("og-default.svg"), og_svg, || {
+            Ok(og_svg.to_string())
+        })?;
 
         // Twitter card image (1200x600)
         let twitter_svg = r##"<svg width="1200" height="600" xmlns="http://www.w3.org/2000/svg">
@@ -893,14 +1309,18 @@ impl SiteGenerator {
             <text x="600" y="300" font-family="Arial, sans-serif" font-size="72" fill="white" text-anchor="middle" dominant-baseline="middle">KPGB Blog</text>
             <text x="600" y="380" font-family="Arial, sans-serif" font-size="36" fill="white" text-anchor="middle" opacity="0.8">Powered by IPFS</text>
         </svg>"##;
-        fs::write(images_dir.join("twitter-card.svg"), twitter_svg)?;
+        self.write_page(&images_dir.join("twitter-card.svg"), twitter_svg, || {
+            Ok(twitter_svg.to_string())
+        })?;
 
         // Logo placeholder
         let logo_svg = r##"<svg width="512" height="512" xmlns="http://www.w3.org/2000/svg">
             <rect width="512" height="512" fill="#3498db"/>
             <text x="256" y="256" font-family="Arial, sans-serif" font-size="200" fill="white" text-anchor="middle" dominant-baseline="middle">K</text>
         </svg>"##;
-        fs::write(images_dir.join("logo.svg"), logo_svg)?;
+        self.write_page(&images_dir.join("logo.svg"), logo_svg, || {
+            Ok(logo_svg.to_string())
+        })?;
 
         // Create favicon placeholders
         let favicon_svg = r##"<svg width="32" height="32" xmlns="http://www.w3.org/2000/svg">
@@ -908,7 +1328,9 @@ impl SiteGenerator {
             <text x="16" y="16" font-family="Arial, sans-serif" font-size="20" fill="white" text-anchor="middle" dominant-baseline="middle">K</text>
         </svg>"##;
 
-        fs::write(self.output_dir.join("favicon.svg"), favicon_svg)?;
+        self.write_page(&self.output_dir.join("favicon.svg"), favicon_svg, || {
+            Ok(favicon_svg.to_string())
+        })?;
 
         // Note: In production, you would convert these SVGs to PNGs
         // For now, we'll use the SVGs as placeholders
@@ -923,23 +1345,105 @@ impl SiteGenerator {
         // Create image optimization config
         let config = crate::utils::ImageOptimizationConfig::default();
 
-        // Optimize images in the output directory
-        let stats = crate::utils::optimize_images_in_directory(&self.output_dir, &config).await?;
+        let mut images = Vec::new();
+        collect_files(&self.output_dir, &mut images)?;
+
+        // Only images that changed since they were last optimized
+        let mut optimized = 0;
+        for path in images
+            .into_iter()
+            .filter(|p| crate::utils::is_image_file(p))
+        {
+            let key = self.output_key(&path);
+            let hash = build_manifest::hash_bytes(&fs::read(&path)?);
+
+            if self.full_rebuild || self.previous.images.get(&key) != Some(&hash) {
+                if let Err(e) = crate::utils::optimize_image(&path, &config) {
+                    warn!("Failed to optimize image {}: {}", path.display(), e);
+                }
+                optimized += 1;
+            }
+
+            let hash = build_manifest::hash_bytes(&fs::read(&path)?);
+            self.manifest.lock().unwrap().images.insert(key, hash);
+        }
 
-        println!("✨ {}", stats.summary());
+        self.stats.lock().unwrap().images_optimized += optimized;
         Ok(())
     }
+}
 
-    async fn minify_resources(&self) -> Result<()> {
-        println!("📦 Minifying HTML/CSS/JS...");
-
-        // Create minification config
-        let config = crate::utils::MinifyConfig::default();
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
 
-        // Minify all resources in the output directory
-        let stats = crate::utils::minify_directory(&self.output_dir, &config).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::blog;
+
+    async fn build(blog_manager: &BlogManager, output: &Path) -> BuildStats {
+        let generator = SiteGenerator::new(blog_manager.clone(), SiteConfig::default(), output)
+            .await
+            .unwrap();
+        generator.generate().await.unwrap();
+        generator.stats.into_inner().unwrap()
+    }
 
-        println!("🗜️  {}", stats.summary());
-        Ok(())
+    #[tokio::test]
+    async fn test_incremental_build() {
+        let (dir, mut blog_manager) = blog("generate").await;
+        let output = dir.path().join("public");
+
+        let mut posts = Vec::new();
+        for title in ["First", "Second"] {
+            let mut post = BlogPost::new(title.to_string(), title.to_string(), "Me".to_string());
+            post.published = true;
+            post.tags = vec![title.to_lowercase()];
+            let storage_id = blog_manager.create_post(post.clone()).await.unwrap();
+            posts.push((storage_id, post));
+        }
+        let page = |post: &BlogPost| output.join("posts").join(format!("{}.html", post.slug));
+
+        let stats = build(&blog_manager, &output).await;
+        assert!(stats.rendered > 0);
+        assert_eq!(stats.skipped, 0);
+        assert!(page(&posts[0].1).exists());
+        assert!(output.join("tags").join("second").exists());
+
+        // Nothing changed: every page is skipped, even one that was tampered with
+        fs::write(page(&posts[0].1), "stale").unwrap();
+        let stats = build(&blog_manager, &output).await;
+        assert_eq!(stats.rendered, 0);
+        assert!(stats.skipped > 0);
+        assert_eq!(fs::read_to_string(page(&posts[0].1)).unwrap(), "stale");
+
+        // An edited post is rendered again
+        let (storage_id, post) = &posts[0];
+        let mut edited = blog_manager.get_post(storage_id).await.unwrap();
+        edited.update_content("First, edited".to_string());
+        blog_manager.update_post(&edited).await.unwrap();
+        let stats = build(&blog_manager, &output).await;
+        assert!(stats.rendered > 0);
+        assert!(fs::read_to_string(page(post))
+            .unwrap()
+            .contains("First, edited"));
+
+        // A deleted post takes its page and its tag's pages with it
+        let removed = &posts[1].1;
+        blog_manager.delete_post(&removed.id).await.unwrap();
+        let stats = build(&blog_manager, &output).await;
+        assert!(stats.removed > 0);
+        assert!(!page(removed).exists());
+        assert!(!output.join("tags").join("second").exists());
+        assert!(page(post).exists());
     }
 }
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::BlogPost;
+
+/// Language settings, the `[i18n]` table of site.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct I18nConfig {
+    /// Language of posts without a `lang` key, and of pages that aren't a post
+    pub default_lang: String,
+    /// Languages that get their own index, feeds and sitemap under `/<code>/`
+    pub languages: Vec<Language>,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        Self {
+            default_lang: "zh-CN".to_string(),
+            languages: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Language {
+    /// BCP 47 tag, e.g. `en` or `zh-CN`; also the output directory
+    pub code: String,
+    /// Name shown in the language switcher
+    pub name: String,
+}
+
+/// A language variant of a post, for hreflang links and the language switcher
+#[derive(Debug, Clone, Serialize)]
+pub struct Translation {
+    pub lang: String,
+    pub name: String,
+    pub title: String,
+    pub slug: String,
+    /// Link for the language switcher
+    pub url: String,
+    /// Absolute URL for `<link rel="alternate" hreflang>`
+    pub absolute_url: String,
+    /// The variant the page shows
+    pub current: bool,
+    /// The post the others translate, used as `x-default`
+    pub original: bool,
+}
+
+impl I18nConfig {
+    pub fn post_lang<'a>(&'a self, post: &'a BlogPost) -> &'a str {
+        post.lang.as_deref().unwrap_or(&self.default_lang)
+    }
+
+    /// Display name of a language, the code itself when it isn't configured
+    pub fn language_name(&self, code: &str) -> String {
+        self.languages
+            .iter()
+            .find(|l| l.code.eq_ignore_ascii_case(code))
+            .map(|l| l.name.clone())
+            .unwrap_or_else(|| code.to_string())
+    }
+
+    /// Posts written in `lang`, keeping their order
+    pub fn posts_in(&self, lang: &str, posts: &[(String, BlogPost)]) -> Vec<(String, BlogPost)> {
+        posts
+            .iter()
+            .filter(|(_, post)| lang_matches(self.post_lang(post), lang))
+            .cloned()
+            .collect()
+    }
+
+    /// All language variants of `post`, the original first. Empty when the
+    /// post has no translations. `url` and `absolute_url` give a post's link.
+    pub fn translations(
+        &self,
+        post: &BlogPost,
+        posts: &[(String, BlogPost)],
+        url: impl Fn(&BlogPost) -> String,
+        absolute_url: impl Fn(&BlogPost) -> String,
+    ) -> Vec<Translation> {
+        let variants = variants(post, posts);
+        if variants.len() < 2 {
+            return Vec::new();
+        }
+
+        variants
+            .into_iter()
+            .map(|variant| {
+                let lang = self.post_lang(variant).to_string();
+                Translation {
+                    name: self.language_name(&lang),
+                    lang,
+                    title: variant.title.clone(),
+                    slug: variant.slug.clone(),
+                    url: url(variant),
+                    absolute_url: absolute_url(variant),
+                    current: variant.id == post.id,
+                    original: variant.translation_of.is_none(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Slug shared by a post and its translations: the original's slug
+pub fn translation_key(post: &BlogPost) -> &str {
+    post.translation_of.as_deref().unwrap_or(&post.slug)
+}
+
+/// `post` and every post in the same translation group, the original first,
+/// at most one per slug
+pub fn variants<'a>(post: &'a BlogPost, posts: &'a [(String, BlogPost)]) -> Vec<&'a BlogPost> {
+    let key = translation_key(post);
+    let mut found: Vec<&BlogPost> = vec![post];
+    for (_, other) in posts {
+        if translation_key(other) == key && found.iter().all(|p| p.slug != other.slug) {
+            found.push(other);
+        }
+    }
+    found.sort_by_key(|p| (p.translation_of.is_some(), p.lang.clone()));
+    found
+}
+
+fn primary_subtag(code: &str) -> &str {
+    code.split(['-', '_']).next().unwrap_or(code)
+}
+
+/// Whether two language tags name the same language, e.g. `zh` and `zh-CN`
+pub fn lang_matches(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b) || primary_subtag(a).eq_ignore_ascii_case(primary_subtag(b))
+}
+
+/// Languages of an `Accept-Language` header, most preferred first.
+/// Wildcards and languages with `q=0` are dropped.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut languages: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';');
+            let tag = params.next()?.trim();
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+
+    // Stable sort keeps the header order between equal weights
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// Index of the offered language the client prefers most. An exact tag
+/// match wins over a match on the primary language only.
+pub fn negotiate(header: &str, offered: &[&str]) -> Option<usize> {
+    for accepted in parse_accept_language(header) {
+        if let Some(i) = offered
+            .iter()
+            .position(|lang| lang.eq_ignore_ascii_case(&accepted))
+        {
+            return Some(i);
+        }
+        if let Some(i) = offered
+            .iter()
+            .position(|lang| lang_matches(lang, &accepted))
+        {
+            return Some(i);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(slug: &str, lang: Option<&str>, translation_of: Option<&str>) -> (String, BlogPost) {
+        let mut post = BlogPost::new(slug.to_string(), String::new(), "me".to_string());
+        post.slug = slug.to_string();
+        post.lang = lang.map(str::to_string);
+        post.translation_of = translation_of.map(str::to_string);
+        (slug.to_string(), post)
+    }
+
+    #[test]
+    fn test_parse_accept_language() {
+        assert_eq!(
+            parse_accept_language("en-US,en;q=0.9,zh-CN;q=0.95, fr;q=0, *;q=0.1"),
+            vec!["en-US", "zh-CN", "en"]
+        );
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let offered = ["zh-CN", "en"];
+        assert_eq!(negotiate("en-GB,en;q=0.8", &offered), Some(1));
+        assert_eq!(negotiate("zh-TW,zh;q=0.9,en;q=0.5", &offered), Some(0));
+        assert_eq!(negotiate("fr,de;q=0.5", &offered), None);
+        assert_eq!(negotiate("fr, en;q=0.1", &offered), Some(1));
+    }
+
+    #[test]
+    fn test_translations() {
+        let config = I18nConfig {
+            default_lang: "zh-CN".to_string(),
+            languages: vec![Language {
+                code: "en".to_string(),
+                name: "English".to_string(),
+            }],
+        };
+        let posts = vec![
+            post("hello-en", Some("en"), Some("hello")),
+            post("hello", None, None),
+            post("other", Some("en"), None),
+        ];
+
+        let translations = config.translations(
+            &posts[0].1,
+            &posts,
+            |p| format!("/posts/{}", p.slug),
+            |p| format!("https://example.com/posts/{}", p.slug),
+        );
+        let summary: Vec<_> = translations
+            .iter()
+            .map(|t| (t.lang.as_str(), t.name.as_str(), t.current, t.original))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("zh-CN", "zh-CN", false, true),
+                ("en", "English", true, false)
+            ]
+        );
+
+        let lone = config.translations(&posts[2].1, &posts, |_| String::new(), |_| String::new());
+        assert!(lone.is_empty());
+
+        let english: Vec<_> = config
+            .posts_in("en", &posts)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(english, vec!["hello-en", "other"]);
+        assert_eq!(config.posts_in("zh", &posts).len(), 1);
+    }
+}
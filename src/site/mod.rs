@@ -1,6 +1,12 @@
+pub mod build_manifest;
 pub mod config;
+pub mod feed;
 pub mod filters;
 pub mod generator;
+pub mod i18n;
+pub mod pagination;
+pub mod templates;
+pub mod theme;
 
 use serde::{Deserialize, Serialize};
 
@@ -37,6 +43,53 @@ impl Default for GiscusConfig {
     }
 }
 
+/// Built-in comments, the `[comments]` table of site.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommentsConfig {
+    /// Show comments and the comment form instead of Giscus
+    pub enabled: bool,
+    /// Hold new comments for `kpgb comments approve`
+    pub moderation: bool,
+    /// Comments accepted from one address per hour
+    pub rate_limit: usize,
+    /// Where generated pages send the comment form, e.g. the `/comments`
+    /// route of a running `kpgb serve`. Without it static pages only show
+    /// approved comments.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+impl Default for CommentsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            moderation: true,
+            rate_limit: 5,
+            endpoint: None,
+        }
+    }
+}
+
+/// ActivityPub federation, the `[activitypub]` table of site.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityPubConfig {
+    /// Serve the blog as an ActivityPub actor and deliver published posts
+    pub enabled: bool,
+    /// The actor's name, followed as `@username@host` of `base_url`
+    pub username: String,
+}
+
+impl Default for ActivityPubConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            username: "blog".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SiteConfig {
     pub title: String,
@@ -48,8 +101,23 @@ pub struct SiteConfig {
     pub posts_per_page: usize,
     pub enable_rss: bool,
     pub theme: String,
+    /// Directory whose templates/ and static/ override the built-in ones file by file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme_dir: Option<String>,
     #[serde(default)]
     pub giscus: GiscusConfig,
+    #[serde(default)]
+    pub comments: CommentsConfig,
+    #[serde(default)]
+    pub activitypub: ActivityPubConfig,
+    #[serde(default)]
+    pub feed: feed::FeedConfig,
+    #[serde(default)]
+    pub i18n: i18n::I18nConfig,
+    /// Webmention endpoint advertised by generated pages, e.g. the
+    /// `/webmention` route of a running `kpgb serve`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webmention_endpoint: Option<String>,
 }
 
 impl SiteConfig {
@@ -79,7 +147,13 @@ impl Default for SiteConfig {
             posts_per_page: 10,
             enable_rss: true,
             theme: "default".to_string(),
+            theme_dir: None,
             giscus: GiscusConfig::default(),
+            comments: CommentsConfig::default(),
+            activitypub: ActivityPubConfig::default(),
+            feed: feed::FeedConfig::default(),
+            i18n: i18n::I18nConfig::default(),
+            webmention_endpoint: None,
         }
     }
 }
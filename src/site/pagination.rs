@@ -0,0 +1,121 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tera::Context;
+
+/// Link to one page of a listing
+#[derive(Debug, Clone, Serialize)]
+pub struct PageLink {
+    pub number: usize,
+    pub url: String,
+}
+
+/// Position of a page in a paginated listing, with the URLs templates link to.
+/// Page 1 lives at `<base>/`, later pages at `<base>/page/<n>/`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Pagination {
+    pub current_page: usize,
+    pub total_pages: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+    pub pages: Vec<PageLink>,
+}
+
+impl Pagination {
+    pub fn new(base: &str, current_page: usize, total_pages: usize) -> Self {
+        let total_pages = total_pages.max(1);
+        let pages = (1..=total_pages)
+            .map(|number| PageLink {
+                number,
+                url: page_url(base, number),
+            })
+            .collect();
+
+        Self {
+            current_page,
+            total_pages,
+            prev_url: (current_page > 1).then(|| page_url(base, current_page - 1)),
+            next_url: (current_page < total_pages).then(|| page_url(base, current_page + 1)),
+            pages,
+        }
+    }
+
+    /// Add the pagination variables templates use
+    pub fn insert_into(&self, context: &mut Context) {
+        context.insert("current_page", &self.current_page);
+        context.insert("total_pages", &self.total_pages);
+        context.insert("has_prev", &self.prev_url.is_some());
+        context.insert("has_next", &self.next_url.is_some());
+        context.insert("prev_url", &self.prev_url);
+        context.insert("next_url", &self.next_url);
+        context.insert("pagination", self);
+    }
+}
+
+/// URL of page `page` of the listing at `base` (e.g. "/tags/rust")
+pub fn page_url(base: &str, page: usize) -> String {
+    let base = base.trim_end_matches('/');
+    if page <= 1 {
+        format!("{}/", base)
+    } else {
+        format!("{}/page/{}/", base, page)
+    }
+}
+
+/// Output file of page `page` of the listing generated into `dir`
+pub fn page_output_path(dir: &Path, page: usize) -> PathBuf {
+    if page <= 1 {
+        dir.join("index.html")
+    } else {
+        dir.join("page").join(page.to_string()).join("index.html")
+    }
+}
+
+/// Number of pages needed for `items` entries; an empty listing still has one page
+pub fn total_pages(items: usize, per_page: usize) -> usize {
+    items.div_ceil(per_page.max(1)).max(1)
+}
+
+/// Split `items` into pages of `per_page` entries, at least one page long
+pub fn paginate<T>(items: &[T], per_page: usize) -> Vec<&[T]> {
+    if items.is_empty() {
+        return vec![items];
+    }
+    items.chunks(per_page.max(1)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_urls() {
+        assert_eq!(page_url("", 1), "/");
+        assert_eq!(page_url("", 3), "/page/3/");
+        assert_eq!(page_url("/blog/tags/rust", 2), "/blog/tags/rust/page/2/");
+
+        let pagination = Pagination::new("/archive", 2, 3);
+        assert_eq!(pagination.prev_url.as_deref(), Some("/archive/"));
+        assert_eq!(pagination.next_url.as_deref(), Some("/archive/page/3/"));
+        assert_eq!(pagination.pages.len(), 3);
+
+        let last = Pagination::new("", 3, 3);
+        assert!(last.next_url.is_none());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (0..25).collect();
+        let pages = paginate(&items, 10);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2], &[20, 21, 22, 23, 24]);
+        assert_eq!(total_pages(items.len(), 10), 3);
+
+        let empty: Vec<u32> = Vec::new();
+        assert_eq!(paginate(&empty, 10).len(), 1);
+        assert_eq!(total_pages(0, 10), 1);
+        assert_eq!(
+            page_output_path(Path::new("public"), 2),
+            Path::new("public/page/2/index.html")
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tera::{Context, Tera};
+
+/// Templates compiled into the binary
+pub const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("../../templates/base.html")),
+    ("index.html", include_str!("../../templates/index.html")),
+    ("post.html", include_str!("../../templates/post.html")),
+    ("archive.html", include_str!("../../templates/archive.html")),
+    ("search.html", include_str!("../../templates/search.html")),
+    ("tags.html", include_str!("../../templates/tags.html")),
+    (
+        "tag_posts.html",
+        include_str!("../../templates/tag_posts.html"),
+    ),
+    ("docs.html", include_str!("../../templates/docs.html")),
+    (
+        "docs_index.html",
+        include_str!("../../templates/docs_index.html"),
+    ),
+    (
+        "docs_category.html",
+        include_str!("../../templates/docs_category.html"),
+    ),
+    (
+        "docs_detail.html",
+        include_str!("../../templates/docs_detail.html"),
+    ),
+    (
+        "pagination.html",
+        include_str!("../../templates/pagination.html"),
+    ),
+];
+
+/// Templates of the `/admin` editor. Themes can't override these.
+pub const ADMIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "admin/base.html",
+        include_str!("../../templates/admin/base.html"),
+    ),
+    (
+        "admin/login.html",
+        include_str!("../../templates/admin/login.html"),
+    ),
+    (
+        "admin/posts.html",
+        include_str!("../../templates/admin/posts.html"),
+    ),
+    (
+        "admin/edit.html",
+        include_str!("../../templates/admin/edit.html"),
+    ),
+];
+
+/// Template sources by name: the built-ins, each replaced by a file of the
+/// same name in `dir` if there is one
+pub fn template_sources(dir: Option<&Path>) -> Result<BTreeMap<String, String>> {
+    let mut sources = BTreeMap::new();
+
+    for (name, source) in BUILTIN_TEMPLATES {
+        let override_path = dir.map(|dir| dir.join(name));
+        let source = match override_path {
+            Some(path) if path.is_file() => fs::read_to_string(path)?,
+            _ => source.to_string(),
+        };
+        sources.insert(name.to_string(), source);
+    }
+
+    Ok(sources)
+}
+
+pub fn build_tera(sources: &BTreeMap<String, String>) -> Result<Tera> {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(
+        sources
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .chain(ADMIN_TEMPLATES.iter().copied()),
+    )?;
+
+    // Add custom filters
+    tera.register_filter("url_safe_tag", crate::site::filters::url_safe_tag);
+    tera.register_filter("highlight_search", crate::site::filters::highlight_search);
+    tera.register_filter("escape", crate::site::filters::escape_html);
+
+    Ok(tera)
+}
+
+/// Templates for the web server, reloadable while it runs
+pub struct Templates {
+    tera: RwLock<Tera>,
+    dir: Option<PathBuf>,
+    live_reload: bool,
+}
+
+impl Templates {
+    pub fn builtin() -> Result<Self> {
+        Self::load(None, false)
+    }
+
+    /// Load templates with overrides from `dir`. With `live_reload` pages
+    /// include the script that reloads them when the server sends an event.
+    pub fn load(dir: Option<PathBuf>, live_reload: bool) -> Result<Self> {
+        let tera = build_tera(&template_sources(dir.as_deref())?)?;
+        Ok(Self {
+            tera: RwLock::new(tera),
+            dir,
+            live_reload,
+        })
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Re-read the override directory. On error the previous templates stay in use.
+    pub fn reload(&self) -> Result<()> {
+        let tera = build_tera(&template_sources(self.dir.as_deref())?)?;
+        *self.tera.write().unwrap() = tera;
+        Ok(())
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> tera::Result<String> {
+        let tera = self.tera.read().unwrap();
+        if self.live_reload {
+            let mut context = context.clone();
+            context.insert("live_reload", &true);
+            tera.render(name, &context)
+        } else {
+            tera.render(name, context)
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::templates::BUILTIN_TEMPLATES;
+use super::SiteConfig;
+
+/// Directory `kpgb theme new` creates themes in
+pub const THEMES_DIR: &str = "themes";
+
+/// Built-in themes, selected by `theme` in site.toml
+pub const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("default", include_str!("../../templates/style.css")),
+    ("hacker", include_str!("../../templates/themes/hacker.css")),
+    (
+        "minimal",
+        include_str!("../../templates/themes/minimal.css"),
+    ),
+    ("dark", include_str!("../../templates/themes/dark.css")),
+    (
+        "cyberpunk",
+        include_str!("../../templates/themes/cyberpunk.css"),
+    ),
+];
+
+/// Heading of the default stylesheet's last section, whose rules every
+/// built-in theme starts from
+const SHARED_SECTION: &str = "Languages, Webmentions and Comments";
+
+/// The shared section of the default stylesheet, comment and all
+fn shared_css() -> &'static str {
+    let css = BUILTIN_THEMES[0].1;
+    let start = css
+        .find(SHARED_SECTION)
+        .and_then(|at| css[..at].rfind("/*"))
+        .unwrap_or(css.len());
+    let end = css
+        .rfind("/* End of CSS */")
+        .unwrap_or(css.len())
+        .max(start);
+    &css[start..end]
+}
+
+/// The stylesheet of a built-in theme; other themes get the shared rules of
+/// the default one first, so their own rules override them
+pub fn builtin_css(theme: &str) -> Cow<'static, str> {
+    match BUILTIN_THEMES.iter().find(|(name, _)| *name == theme) {
+        Some((name, css)) if *name != BUILTIN_THEMES[0].0 => {
+            Cow::Owned(format!("{}{}", shared_css(), css))
+        }
+        _ => Cow::Borrowed(BUILTIN_THEMES[0].1),
+    }
+}
+
+impl SiteConfig {
+    /// Tera templates of the theme directory, overriding the built-ins by name
+    pub fn theme_templates_dir(&self) -> Option<PathBuf> {
+        self.theme_dir
+            .as_ref()
+            .map(|dir| Path::new(dir).join("templates"))
+    }
+
+    /// Static files of the theme directory, copied to the site root
+    pub fn theme_static_dir(&self) -> Option<PathBuf> {
+        self.theme_dir
+            .as_ref()
+            .map(|dir| Path::new(dir).join("static"))
+    }
+
+    /// The stylesheet served as /css/style.css
+    pub fn theme_css(&self) -> Result<String> {
+        if let Some(path) = self
+            .theme_static_dir()
+            .map(|dir| dir.join("css").join("style.css"))
+            .filter(|path| path.is_file())
+        {
+            return Ok(fs::read_to_string(path)?);
+        }
+        Ok(builtin_css(&self.theme).to_string())
+    }
+
+    /// Files in the theme's static directory, keyed by their path relative to it
+    pub fn theme_assets(&self) -> Result<BTreeMap<String, PathBuf>> {
+        let mut assets = BTreeMap::new();
+        if let Some(dir) = self.theme_static_dir().filter(|dir| dir.is_dir()) {
+            collect_assets(&dir, &dir, &mut assets)?;
+        }
+        Ok(assets)
+    }
+}
+
+fn collect_assets(root: &Path, dir: &Path, assets: &mut BTreeMap<String, PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_assets(root, &path, assets)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            assets.insert(relative.to_string_lossy().replace('\\', "/"), path);
+        }
+    }
+    Ok(())
+}
+
+/// Theme directories under `themes_dir`
+pub fn list_theme_dirs(themes_dir: &Path) -> Result<Vec<String>> {
+    let mut themes = Vec::new();
+    if themes_dir.is_dir() {
+        for entry in fs::read_dir(themes_dir)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                themes.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+    themes.sort();
+    Ok(themes)
+}
+
+/// Copy the built-in templates and the CSS of built-in theme `base` into
+/// `<themes_dir>/<name>` as a starting point. Returns the new theme directory.
+pub fn create_theme(themes_dir: &Path, name: &str, base: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(anyhow!("Invalid theme name: {}", name));
+    }
+    if !BUILTIN_THEMES.iter().any(|(theme, _)| *theme == base) {
+        return Err(anyhow!("Unknown built-in theme: {}", base));
+    }
+
+    let dir = themes_dir.join(name);
+    if dir.exists() {
+        return Err(anyhow!("Theme directory already exists: {}", dir.display()));
+    }
+
+    let templates_dir = dir.join("templates");
+    fs::create_dir_all(&templates_dir)?;
+    for (template, source) in BUILTIN_TEMPLATES {
+        fs::write(templates_dir.join(template), source)?;
+    }
+
+    let css_dir = dir.join("static").join("css");
+    fs::create_dir_all(&css_dir)?;
+    fs::write(css_dir.join("style.css"), builtin_css(base).as_bytes())?;
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_themes_share_default_rules() {
+        let shared = shared_css();
+        assert!(shared.contains(".language-switcher {"));
+        assert!(shared.contains(".comment-form {"));
+        for (name, _) in BUILTIN_THEMES {
+            let css = builtin_css(name);
+            assert_eq!(css.matches(".language-switcher {").count(), 1, "{}", name);
+        }
+        assert!(builtin_css("dark").starts_with(shared));
+        assert!(builtin_css("dark").contains("--accent"));
+    }
+
+    #[test]
+    fn test_create_theme_overrides_builtins() {
+        let root = std::env::temp_dir().join(format!("kpgb-themes-{}", uuid::Uuid::new_v4()));
+        let dir = create_theme(&root, "mine", "hacker").unwrap();
+        assert!(create_theme(&root, "mine", "hacker").is_err());
+        assert_eq!(list_theme_dirs(&root).unwrap(), vec!["mine".to_string()]);
+
+        let mut config = SiteConfig::default();
+        assert_eq!(config.theme_css().unwrap(), builtin_css("default"));
+
+        config.theme_dir = Some(dir.to_string_lossy().to_string());
+        fs::write(dir.join("static").join("css").join("style.css"), "body{}").unwrap();
+        assert_eq!(config.theme_css().unwrap(), "body{}");
+        assert!(config.theme_assets().unwrap().contains_key("css/style.css"));
+        assert!(config
+            .theme_templates_dir()
+            .unwrap()
+            .join("base.html")
+            .is_file());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
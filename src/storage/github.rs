@@ -1,52 +1,309 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
+use reqwest::{Method, RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
 
+use super::s3::uri_encode;
 use super::{Storage, StorageMetadata, StorageResult};
+use crate::site::generator::sanitize_slug;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+const JSON: &str = "application/vnd.github.v3+json";
+const RAW: &str = "application/vnd.github.v3.raw";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GitHubContent {
     message: String,
     content: String,
+    branch: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     sha: Option<String>,
 }
 
+/// Files written since `begin_batch`, by path; `None` deletes the file
+type Batch = BTreeMap<String, Option<Vec<u8>>>;
+
 pub struct GitHubStorage {
     client: reqwest::Client,
+    api_url: String,
     owner: String,
     repo: String,
     branch: String,
     token: String,
+    batch: Mutex<Option<Batch>>,
 }
 
 impl GitHubStorage {
     pub fn new(owner: String, repo: String, branch: String, token: String) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("kpgb/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_default();
         Self {
-            client: reqwest::Client::new(),
+            client,
+            api_url: DEFAULT_API_URL.to_string(),
             owner,
             repo,
             branch,
             token,
+            batch: Mutex::new(None),
         }
     }
 
+    /// Talk to another API host, e.g. GitHub Enterprise
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
     pub fn from_env() -> Result<Self> {
-        Ok(Self::new(
+        let storage = Self::new(
             std::env::var("GITHUB_OWNER")?,
             std::env::var("GITHUB_REPO")?,
             std::env::var("GITHUB_BRANCH").unwrap_or_else(|_| "main".to_string()),
             std::env::var("GITHUB_TOKEN")?,
-        ))
+        );
+        Ok(match std::env::var("GITHUB_API_URL") {
+            Ok(api_url) => storage.with_api_url(&api_url),
+            Err(_) => storage,
+        })
+    }
+
+    fn request(&self, method: Method, endpoint: &str, accept: &str) -> RequestBuilder {
+        self.client
+            .request(
+                method,
+                format!(
+                    "{}/repos/{}/{}/{}",
+                    self.api_url, self.owner, self.repo, endpoint
+                ),
+            )
+            .header("Authorization", format!("token {}", self.token))
+            .header("Accept", accept)
     }
 
-    fn api_url(&self, path: &str) -> String {
+    fn contents(&self, path: &str) -> String {
         format!(
-            "https://api.github.com/repos/{}/{}/contents/{}",
-            self.owner, self.repo, path
+            "contents/{}?ref={}",
+            uri_encode(path, false),
+            uri_encode(&self.branch, true)
+        )
+    }
+
+    /// SHA of the file at `path`, which updating or deleting it needs
+    async fn file_sha(&self, path: &str) -> Result<Option<String>> {
+        let response = self
+            .request(Method::GET, &self.contents(path), JSON)
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let file: Value = check(response).await?.json().await?;
+        Ok(file["sha"].as_str().map(str::to_string))
+    }
+
+    /// The batched write for `path`, when a batch is open and has one
+    fn batched(&self, path: &str) -> Option<Option<Vec<u8>>> {
+        let batch = self.batch.lock().unwrap();
+        batch.as_ref().and_then(|batch| batch.get(path).cloned())
+    }
+
+    /// Queue a write when a batch is open; returns false otherwise
+    fn add_to_batch(&self, path: &str, content: Option<Vec<u8>>) -> bool {
+        let mut batch = self.batch.lock().unwrap();
+        match batch.as_mut() {
+            Some(batch) => {
+                batch.insert(path.to_string(), content);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn get_json(&self, endpoint: &str) -> Result<Value> {
+        let response = self.request(Method::GET, endpoint, JSON).send().await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    async fn post_json(&self, method: Method, endpoint: &str, body: &Value) -> Result<Value> {
+        let response = self
+            .request(method, endpoint, JSON)
+            .json(body)
+            .send()
+            .await?;
+        Ok(check(response).await?.json().await?)
+    }
+
+    /// Write all `files` in one commit on the branch through the git data API
+    async fn commit_files(&self, files: &Batch, message: &str) -> Result<String> {
+        let branch = uri_encode(&self.branch, false);
+        let head = self.get_json(&format!("git/ref/heads/{}", branch)).await?;
+        let head_sha = json_str(&head["object"]["sha"])?;
+        let head_commit = self.get_json(&format!("git/commits/{}", head_sha)).await?;
+        let base_tree = json_str(&head_commit["tree"]["sha"])?;
+
+        let mut entries = Vec::new();
+        for (path, content) in files {
+            let sha = match content {
+                Some(content) => {
+                    let blob = self
+                        .post_json(
+                            Method::POST,
+                            "git/blobs",
+                            &json!({
+                                "content": general_purpose::STANDARD.encode(content),
+                                "encoding": "base64",
+                            }),
+                        )
+                        .await?;
+                    Value::String(json_str(&blob["sha"])?)
+                }
+                None => Value::Null,
+            };
+            entries.push(json!({
+                "path": path,
+                "mode": "100644",
+                "type": "blob",
+                "sha": sha,
+            }));
+        }
+
+        let tree = self
+            .post_json(
+                Method::POST,
+                "git/trees",
+                &json!({ "base_tree": base_tree, "tree": entries }),
+            )
+            .await?;
+        let commit = self
+            .post_json(
+                Method::POST,
+                "git/commits",
+                &json!({
+                    "message": message,
+                    "tree": json_str(&tree["sha"])?,
+                    "parents": [head_sha],
+                }),
+            )
+            .await?;
+        let commit_sha = json_str(&commit["sha"])?;
+        self.post_json(
+            Method::PATCH,
+            &format!("git/refs/heads/{}", branch),
+            &json!({ "sha": commit_sha }),
         )
+        .await?;
+        Ok(commit_sha)
+    }
+
+    /// Blobs under `prefix`. The recursive tree listing is cut off for large
+    /// repositories; then the tree is walked a directory at a time.
+    async fn list_blobs(&self, prefix: &str) -> Result<Vec<Value>> {
+        let branch = uri_encode(&self.branch, true);
+        let tree = self
+            .get_json(&format!("git/trees/{}?recursive=1", branch))
+            .await?;
+        let in_prefix = |path: &str| path.starts_with(prefix);
+
+        if tree["truncated"] != true {
+            return Ok(tree["tree"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|entry| entry["type"] == "blob")
+                .filter(|entry| entry["path"].as_str().is_some_and(in_prefix))
+                .cloned()
+                .collect());
+        }
+
+        let mut blobs = Vec::new();
+        let mut pending = vec![(String::new(), json_str(&tree["sha"])?)];
+        while let Some((dir, sha)) = pending.pop() {
+            let tree = self.get_json(&format!("git/trees/{}", sha)).await?;
+            for entry in tree["tree"].as_array().into_iter().flatten() {
+                let path = format!("{}{}", dir, entry["path"].as_str().unwrap_or_default());
+                match entry["type"].as_str() {
+                    // Only directories that can hold paths with the prefix
+                    Some("tree")
+                        if prefix.starts_with(&format!("{}/", path)) || in_prefix(&path) =>
+                    {
+                        pending.push((format!("{}/", path), json_str(&entry["sha"])?));
+                    }
+                    Some("blob") if in_prefix(&path) => {
+                        let mut entry = entry.clone();
+                        entry["path"] = Value::String(path);
+                        blobs.push(entry);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(blobs)
+    }
+}
+
+/// How much of a post's ID goes in its file name
+const POST_ID_CHARS: usize = 8;
+
+/// Where content goes in the repository: `path` from the metadata if given,
+/// posts under `posts/<yyyy>/<slug>-<id>.json`, anything else under
+/// `files/<hash>.<ext>`
+///
+/// `<id>` is the start of the post ID (or of the content hash without one),
+/// so two posts whose slugs sanitize the same never share a file, while
+/// saving a post again still updates its own
+fn derive_path(metadata: &HashMap<String, String>, hash: &str) -> String {
+    if let Some(path) = metadata.get("path") {
+        return path.trim_start_matches('/').to_string();
+    }
+    if let Some(slug) = metadata.get("slug") {
+        let year = metadata
+            .get("created_at")
+            .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.format("%Y").to_string())
+            .unwrap_or_else(|| chrono::Utc::now().format("%Y").to_string());
+        let id: String = metadata
+            .get("post_id")
+            .map(String::as_str)
+            .unwrap_or(hash)
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(POST_ID_CHARS)
+            .collect();
+        return format!("posts/{}/{}-{}.json", year, sanitize_slug(slug), id);
+    }
+    let extension = metadata
+        .get("filename")
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|ext| ext.to_str())
+        .map(|ext| format!(".{}", ext.to_lowercase()))
+        .unwrap_or_default();
+    format!("files/{}{}", hash, extension)
+}
+
+fn json_str(value: &Value) -> Result<String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Unexpected GitHub API response"))
+}
+
+/// Turn an error response into an error with GitHub's message
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body: Value = response.json().await.unwrap_or_default();
+    match body["message"].as_str() {
+        Some(message) => Err(anyhow!("GitHub API error: {} ({})", status, message)),
+        None => Err(anyhow!("GitHub API error: {}", status)),
     }
 }
 
@@ -57,50 +314,46 @@ impl Storage for GitHubStorage {
         content: &[u8],
         metadata: HashMap<String, String>,
     ) -> Result<StorageResult> {
-        let path = metadata
-            .get("path")
-            .ok_or_else(|| anyhow::anyhow!("GitHub storage requires 'path' in metadata"))?;
-
-        let encoded_content = general_purpose::STANDARD.encode(content);
         use sha2::{Digest, Sha256};
-        let hash = Sha256::digest(content);
-        let hash_str = hex::encode(hash);
-
-        let github_content = GitHubContent {
-            message: metadata
-                .get("message")
-                .unwrap_or(&format!("Add content to {path}"))
-                .clone(),
-            content: encoded_content,
-            sha: None,
-        };
+        let hash_str = hex::encode(Sha256::digest(content));
+        let path = derive_path(&metadata, &hash_str);
 
-        let response = self
-            .client
-            .put(self.api_url(path))
-            .header("Authorization", format!("token {}", self.token))
-            .header("Accept", "application/vnd.github.v3+json")
-            .json(&github_content)
-            .send()
-            .await?;
+        if !self.add_to_batch(&path, Some(content.to_vec())) {
+            // Updating a file needs the SHA of what it replaces
+            let sha = self.file_sha(&path).await?;
+            let github_content = GitHubContent {
+                message: metadata
+                    .get("message")
+                    .cloned()
+                    .unwrap_or_else(|| match sha {
+                        Some(_) => format!("Update {path}"),
+                        None => format!("Add {path}"),
+                    }),
+                content: general_purpose::STANDARD.encode(content),
+                branch: self.branch.clone(),
+                sha,
+            };
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("GitHub API error: {}", response.status()));
+            let response = self
+                .request(
+                    Method::PUT,
+                    &format!("contents/{}", uri_encode(&path, false)),
+                    JSON,
+                )
+                .json(&github_content)
+                .send()
+                .await?;
+            check(response).await?;
         }
 
-        let result: serde_json::Value = response.json().await?;
-        let sha = result["commit"]["sha"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("No SHA in GitHub response"))?;
-
         Ok(StorageResult {
-            id: sha.to_string(),
+            id: path.clone(),
             url: Some(format!(
                 "https://raw.githubusercontent.com/{}/{}/{}/{}",
                 self.owner, self.repo, self.branch, path
             )),
             metadata: StorageMetadata {
-                id: sha.to_string(),
+                id: path,
                 hash: hash_str,
                 size: content.len(),
                 created_at: chrono::Utc::now(),
@@ -114,55 +367,421 @@ impl Storage for GitHubStorage {
     }
 
     async fn retrieve(&self, id: &str) -> Result<Vec<u8>> {
+        match self.batched(id) {
+            Some(Some(content)) => return Ok(content),
+            Some(None) => bail!("{} was deleted", id),
+            None => {}
+        }
+
         let response = self
-            .client
-            .get(self.api_url(id))
-            .header("Authorization", format!("token {}", self.token))
-            .header("Accept", "application/vnd.github.v3.raw")
+            .request(Method::GET, &self.contents(id), RAW)
             .send()
             .await?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("GitHub API error: {}", response.status()));
-        }
-
-        let content = response.bytes().await?;
+        let content = check(response).await?.bytes().await?;
         Ok(content.to_vec())
     }
 
     async fn exists(&self, id: &str) -> Result<bool> {
+        if let Some(content) = self.batched(id) {
+            return Ok(content.is_some());
+        }
+
         let response = self
-            .client
-            .head(self.api_url(id))
-            .header("Authorization", format!("token {}", self.token))
+            .request(Method::HEAD, &self.contents(id), JSON)
             .send()
             .await?;
-
-        Ok(response.status().is_success())
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => bail!("GitHub API error: {}", status),
+        }
     }
 
-    async fn delete(&self, _id: &str) -> Result<()> {
-        Err(anyhow::anyhow!("GitHub storage deletion not implemented"))
-    }
+    async fn delete(&self, id: &str) -> Result<()> {
+        if self.add_to_batch(id, None) {
+            return Ok(());
+        }
 
-    async fn list(&self, prefix: Option<&str>) -> Result<Vec<StorageMetadata>> {
-        let path = prefix.unwrap_or("");
+        let sha = self
+            .file_sha(id)
+            .await?
+            .ok_or_else(|| anyhow!("{} not found in {}/{}", id, self.owner, self.repo))?;
         let response = self
-            .client
-            .get(self.api_url(path))
-            .header("Authorization", format!("token {}", self.token))
-            .header("Accept", "application/vnd.github.v3+json")
+            .request(
+                Method::DELETE,
+                &format!("contents/{}", uri_encode(id, false)),
+                JSON,
+            )
+            .json(&json!({
+                "message": format!("Delete {id}"),
+                "sha": sha,
+                "branch": self.branch,
+            }))
             .send()
             .await?;
+        check(response).await?;
+        Ok(())
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("GitHub API error: {}", response.status()));
-        }
-
-        Ok(Vec::new())
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<StorageMetadata>> {
+        let blobs = self.list_blobs(prefix.unwrap_or("")).await?;
+        Ok(blobs
+            .into_iter()
+            .map(|blob| {
+                let id = blob["path"].as_str().unwrap_or_default().to_string();
+                let sha = blob["sha"].as_str().unwrap_or_default().to_string();
+                StorageMetadata {
+                    id,
+                    hash: String::new(),
+                    size: blob["size"].as_u64().unwrap_or(0) as usize,
+                    created_at: chrono::Utc::now(),
+                    content_type: "application/octet-stream".to_string(),
+                    extra: HashMap::from([("sha".to_string(), sha)]),
+                }
+            })
+            .collect())
     }
 
     fn storage_type(&self) -> &'static str {
         "github"
     }
+
+    async fn begin_batch(&self) -> Result<()> {
+        let mut batch = self.batch.lock().unwrap();
+        if batch.is_none() {
+            *batch = Some(Batch::new());
+        }
+        Ok(())
+    }
+
+    async fn commit_batch(&self, message: &str) -> Result<()> {
+        let Some(files) = self.batch.lock().unwrap().take() else {
+            return Ok(());
+        };
+        if files.is_empty() {
+            return Ok(());
+        }
+        let commit = self.commit_files(&files, message).await?;
+        tracing::info!("Committed {} files to GitHub as {}", files.len(), commit);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, Method as HttpMethod, StatusCode as HttpStatus, Uri};
+    use axum::response::{IntoResponse, Response};
+    use std::sync::Arc;
+
+    /// A repository behind just enough of the contents and git data APIs
+    #[derive(Default)]
+    struct FakeRepo {
+        files: BTreeMap<String, Vec<u8>>,
+        blobs: HashMap<String, Vec<u8>>,
+        trees: HashMap<String, BTreeMap<String, Vec<u8>>>,
+        /// Messages, oldest first
+        commits: Vec<String>,
+        /// Cut recursive tree listings short, like GitHub does for big repos
+        truncate: bool,
+    }
+
+    fn sha(content: &[u8]) -> String {
+        use sha2::{Digest, Sha256};
+        hex::encode(Sha256::digest(content))[..40].to_string()
+    }
+
+    impl FakeRepo {
+        fn head(&self) -> String {
+            match self.commits.len() {
+                0 => "root".to_string(),
+                n => format!("commit-{}", n),
+            }
+        }
+
+        fn commit(&mut self, message: &str) -> String {
+            self.commits.push(message.to_string());
+            self.head()
+        }
+
+        /// Entries directly in `dir` ("" for the root), or all of them
+        fn tree(&self, dir: &str, recursive: bool) -> Vec<Value> {
+            let mut entries = Vec::new();
+            let mut dirs = std::collections::BTreeSet::new();
+            for (path, content) in &self.files {
+                let Some(rest) = path.strip_prefix(dir) else {
+                    continue;
+                };
+                let parts: Vec<&str> = rest.split('/').collect();
+                for depth in 1..parts.len() {
+                    if recursive || depth == 1 {
+                        dirs.insert(parts[..depth].join("/"));
+                    }
+                }
+                if recursive || parts.len() == 1 {
+                    entries.push(json!({
+                        "path": rest, "type": "blob", "sha": sha(content), "size": content.len()
+                    }));
+                }
+            }
+            for sub in dirs {
+                entries.push(json!({
+                    "path": sub, "type": "tree", "sha": format!("tree:{}{}/", dir, sub)
+                }));
+            }
+            entries
+        }
+    }
+
+    async fn fake_github(
+        axum::extract::State(repo): axum::extract::State<Arc<Mutex<FakeRepo>>>,
+        method: HttpMethod,
+        uri: Uri,
+        headers: HeaderMap,
+        body: axum::body::Bytes,
+    ) -> Response {
+        if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some("token secret") {
+            return HttpStatus::UNAUTHORIZED.into_response();
+        }
+        let raw = headers.get("accept").and_then(|v| v.to_str().ok()) == Some(RAW);
+        let url = reqwest::Url::parse(&format!("http://fake{}", uri)).unwrap();
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let path = url
+            .path()
+            .strip_prefix("/repos/me/blog/")
+            .unwrap_or_default();
+        let body: Value = serde_json::from_slice(&body).unwrap_or_default();
+        let not_found = || (HttpStatus::NOT_FOUND, r#"{"message":"Not Found"}"#).into_response();
+        let mut repo = repo.lock().unwrap();
+
+        if let Some(file) = path.strip_prefix("contents/") {
+            let file = file.to_string();
+            return match method.as_str() {
+                "GET" | "HEAD" => {
+                    assert_eq!(query.get("ref").map(String::as_str), Some("main"));
+                    match repo.files.get(&file) {
+                        Some(content) if raw => content.clone().into_response(),
+                        Some(content) => axum::Json(json!({ "sha": sha(content) })).into_response(),
+                        None => not_found(),
+                    }
+                }
+                "PUT" => {
+                    let current = repo.files.get(&file).map(|content| sha(content));
+                    if body["sha"].as_str().map(str::to_string) != current {
+                        return (HttpStatus::CONFLICT, r#"{"message":"sha mismatch"}"#)
+                            .into_response();
+                    }
+                    let content = general_purpose::STANDARD
+                        .decode(body["content"].as_str().unwrap())
+                        .unwrap();
+                    repo.files.insert(file, content);
+                    let commit = repo.commit(body["message"].as_str().unwrap());
+                    axum::Json(json!({ "commit": { "sha": commit } })).into_response()
+                }
+                "DELETE" => match repo.files.get(&file) {
+                    Some(content) if body["sha"] == sha(content) => {
+                        repo.files.remove(&file);
+                        let commit = repo.commit(body["message"].as_str().unwrap());
+                        axum::Json(json!({ "commit": { "sha": commit } })).into_response()
+                    }
+                    Some(_) => HttpStatus::CONFLICT.into_response(),
+                    None => not_found(),
+                },
+                _ => HttpStatus::METHOD_NOT_ALLOWED.into_response(),
+            };
+        }
+
+        match (method.as_str(), path) {
+            ("GET", "git/ref/heads/main") => {
+                axum::Json(json!({ "object": { "sha": repo.head() } })).into_response()
+            }
+            ("GET", commit) if commit.starts_with("git/commits/") => {
+                axum::Json(json!({ "tree": { "sha": "base" } })).into_response()
+            }
+            ("GET", "git/trees/main") => {
+                let recursive = query.contains_key("recursive");
+                let truncated = recursive && repo.truncate;
+                let entries = repo.tree("", recursive && !truncated);
+                axum::Json(json!({ "sha": "tree:", "tree": entries, "truncated": truncated }))
+                    .into_response()
+            }
+            ("GET", tree) if tree.starts_with("git/trees/tree:") => {
+                let dir = &tree["git/trees/tree:".len()..];
+                axum::Json(json!({ "tree": repo.tree(dir, false) })).into_response()
+            }
+            ("POST", "git/blobs") => {
+                let content = general_purpose::STANDARD
+                    .decode(body["content"].as_str().unwrap())
+                    .unwrap();
+                let id = sha(&content);
+                repo.blobs.insert(id.clone(), content);
+                axum::Json(json!({ "sha": id })).into_response()
+            }
+            ("POST", "git/trees") => {
+                assert_eq!(body["base_tree"], "base");
+                let mut files = repo.files.clone();
+                for entry in body["tree"].as_array().unwrap() {
+                    let path = entry["path"].as_str().unwrap().to_string();
+                    match entry["sha"].as_str() {
+                        Some(blob) => files.insert(path, repo.blobs[blob].clone()),
+                        None => files.remove(&path),
+                    };
+                }
+                let id = format!("tree-{}", repo.trees.len() + 1);
+                repo.trees.insert(id.clone(), files);
+                axum::Json(json!({ "sha": id })).into_response()
+            }
+            ("POST", "git/commits") => {
+                assert_eq!(body["parents"][0], repo.head().as_str());
+                let files = repo.trees[body["tree"].as_str().unwrap()].clone();
+                repo.files = files;
+                let commit = repo.commit(body["message"].as_str().unwrap());
+                axum::Json(json!({ "sha": commit })).into_response()
+            }
+            ("PATCH", "git/refs/heads/main") => {
+                assert_eq!(body["sha"], repo.head().as_str());
+                axum::Json(json!({ "object": { "sha": repo.head() } })).into_response()
+            }
+            _ => not_found(),
+        }
+    }
+
+    async fn storage() -> (GitHubStorage, Arc<Mutex<FakeRepo>>) {
+        let repo = Arc::new(Mutex::new(FakeRepo::default()));
+        let app = axum::Router::new()
+            .fallback(fake_github)
+            .with_state(repo.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let storage = GitHubStorage::new(
+            "me".to_string(),
+            "blog".to_string(),
+            "main".to_string(),
+            "secret".to_string(),
+        )
+        .with_api_url(&format!("http://{}", addr));
+        (storage, repo)
+    }
+
+    fn post_metadata(slug: &str, post_id: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("slug".to_string(), slug.to_string()),
+            ("post_id".to_string(), post_id.to_string()),
+            (
+                "created_at".to_string(),
+                "2024-05-01T10:00:00+00:00".to_string(),
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_store_update_list_delete() {
+        let (github, repo) = storage().await;
+
+        let stored = github
+            .store(b"v1", post_metadata("hello", "post1"))
+            .await
+            .unwrap();
+        assert_eq!(stored.id, "posts/2024/hello-post1.json");
+        // Storing the same path again updates it with the current SHA
+        github
+            .store(b"v2", post_metadata("hello", "post1"))
+            .await
+            .unwrap();
+        assert_eq!(github.retrieve(&stored.id).await.unwrap(), b"v2");
+
+        let metadata = HashMap::from([("filename".to_string(), "Cat.JPG".to_string())]);
+        let image = github.store(b"meow", metadata).await.unwrap();
+        assert!(image.id.starts_with("files/") && image.id.ends_with(".jpg"));
+        assert_eq!(repo.lock().unwrap().commits.len(), 3);
+
+        let ids = |list: Vec<StorageMetadata>| {
+            let mut ids: Vec<String> = list.into_iter().map(|m| m.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids(github.list(Some("posts/")).await.unwrap()),
+            vec![stored.id.clone()]
+        );
+        assert_eq!(
+            ids(github.list(None).await.unwrap()),
+            vec![image.id.clone(), stored.id.clone()]
+        );
+        repo.lock().unwrap().truncate = true;
+        assert_eq!(
+            ids(github.list(Some("posts/2024")).await.unwrap()),
+            vec![stored.id.clone()]
+        );
+        assert_eq!(ids(github.list(None).await.unwrap()).len(), 2);
+
+        github.delete(&stored.id).await.unwrap();
+        assert!(!github.exists(&stored.id).await.unwrap());
+        assert!(github.exists(&image.id).await.unwrap());
+        assert!(github.delete(&stored.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_same_slug_different_posts() {
+        let (github, _repo) = storage().await;
+
+        let first = github
+            .store(b"one", post_metadata("hello world", "0c9e1f2a-1111"))
+            .await
+            .unwrap();
+        let second = github
+            .store(b"two", post_metadata("hello-world", "7b3d4e5f-2222"))
+            .await
+            .unwrap();
+        assert_eq!(first.id, "posts/2024/hello-world-0c9e1f2a.json");
+        assert_eq!(second.id, "posts/2024/hello-world-7b3d4e5f.json");
+        assert_eq!(github.retrieve(&first.id).await.unwrap(), b"one");
+        assert_eq!(github.retrieve(&second.id).await.unwrap(), b"two");
+    }
+
+    #[tokio::test]
+    async fn test_batch_makes_one_commit() {
+        let (github, repo) = storage().await;
+        github
+            .store(b"old", post_metadata("gone", "post2"))
+            .await
+            .unwrap();
+
+        github.begin_batch().await.unwrap();
+        let first = github
+            .store(b"one", post_metadata("first", "post3"))
+            .await
+            .unwrap();
+        github
+            .store(b"two", post_metadata("second", "post4"))
+            .await
+            .unwrap();
+        github.delete("posts/2024/gone-post2.json").await.unwrap();
+        // Readable before the commit, and nothing is written yet
+        assert_eq!(github.retrieve(&first.id).await.unwrap(), b"one");
+        assert!(!github.exists("posts/2024/gone-post2.json").await.unwrap());
+        assert_eq!(repo.lock().unwrap().commits.len(), 1);
+
+        github.commit_batch("Import posts").await.unwrap();
+        let files: Vec<String> = repo.lock().unwrap().files.keys().cloned().collect();
+        assert_eq!(
+            files,
+            vec![
+                "posts/2024/first-post3.json",
+                "posts/2024/second-post4.json"
+            ]
+        );
+        assert_eq!(
+            repo.lock().unwrap().commits,
+            vec!["Add posts/2024/gone-post2.json", "Import posts"]
+        );
+        assert_eq!(github.retrieve(&first.id).await.unwrap(), b"one");
+
+        // Nothing batched, nothing committed
+        github.begin_batch().await.unwrap();
+        github.commit_batch("Empty").await.unwrap();
+        assert_eq!(repo.lock().unwrap().commits.len(), 2);
+    }
 }
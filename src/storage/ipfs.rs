@@ -1,6 +1,7 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::path::Path;
 
 use super::{Storage, StorageMetadata, StorageResult};
 use crate::constants::{
@@ -13,6 +14,15 @@ pub struct IpfsStorage {
     client: reqwest::Client,
 }
 
+/// A directory added by `add_directory`
+#[derive(Debug, Clone)]
+pub struct AddedDirectory {
+    /// CID of the directory wrapping the added files
+    pub cid: String,
+    pub files: usize,
+    pub size: u64,
+}
+
 impl IpfsStorage {
     pub fn new(api_url: &str) -> Result<Self> {
         let client = reqwest::Client::builder().no_proxy().build()?;
@@ -66,7 +76,7 @@ impl IpfsStorage {
         Ok(response.bytes().await?.to_vec())
     }
 
-    async fn ipfs_pin(&self, cid: &str) -> Result<()> {
+    pub async fn ipfs_pin(&self, cid: &str) -> Result<()> {
         let response = self
             .client
             .post(format!("{}/api/v0/pin/add?arg={}", self.api_url, cid))
@@ -79,6 +89,111 @@ impl IpfsStorage {
 
         Ok(())
     }
+
+    /// Add every file under `dir` in one request, wrapped in a directory so
+    /// the root CID serves `dir` itself. Dotfiles are left out. Nothing is
+    /// pinned; pin the returned CID.
+    pub async fn add_directory(&self, dir: &Path) -> Result<AddedDirectory> {
+        let mut form = reqwest::multipart::Form::new();
+        let mut files = 0;
+        let mut size = 0;
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            let mut entries = Vec::new();
+            let mut read_dir = tokio::fs::read_dir(&current).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    entries.push(entry.path());
+                }
+            }
+            entries.sort();
+
+            for path in entries {
+                // Kubo reads part names as URL-encoded paths
+                let name = super::s3::uri_encode(
+                    &path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/"),
+                    false,
+                );
+                let part = if path.is_dir() {
+                    pending.push(path);
+                    reqwest::multipart::Part::bytes(Vec::new())
+                        .file_name(name)
+                        .mime_str("application/x-directory")?
+                } else {
+                    // Streamed from disk as the request goes out, so a large
+                    // site never has to fit in memory
+                    let file = tokio::fs::File::open(&path).await?;
+                    let length = file.metadata().await?.len();
+                    files += 1;
+                    size += length;
+                    reqwest::multipart::Part::stream_with_length(file, length)
+                        .file_name(name)
+                        .mime_str(CONTENT_TYPE_OCTET_STREAM)?
+                };
+                form = form.part("file", part);
+            }
+        }
+        if files == 0 {
+            anyhow::bail!("No files to add in {}", dir.display());
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/api/v0/add", self.api_url))
+            .query(&[
+                ("wrap-with-directory", "true"),
+                ("recursive", "true"),
+                ("pin", "false"),
+            ])
+            .multipart(form)
+            .send()
+            .await?;
+        let body = kubo_response(response, "add").await?;
+
+        // One JSON object per added entry; the wrapping directory has no name
+        let mut root = None;
+        for line in body.lines().filter(|line| !line.trim().is_empty()) {
+            let entry: serde_json::Value = serde_json::from_str(line)?;
+            if entry["Name"].as_str() == Some("") {
+                root = entry["Hash"].as_str().map(str::to_string);
+            }
+        }
+        let cid = root.ok_or_else(|| anyhow::anyhow!("No root directory in IPFS response"))?;
+
+        Ok(AddedDirectory { cid, files, size })
+    }
+
+    /// Point an IPNS key at a CID, returning the IPNS name
+    pub async fn publish_name(&self, cid: &str, key: &str) -> Result<String> {
+        let path = format!("/ipfs/{}", cid);
+        let response = self
+            .client
+            .post(format!("{}/api/v0/name/publish", self.api_url))
+            .query(&[("arg", path.as_str()), ("key", key)])
+            .send()
+            .await?;
+        let result: serde_json::Value =
+            serde_json::from_str(&kubo_response(response, "name publish").await?)?;
+
+        result["Name"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("No name in IPNS publish response"))
+    }
+}
+
+/// The body of a Kubo response, or its error message
+async fn kubo_response(response: reqwest::Response, command: &str) -> Result<String> {
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|error| error["Message"].as_str().map(str::to_string))
+            .unwrap_or(body);
+        anyhow::bail!("IPFS {} failed: {} {}", command, status, message);
+    }
+    Ok(body)
 }
 
 #[async_trait]
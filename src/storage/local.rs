@@ -1,24 +1,213 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokio::fs;
 
-use super::{Storage, StorageMetadata, StorageResult};
+use super::{content_hash, IntegrityError, Storage, StorageMetadata, StorageResult};
 
+/// Maps the names content was stored under to its hash
+const INDEX_FILE: &str = "names.json";
+
+/// Content-addressed storage: every blob lives at `ab/cd/<sha256>`, so the
+/// same bytes are stored once and different bytes never collide
 pub struct LocalStorage {
     base_path: PathBuf,
+    /// Name -> hash, for content stored with a `filename` and for IDs from
+    /// before storage was content-addressed
+    index: Mutex<BTreeMap<String, String>>,
+}
+
+/// What may point at stored blobs, collected from the database
+#[derive(Debug, Default)]
+pub struct StorageReferences {
+    /// Storage IDs: hashes, or names from the index
+    pub ids: HashSet<String>,
+    /// Text that may link blobs, like the markdown of posts with images
+    pub texts: Vec<String>,
+}
+
+/// What `gc` found and removed
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub kept: usize,
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+fn is_hash(id: &str) -> bool {
+    id.len() == 64
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
 }
 
 impl LocalStorage {
     pub fn new(base_path: impl AsRef<Path>) -> Result<Self> {
         let base_path = base_path.as_ref().to_path_buf();
         std::fs::create_dir_all(&base_path)?;
-        Ok(Self { base_path })
+
+        let index_path = base_path.join(INDEX_FILE);
+        let index = match std::fs::read(&index_path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let storage = Self {
+            base_path,
+            index: Mutex::new(index),
+        };
+        storage.migrate_flat_files()?;
+        Ok(storage)
     }
 
-    fn get_path(&self, id: &str) -> PathBuf {
-        self.base_path.join(id)
+    /// Move files from the old flat layout into their shards, keeping the
+    /// names they were stored under resolvable
+    fn migrate_flat_files(&self) -> Result<()> {
+        let mut migrated = 0;
+        for entry in std::fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !entry.file_type()?.is_file() || name == INDEX_FILE || name.starts_with('.') {
+                continue;
+            }
+
+            let content = std::fs::read(entry.path())?;
+            let hash = content_hash(&content);
+            let blob = self.blob_path(&hash);
+            if let Some(parent) = blob.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if blob.exists() {
+                std::fs::remove_file(entry.path())?;
+            } else {
+                std::fs::rename(entry.path(), &blob)?;
+            }
+            if name != hash {
+                self.index.lock().unwrap().insert(name, hash);
+            }
+            migrated += 1;
+        }
+
+        if migrated > 0 {
+            tracing::info!(
+                "Moved {} local files into content-addressed storage",
+                migrated
+            );
+            self.save_index_sync()?;
+        }
+        Ok(())
+    }
+
+    /// `ab/cd/<hash>` under the base path
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.base_path.join(&hash[..2]).join(&hash[2..4]).join(hash)
+    }
+
+    /// The hash an ID names: the ID itself, or what the index maps it to
+    fn resolve(&self, id: &str) -> Option<String> {
+        if is_hash(id) {
+            return Some(id.to_string());
+        }
+        self.index.lock().unwrap().get(id).cloned()
+    }
+
+    fn save_index_sync(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&*self.index.lock().unwrap())?;
+        let tmp = self.base_path.join(format!(".{}.tmp", INDEX_FILE));
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, self.base_path.join(INDEX_FILE))?;
+        Ok(())
+    }
+
+    async fn save_index(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&*self.index.lock().unwrap())?;
+        let tmp = self.base_path.join(format!(".{}.tmp", INDEX_FILE));
+        fs::write(&tmp, json).await?;
+        fs::rename(&tmp, self.base_path.join(INDEX_FILE)).await?;
+        Ok(())
+    }
+
+    /// Every blob: its hash, size and modification time
+    async fn blobs(&self) -> Result<Vec<(String, u64, DateTime<Utc>)>> {
+        let mut blobs = Vec::new();
+        let mut dirs = vec![(self.base_path.clone(), 0)];
+        while let Some((dir, depth)) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let file_type = entry.file_type().await?;
+                if depth < 2 && file_type.is_dir() && name.len() == 2 {
+                    dirs.push((entry.path(), depth + 1));
+                } else if depth == 2 && file_type.is_file() && is_hash(&name) {
+                    let metadata = entry.metadata().await?;
+                    let modified = metadata
+                        .modified()
+                        .map(DateTime::<Utc>::from)
+                        .unwrap_or_else(|_| Utc::now());
+                    blobs.push((name, metadata.len(), modified));
+                }
+            }
+        }
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    /// Delete blobs nothing refers to. Blobs modified after `cutoff` are
+    /// kept, since whatever stored them may not have recorded them yet.
+    pub async fn gc(
+        &self,
+        references: &StorageReferences,
+        cutoff: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<GcReport> {
+        let index = self.index.lock().unwrap().clone();
+
+        let mut referenced: HashSet<String> = references
+            .ids
+            .iter()
+            .filter_map(|id| self.resolve(id))
+            .collect();
+        // Links to blobs carry their hash; older ones their name
+        let hex = Regex::new(r"[0-9a-f]{64}")?;
+        for text in &references.texts {
+            referenced.extend(hex.find_iter(text).map(|m| m.as_str().to_string()));
+        }
+        for (name, hash) in &index {
+            if references
+                .texts
+                .iter()
+                .any(|text| text.contains(name.as_str()))
+            {
+                referenced.insert(hash.clone());
+            }
+        }
+
+        let mut report = GcReport::default();
+        for (hash, size, modified) in self.blobs().await? {
+            if referenced.contains(&hash) || modified > cutoff {
+                report.kept += 1;
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(self.blob_path(&hash)).await?;
+            }
+            report.freed_bytes += size;
+            report.removed.push(hash);
+        }
+
+        if !dry_run && !report.removed.is_empty() {
+            let removed: HashSet<&String> = report.removed.iter().collect();
+            self.index
+                .lock()
+                .unwrap()
+                .retain(|_, hash| !removed.contains(hash));
+            self.save_index().await?;
+        }
+        Ok(report)
     }
 }
 
@@ -29,25 +218,36 @@ impl Storage for LocalStorage {
         content: &[u8],
         metadata: HashMap<String, String>,
     ) -> Result<StorageResult> {
-        use sha2::{Digest, Sha256};
-        let hash = Sha256::digest(content);
-        let hash_str = hex::encode(hash);
+        let hash = content_hash(content);
+        let file_path = self.blob_path(&hash);
 
-        let id = metadata.get("filename").unwrap_or(&hash_str).clone();
-
-        let file_path = self.get_path(&id);
-        if let Some(parent) = file_path.parent() {
-            fs::create_dir_all(parent).await?;
+        // Same hash, same bytes: nothing to write
+        if !file_path.exists() {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            let tmp = file_path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+            fs::write(&tmp, content).await?;
+            fs::rename(&tmp, &file_path).await?;
         }
 
-        fs::write(&file_path, content).await?;
+        if let Some(name) = metadata.get("filename") {
+            let previous = self
+                .index
+                .lock()
+                .unwrap()
+                .insert(name.clone(), hash.clone());
+            if previous.as_ref() != Some(&hash) {
+                self.save_index().await?;
+            }
+        }
 
         Ok(StorageResult {
-            id: id.clone(),
+            id: hash.clone(),
             url: Some(format!("file://{}", file_path.display())),
             metadata: StorageMetadata {
-                id,
-                hash: hash_str,
+                id: hash.clone(),
+                hash,
                 size: content.len(),
                 created_at: chrono::Utc::now(),
                 content_type: metadata
@@ -60,60 +260,153 @@ impl Storage for LocalStorage {
     }
 
     async fn retrieve(&self, id: &str) -> Result<Vec<u8>> {
-        let file_path = self.get_path(id);
-        let content = fs::read(&file_path).await?;
+        let hash = self
+            .resolve(id)
+            .ok_or_else(|| anyhow!("No local content named {}", id))?;
+        let content = fs::read(self.blob_path(&hash)).await?;
+        if content_hash(&content) != hash {
+            return Err(IntegrityError { id: id.to_string() }.into());
+        }
         Ok(content)
     }
 
     async fn exists(&self, id: &str) -> Result<bool> {
-        let file_path = self.get_path(id);
-        Ok(file_path.exists())
+        Ok(self
+            .resolve(id)
+            .is_some_and(|hash| self.blob_path(&hash).exists()))
     }
 
     async fn delete(&self, id: &str) -> Result<()> {
-        let file_path = self.get_path(id);
-        fs::remove_file(&file_path).await?;
+        let hash = self
+            .resolve(id)
+            .ok_or_else(|| anyhow!("No local content named {}", id))?;
+        fs::remove_file(self.blob_path(&hash)).await?;
+
+        let removed = {
+            let mut index = self.index.lock().unwrap();
+            let before = index.len();
+            index.retain(|_, indexed| *indexed != hash);
+            index.len() != before
+        };
+        if removed {
+            self.save_index().await?;
+        }
         Ok(())
     }
 
+    /// Blobs whose hash starts with `prefix`
     async fn list(&self, prefix: Option<&str>) -> Result<Vec<StorageMetadata>> {
-        let search_path = match prefix {
-            Some(p) => self.base_path.join(p),
-            None => self.base_path.clone(),
-        };
-
-        let mut metadata_list = Vec::new();
-        let mut entries = fs::read_dir(&search_path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            if entry.file_type().await?.is_file() {
-                let path = entry.path();
-                let metadata = entry.metadata().await?;
-
-                metadata_list.push(StorageMetadata {
-                    id: path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string(),
-                    hash: String::new(),
-                    size: metadata.len() as usize,
-                    created_at: metadata
-                        .created()
-                        .ok()
-                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                        .and_then(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0))
-                        .unwrap_or_else(chrono::Utc::now),
-                    content_type: "application/octet-stream".to_string(),
-                    extra: HashMap::new(),
-                });
-            }
-        }
+        let names: HashMap<String, String> = self
+            .index
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, hash)| (hash.clone(), name.clone()))
+            .collect();
 
-        Ok(metadata_list)
+        Ok(self
+            .blobs()
+            .await?
+            .into_iter()
+            .filter(|(hash, _, _)| prefix.is_none_or(|prefix| hash.starts_with(prefix)))
+            .map(|(hash, size, modified)| StorageMetadata {
+                id: hash.clone(),
+                size: size as usize,
+                created_at: modified,
+                content_type: "application/octet-stream".to_string(),
+                extra: names
+                    .get(&hash)
+                    .map(|name| HashMap::from([("filename".to_string(), name.clone())]))
+                    .unwrap_or_default(),
+                hash,
+            })
+            .collect())
     }
 
     fn storage_type(&self) -> &'static str {
         "local"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("kpgb-local-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_content_addressed_store() {
+        let dir = temp_dir();
+        let storage = LocalStorage::new(&dir).unwrap();
+        let named = |name: &str| HashMap::from([("filename".to_string(), name.to_string())]);
+
+        // Same name, different images: both are kept
+        let first = storage.store(b"cat", named("image.png")).await.unwrap();
+        let second = storage.store(b"dog", named("image.png")).await.unwrap();
+        assert_ne!(first.id, second.id);
+        let hash = &first.id;
+        assert!(dir.join(&hash[..2]).join(&hash[2..4]).join(hash).exists());
+        assert_eq!(storage.retrieve(&first.id).await.unwrap(), b"cat");
+        assert_eq!(storage.retrieve("image.png").await.unwrap(), b"dog");
+        assert_eq!(storage.list(None).await.unwrap().len(), 2);
+
+        // The index survives a restart
+        let reopened = LocalStorage::new(&dir).unwrap();
+        assert_eq!(reopened.retrieve("image.png").await.unwrap(), b"dog");
+
+        // Corruption is caught on read
+        std::fs::write(dir.join(&hash[..2]).join(&hash[2..4]).join(hash), b"cow").unwrap();
+        let error = storage.retrieve(hash).await.unwrap_err();
+        assert!(error.downcast_ref::<IntegrityError>().is_some());
+
+        storage.delete("image.png").await.unwrap();
+        assert!(!storage.exists(&second.id).await.unwrap());
+        assert!(!storage.exists("image.png").await.unwrap());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_and_gc() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("old-photo.jpg"), b"old photo").unwrap();
+        std::fs::write(dir.join(content_hash(b"old post")), b"old post").unwrap();
+
+        let storage = LocalStorage::new(&dir).unwrap();
+        assert!(!dir.join("old-photo.jpg").exists());
+        assert_eq!(
+            storage.retrieve("old-photo.jpg").await.unwrap(),
+            b"old photo"
+        );
+        let post = storage.store(b"new post", HashMap::new()).await.unwrap();
+        let image = storage.store(b"image", HashMap::new()).await.unwrap();
+        let orphan = storage.store(b"orphan", HashMap::new()).await.unwrap();
+
+        let references = StorageReferences {
+            ids: HashSet::from([post.id.clone(), content_hash(b"old post")]),
+            texts: vec![
+                format!("![a]({})", image.url.unwrap()),
+                "![b](file:///srv/storage/local/old-photo.jpg)".to_string(),
+            ],
+        };
+
+        // Everything is too new to collect yet
+        let report = storage.gc(&references, Utc::now() - chrono::Duration::hours(1), false);
+        assert!(report.await.unwrap().removed.is_empty());
+
+        let cutoff = Utc::now() + chrono::Duration::seconds(1);
+        let report = storage.gc(&references, cutoff, true).await.unwrap();
+        assert_eq!(report.removed, vec![orphan.id.clone()]);
+        assert!(storage.exists(&orphan.id).await.unwrap());
+
+        let report = storage.gc(&references, cutoff, false).await.unwrap();
+        assert_eq!(report.removed, vec![orphan.id.clone()]);
+        assert_eq!(report.kept, 4);
+        assert_eq!(report.freed_bytes, 6);
+        assert!(!storage.exists(&orphan.id).await.unwrap());
+        assert!(storage.exists("old-photo.jpg").await.unwrap());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
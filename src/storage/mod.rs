@@ -1,11 +1,15 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::warn;
 
 pub mod github;
 pub mod ipfs;
 pub mod local;
+pub mod s3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageMetadata {
@@ -43,6 +47,17 @@ pub trait Storage: Send + Sync {
     async fn list(&self, prefix: Option<&str>) -> Result<Vec<StorageMetadata>>;
 
     fn storage_type(&self) -> &'static str;
+
+    /// Hold back the writes that follow until `commit_batch`, for backends
+    /// where each write is a commit
+    async fn begin_batch(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write everything held back since `begin_batch` at once
+    async fn commit_batch(&self, _message: &str) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
@@ -53,21 +68,164 @@ pub enum StorageBackend {
     S3,
 }
 
+impl StorageBackend {
+    /// Name used in config and in `post_locations`
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageBackend::Ipfs => "ipfs",
+            StorageBackend::GitHub => "github",
+            StorageBackend::Local => "local",
+            StorageBackend::S3 => "s3",
+        }
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "ipfs" => Ok(StorageBackend::Ipfs),
+            "github" => Ok(StorageBackend::GitHub),
+            "local" => Ok(StorageBackend::Local),
+            "s3" => Ok(StorageBackend::S3),
+            other => bail!("Unknown storage backend: {}", other),
+        }
+    }
+}
+
+/// Where content is written: the primary backend, whose ID a post is known
+/// by, and the mirrors that get a copy
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationPolicy {
+    pub primary: StorageBackend,
+    pub mirrors: Vec<StorageBackend>,
+}
+
+impl ReplicationPolicy {
+    pub fn single(primary: StorageBackend) -> Self {
+        Self {
+            primary,
+            mirrors: Vec::new(),
+        }
+    }
+
+    /// `STORAGE_PRIMARY` and a comma separated `STORAGE_MIRRORS`. Without a
+    /// primary, IPFS is used when `IPFS_API_URL` is set and local storage
+    /// otherwise.
+    pub fn from_env() -> Result<Self> {
+        let default = if std::env::var("IPFS_API_URL").is_ok() {
+            StorageBackend::Ipfs
+        } else {
+            StorageBackend::Local
+        };
+        Self::parse(
+            std::env::var("STORAGE_PRIMARY").ok().as_deref(),
+            std::env::var("STORAGE_MIRRORS").ok().as_deref(),
+            default,
+        )
+    }
+
+    pub fn parse(
+        primary: Option<&str>,
+        mirrors: Option<&str>,
+        default: StorageBackend,
+    ) -> Result<Self> {
+        let primary = match primary.filter(|name| !name.trim().is_empty()) {
+            Some(name) => name.parse()?,
+            None => default,
+        };
+        let mut policy = Self::single(primary);
+        for name in mirrors.unwrap_or_default().split(',') {
+            if name.trim().is_empty() {
+                continue;
+            }
+            let backend: StorageBackend = name.parse()?;
+            if backend != policy.primary && !policy.mirrors.contains(&backend) {
+                policy.mirrors.push(backend);
+            }
+        }
+        Ok(policy)
+    }
+
+    /// The primary first, then the mirrors
+    pub fn backends(&self) -> impl Iterator<Item = &StorageBackend> {
+        std::iter::once(&self.primary).chain(&self.mirrors)
+    }
+}
+
+/// A copy of stored content in one backend
+#[derive(Debug, Clone)]
+pub struct Replica {
+    pub backend: StorageBackend,
+    pub result: StorageResult,
+}
+
+/// What `verify` found for a copy
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplicaStatus {
+    Ok,
+    /// The backend doesn't have it, or couldn't be reached
+    Missing(String),
+    /// The backend returned different bytes
+    Corrupt,
+    /// The backend isn't configured here
+    Unavailable,
+}
+
+impl ReplicaStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplicaStatus::Ok => "ok",
+            ReplicaStatus::Missing(_) => "missing",
+            ReplicaStatus::Corrupt => "corrupt",
+            ReplicaStatus::Unavailable => "unavailable",
+        }
+    }
+}
+
+/// SHA-256 of content, as backends report it in `StorageMetadata::hash`
+pub fn content_hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+/// A backend read content that no longer matches its hash
+#[derive(Debug)]
+pub struct IntegrityError {
+    pub id: String,
+}
+
+impl std::fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} doesn't match its hash", self.id)
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
 #[derive(Clone)]
 pub struct StorageManager {
     backends:
         std::sync::Arc<std::sync::Mutex<HashMap<StorageBackend, std::sync::Arc<dyn Storage>>>>,
-    default_backend: StorageBackend,
+    policy: ReplicationPolicy,
 }
 
 impl StorageManager {
     pub fn new(default_backend: StorageBackend) -> Self {
+        Self::with_policy(ReplicationPolicy::single(default_backend))
+    }
+
+    pub fn with_policy(policy: ReplicationPolicy) -> Self {
         Self {
             backends: std::sync::Arc::new(std::sync::Mutex::new(HashMap::new())),
-            default_backend,
+            policy,
         }
     }
 
+    pub fn policy(&self) -> &ReplicationPolicy {
+        &self.policy
+    }
+
     pub fn add_backend(&mut self, backend_type: StorageBackend, backend: Box<dyn Storage>) {
         let mut backends = self.backends.lock().unwrap();
         backends.insert(backend_type, std::sync::Arc::from(backend));
@@ -81,10 +239,31 @@ impl StorageManager {
         backends.get(backend_type).cloned()
     }
 
+    fn all_backends(&self) -> Vec<std::sync::Arc<dyn Storage>> {
+        let backends = self.backends.lock().unwrap();
+        backends.values().cloned().collect()
+    }
+
+    /// Group the writes that follow into one commit in backends that have
+    /// commits
+    pub async fn begin_batch(&self) -> Result<()> {
+        for backend in self.all_backends() {
+            backend.begin_batch().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn commit_batch(&self, message: &str) -> Result<()> {
+        for backend in self.all_backends() {
+            backend.commit_batch(message).await?;
+        }
+        Ok(())
+    }
+
     pub fn default_backend(&self) -> std::sync::Arc<dyn Storage> {
         let backends = self.backends.lock().unwrap();
         backends
-            .get(&self.default_backend)
+            .get(&self.policy.primary)
             .expect("Default backend not configured")
             .clone()
     }
@@ -104,4 +283,167 @@ impl StorageManager {
     pub async fn exists(&self, id: &str) -> Result<bool> {
         self.default_backend().exists(id).await
     }
+
+    /// Store content in the primary backend and copy it to every mirror.
+    /// Fails only when the primary does; mirrors that fail are logged and
+    /// left out. The primary's copy comes first.
+    pub async fn replicate(
+        &self,
+        content: &[u8],
+        metadata: HashMap<String, String>,
+    ) -> Result<Vec<Replica>> {
+        let primary = self.policy.primary.clone();
+        let result = self
+            .default_backend()
+            .store(content, metadata.clone())
+            .await?;
+        let mut replicas = vec![Replica {
+            backend: primary,
+            result,
+        }];
+
+        for backend in &self.policy.mirrors {
+            let Some(storage) = self.get_backend(backend) else {
+                warn!("Mirror {} is not configured, skipped", backend.name());
+                continue;
+            };
+            match storage.store(content, metadata.clone()).await {
+                Ok(result) => replicas.push(Replica {
+                    backend: backend.clone(),
+                    result,
+                }),
+                Err(e) => warn!("Failed to mirror to {}: {}", backend.name(), e),
+            }
+        }
+        Ok(replicas)
+    }
+
+    /// Read content from the first copy that can be read and still has
+    /// `hash`, trying them in order
+    pub async fn retrieve_replica(
+        &self,
+        copies: &[(StorageBackend, String)],
+        hash: &str,
+    ) -> Result<Vec<u8>> {
+        let mut errors = Vec::new();
+        for (backend, id) in copies {
+            match self.retrieve_from(backend, id, hash).await {
+                Ok(content) => return Ok(content),
+                Err(e) => {
+                    warn!("Reading {} from {} failed: {}", id, backend.name(), e);
+                    errors.push(format!("{}: {}", backend.name(), e));
+                }
+            }
+        }
+        Err(anyhow!("No copy could be read ({})", errors.join("; ")))
+    }
+
+    async fn retrieve_from(
+        &self,
+        backend: &StorageBackend,
+        id: &str,
+        hash: &str,
+    ) -> Result<Vec<u8>> {
+        let storage = self
+            .get_backend(backend)
+            .ok_or_else(|| anyhow!("backend not configured"))?;
+        let content = storage.retrieve(id).await?;
+        if content_hash(&content) != hash {
+            bail!("content doesn't match its hash");
+        }
+        Ok(content)
+    }
+
+    /// Check that a backend still has a copy with the expected hash
+    pub async fn verify(&self, backend: &StorageBackend, id: &str, hash: &str) -> ReplicaStatus {
+        let Some(storage) = self.get_backend(backend) else {
+            return ReplicaStatus::Unavailable;
+        };
+        match storage.retrieve(id).await {
+            Ok(content) if content_hash(&content) == hash => ReplicaStatus::Ok,
+            Ok(_) => ReplicaStatus::Corrupt,
+            Err(e) if e.downcast_ref::<IntegrityError>().is_some() => ReplicaStatus::Corrupt,
+            Err(e) => ReplicaStatus::Missing(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use local::LocalStorage;
+
+    #[test]
+    fn test_replication_policy() {
+        let policy = ReplicationPolicy::parse(
+            Some("ipfs"),
+            Some("local, GitHub,ipfs,local"),
+            StorageBackend::Local,
+        )
+        .unwrap();
+        assert_eq!(policy.primary, StorageBackend::Ipfs);
+        assert_eq!(
+            policy.mirrors,
+            vec![StorageBackend::Local, StorageBackend::GitHub]
+        );
+
+        let policy = ReplicationPolicy::parse(None, Some(""), StorageBackend::Local).unwrap();
+        assert_eq!(policy, ReplicationPolicy::single(StorageBackend::Local));
+        assert!(ReplicationPolicy::parse(Some("floppy"), None, StorageBackend::Local).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replicate_and_fall_back() {
+        let dir = std::env::temp_dir().join(format!("kpgb-replicas-{}", uuid::Uuid::new_v4()));
+        let mut manager = StorageManager::with_policy(ReplicationPolicy {
+            primary: StorageBackend::Local,
+            mirrors: vec![StorageBackend::S3, StorageBackend::Ipfs],
+        });
+        manager.add_backend(
+            StorageBackend::Local,
+            Box::new(LocalStorage::new(dir.join("primary")).unwrap()),
+        );
+        // Stands in for a second backend; S3 is left unconfigured
+        manager.add_backend(
+            StorageBackend::Ipfs,
+            Box::new(LocalStorage::new(dir.join("mirror")).unwrap()),
+        );
+
+        let content = b"{\"title\":\"Hello\"}";
+        let replicas = manager.replicate(content, HashMap::new()).await.unwrap();
+        assert_eq!(replicas.len(), 2);
+        assert_eq!(replicas[0].backend, StorageBackend::Local);
+        assert_eq!(replicas[1].backend, StorageBackend::Ipfs);
+
+        let hash = content_hash(content);
+        let id = replicas[0].result.id.clone();
+        let copies = vec![
+            (StorageBackend::Local, id.clone()),
+            (StorageBackend::Ipfs, replicas[1].result.id.clone()),
+        ];
+
+        // The primary's copy goes bad; the mirror still serves it
+        let shard = |backend: &str| dir.join(backend).join(&id[..2]).join(&id[2..4]).join(&id);
+        std::fs::write(shard("primary"), b"garbage").unwrap();
+        assert_eq!(
+            manager.verify(&StorageBackend::Local, &id, &hash).await,
+            ReplicaStatus::Corrupt
+        );
+        assert_eq!(
+            manager.retrieve_replica(&copies, &hash).await.unwrap(),
+            content
+        );
+
+        std::fs::remove_file(shard("mirror")).unwrap();
+        assert!(matches!(
+            manager.verify(&StorageBackend::Ipfs, &id, &hash).await,
+            ReplicaStatus::Missing(_)
+        ));
+        assert!(manager.retrieve_replica(&copies, &hash).await.is_err());
+        assert_eq!(
+            manager.verify(&StorageBackend::S3, &id, &hash).await,
+            ReplicaStatus::Unavailable
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,34 @@
+use similar::TextDiff;
+
+/// Render a unified diff between two versions of a post's content
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "# Title\n\nFirst line\nSecond line\n";
+        let new = "# Title\n\nFirst line\nChanged line\n";
+
+        let diff = unified_diff(old, new, "rev 1", "rev 2");
+
+        assert!(diff.starts_with("--- rev 1\n+++ rev 2\n"));
+        assert!(diff.contains("-Second line\n"));
+        assert!(diff.contains("+Changed line\n"));
+        assert!(diff.contains(" First line\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_identical() {
+        let content = "Same content\n";
+        assert!(unified_diff(content, content, "a", "b").is_empty());
+    }
+}
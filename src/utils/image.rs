@@ -48,30 +48,7 @@ pub async fn process_images_in_markdown(
             .and_then(|n| n.to_str())
             .unwrap_or("image");
 
-        // Determine MIME type
-        let mime_type = match resolved_path.extension().and_then(|e| e.to_str()) {
-            Some("jpg") | Some("jpeg") => "image/jpeg",
-            Some("png") => "image/png",
-            Some("gif") => "image/gif",
-            Some("svg") => "image/svg+xml",
-            Some("webp") => "image/webp",
-            _ => "application/octet-stream",
-        };
-
-        // Prepare metadata
-        let mut metadata = HashMap::new();
-        metadata.insert("filename".to_string(), file_name.to_string());
-        metadata.insert("content_type".to_string(), mime_type.to_string());
-
-        // Upload to storage (IPFS)
-        info!("Uploading image to IPFS: {}", file_name);
-        let storage_result = storage_manager.store(&image_data, metadata).await?;
-
-        // Get the IPFS URL
-        let ipfs_url = storage_result
-            .url
-            .unwrap_or_else(|| format!("/ipfs/{}", storage_result.id));
-
+        let ipfs_url = upload_image(&image_data, file_name, storage_manager).await?;
         info!("Image uploaded: {} -> {}", img_path, ipfs_url);
 
         // Store the mapping
@@ -90,6 +67,84 @@ pub async fn process_images_in_markdown(
     Ok((processed_content, image_map))
 }
 
+/// Like `process_images_in_markdown`, but images come with the content
+/// instead of from disk, keyed by the file name the markdown refers to.
+/// References to anything else are left alone.
+pub async fn process_uploaded_images(
+    content: &str,
+    images: &HashMap<String, Vec<u8>>,
+    storage_manager: &StorageManager,
+) -> Result<(String, HashMap<String, String>)> {
+    let img_regex = Regex::new(r"!\[([^\]]*)\]\(([^)]+)\)")?;
+
+    let mut image_map = HashMap::new();
+    let mut replacements = Vec::new();
+
+    for cap in img_regex.captures_iter(content) {
+        let img_path = cap[2].trim_start_matches("./");
+        let Some(image_data) = images.get(img_path) else {
+            continue;
+        };
+
+        let ipfs_url = match image_map.get(img_path) {
+            Some(url) => String::clone(url),
+            None => {
+                let file_name = Path::new(img_path)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("image");
+                let url = upload_image(image_data, file_name, storage_manager).await?;
+                info!("Image uploaded: {} -> {}", img_path, url);
+                image_map.insert(img_path.to_string(), url.clone());
+                url
+            }
+        };
+        replacements.push((cap[0].to_string(), format!("![{}]({})", &cap[1], ipfs_url)));
+    }
+
+    let mut processed_content = content.to_string();
+    for (old, new) in replacements {
+        processed_content = processed_content.replace(&old, &new);
+    }
+
+    Ok((processed_content, image_map))
+}
+
+/// Store an image in the default backend, returning its URL
+pub async fn upload_image(
+    image_data: &[u8],
+    file_name: &str,
+    storage_manager: &StorageManager,
+) -> Result<String> {
+    // Determine MIME type
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+    let mime_type = match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    };
+
+    // Prepare metadata
+    let mut metadata = HashMap::new();
+    metadata.insert("filename".to_string(), file_name.to_string());
+    metadata.insert("content_type".to_string(), mime_type.to_string());
+
+    // Upload to storage (IPFS)
+    info!("Uploading image to IPFS: {}", file_name);
+    let storage_result = storage_manager.store(image_data, metadata).await?;
+
+    // Get the IPFS URL
+    Ok(storage_result
+        .url
+        .unwrap_or_else(|| format!("/ipfs/{}", storage_result.id)))
+}
+
 /// Resolve image path relative to base path
 fn resolve_image_path(img_path: &str, base_path: Option<&Path>) -> Result<PathBuf> {
     let path = Path::new(img_path);
@@ -77,7 +77,7 @@ fn optimize_directory_recursive(
 }
 
 /// Check if a file is an image based on extension
-fn is_image_file(path: &Path) -> bool {
+pub fn is_image_file(path: &Path) -> bool {
     match path.extension().and_then(|s| s.to_str()) {
         Some(ext) => matches!(
             ext.to_lowercase().as_str(),
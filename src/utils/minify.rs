@@ -94,7 +94,7 @@ fn minify_directory_recursive(
 }
 
 /// Minify a single file
-fn minify_file(path: &Path, config: &MinifyConfig) -> Result<i64> {
+pub fn minify_file(path: &Path, config: &MinifyConfig) -> Result<i64> {
     let content = fs::read_to_string(path)?;
     let original_size = content.len() as i64;
 
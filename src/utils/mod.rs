@@ -1,3 +1,4 @@
+pub mod diff;
 pub mod excerpt;
 pub mod image;
 pub mod image_optimize;
@@ -5,9 +6,10 @@ pub mod minify;
 pub mod reading_time;
 pub mod toc;
 
+pub use diff::unified_diff;
 pub use excerpt::{generate_excerpt, generate_formatted_excerpt};
-pub use image::process_images_in_markdown;
-pub use image_optimize::{optimize_images_in_directory, ImageOptimizationConfig};
-pub use minify::{minify_directory, MinifyConfig};
+pub use image::{process_images_in_markdown, process_uploaded_images, upload_image};
+pub use image_optimize::{is_image_file, optimize_image, ImageOptimizationConfig};
+pub use minify::{minify_file, MinifyConfig};
 pub use reading_time::calculate_reading_time;
 pub use toc::generate_toc;
@@ -0,0 +1,279 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::activitypub::objects::{self, id_of, PostReference, ACTIVITY_STREAMS, CONTENT_TYPE};
+use crate::activitypub::Federation;
+use crate::comments::NewComment;
+use crate::models::BlogPost;
+use crate::web::AppState;
+use crate::webmention::receive::text_of;
+
+/// Longest reply kept as a comment, like the comment form allows
+const MAX_REPLY_CHARS: usize = 5000;
+
+fn activity_json(value: Value) -> Response {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], value.to_string()).into_response()
+}
+
+fn federation(state: &AppState) -> Result<&Federation, StatusCode> {
+    state.blog_manager.federation().ok_or(StatusCode::NOT_FOUND)
+}
+
+fn internal(e: anyhow::Error) -> StatusCode {
+    warn!("ActivityPub request failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[derive(Deserialize)]
+pub struct WebFingerQuery {
+    resource: String,
+}
+
+/// `GET /.well-known/webfinger?resource=acct:user@host`
+pub async fn webfinger(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WebFingerQuery>,
+) -> Result<Response, StatusCode> {
+    let actor = federation(&state)?.actor();
+    if !query.resource.eq_ignore_ascii_case(&actor.acct()) && query.resource != actor.id() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "application/jrd+json")],
+        actor.webfinger().to_string(),
+    )
+        .into_response())
+}
+
+/// `GET /activitypub/actor`
+pub async fn actor(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    Ok(activity_json(federation(&state)?.actor_json()))
+}
+
+/// `GET /activitypub/outbox`: a `Create` for every published post
+pub async fn outbox(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    let actor = federation(&state)?.actor();
+    let posts = state
+        .blog_manager
+        .list_posts(true)
+        .await
+        .map_err(internal)?;
+    let items = posts.iter().map(|(_, post)| actor.create(post)).collect();
+    Ok(activity_json(objects::ordered_collection(
+        &actor.outbox(),
+        items,
+    )))
+}
+
+/// `GET /activitypub/followers`: only the count, followers stay private
+pub async fn followers(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    let federation = federation(&state)?;
+    let count = federation.followers().list().await.map_err(internal)?.len();
+    Ok(activity_json(json!({
+        "@context": ACTIVITY_STREAMS,
+        "id": federation.actor().followers(),
+        "type": "OrderedCollection",
+        "totalItems": count,
+    })))
+}
+
+/// `GET /activitypub/posts/:id`: the Article of a published post
+pub async fn article(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let actor = federation(&state)?.actor();
+    let post = find_post(&state, PostReference::Id(&id))
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut article = actor.article(&post);
+    article["@context"] = Value::String(ACTIVITY_STREAMS.to_string());
+    Ok(activity_json(article))
+}
+
+async fn find_post(
+    state: &AppState,
+    reference: PostReference<'_>,
+) -> anyhow::Result<Option<BlogPost>> {
+    let posts = state.blog_manager.list_posts(true).await?;
+    Ok(posts
+        .into_iter()
+        .map(|(_, post)| post)
+        .filter(|post| match reference {
+            PostReference::Id(id) => post.id == id,
+            PostReference::Slug(slug) => post.slug == slug,
+        })
+        .max_by_key(|post| post.created_at))
+}
+
+/// `POST /activitypub/inbox`: follows, unfollows, replies and deletions,
+/// accepted only with a valid HTTP signature
+pub async fn inbox(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+    let federation = federation(&state)?;
+    let activity: Value = serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let actor = match federation
+        .verify_request("POST", path, &headers, &body)
+        .await
+    {
+        Ok(actor) => actor,
+        Err(e) => {
+            info!("Inbox request rejected: {}", e);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    };
+    let actor_id = actor["id"].as_str().unwrap_or_default();
+    if actor_id.is_empty() || id_of(&activity["actor"]) != Some(actor_id) {
+        info!("Inbox activity not sent by its signer: {}", actor_id);
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let result = match activity["type"].as_str().unwrap_or_default() {
+        "Follow" => follow(federation, &actor, &activity).await,
+        "Undo" if activity["object"]["type"] == "Follow" => {
+            info!("Unfollowed by {}", actor_id);
+            federation.followers().remove(actor_id).await.map(|_| ())
+        }
+        "Create" => reply(&state, federation, &actor, &activity["object"]).await,
+        "Delete" => delete(&state, federation, actor_id, &activity["object"]).await,
+        _ => Ok(()),
+    };
+    result.map_err(internal)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn follow(federation: &Federation, actor: &Value, activity: &Value) -> anyhow::Result<()> {
+    if id_of(&activity["object"]) != Some(federation.actor().id().as_str()) {
+        return Ok(());
+    }
+    let actor_id = actor["id"].as_str().unwrap_or_default();
+    let inbox = actor["inbox"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Follower has no inbox"))?;
+    let shared_inbox = actor["endpoints"]["sharedInbox"].as_str();
+    federation
+        .followers()
+        .add(actor_id, inbox, shared_inbox)
+        .await?;
+    info!("Followed by {}", actor_id);
+
+    let accept = federation.actor().accept(activity);
+    let federation = federation.clone();
+    let inbox = inbox.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = federation.send(&inbox, &accept).await {
+            warn!("Failed to accept follow from {}: {}", inbox, e);
+        }
+    });
+    Ok(())
+}
+
+/// Whether two URLs are on the same host
+fn same_host(a: &str, b: &str) -> bool {
+    let host = |url: &str| {
+        reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+    };
+    host(a).is_some() && host(a) == host(b)
+}
+
+/// Store a reply to one of our posts as a comment
+async fn reply(
+    state: &AppState,
+    federation: &Federation,
+    actor: &Value,
+    note: &Value,
+) -> anyhow::Result<()> {
+    let Some(in_reply_to) = id_of(&note["inReplyTo"]) else {
+        return Ok(());
+    };
+    let Some(reference) = federation.actor().post_reference(in_reply_to) else {
+        return Ok(());
+    };
+    let Some(post) = find_post(state, reference).await? else {
+        return Ok(());
+    };
+
+    let actor_id = actor["id"].as_str().unwrap_or_default();
+    let Some(note_id) = note["id"].as_str().filter(|id| same_host(id, actor_id)) else {
+        return Ok(());
+    };
+    if state.comments.get_remote(note_id).await?.is_some() {
+        return Ok(());
+    }
+    let Some(content) = note["content"].as_str().and_then(text_of) else {
+        return Ok(());
+    };
+
+    let author_name = actor["name"]
+        .as_str()
+        .filter(|name| !name.trim().is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            let username = actor["preferredUsername"].as_str()?;
+            let host = reqwest::Url::parse(actor_id).ok()?.host_str()?.to_string();
+            Some(format!("@{}@{}", username, host))
+        })
+        .unwrap_or_else(|| actor_id.to_string());
+    let new = NewComment {
+        post_id: post.id.clone(),
+        parent_id: None,
+        author_name,
+        author_email: None,
+        author_url: Some(actor["url"].as_str().unwrap_or(actor_id).to_string()),
+        content: content.chars().take(MAX_REPLY_CHARS).collect(),
+        remote_id: Some(note_id.to_string()),
+    };
+
+    let approved = !state.site_config.comments.moderation;
+    let comment = state.comments.add(&new, approved).await?;
+    info!(
+        "Reply {} on {} from {} ({})",
+        comment.id, post.slug, actor_id, comment.status
+    );
+    if approved {
+        let _ = state.reload_tx.send(());
+    }
+    Ok(())
+}
+
+/// A deleted account stops following; a deleted note loses its comment
+async fn delete(
+    state: &AppState,
+    federation: &Federation,
+    actor_id: &str,
+    object: &Value,
+) -> anyhow::Result<()> {
+    let Some(object_id) = id_of(object) else {
+        return Ok(());
+    };
+    if object_id == actor_id {
+        federation.followers().remove(actor_id).await?;
+        return Ok(());
+    }
+    if !same_host(object_id, actor_id) {
+        return Ok(());
+    }
+    if let Some(comment) = state.comments.get_remote(object_id).await? {
+        state.comments.delete(&comment.id).await?;
+        info!("Reply {} deleted by {}", comment.id, actor_id);
+    }
+    Ok(())
+}
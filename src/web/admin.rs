@@ -0,0 +1,593 @@
+use axum::{
+    async_trait,
+    extract::{Form, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Json, Redirect, Response},
+};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+use super::handlers_helpers::{create_base_context, markdown_to_html, render_template};
+use super::AppState;
+use crate::auth::generate_secret;
+use crate::models::BlogPost;
+
+const SESSION_COOKIE: &str = "kpgb_admin";
+
+/// How long an admin login lasts
+const SESSION_TTL_HOURS: i64 = 12;
+
+/// Logged in admin sessions. They live in memory, so restarting the server
+/// logs everyone out.
+pub struct AdminSessions {
+    sessions: RwLock<HashMap<String, DateTime<Utc>>>,
+    ttl: Duration,
+}
+
+impl Default for AdminSessions {
+    fn default() -> Self {
+        Self::new(Duration::hours(SESSION_TTL_HOURS))
+    }
+}
+
+impl AdminSessions {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Start a session, returning its ID for the cookie
+    pub fn create(&self) -> String {
+        let id = generate_secret();
+        let mut sessions = self.sessions.write().unwrap();
+        let now = Utc::now();
+        sessions.retain(|_, expires| *expires > now);
+        sessions.insert(id.clone(), now + self.ttl);
+        id
+    }
+
+    pub fn is_valid(&self, id: &str) -> bool {
+        self.sessions
+            .read()
+            .unwrap()
+            .get(id)
+            .is_some_and(|expires| *expires > Utc::now())
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.sessions.write().unwrap().remove(id);
+    }
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == SESSION_COOKIE).then_some(value)
+        })
+}
+
+/// A request from a logged in admin. Anyone else is sent to the login page.
+pub struct AdminUser;
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AdminUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        match session_id(&parts.headers) {
+            Some(id) if state.admin_sessions.is_valid(id) => Ok(Self),
+            _ => Err(Redirect::to("/admin/login").into_response()),
+        }
+    }
+}
+
+pub async fn admin_index() -> Redirect {
+    Redirect::to("/admin/posts")
+}
+
+pub async fn login_page(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
+    let context = create_base_context(&state.site_config, "登录");
+    let html = render_template(&state.templates, "admin/login.html", &context)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    token: String,
+}
+
+/// Log in with an API token from `kpgb token create`
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<LoginForm>,
+) -> Result<Response, StatusCode> {
+    let token = state
+        .tokens
+        .verify(form.token.trim())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let Some(token) = token else {
+        let mut context = create_base_context(&state.site_config, "登录");
+        context.insert("error", "Token 无效或已吊销");
+        let html = render_template(&state.templates, "admin/login.html", &context)?;
+        return Ok((StatusCode::UNAUTHORIZED, Html(html)).into_response());
+    };
+
+    info!("Admin login with token {}", token.name);
+    let session = state.admin_sessions.create();
+    let cookie = format!(
+        "{}={}; Path=/admin; HttpOnly; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE,
+        session,
+        SESSION_TTL_HOURS * 3600
+    );
+    let mut response = Redirect::to("/admin/posts").into_response();
+    response.headers_mut().insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&cookie).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    );
+    Ok(response)
+}
+
+pub async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Some(id) = session_id(&headers) {
+        state.admin_sessions.remove(id);
+    }
+    let mut response = Redirect::to("/admin/login").into_response();
+    let cookie = format!(
+        "{}=; Path=/admin; HttpOnly; SameSite=Strict; Max-Age=0",
+        SESSION_COOKIE
+    );
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().insert(header::SET_COOKIE, value);
+    }
+    response
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    status: Option<String>,
+}
+
+#[derive(Serialize)]
+struct StatusFilter {
+    key: &'static str,
+    label: &'static str,
+    count: usize,
+    active: bool,
+}
+
+fn matches_status(post: &BlogPost, status: &str) -> bool {
+    match status {
+        "published" => post.published,
+        "draft" => !post.published,
+        _ => true,
+    }
+}
+
+pub async fn posts(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let status = query.status.unwrap_or_else(|| "all".to_string());
+    let posts = state
+        .blog_manager
+        .list_posts(false)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let filters: Vec<StatusFilter> = [("all", "全部"), ("draft", "草稿"), ("published", "已发布")]
+        .into_iter()
+        .map(|(key, label)| StatusFilter {
+            key,
+            label,
+            count: posts.iter().filter(|(_, p)| matches_status(p, key)).count(),
+            active: key == status,
+        })
+        .collect();
+
+    let listed: Vec<_> = posts
+        .iter()
+        .filter(|(_, post)| matches_status(post, &status))
+        .map(|(storage_id, post)| {
+            let mut value = serde_json::to_value(post).unwrap();
+            value["storage_id"] = serde_json::Value::String(storage_id.clone());
+            value
+        })
+        .collect();
+
+    let mut context = create_base_context(&state.site_config, "文章管理");
+    context.insert("posts", &listed);
+    context.insert("filters", &filters);
+    context.insert("status", &status);
+    let html = render_template(&state.templates, "admin/posts.html", &context)?;
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct EditQuery {
+    saved: Option<bool>,
+}
+
+pub async fn new_post(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Html<String>, StatusCode> {
+    let mut post = BlogPost::new(
+        String::new(),
+        String::new(),
+        state.site_config.author.clone(),
+    );
+    post.slug = String::new();
+    render_editor(&state, None, &post, false).await
+}
+
+pub async fn edit_post(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<EditQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let (storage_id, post) = state
+        .blog_manager
+        .find_post(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    render_editor(
+        &state,
+        Some(&storage_id),
+        &post,
+        query.saved.unwrap_or(false),
+    )
+    .await
+}
+
+async fn render_editor(
+    state: &AppState,
+    storage_id: Option<&str>,
+    post: &BlogPost,
+    saved: bool,
+) -> Result<Html<String>, StatusCode> {
+    let tags = state
+        .blog_manager
+        .get_all_tags()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let categories: BTreeSet<String> = state
+        .blog_manager
+        .list_posts(false)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter_map(|(_, p)| p.category)
+        .collect();
+
+    let title = if storage_id.is_some() {
+        "编辑文章"
+    } else {
+        "新建文章"
+    };
+    let mut context = create_base_context(&state.site_config, title);
+    context.insert("post", post);
+    context.insert(
+        "publish_at",
+        &post
+            .publish_at
+            .map(|at| {
+                at.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%dT%H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default(),
+    );
+    context.insert("storage_id", &storage_id);
+    context.insert("preview_html", &markdown_to_html(&post.content));
+    context.insert("all_tags", &tags);
+    context.insert("all_categories", &categories);
+    context.insert("saved", &saved);
+    let html = render_template(&state.templates, "admin/edit.html", &context)?;
+    Ok(Html(html))
+}
+
+/// The editor form. Tags are comma separated; unchecked boxes aren't sent.
+#[derive(Deserialize)]
+pub struct PostForm {
+    title: String,
+    #[serde(default)]
+    slug: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    excerpt: String,
+    #[serde(default)]
+    lang: String,
+    #[serde(default)]
+    translation_of: String,
+    published: Option<String>,
+    /// Local time to publish at, as sent by `<input type="datetime-local">`
+    #[serde(default)]
+    publish_at: String,
+    #[serde(default)]
+    content: String,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+impl PostForm {
+    fn apply_to(&self, post: &mut BlogPost) -> anyhow::Result<()> {
+        post.title = self.title.trim().to_string();
+        if let Some(author) = non_empty(&self.author) {
+            post.author = author;
+        }
+        post.slug =
+            BlogPost::generate_slug(non_empty(&self.slug).as_deref().unwrap_or(&post.title));
+        post.tags.clear();
+        for tag in self.tags.split(',').filter_map(non_empty) {
+            if !post.tags.contains(&tag) {
+                post.tags.push(tag);
+            }
+        }
+        post.category = non_empty(&self.category);
+        post.excerpt = non_empty(&self.excerpt)
+            .or_else(|| Some(crate::utils::generate_excerpt(&self.content, 50)));
+        post.lang = non_empty(&self.lang);
+        post.translation_of = non_empty(&self.translation_of);
+        post.published = self.published.is_some();
+        post.publish_at = non_empty(&self.publish_at)
+            .map(|at| crate::frontmatter::parse_date(&at))
+            .transpose()?;
+        if post.content != self.content {
+            post.update_content(self.content.clone());
+        }
+        Ok(())
+    }
+}
+
+pub async fn create_post(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<PostForm>,
+) -> Result<Redirect, StatusCode> {
+    if form.title.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let mut post = BlogPost::new(
+        String::new(),
+        String::new(),
+        state.site_config.author.clone(),
+    );
+    form.apply_to(&mut post)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let storage_id = blog_manager
+        .create_post(post)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let _ = state.reload_tx.send(());
+
+    Ok(Redirect::to(&format!(
+        "/admin/posts/{}?saved=true",
+        storage_id
+    )))
+}
+
+pub async fn update_post(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Form(form): Form<PostForm>,
+) -> Result<Redirect, StatusCode> {
+    if form.title.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let (storage_id, mut post) = blog_manager
+        .find_post(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    post.storage_id = Some(storage_id);
+    form.apply_to(&mut post)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let storage_id = blog_manager
+        .update_post(&post)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let _ = state.reload_tx.send(());
+
+    Ok(Redirect::to(&format!(
+        "/admin/posts/{}?saved=true",
+        storage_id
+    )))
+}
+
+pub async fn delete_post(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Redirect, StatusCode> {
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let (_, post) = blog_manager
+        .find_post(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    blog_manager
+        .delete_post(&post.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let _ = state.reload_tx.send(());
+
+    Ok(Redirect::to("/admin/posts"))
+}
+
+#[derive(Deserialize)]
+pub struct PreviewRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+pub struct PreviewResponse {
+    html: String,
+}
+
+/// Render markdown the way post pages do, for the editor's live preview
+pub async fn preview(_admin: AdminUser, Json(req): Json<PreviewRequest>) -> Json<PreviewResponse> {
+    Json(PreviewResponse {
+        html: markdown_to_html(&req.content),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct UploadRequest {
+    name: String,
+    /// Base64 encoded file contents
+    data: String,
+}
+
+#[derive(Serialize)]
+pub struct UploadResponse {
+    url: String,
+    markdown: String,
+}
+
+/// Store an image in the configured storage backend
+pub async fn upload(
+    _admin: AdminUser,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UploadRequest>,
+) -> Result<Json<UploadResponse>, StatusCode> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(req.data.as_bytes())
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let name = std::path::Path::new(&req.name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("image");
+
+    let url = crate::utils::upload_image(&data, name, state.blog_manager.storage_manager())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let alt = std::path::Path::new(name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name);
+
+    Ok(Json(UploadResponse {
+        markdown: format!("![{}]({})", alt, url),
+        url,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{stored_post, TempDir};
+    use crate::web::test_support::{send, state};
+    use axum::body::Body;
+    use axum::http::Request;
+
+    #[test]
+    fn test_sessions() {
+        let sessions = AdminSessions::default();
+        let id = sessions.create();
+        assert!(sessions.is_valid(&id));
+        assert!(!sessions.is_valid("other"));
+        sessions.remove(&id);
+        assert!(!sessions.is_valid(&id));
+
+        let expired = AdminSessions::new(Duration::zero());
+        let id = expired.create();
+        assert!(!expired.is_valid(&id));
+    }
+
+    #[test]
+    fn test_session_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; kpgb_admin=abc123; other=1"),
+        );
+        assert_eq!(session_id(&headers), Some("abc123"));
+        assert_eq!(session_id(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_edit_post() {
+        let dir = TempDir::new("admin");
+        let state = state(&dir).await;
+        let post = BlogPost::new("Draft".to_string(), "Body".to_string(), "Me".to_string());
+        let storage_id = state
+            .blog_manager
+            .clone()
+            .create_post(post.clone())
+            .await
+            .unwrap();
+        let session = state.admin_sessions.create();
+
+        let form =
+            "title=Draft&slug=renamed&published=true&publish_at=2030-01-02T09:30&content=Body";
+        let request = Request::post(format!("/admin/posts/{}", storage_id))
+            .header(header::COOKIE, format!("{}={}", SESSION_COOKIE, session))
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+        let (status, _) = send(&state, request).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        let stored = stored_post(&state.blog_manager, &post.id).await;
+        assert_eq!(stored.slug, "renamed");
+        assert!(stored.published);
+        assert_eq!(
+            stored.publish_at,
+            Some(crate::frontmatter::parse_date("2030-01-02 09:30").unwrap())
+        );
+
+        // The editor shows the schedule again
+        let (storage_id, _) = state
+            .blog_manager
+            .find_post_by_slug("renamed")
+            .await
+            .unwrap()
+            .unwrap();
+        let request = Request::get(format!("/admin/posts/{}", storage_id))
+            .header(header::COOKIE, format!("{}={}", SESSION_COOKIE, session))
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#"value="2030-01-02T09:30""#));
+    }
+}
@@ -49,7 +49,8 @@ pub async fn get_post(
 ) -> Result<Json<ApiResponse<serde_json::Value>>, StatusCode> {
     let result = state.blog_manager.get_post(&id).await.map(|post| {
         let mut post_json = serde_json::to_value(&post).unwrap();
-        post_json["storage_id"] = serde_json::Value::String(id);
+        // Old IDs of edited posts resolve to the post's current storage ID
+        post_json["storage_id"] = serde_json::Value::String(post.storage_id.unwrap_or(id));
         post_json
     });
     Ok(handle_result(result))
@@ -0,0 +1,45 @@
+use crate::models::PostRevision;
+use crate::web::api::ApiResponse;
+use crate::web::api_helpers::handle_result;
+use crate::web::AppState;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct RevisionSummary {
+    revision: i64,
+    title: String,
+    content_hash: String,
+    storage_id: String,
+    created_at: String,
+}
+
+impl From<PostRevision> for RevisionSummary {
+    fn from(revision: PostRevision) -> Self {
+        Self {
+            revision: revision.revision,
+            title: revision.title,
+            content_hash: revision.content_hash,
+            storage_id: revision.storage_id,
+            created_at: revision.created_at.to_rfc3339(),
+        }
+    }
+}
+
+pub async fn list_revisions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<RevisionSummary>>>, StatusCode> {
+    let result = match state.blog_manager.get_post(&id).await {
+        Ok(post) => state.blog_manager.list_revisions(&post.id).await,
+        Err(e) => Err(e),
+    }
+    .map(|revisions| revisions.into_iter().map(RevisionSummary::from).collect());
+
+    Ok(handle_result(result))
+}
@@ -0,0 +1,373 @@
+use crate::auth::ApiToken;
+use crate::blog::BlogManager;
+use crate::frontmatter;
+use crate::models::BlogPost;
+use crate::web::api_helpers::{error_response, success_response};
+use crate::web::AppState;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+/// The API token a write request authenticated with
+pub struct BearerToken(pub ApiToken);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for BearerToken {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let secret = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        let Some(secret) = secret else {
+            return Err(unauthorized("Missing bearer token"));
+        };
+
+        match state.tokens.verify(secret).await {
+            Ok(Some(token)) => Ok(Self(token)),
+            Ok(None) => Err(unauthorized("Invalid or revoked token")),
+            Err(e) => Err(reject(StatusCode::INTERNAL_SERVER_ERROR, e)),
+        }
+    }
+}
+
+/// Fields of a post sent to the write API. Everything is optional on update;
+/// on create `content` is required, and may carry frontmatter like the
+/// files `kpgb new` reads. Fields set here override the frontmatter.
+#[derive(Deserialize, Default)]
+pub struct PostInput {
+    title: Option<String>,
+    content: Option<String>,
+    author: Option<String>,
+    tags: Option<Vec<String>>,
+    category: Option<String>,
+    excerpt: Option<String>,
+    slug: Option<String>,
+    lang: Option<String>,
+    translation_of: Option<String>,
+    published: Option<bool>,
+    /// Base64 encoded images, keyed by the file name the content links to
+    #[serde(default)]
+    images: HashMap<String, String>,
+}
+
+impl PostInput {
+    fn apply_to(&self, post: &mut BlogPost) {
+        if let Some(title) = &self.title {
+            post.title = title.clone();
+        }
+        if let Some(author) = &self.author {
+            post.author = author.clone();
+        }
+        if let Some(tags) = &self.tags {
+            post.tags = tags.clone();
+        }
+        if let Some(category) = &self.category {
+            post.category = Some(category.clone()).filter(|c| !c.is_empty());
+        }
+        if let Some(excerpt) = &self.excerpt {
+            post.excerpt = Some(excerpt.clone());
+        }
+        if let Some(slug) = &self.slug {
+            post.slug = slug.clone();
+        }
+        if let Some(lang) = &self.lang {
+            post.lang = Some(lang.clone()).filter(|l| !l.is_empty());
+        }
+        if let Some(translation_of) = &self.translation_of {
+            post.translation_of = Some(translation_of.clone()).filter(|s| !s.is_empty());
+        }
+        if let Some(published) = self.published {
+            post.published = published;
+        }
+    }
+
+    fn decode_images(&self) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+        self.images
+            .iter()
+            .map(|(name, data)| {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .map_err(|e| anyhow::anyhow!("Image {} is not valid base64: {}", name, e))?;
+                Ok((name.trim_start_matches("./").to_string(), bytes))
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct PublishInput {
+    /// Schedule instead of publishing now, in any format frontmatter dates take
+    at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PostWriteResult {
+    id: String,
+    storage_id: String,
+    slug: String,
+    title: String,
+    published: bool,
+    publish_at: Option<String>,
+    /// Images uploaded with the request and the URLs they were stored under
+    images: HashMap<String, String>,
+}
+
+impl PostWriteResult {
+    fn new(storage_id: String, post: &BlogPost) -> Self {
+        Self {
+            id: post.id.clone(),
+            storage_id,
+            slug: post.slug.clone(),
+            title: post.title.clone(),
+            published: post.published,
+            publish_at: post.publish_at.map(|at| at.to_rfc3339()),
+            images: HashMap::new(),
+        }
+    }
+}
+
+type WriteResult = Result<Response, Response>;
+
+pub async fn create_post(
+    State(state): State<Arc<AppState>>,
+    BearerToken(token): BearerToken,
+    Json(input): Json<PostInput>,
+) -> WriteResult {
+    let Some(content) = input.content.as_deref() else {
+        return Err(reject(StatusCode::BAD_REQUEST, "content is required"));
+    };
+    let (frontmatter, content) =
+        frontmatter::parse_frontmatter(content).map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+
+    let title = input
+        .title
+        .clone()
+        .or_else(|| frontmatter.as_ref().map(|fm| fm.title.clone()))
+        .filter(|title| !title.trim().is_empty())
+        .ok_or_else(|| reject(StatusCode::BAD_REQUEST, "title is required"))?;
+    let author = input
+        .author
+        .clone()
+        .or_else(|| frontmatter.as_ref().map(|fm| fm.author.clone()))
+        .filter(|author| !author.is_empty())
+        .unwrap_or_else(|| "Anonymous".to_string());
+
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+
+    let (content, image_map) = upload_images(&blog_manager, &input, &content).await?;
+
+    let mut post = BlogPost::new(title, content, author);
+    if let Some(fm) = &frontmatter {
+        fm.apply_to(&mut post)
+            .map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+    }
+    input.apply_to(&mut post);
+    post.slug = BlogPost::generate_slug(&post.slug);
+    if post.excerpt.is_none() {
+        post.excerpt = Some(crate::utils::generate_excerpt(&post.content, 50));
+    }
+
+    let storage_id = blog_manager
+        .create_post(post.clone())
+        .await
+        .map_err(internal)?;
+    info!("Post {} created with token {}", post.slug, token.name);
+    let _ = state.reload_tx.send(());
+
+    let mut result = PostWriteResult::new(storage_id, &post);
+    result.images = image_map;
+    Ok((StatusCode::CREATED, success_response(result)).into_response())
+}
+
+pub async fn update_post(
+    State(state): State<Arc<AppState>>,
+    BearerToken(token): BearerToken,
+    Path(id): Path<String>,
+    Json(input): Json<PostInput>,
+) -> WriteResult {
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let (storage_id, mut post) = find_post(&blog_manager, &id).await?;
+    post.storage_id = Some(storage_id);
+
+    let mut image_map = HashMap::new();
+    if let Some(content) = input.content.as_deref() {
+        let (frontmatter, content) = frontmatter::parse_frontmatter(content)
+            .map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+        let (content, uploaded) = upload_images(&blog_manager, &input, &content).await?;
+        image_map = uploaded;
+
+        if let Some(fm) = &frontmatter {
+            post.title = fm.title.clone();
+            if !fm.author.is_empty() {
+                post.author = fm.author.clone();
+            }
+            fm.apply_to(&mut post)
+                .map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+        }
+        post.update_content(content);
+    }
+    input.apply_to(&mut post);
+    post.slug = BlogPost::generate_slug(&post.slug);
+
+    let storage_id = blog_manager.update_post(&post).await.map_err(internal)?;
+    info!("Post {} updated with token {}", post.slug, token.name);
+    let _ = state.reload_tx.send(());
+
+    let mut result = PostWriteResult::new(storage_id, &post);
+    result.images = image_map;
+    Ok(success_response(result).into_response())
+}
+
+pub async fn publish_post(
+    State(state): State<Arc<AppState>>,
+    BearerToken(token): BearerToken,
+    Path(id): Path<String>,
+    input: Option<Json<PublishInput>>,
+) -> WriteResult {
+    let input = input.map(|Json(input)| input).unwrap_or_default();
+
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let (storage_id, mut post) = find_post(&blog_manager, &id).await?;
+
+    match input.at.as_deref() {
+        Some(at) => {
+            let publish_at =
+                frontmatter::parse_date(at).map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+            blog_manager
+                .schedule_post(&storage_id, publish_at)
+                .await
+                .map_err(internal)?;
+            post.publish_at = Some(publish_at);
+        }
+        None => {
+            blog_manager
+                .publish_post(&storage_id)
+                .await
+                .map_err(internal)?;
+            post.published = true;
+        }
+    }
+    info!("Post {} published with token {}", post.slug, token.name);
+    let _ = state.reload_tx.send(());
+
+    Ok(success_response(PostWriteResult::new(storage_id, &post)).into_response())
+}
+
+pub async fn delete_post(
+    State(state): State<Arc<AppState>>,
+    BearerToken(token): BearerToken,
+    Path(id): Path<String>,
+) -> WriteResult {
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+    let (storage_id, post) = find_post(&blog_manager, &id).await?;
+
+    blog_manager.delete_post(&post.id).await.map_err(internal)?;
+    info!("Post {} deleted with token {}", post.slug, token.name);
+    let _ = state.reload_tx.send(());
+
+    Ok(success_response(PostWriteResult::new(storage_id, &post)).into_response())
+}
+
+async fn find_post(blog_manager: &BlogManager, id: &str) -> Result<(String, BlogPost), Response> {
+    blog_manager
+        .find_post(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| {
+            reject(
+                StatusCode::NOT_FOUND,
+                format!("Post not found with ID: {}", id),
+            )
+        })
+}
+
+/// Store the images sent with the request and point the content at them.
+/// Unlike `kpgb new`, nothing is read from the server's disk.
+async fn upload_images(
+    blog_manager: &BlogManager,
+    input: &PostInput,
+    content: &str,
+) -> Result<(String, HashMap<String, String>), Response> {
+    let images = input
+        .decode_images()
+        .map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+    if images.is_empty() {
+        return Ok((content.to_string(), HashMap::new()));
+    }
+    crate::utils::process_uploaded_images(content, &images, blog_manager.storage_manager())
+        .await
+        .map_err(internal)
+}
+
+fn reject(status: StatusCode, error: impl ToString) -> Response {
+    (status, error_response::<()>(error)).into_response()
+}
+
+fn internal(error: anyhow::Error) -> Response {
+    reject(StatusCode::INTERNAL_SERVER_ERROR, error)
+}
+
+fn unauthorized(error: &str) -> Response {
+    let mut response = reject(StatusCode::UNAUTHORIZED, error);
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        header::HeaderValue::from_static("Bearer"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{stored_post, TempDir};
+    use crate::web::test_support::{send, state};
+    use axum::body::Body;
+    use axum::http::Request;
+
+    #[tokio::test]
+    async fn test_update_post() {
+        let dir = TempDir::new("api-write");
+        let state = state(&dir).await;
+        let post = BlogPost::new("Draft".to_string(), "Body".to_string(), "Me".to_string());
+        let storage_id = state
+            .blog_manager
+            .clone()
+            .create_post(post.clone())
+            .await
+            .unwrap();
+        let (_, secret) = state.tokens.create("editor").await.unwrap();
+
+        let request = Request::put(format!("/api/posts/{}", storage_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"published": true, "slug": "Renamed Post"}"#))
+            .unwrap();
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let stored = stored_post(&state.blog_manager, &post.id).await;
+        assert_eq!(stored.slug, "renamed-post");
+        assert!(stored.published);
+        assert_eq!(stored.title, "Draft");
+    }
+}
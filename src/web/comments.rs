@@ -0,0 +1,248 @@
+use axum::{
+    extract::{ConnectInfo, Form, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::comments::NewComment;
+use crate::web::micropub::slug_from_url;
+use crate::web::AppState;
+
+const MAX_NAME_CHARS: usize = 100;
+const MAX_CONTENT_CHARS: usize = 5000;
+
+/// Counts comments per client address over a sliding hour
+pub struct RateLimiter {
+    hits: Mutex<HashMap<String, Vec<DateTime<Utc>>>>,
+    window: Duration,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Duration::hours(1))
+    }
+}
+
+impl RateLimiter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            hits: Mutex::new(HashMap::new()),
+            window,
+        }
+    }
+
+    /// Record a hit for `key`, unless it already had `limit` in the window
+    pub fn allow(&self, key: &str, limit: usize) -> bool {
+        let mut hits = self.hits.lock().unwrap();
+        let since = Utc::now() - self.window;
+        hits.retain(|_, times| {
+            times.retain(|at| *at > since);
+            !times.is_empty()
+        });
+
+        let times = hits.entry(key.to_string()).or_default();
+        if times.len() >= limit {
+            return false;
+        }
+        times.push(Utc::now());
+        true
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommentForm {
+    post_id: String,
+    #[serde(default)]
+    parent_id: String,
+    author_name: String,
+    #[serde(default)]
+    author_email: String,
+    #[serde(default)]
+    author_url: String,
+    content: String,
+    /// Honeypot, hidden from people; bots fill it in
+    #[serde(default)]
+    website: String,
+}
+
+impl CommentForm {
+    fn validate(self) -> Result<NewComment, &'static str> {
+        let optional = |value: String| {
+            let value = value.trim().to_string();
+            (!value.is_empty()).then_some(value)
+        };
+
+        let author_name = self.author_name.trim().to_string();
+        let content = self.content.trim().to_string();
+        if author_name.is_empty() || author_name.chars().count() > MAX_NAME_CHARS {
+            return Err("Name is required and must be at most 100 characters");
+        }
+        if content.is_empty() || content.chars().count() > MAX_CONTENT_CHARS {
+            return Err("Comment is required and must be at most 5000 characters");
+        }
+
+        let author_url = optional(self.author_url);
+        if author_url
+            .as_ref()
+            .is_some_and(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err("Website must be an http(s) URL");
+        }
+        let author_email = optional(self.author_email);
+        if author_email
+            .as_ref()
+            .is_some_and(|email| !email.contains('@'))
+        {
+            return Err("Email address is invalid");
+        }
+
+        Ok(NewComment {
+            post_id: self.post_id,
+            parent_id: optional(self.parent_id),
+            author_name,
+            author_email,
+            author_url,
+            content,
+            remote_id: None,
+        })
+    }
+}
+
+/// The client address, from the proxy headers when behind one
+fn client_key(headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .or_else(|| {
+            headers
+                .get("x-real-ip")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(|value| value.trim().to_string())
+        .or_else(|| peer.map(|addr| addr.ip().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Back to the post page the form was sent from: the static page named by
+/// the `Referer`, or the post on this server
+fn post_page(headers: &HeaderMap, slug: &str, status: &str) -> Redirect {
+    let page = headers
+        .get(header::REFERER)
+        .and_then(|value| value.to_str().ok())
+        .map(|referer| referer.split(['?', '#']).next().unwrap_or(referer))
+        .filter(|referer| slug_from_url(referer) == Some(slug))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("/posts/{}", slug));
+    Redirect::to(&format!("{}?comment={}#comments", page, status))
+}
+
+/// `POST /comments`: leave a comment from the form under a post
+pub async fn submit(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Form(form): Form<CommentForm>,
+) -> Response {
+    let config = &state.site_config.comments;
+    if !config.enabled {
+        return (StatusCode::NOT_FOUND, "Comments are disabled").into_response();
+    }
+
+    let post = match state.blog_manager.list_posts(true).await {
+        Ok(posts) => posts
+            .into_iter()
+            .map(|(_, post)| post)
+            .find(|post| post.id == form.post_id),
+        Err(e) => {
+            warn!("Failed to list posts: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Some(post) = post else {
+        return (StatusCode::BAD_REQUEST, "Post not found").into_response();
+    };
+
+    // Bots get the same answer as people, so they don't learn to skip it
+    if !form.website.is_empty() {
+        info!("Comment on {} dropped by the honeypot", post.slug);
+        return post_page(&headers, &post.slug, "pending").into_response();
+    }
+
+    let key = client_key(&headers, peer.map(|ConnectInfo(addr)| addr));
+    if !state.comment_limiter.allow(&key, config.rate_limit) {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many comments, please try again later",
+        )
+            .into_response();
+    }
+
+    let new = match form.validate() {
+        Ok(new) => new,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    let approved = !config.moderation;
+    let comment = match state.comments.add(&new, approved).await {
+        Ok(comment) => comment,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    info!(
+        "Comment {} on {} by {} ({})",
+        comment.id, post.slug, comment.author_name, comment.status
+    );
+    if approved {
+        let _ = state.reload_tx.send(());
+    }
+
+    post_page(&headers, &post.slug, &comment.status).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::default();
+        assert!(limiter.allow("1.2.3.4", 2));
+        assert!(limiter.allow("1.2.3.4", 2));
+        assert!(!limiter.allow("1.2.3.4", 2));
+        assert!(limiter.allow("5.6.7.8", 2));
+
+        let expired = RateLimiter::new(Duration::zero());
+        assert!(expired.allow("1.2.3.4", 1));
+        assert!(expired.allow("1.2.3.4", 1));
+    }
+
+    #[test]
+    fn test_validate() {
+        let form = |name: &str, url: &str, content: &str| CommentForm {
+            post_id: "p".to_string(),
+            parent_id: String::new(),
+            author_name: name.to_string(),
+            author_email: String::new(),
+            author_url: url.to_string(),
+            content: content.to_string(),
+            website: String::new(),
+        };
+
+        let new = form(" Ann ", "https://ann.example", " Hi ")
+            .validate()
+            .unwrap();
+        assert_eq!(new.author_name, "Ann");
+        assert_eq!(new.content, "Hi");
+        assert_eq!(new.parent_id, None);
+        assert_eq!(new.author_email, None);
+        assert!(form("", "", "Hi").validate().is_err());
+        assert!(form("Ann", "", " ").validate().is_err());
+        assert!(form("Ann", "javascript:alert(1)", "Hi").validate().is_err());
+    }
+}
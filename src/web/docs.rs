@@ -1,38 +1,36 @@
 use axum::{
-    extract::{Path, Query, State},
-    response::Html,
+    extract::{Path, State},
     http::StatusCode,
+    response::Html,
 };
 use serde::Deserialize;
 use std::sync::Arc;
-use tera::Context;
-use crate::docs::DocsDatabase;
-use super::AppState;
+use tracing::info;
 
-#[derive(Deserialize)]
-pub struct DocsQuery {
-    category: Option<String>,
-}
+use super::api_write::BearerToken;
+use super::handlers_helpers::{create_base_context, markdown_to_html, render_template};
+use super::AppState;
+use crate::docs::tree::{SectionLink, SectionTree};
 
 /// 文档首页 - 显示所有分类
-pub async fn docs_index(
-    State(state): State<Arc<AppState>>,
-) -> Result<Html<String>, StatusCode> {
-    let categories = state.docs_db
+pub async fn docs_index(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
+    let categories = state
+        .docs_db
         .get_all_categories()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let mut context = Context::new();
-    context.insert("site", &state.site_config);
-    context.insert("page_title", "Documentation");
-    context.insert("categories", &categories);
+    // Without any categories the docs landing page is shown instead
+    let template = if categories.is_empty() {
+        "docs.html"
+    } else {
+        "docs_index.html"
+    };
 
-    let html = state
-        .templates
-        .render("docs_index.html", &context)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut context = create_base_context(&state.site_config, "技术文档中心");
+    context.insert("categories", &categories);
 
+    let html = render_template(&state.templates, template, &context)?;
     Ok(Html(html))
 }
 
@@ -42,7 +40,8 @@ pub async fn docs_category(
     Path(category_slug): Path<String>,
 ) -> Result<Html<String>, StatusCode> {
     // 查找分类
-    let categories = state.docs_db
+    let categories = state
+        .docs_db
         .get_all_categories()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -53,23 +52,23 @@ pub async fn docs_category(
         .ok_or(StatusCode::NOT_FOUND)?;
 
     // 获取该分类下的所有章节
-    let sections = state.docs_db
+    let sections = state
+        .docs_db
         .get_sections_by_category(&category.id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let tree = SectionTree::build(sections);
 
-    let mut context = Context::new();
-    context.insert("site", &state.site_config);
-    context.insert("page_title", &format!("{} - Documentation", category.name));
+    let mut context = create_base_context(
+        &state.site_config,
+        &format!("{} - Documentation", category.name),
+    );
     context.insert("category", category);
-    context.insert("sections", &sections);
+    context.insert("sections", tree.sections());
+    context.insert("toc", &tree.toc());
     context.insert("categories", &categories); // 用于侧边栏
 
-    let html = state
-        .templates
-        .render("docs_category.html", &context)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
+    let html = render_template(&state.templates, "docs_category.html", &context)?;
     Ok(Html(html))
 }
 
@@ -79,43 +78,59 @@ pub async fn docs_detail(
     Path(slug): Path<String>,
 ) -> Result<Html<String>, StatusCode> {
     // 获取文档
-    let section = state.docs_db
+    let section = state
+        .docs_db
         .get_section_by_slug(&slug)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
     // 获取所有分类（用于侧边栏）
-    let categories = state.docs_db
+    let categories = state
+        .docs_db
         .get_all_categories()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let category = categories
+        .iter()
+        .find(|c| c.id == section.category_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let sections = state
+        .docs_db
+        .get_sections_by_category(&category.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let tree = SectionTree::build(sections);
 
     // 将 Markdown 转换为 HTML
-    let content_html = super::markdown_to_html(&section.content);
+    let content_html = markdown_to_html(&section.content);
 
-    let mut context = Context::new();
-    context.insert("site", &state.site_config);
-    context.insert("page_title", &format!("{} - Documentation", section.title));
+    let mut context = create_base_context(
+        &state.site_config,
+        &format!("{} - Documentation", section.title),
+    );
     context.insert("section", &section);
     context.insert("content_html", &content_html);
     context.insert("categories", &categories);
+    context.insert("category", category);
+    context.insert("toc", &tree.toc());
+    context.insert("breadcrumbs", &tree.breadcrumbs(&slug));
+    context.insert("prev_section", &tree.prev(&slug).map(SectionLink::from));
+    context.insert("next_section", &tree.next(&slug).map(SectionLink::from));
 
-    let html = state
-        .templates
-        .render("docs_detail.html", &context)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
+    let html = render_template(&state.templates, "docs_detail.html", &context)?;
     Ok(Html(html))
 }
 
 /// API: 获取文档内容（用于翻译）
 pub async fn api_get_doc(
     State(state): State<Arc<AppState>>,
-    Path(slug): Path<String>,
+    Path(id): Path<String>,
 ) -> Result<axum::Json<serde_json::Value>, StatusCode> {
-    let section = state.docs_db
-        .get_section_by_slug(&slug)
+    let section = state
+        .docs_db
+        .get_section(&id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
@@ -125,11 +140,15 @@ pub async fn api_get_doc(
         "title": section.title,
         "content": section.content,
         "source_url": section.source_url,
-        "is_translated": section.is_translated
+        "source_text": section.source_text,
+        "source_hash": section.source_hash,
+        "source_synced_at": section.source_synced_at,
+        "is_translated": section.is_translated,
+        "is_stale": section.is_stale
     })))
 }
 
-/// API: 更新文档翻译
+/// API: 更新文档翻译（同时清除过期标记）
 #[derive(Deserialize)]
 pub struct UpdateDocRequest {
     content: String,
@@ -137,13 +156,103 @@ pub struct UpdateDocRequest {
 
 pub async fn api_update_doc(
     State(state): State<Arc<AppState>>,
+    BearerToken(token): BearerToken,
     Path(id): Path<String>,
     axum::Json(req): axum::Json<UpdateDocRequest>,
 ) -> Result<StatusCode, StatusCode> {
-    state.docs_db
+    let updated = state
+        .docs_db
         .update_section_content(&id, &req.content)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
 
+    info!("Doc section {} updated with token {}", id, token.name);
+    let _ = state.reload_tx.send(());
     Ok(StatusCode::OK)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::TempDir;
+    use crate::docs::{DocCategory, DocSection};
+    use crate::web::test_support::{send, state};
+    use axum::body::Body;
+    use axum::http::{header, Method, Request};
+
+    fn put(id: &str, secret: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder()
+            .method(Method::PUT)
+            .uri(format!("/api/docs/{}", id))
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = secret {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", secret));
+        }
+        request
+            .body(Body::from(r#"{"content": "Übersetzt"}"#))
+            .unwrap()
+    }
+
+    fn get(id: &str) -> Request<Body> {
+        Request::get(format!("/api/docs/{}", id))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_update_doc() {
+        let dir = TempDir::new("docs-api");
+        let state = state(&dir).await;
+        let category = DocCategory {
+            id: "guide".to_string(),
+            name: "Guide".to_string(),
+            slug: "guide".to_string(),
+            description: String::new(),
+            order: 0,
+            icon: None,
+        };
+        state.docs_db.create_category(&category).await.unwrap();
+        let now = chrono::Utc::now();
+        let section = DocSection {
+            id: "section-id".to_string(),
+            category_id: category.id.clone(),
+            title: "Intro".to_string(),
+            slug: "intro".to_string(),
+            content: "Translated".to_string(),
+            parent_id: None,
+            order: 0,
+            created_at: now,
+            updated_at: now,
+            source_url: Some("https://example.com/intro.md".to_string()),
+            is_translated: true,
+            source_text: None,
+            source_hash: None,
+            source_synced_at: None,
+            is_stale: true,
+        };
+        state.docs_db.create_section(&section).await.unwrap();
+        let (_, secret) = state.tokens.create("translator").await.unwrap();
+
+        let (status, _) = send(&state, put(&section.id, None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&state, put(&section.id, Some("kpgb_wrong"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&state, put("missing", Some(&secret))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&state, put(&section.id, Some(&secret))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Read back under the same key that was written
+        let (status, body) = send(&state, get(&section.id)).await;
+        assert_eq!(status, StatusCode::OK);
+        let doc: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(doc["content"], "Übersetzt");
+        assert_eq!(doc["is_stale"], false);
+        let (status, _) = send(&state, get(&section.slug)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
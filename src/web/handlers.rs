@@ -1,12 +1,16 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use serde::Deserialize;
 use std::sync::Arc;
 use tera::Context;
 
+use crate::site::feed::{Feed, FeedFormat};
+use crate::site::generator::sanitize_tag_for_url;
+use crate::site::i18n;
+use crate::site::pagination::{paginate, Pagination};
 use crate::web::handlers_helpers::{markdown_to_html, render_template};
 use crate::web::AppState;
 
@@ -29,20 +33,25 @@ pub async fn index(
     State(state): State<Arc<AppState>>,
     Query(params): Query<PageQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let page = params.page.unwrap_or(1);
-    let posts_per_page = state.site_config.posts_per_page;
+    index_page(State(state), Path(params.page.unwrap_or(1))).await
+}
 
+/// Page of the index at /page/:page, the URL the static site uses
+pub async fn index_page(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<usize>,
+) -> Result<Html<String>, StatusCode> {
     let all_posts = state
         .blog_manager
         .list_posts(true)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let total_pages = all_posts.len().div_ceil(posts_per_page);
-    let start = (page - 1) * posts_per_page;
-    let end = (start + posts_per_page).min(all_posts.len());
+    let pages = paginate(&all_posts, state.site_config.posts_per_page);
+    let total_pages = pages.len();
+    let page_posts = page_of(&pages, page)?;
 
-    let posts: Vec<_> = all_posts[start..end]
+    let posts: Vec<_> = page_posts
         .iter()
         .map(|(id, post)| {
             let mut post_context = serde_json::to_value(post).unwrap();
@@ -72,19 +81,28 @@ pub async fn index(
     context.insert("site", &site_config);
     context.insert("page_title", "Home");
     context.insert("posts", &posts);
-    context.insert("current_page", &page);
-    context.insert("total_pages", &total_pages);
-    context.insert("has_prev", &(page > 1));
-    context.insert("has_next", &(page < total_pages));
+    // Lets Micropub clients find the endpoint from the home page
+    context.insert("micropub_endpoint", "/micropub");
+    Pagination::new("", page, total_pages).insert_into(&mut context);
 
-    let rendered = render_template("index.html", &context)?;
+    let rendered = render_template(&state.templates, "index.html", &context)?;
     Ok(Html(rendered))
 }
 
+#[derive(Deserialize)]
+pub struct PostQuery {
+    /// Language variant to show, overriding Accept-Language
+    lang: Option<String>,
+    /// Status of the comment just submitted, `pending` or `approved`
+    comment: Option<String>,
+}
+
 pub async fn post(
     State(state): State<Arc<AppState>>,
     Path(slug): Path<String>,
-) -> Result<Html<String>, StatusCode> {
+    Query(params): Query<PostQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
     let posts = state
         .blog_manager
         .list_posts(true)
@@ -92,13 +110,38 @@ pub async fn post(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Find post by slug, preferring ones with tags if there are duplicates
-    let post_data = posts
+    let requested = posts
         .iter()
         .filter(|(_, p)| p.slug == slug)
         .max_by_key(|(_, p)| (p.tags.len(), p.created_at))
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    let (storage_id, post) = post_data;
+    // Pick the language variant; an explicit ?lang= wins over Accept-Language
+    let i18n = &state.site_config.i18n;
+    let variants = i18n::variants(&requested.1, &posts);
+    let langs: Vec<&str> = variants.iter().map(|p| i18n.post_lang(p)).collect();
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let chosen = match (params.lang.as_deref(), accept_language) {
+        (Some(lang), _) => langs.iter().position(|l| i18n::lang_matches(l, lang)),
+        (None, Some(accept)) if variants.len() > 1 => i18n::negotiate(accept, &langs),
+        _ => None,
+    };
+    let (storage_id, post) = match chosen {
+        Some(i) => posts
+            .iter()
+            .find(|(_, p)| p.id == variants[i].id)
+            .unwrap_or(requested),
+        None => requested,
+    };
+
+    let translations = i18n.translations(
+        post,
+        &posts,
+        |p| format!("/posts/{}?lang={}", p.slug, i18n.post_lang(p)),
+        |p| format!("{}/posts/{}.html", state.site_config.base_url, p.slug),
+    );
 
     // Get related posts
     let related_posts = state
@@ -129,6 +172,8 @@ pub async fn post(
     context.insert("site", &site_config);
     context.insert("page_title", &post.title);
     context.insert("post", post);
+    context.insert("page_lang", i18n.post_lang(post));
+    context.insert("translations", &translations);
 
     // Generate HTML content with heading IDs
     let content_html = markdown_to_html(&post.content);
@@ -136,6 +181,25 @@ pub async fn post(
     context.insert("storage_id", storage_id);
     context.insert("related_posts", &related_posts_data);
 
+    let webmentions = state
+        .webmentions
+        .verified_for_post(&post.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    context.insert("webmentions", &webmentions);
+    context.insert("webmention_endpoint", "/webmention");
+
+    if state.site_config.comments.enabled {
+        let comments = state
+            .comments
+            .approved_for_post(&post.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        context.insert("comments", &crate::comments::thread(comments));
+        context.insert("comment_endpoint", "/comments");
+        context.insert("comment_status", &params.comment);
+    }
+
     // Generate table of contents
     let toc = crate::utils::generate_toc(&post.content);
     let toc_html = crate::utils::toc::generate_toc_html(&toc);
@@ -150,23 +214,48 @@ pub async fn post(
         );
     }
 
-    let rendered = render_template("post.html", &context)?;
-    Ok(Html(rendered))
+    let rendered = render_template(&state.templates, "post.html", &context)?;
+    let content_language = HeaderValue::from_str(i18n.post_lang(post))
+        .unwrap_or_else(|_| HeaderValue::from_static("und"));
+    let mut response = (
+        [(header::CONTENT_LANGUAGE, content_language)],
+        Html(rendered),
+    )
+        .into_response();
+    if variants.len() > 1 {
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+    }
+    Ok(response)
+}
+
+pub async fn archive(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PageQuery>,
+) -> Result<Html<String>, StatusCode> {
+    archive_page(State(state), Path(params.page.unwrap_or(1))).await
 }
 
-pub async fn archive(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
+pub async fn archive_page(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<usize>,
+) -> Result<Html<String>, StatusCode> {
     let posts = state
         .blog_manager
         .list_posts(true)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let pages = paginate(&posts, state.site_config.posts_per_page);
+    let total_pages = pages.len();
+
     let mut posts_by_year: std::collections::HashMap<i32, Vec<_>> =
         std::collections::HashMap::new();
 
-    for (id, post) in posts {
+    for (id, post) in page_of(&pages, page)? {
         let year = post.created_at.year();
-        let mut post_context = serde_json::to_value(&post).unwrap();
+        let mut post_context = serde_json::to_value(post).unwrap();
         post_context["url"] = serde_json::Value::String(format!("/posts/{}", post.slug));
         post_context["storage_id"] = serde_json::Value::String(id.clone());
 
@@ -186,8 +275,9 @@ pub async fn archive(State(state): State<Arc<AppState>>) -> Result<Html<String>,
     context.insert("site", &site_config);
     context.insert("page_title", "Archive");
     context.insert("years", &years);
+    Pagination::new("/archive", page, total_pages).insert_into(&mut context);
 
-    let rendered = render_template("archive.html", &context)?;
+    let rendered = render_template(&state.templates, "archive.html", &context)?;
     Ok(Html(rendered))
 }
 
@@ -392,36 +482,59 @@ pub async fn search(
     context.insert("popular_tags", &popular_tags);
     context.insert("recent_posts", &recent_posts);
 
-    let rendered = render_template("search.html", &context)?;
+    let rendered = render_template(&state.templates, "search.html", &context)?;
     Ok(Html(rendered))
 }
 
-pub async fn style_css(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let css_content = match state.site_config.theme.as_str() {
-        "hacker" => include_str!("../../templates/themes/hacker.css"),
-        "minimal" => include_str!("../../templates/themes/minimal.css"),
-        "dark" => include_str!("../../templates/themes/dark.css"),
-        "cyberpunk" => include_str!("../../templates/themes/cyberpunk.css"),
-        _ => include_str!("../../templates/style.css"), // default theme
-    };
+pub async fn style_css(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Theme directory CSS, or the built-in theme
+    let css_content = state
+        .site_config
+        .theme_css()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    (StatusCode::OK, [("content-type", "text/css")], css_content)
+    Ok((StatusCode::OK, [("content-type", "text/css")], css_content))
 }
 
-pub async fn docs(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
-    let mut context = Context::new();
-    // For web server, always use empty base_path
-    let mut site_config = state.site_config.clone();
-    site_config.base_path = None;
-    context.insert("site", &site_config);
-    context.insert("page_title", "技术文档中心");
+/// Serve files from the theme's static directory
+pub async fn theme_asset(
+    State(state): State<Arc<AppState>>,
+    uri: axum::http::Uri,
+) -> Result<impl IntoResponse, StatusCode> {
+    let path = uri.path().trim_start_matches('/');
+    if path.split('/').any(|part| part == "..") {
+        return Err(StatusCode::NOT_FOUND);
+    }
 
-    let rendered = render_template("docs.html", &context)?;
-    Ok(Html(rendered))
+    let file = state
+        .site_config
+        .theme_static_dir()
+        .map(|dir| dir.join(path))
+        .filter(|file| file.is_file())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let data = std::fs::read(&file).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let content_type = match file.extension().and_then(|e| e.to_str()) {
+        Some("css") => "text/css",
+        Some("js") => "application/javascript",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    };
+
+    Ok((StatusCode::OK, [("content-type", content_type)], data))
 }
 
 use chrono::Datelike;
-use rss::{ChannelBuilder, ItemBuilder};
 
 /// Calculate search relevance score for a post
 fn calculate_search_score(post: &crate::models::BlogPost, query: &str) -> f32 {
@@ -545,47 +658,95 @@ fn generate_highlighted_excerpt(content: &str, query: &str, max_length: usize) -
 }
 
 pub async fn rss_feed(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, StatusCode> {
+    site_feed(state, FeedFormat::Rss).await
+}
+
+pub async fn atom_feed(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    site_feed(state, FeedFormat::Atom).await
+}
+
+pub async fn json_feed(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    site_feed(state, FeedFormat::Json).await
+}
+
+async fn site_feed(
+    state: Arc<AppState>,
+    format: FeedFormat,
+) -> Result<impl IntoResponse, StatusCode> {
     let posts = state
         .blog_manager
         .list_posts(true)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let mut items = Vec::new();
+    feed_response(&state, format, &Feed::site(&state.site_config, &posts))
+}
 
-    // Take the 20 most recent posts
-    let recent_posts: Vec<_> = posts.into_iter().take(20).collect();
+/// /tags/:tag/feed.xml, atom.xml or feed.json
+pub async fn tag_feed(
+    State(state): State<Arc<AppState>>,
+    Path((tag, file)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = FeedFormat::from_file_name(&file).ok_or(StatusCode::NOT_FOUND)?;
+    let posts = state
+        .blog_manager
+        .list_posts(true)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    for (storage_id, post) in recent_posts {
-        let link = format!("{}/posts/{}", state.site_config.base_url, post.slug);
-        let content_html = markdown_to_html(&post.content);
+    // The URL holds the tag as the static site writes it
+    let name = posts
+        .iter()
+        .flat_map(|(_, post)| post.tags.iter())
+        .find(|name| **name == tag || sanitize_tag_for_url(name) == tag)
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-        let item = ItemBuilder::default()
-            .title(Some(post.title))
-            .link(Some(link))
-            .description(Some(content_html))
-            .author(Some(post.author))
-            .pub_date(Some(post.created_at.to_rfc2822()))
-            .guid(Some(rss::Guid {
-                value: storage_id,
-                permalink: false,
-            }))
-            .build();
+    let feed = Feed::tag(&state.site_config, name, &tag, &posts);
+    feed_response(&state, format, &feed)
+}
 
-        items.push(item);
-    }
+/// /categories/:category/feed.xml, atom.xml or feed.json
+pub async fn category_feed(
+    State(state): State<Arc<AppState>>,
+    Path((category, file)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = FeedFormat::from_file_name(&file).ok_or(StatusCode::NOT_FOUND)?;
+    let posts = state
+        .blog_manager
+        .list_posts(true)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let name = posts
+        .iter()
+        .filter_map(|(_, post)| post.category.as_ref())
+        .find(|name| **name == category || sanitize_tag_for_url(name) == category)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let feed = Feed::category(&state.site_config, name, &category, &posts);
+    feed_response(&state, format, &feed)
+}
 
-    let channel = ChannelBuilder::default()
-        .title(&state.site_config.title)
-        .link(&state.site_config.base_url)
-        .description(&state.site_config.description)
-        .items(items)
-        .build();
+fn feed_response(
+    state: &AppState,
+    format: FeedFormat,
+    feed: &Feed,
+) -> Result<impl IntoResponse, StatusCode> {
+    let base_url = &state.site_config.base_url;
+    let body = feed
+        .render(&state.site_config, format, |post| {
+            format!("{}/posts/{}", base_url, post.slug)
+        })
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok((
         StatusCode::OK,
-        [("content-type", "application/rss+xml")],
-        channel.to_string(),
+        [("content-type", format.content_type())],
+        body,
     ))
 }
 
@@ -619,7 +780,7 @@ pub async fn tags(State(state): State<Arc<AppState>>) -> Result<Html<String>, St
     context.insert("tags", &tag_data);
     context.insert("title", "Tags");
 
-    let html = render_template("tags.html", &context)?;
+    let html = render_template(&state.templates, "tags.html", &context)?;
 
     Ok(Html(html))
 }
@@ -629,20 +790,23 @@ pub async fn tag_posts(
     Path(tag): Path<String>,
     Query(params): Query<PageQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let page = params.page.unwrap_or(1);
-    let posts_per_page = state.site_config.posts_per_page;
+    tag_posts_page(State(state), Path((tag, params.page.unwrap_or(1)))).await
+}
 
+pub async fn tag_posts_page(
+    State(state): State<Arc<AppState>>,
+    Path((tag, page)): Path<(String, usize)>,
+) -> Result<Html<String>, StatusCode> {
     let all_posts = state
         .blog_manager
         .get_posts_by_tag(&tag, true)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let total_pages = all_posts.len().div_ceil(posts_per_page);
-    let start = (page - 1) * posts_per_page;
-    let end = (start + posts_per_page).min(all_posts.len());
+    let pages = paginate(&all_posts, state.site_config.posts_per_page);
+    let total_pages = pages.len();
 
-    let posts_data: Vec<_> = all_posts[start..end]
+    let posts_data: Vec<_> = page_of(&pages, page)?
         .iter()
         .map(|(id, post)| {
             let mut post_context = serde_json::to_value(post).unwrap();
@@ -673,18 +837,25 @@ pub async fn tag_posts(
     context.insert("posts", &posts_data);
     context.insert("tag", &tag);
     context.insert("title", &format!("Posts tagged '{}'", tag));
+    context.insert("tag_post_count", &all_posts.len());
+    if state.site_config.enable_rss && state.site_config.feed.taxonomy_feeds {
+        context.insert("tag_feed_url", &format!("/tags/{}/feed.xml", tag));
+    }
+    Pagination::new(&format!("/tags/{}", tag), page, total_pages).insert_into(&mut context);
 
-    // Pagination context
-    context.insert("current_page", &page);
-    context.insert("total_pages", &total_pages);
-    context.insert("has_prev", &(page > 1));
-    context.insert("has_next", &(page < total_pages));
-
-    let html = render_template("tag_posts.html", &context)?;
+    let html = render_template(&state.templates, "tag_posts.html", &context)?;
 
     Ok(Html(html))
 }
 
+/// Posts of page `page`, or 404 for a page past the end
+fn page_of<'a, T>(pages: &[&'a [T]], page: usize) -> Result<&'a [T], StatusCode> {
+    page.checked_sub(1)
+        .and_then(|i| pages.get(i))
+        .copied()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 // Redirect handlers for backward compatibility
 pub async fn redirect_archive() -> impl IntoResponse {
     Redirect::permanent("/archive")
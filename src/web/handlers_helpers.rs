@@ -1,4 +1,5 @@
 use crate::models::BlogPost;
+use crate::site::templates::Templates;
 use crate::site::SiteConfig;
 use pulldown_cmark::{html, Options, Parser};
 use serde_json::Value;
@@ -133,32 +134,11 @@ fn add_copy_buttons_to_code_blocks(html: &str) -> String {
 }
 
 pub fn render_template(
+    templates: &Templates,
     template_name: &str,
     context: &Context,
 ) -> Result<String, axum::http::StatusCode> {
-    let mut tera = tera::Tera::default();
-
-    // Add templates
-    tera.add_raw_templates(vec![
-        ("base.html", include_str!("../../templates/base.html")),
-        ("index.html", include_str!("../../templates/index.html")),
-        ("post.html", include_str!("../../templates/post.html")),
-        ("archive.html", include_str!("../../templates/archive.html")),
-        ("search.html", include_str!("../../templates/search.html")),
-        ("tags.html", include_str!("../../templates/tags.html")),
-        (
-            "tag_posts.html",
-            include_str!("../../templates/tag_posts.html"),
-        ),
-        ("docs.html", include_str!("../../templates/docs.html")),
-    ])
-    .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Register filters
-    tera.register_filter("highlight_search", crate::site::filters::highlight_search);
-    tera.register_filter("escape", crate::site::filters::escape_html);
-    tera.register_filter("url_safe_tag", crate::site::filters::url_safe_tag);
-
-    tera.render(template_name, context)
+    templates
+        .render(template_name, context)
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)
 }
@@ -0,0 +1,796 @@
+use axum::{
+    extract::{FromRequest, Multipart, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Form,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::info;
+
+use super::AppState;
+use crate::auth::ApiToken;
+use crate::frontmatter;
+use crate::models::BlogPost;
+
+/// Microformats2 properties: every property holds a list of values
+pub type Properties = BTreeMap<String, Vec<Value>>;
+
+/// A Micropub request, from either a form or a JSON body
+#[derive(Debug, PartialEq)]
+pub enum MicropubAction {
+    Create(Properties),
+    Update {
+        url: String,
+        replace: Properties,
+        add: Properties,
+        delete: Deletions,
+    },
+    Delete {
+        url: String,
+    },
+}
+
+/// The `delete` of an update: whole properties, or single values of them
+#[derive(Debug, Default, PartialEq)]
+pub struct Deletions {
+    pub properties: Vec<String>,
+    pub values: Properties,
+}
+
+/// An error in the format the Micropub spec asks for
+#[derive(Debug)]
+pub struct MicropubError {
+    status: StatusCode,
+    error: &'static str,
+    description: String,
+}
+
+impl MicropubError {
+    fn invalid_request(description: impl ToString) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "invalid_request",
+            description: description.to_string(),
+        }
+    }
+
+    fn unauthorized(description: &str) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            error: "unauthorized",
+            description: description.to_string(),
+        }
+    }
+
+    fn not_found(url: &str) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "invalid_request",
+            description: format!("No post at {}", url),
+        }
+    }
+
+    fn internal(error: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "server_error",
+            description: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for MicropubError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": self.error,
+            "error_description": self.description,
+        }));
+        let mut response = (self.status, body).into_response();
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Parse a form-encoded request. Returns the action and the `access_token`
+/// field, which clients may send instead of the `Authorization` header.
+pub fn parse_form(
+    pairs: Vec<(String, String)>,
+) -> Result<(MicropubAction, Option<String>), MicropubError> {
+    let mut token = None;
+    let mut action = None;
+    let mut url = None;
+    let mut properties = Properties::new();
+
+    for (key, value) in pairs {
+        match key.as_str() {
+            "access_token" => token = Some(value),
+            "action" => action = Some(value),
+            "url" => url = Some(value),
+            "h" if value != "entry" => {
+                return Err(MicropubError::invalid_request(format!(
+                    "Unsupported type h-{}",
+                    value
+                )))
+            }
+            "h" => {}
+            _ => {
+                let key = key.strip_suffix("[]").unwrap_or(&key).to_string();
+                properties
+                    .entry(key)
+                    .or_default()
+                    .push(Value::String(value));
+            }
+        }
+    }
+
+    let action = match action.as_deref() {
+        None => MicropubAction::Create(properties),
+        Some("delete") => MicropubAction::Delete {
+            url: url.ok_or_else(|| MicropubError::invalid_request("Missing url"))?,
+        },
+        Some(other) => {
+            return Err(MicropubError::invalid_request(format!(
+                "Unsupported form action: {}",
+                other
+            )))
+        }
+    };
+    Ok((action, token))
+}
+
+/// Parse a JSON request
+pub fn parse_json(body: &Value) -> Result<MicropubAction, MicropubError> {
+    let url = || {
+        body["url"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| MicropubError::invalid_request("Missing url"))
+    };
+
+    match body["action"].as_str() {
+        None => {
+            let is_entry = body["type"]
+                .as_array()
+                .is_some_and(|types| types.iter().any(|t| t == "h-entry"));
+            if !is_entry {
+                return Err(MicropubError::invalid_request("Only h-entry is supported"));
+            }
+            Ok(MicropubAction::Create(json_properties(
+                &body["properties"],
+            )?))
+        }
+        Some("update") => {
+            let delete = match &body["delete"] {
+                Value::Null => Deletions::default(),
+                Value::Array(names) => Deletions {
+                    properties: names
+                        .iter()
+                        .filter_map(|n| n.as_str().map(str::to_string))
+                        .collect(),
+                    values: Properties::new(),
+                },
+                values @ Value::Object(_) => Deletions {
+                    properties: Vec::new(),
+                    values: json_properties(values)?,
+                },
+                _ => return Err(MicropubError::invalid_request("Invalid delete")),
+            };
+            Ok(MicropubAction::Update {
+                url: url()?,
+                replace: json_properties(&body["replace"])?,
+                add: json_properties(&body["add"])?,
+                delete,
+            })
+        }
+        Some("delete") => Ok(MicropubAction::Delete { url: url()? }),
+        Some(other) => Err(MicropubError::invalid_request(format!(
+            "Unsupported action: {}",
+            other
+        ))),
+    }
+}
+
+fn json_properties(value: &Value) -> Result<Properties, MicropubError> {
+    let object = match value {
+        Value::Null => return Ok(Properties::new()),
+        Value::Object(object) => object,
+        _ => {
+            return Err(MicropubError::invalid_request(
+                "Properties must be an object",
+            ))
+        }
+    };
+
+    object
+        .iter()
+        .map(|(key, values)| match values {
+            Value::Array(values) => Ok((key.clone(), values.clone())),
+            _ => Err(MicropubError::invalid_request(format!(
+                "Property {} must be an array",
+                key
+            ))),
+        })
+        .collect()
+}
+
+/// Text of a value: a plain string, or the `html` or `value` of an object
+fn text_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Object(object) => object
+            .get("html")
+            .or_else(|| object.get("value"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+fn first_text(properties: &Properties, key: &str) -> Option<String> {
+    properties.get(key)?.iter().find_map(text_value)
+}
+
+fn all_text(properties: &Properties, key: &str) -> Vec<String> {
+    properties
+        .get(key)
+        .map(|values| values.iter().filter_map(text_value).collect())
+        .unwrap_or_default()
+}
+
+/// Markdown for a `photo` value, a URL or `{"value": url, "alt": ...}`
+fn photo_markdown(value: &Value) -> Option<String> {
+    let url = match value {
+        Value::String(url) => url.as_str(),
+        Value::Object(object) => object.get("value")?.as_str()?,
+        _ => return None,
+    };
+    let alt = value["alt"].as_str().unwrap_or("");
+    Some(format!("![{}]({})", alt, url))
+}
+
+fn append_photos(post: &mut BlogPost, properties: &Properties) {
+    let photos: Vec<String> = properties
+        .get("photo")
+        .map(|values| values.iter().filter_map(photo_markdown).collect())
+        .unwrap_or_default();
+    if photos.is_empty() {
+        return;
+    }
+
+    let mut content = post.content.trim_end().to_string();
+    for photo in photos {
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str(&photo);
+    }
+    post.update_content(content);
+}
+
+/// Set the post fields the properties carry: `name`, `content`, `summary`,
+/// `category` (the tags), `mp-slug`, `published` and `post-status`.
+/// Properties without a place in `BlogPost` are ignored.
+fn apply_properties(post: &mut BlogPost, properties: &Properties) -> Result<(), MicropubError> {
+    if let Some(name) = first_text(properties, "name") {
+        post.title = name;
+    }
+    if let Some(content) = first_text(properties, "content") {
+        post.update_content(content);
+    }
+    if let Some(summary) = first_text(properties, "summary") {
+        post.excerpt = Some(summary);
+    }
+    if properties.contains_key("category") {
+        post.tags = all_text(properties, "category");
+    }
+    if let Some(slug) = first_text(properties, "mp-slug") {
+        post.slug = BlogPost::generate_slug(&slug);
+    }
+    if let Some(published) = first_text(properties, "published") {
+        post.created_at =
+            frontmatter::parse_date(&published).map_err(MicropubError::invalid_request)?;
+    }
+    if let Some(status) = first_text(properties, "post-status") {
+        post.published = status != "draft";
+    }
+    Ok(())
+}
+
+/// Title for a post without a `name`, such as a note: its first line
+fn note_title(content: &str) -> String {
+    let line = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Note");
+    let title: String = line.chars().take(50).collect();
+    if title.chars().count() < line.chars().count() {
+        format!("{}...", title)
+    } else {
+        title
+    }
+}
+
+/// A new post from the properties of an h-entry. Posts are published unless
+/// `post-status` is `draft`.
+pub fn create_post(properties: &Properties, author: &str) -> Result<BlogPost, MicropubError> {
+    let mut post = BlogPost::new(String::new(), String::new(), author.to_string());
+    post.published = true;
+    apply_properties(&mut post, properties)?;
+    append_photos(&mut post, properties);
+
+    if post.title.is_empty() {
+        post.title = note_title(&post.content);
+    }
+    if !properties.contains_key("mp-slug") {
+        post.slug = BlogPost::generate_slug(&post.title);
+    }
+    if post.excerpt.is_none() {
+        post.excerpt = Some(crate::utils::generate_excerpt(&post.content, 50));
+    }
+    Ok(post)
+}
+
+/// Apply the `replace`, `add` and `delete` parts of an update
+pub fn update_post(
+    post: &mut BlogPost,
+    replace: &Properties,
+    add: &Properties,
+    delete: &Deletions,
+) -> Result<(), MicropubError> {
+    apply_properties(post, replace)?;
+
+    for tag in all_text(add, "category") {
+        if !post.tags.contains(&tag) {
+            post.tags.push(tag);
+        }
+    }
+    append_photos(post, add);
+    let single: Properties = add
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "category" | "photo"))
+        .map(|(key, values)| (key.clone(), values.clone()))
+        .collect();
+    apply_properties(post, &single)?;
+
+    for property in &delete.properties {
+        match property.as_str() {
+            "category" => post.tags.clear(),
+            "summary" => post.excerpt = None,
+            "content" => post.update_content(String::new()),
+            _ => {}
+        }
+    }
+    let removed = all_text(&delete.values, "category");
+    post.tags.retain(|tag| !removed.contains(tag));
+    Ok(())
+}
+
+/// The h-entry of a post, for `q=source`
+pub fn post_properties(post: &BlogPost) -> Properties {
+    let mut properties = Properties::new();
+    properties.insert("name".to_string(), vec![json!(post.title)]);
+    properties.insert("content".to_string(), vec![json!(post.content)]);
+    properties.insert(
+        "category".to_string(),
+        post.tags.iter().map(|tag| json!(tag)).collect(),
+    );
+    properties.insert("mp-slug".to_string(), vec![json!(post.slug)]);
+    properties.insert(
+        "published".to_string(),
+        vec![json!(post.created_at.to_rfc3339())],
+    );
+    let status = if post.published { "published" } else { "draft" };
+    properties.insert("post-status".to_string(), vec![json!(status)]);
+    if let Some(excerpt) = &post.excerpt {
+        properties.insert("summary".to_string(), vec![json!(excerpt)]);
+    }
+    properties
+}
+
+/// Slug of a post URL such as `https://example.com/posts/hello.html`
+pub fn slug_from_url(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, rest) = path.rsplit_once("/posts/")?;
+    let slug = rest.trim_end_matches('/');
+    let slug = slug.strip_suffix(".html").unwrap_or(slug);
+    (!slug.is_empty() && !slug.contains('/')).then_some(slug)
+}
+
+fn post_url(state: &AppState, post: &BlogPost) -> String {
+    format!("{}/posts/{}", state.site_config.base_url, post.slug)
+}
+
+/// Absolute URL of an endpoint on this server, for `q=config`
+fn endpoint_url(headers: &HeaderMap, path: &str) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("http");
+    format!("{}://{}{}", scheme, host, path)
+}
+
+/// Check the token from the `Authorization` header or the request body
+async fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+    body_token: Option<String>,
+) -> Result<ApiToken, MicropubError> {
+    let header_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    let secret = match (header_token, body_token) {
+        (Some(_), Some(_)) => {
+            return Err(MicropubError::invalid_request(
+                "Send the token in the header or the body, not both",
+            ))
+        }
+        (Some(token), None) | (None, Some(token)) => token,
+        (None, None) => return Err(MicropubError::unauthorized("Missing access token")),
+    };
+
+    state
+        .tokens
+        .verify(&secret)
+        .await
+        .map_err(MicropubError::internal)?
+        .ok_or_else(|| MicropubError::unauthorized("Invalid or revoked access token"))
+}
+
+async fn find_by_url(state: &AppState, url: &str) -> Result<(String, BlogPost), MicropubError> {
+    let slug = slug_from_url(url).ok_or_else(|| MicropubError::not_found(url))?;
+    state
+        .blog_manager
+        .find_post_by_slug(slug)
+        .await
+        .map_err(MicropubError::internal)?
+        .ok_or_else(|| MicropubError::not_found(url))
+}
+
+/// `GET /micropub`: the `config`, `source` and `syndicate-to` queries
+pub async fn query(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, MicropubError> {
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    authorize(&state, &headers, param("access_token")).await?;
+
+    match param("q").as_deref() {
+        Some("config") => Ok(Json(json!({
+            "media-endpoint": endpoint_url(&headers, "/micropub/media"),
+            "syndicate-to": [],
+            "post-types": [
+                { "type": "note", "name": "Note" },
+                { "type": "article", "name": "Article" },
+                { "type": "photo", "name": "Photo" },
+            ],
+        }))),
+        Some("syndicate-to") => Ok(Json(json!({ "syndicate-to": [] }))),
+        Some("source") => {
+            let url = param("url").ok_or_else(|| MicropubError::invalid_request("Missing url"))?;
+            let (_, post) = find_by_url(&state, &url).await?;
+            let mut properties = post_properties(&post);
+
+            let wanted: Vec<String> = params
+                .iter()
+                .filter(|(key, _)| key == "properties[]" || key == "properties")
+                .map(|(_, value)| value.clone())
+                .collect();
+            if !wanted.is_empty() {
+                properties.retain(|key, _| wanted.contains(key));
+                return Ok(Json(json!({ "properties": properties })));
+            }
+            Ok(Json(
+                json!({ "type": ["h-entry"], "properties": properties }),
+            ))
+        }
+        Some(other) => Err(MicropubError::invalid_request(format!(
+            "Unsupported query: {}",
+            other
+        ))),
+        None => Err(MicropubError::invalid_request("Missing q")),
+    }
+}
+
+/// `POST /micropub`: create, update or delete a post
+pub async fn micropub(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response, MicropubError> {
+    let headers = request.headers().clone();
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    let (action, body_token) = if content_type.starts_with("application/json") {
+        let Json(body) = Json::<Value>::from_request(request, &state)
+            .await
+            .map_err(|e| MicropubError::invalid_request(e.body_text()))?;
+        (parse_json(&body)?, None)
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        let Form(pairs) = Form::<Vec<(String, String)>>::from_request(request, &state)
+            .await
+            .map_err(|e| MicropubError::invalid_request(e.body_text()))?;
+        parse_form(pairs)?
+    } else {
+        return Err(MicropubError::invalid_request(
+            "Send application/json or application/x-www-form-urlencoded; upload files to the media endpoint",
+        ));
+    };
+    let token = authorize(&state, &headers, body_token).await?;
+
+    let _guard = state.write_lock.lock().await;
+    let mut blog_manager = state.blog_manager.clone();
+
+    let response = match action {
+        MicropubAction::Create(properties) => {
+            let post = create_post(&properties, &state.site_config.author)?;
+            blog_manager
+                .create_post(post.clone())
+                .await
+                .map_err(MicropubError::internal)?;
+            info!("Micropub: created {} with token {}", post.slug, token.name);
+
+            let location = HeaderValue::from_str(&post_url(&state, &post))
+                .map_err(|e| MicropubError::internal(e.into()))?;
+            (StatusCode::CREATED, [(header::LOCATION, location)]).into_response()
+        }
+        MicropubAction::Update {
+            url,
+            replace,
+            add,
+            delete,
+        } => {
+            let (storage_id, mut post) = find_by_url(&state, &url).await?;
+            post.storage_id = Some(storage_id);
+            update_post(&mut post, &replace, &add, &delete)?;
+            blog_manager
+                .update_post(&post)
+                .await
+                .map_err(MicropubError::internal)?;
+            info!("Micropub: updated {} with token {}", post.slug, token.name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        MicropubAction::Delete { url } => {
+            let (_, post) = find_by_url(&state, &url).await?;
+            blog_manager
+                .delete_post(&post.id)
+                .await
+                .map_err(MicropubError::internal)?;
+            info!("Micropub: deleted {} with token {}", post.slug, token.name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+    };
+    let _ = state.reload_tx.send(());
+
+    Ok(response)
+}
+
+/// `POST /micropub/media`: store the `file` part in the storage backend
+pub async fn media(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response, MicropubError> {
+    let mut file = None;
+    let mut body_token = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| MicropubError::invalid_request(e.body_text()))?
+    {
+        match field.name() {
+            Some("file") => {
+                let name = field.file_name().unwrap_or("upload").to_string();
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|e| MicropubError::invalid_request(e.body_text()))?;
+                file = Some((name, data));
+            }
+            Some("access_token") => {
+                body_token = Some(
+                    field
+                        .text()
+                        .await
+                        .map_err(|e| MicropubError::invalid_request(e.body_text()))?,
+                );
+            }
+            _ => {}
+        }
+    }
+    let token = authorize(&state, &headers, body_token).await?;
+
+    let (name, data) = file.ok_or_else(|| MicropubError::invalid_request("Missing file"))?;
+    let name = std::path::Path::new(&name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("upload");
+    let url = crate::utils::upload_image(&data, name, state.blog_manager.storage_manager())
+        .await
+        .map_err(MicropubError::internal)?;
+    info!("Micropub: uploaded {} with token {}", name, token.name);
+
+    let location = HeaderValue::from_str(&url).map_err(|e| MicropubError::internal(e.into()))?;
+    let mut body = Map::new();
+    body.insert("url".to_string(), Value::String(url));
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, location)],
+        Json(Value::Object(body)),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{stored_post, TempDir};
+    use crate::web::test_support::{send, state};
+    use axum::body::Body;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_form() {
+        let (action, token) = parse_form(pairs(&[
+            ("h", "entry"),
+            ("content", "Hello world"),
+            ("category[]", "rust"),
+            ("category[]", "web"),
+            ("mp-slug", "Hello There"),
+            ("access_token", "kpgb_abc"),
+        ]))
+        .unwrap();
+        assert_eq!(token.as_deref(), Some("kpgb_abc"));
+
+        let MicropubAction::Create(properties) = action else {
+            panic!("expected create");
+        };
+        let post = create_post(&properties, "me").unwrap();
+        assert_eq!(post.tags, vec!["rust", "web"]);
+        assert_eq!(post.slug, "hello-there");
+        assert_eq!(post.title, "Hello world");
+        assert!(post.published);
+
+        let (action, _) =
+            parse_form(pairs(&[("action", "delete"), ("url", "https://x/posts/a")])).unwrap();
+        assert_eq!(
+            action,
+            MicropubAction::Delete {
+                url: "https://x/posts/a".to_string()
+            }
+        );
+        assert!(parse_form(pairs(&[("h", "event")])).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_create() {
+        let body = json!({
+            "type": ["h-entry"],
+            "properties": {
+                "name": ["A Title"],
+                "content": [{"html": "<p>Hi</p>"}],
+                "category": ["one"],
+                "post-status": ["draft"],
+                "photo": [{"value": "https://img/x.png", "alt": "cat"}],
+                "published": ["2024-01-02T03:04:05Z"],
+            }
+        });
+        let MicropubAction::Create(properties) = parse_json(&body).unwrap() else {
+            panic!("expected create");
+        };
+        let post = create_post(&properties, "me").unwrap();
+        assert_eq!(post.title, "A Title");
+        assert_eq!(post.slug, "a-title");
+        assert_eq!(post.content, "<p>Hi</p>\n\n![cat](https://img/x.png)");
+        assert!(!post.published);
+        assert_eq!(post.created_at.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+
+        assert!(parse_json(&json!({"type": ["h-card"], "properties": {}})).is_err());
+        assert!(parse_json(&json!({"type": ["h-entry"], "properties": {"name": "x"}})).is_err());
+    }
+
+    #[test]
+    fn test_update() {
+        let body = json!({
+            "action": "update",
+            "url": "https://example.com/posts/hello.html",
+            "replace": {"content": ["New"]},
+            "add": {"category": ["added", "keep"]},
+            "delete": {"category": ["old"]},
+        });
+        let MicropubAction::Update {
+            url,
+            replace,
+            add,
+            delete,
+        } = parse_json(&body).unwrap()
+        else {
+            panic!("expected update");
+        };
+        assert_eq!(slug_from_url(&url), Some("hello"));
+
+        let mut post = BlogPost::new("Hello".to_string(), "Old".to_string(), "me".to_string());
+        post.tags = vec!["old".to_string(), "keep".to_string()];
+        update_post(&mut post, &replace, &add, &delete).unwrap();
+        assert_eq!(post.content, "New");
+        assert_eq!(post.tags, vec!["keep", "added"]);
+
+        let body = json!({"action": "update", "url": "u", "delete": ["category"]});
+        let MicropubAction::Update { delete, .. } = parse_json(&body).unwrap() else {
+            panic!("expected update");
+        };
+        update_post(&mut post, &Properties::new(), &Properties::new(), &delete).unwrap();
+        assert!(post.tags.is_empty());
+    }
+
+    #[test]
+    fn test_slug_from_url() {
+        assert_eq!(slug_from_url("http://localhost/posts/a-b"), Some("a-b"));
+        assert_eq!(
+            slug_from_url("https://x.io/kpgb/posts/a.html?x=1"),
+            Some("a")
+        );
+        assert_eq!(slug_from_url("https://x.io/tags/a"), None);
+        assert_eq!(slug_from_url("https://x.io/posts/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_update_publishes_draft() {
+        let dir = TempDir::new("micropub");
+        let state = state(&dir).await;
+        let mut post = BlogPost::new("Hello".to_string(), "Body".to_string(), "me".to_string());
+        post.slug = "hello".to_string();
+        state
+            .blog_manager
+            .clone()
+            .create_post(post.clone())
+            .await
+            .unwrap();
+        let (_, secret) = state.tokens.create("client").await.unwrap();
+
+        let body = json!({
+            "action": "update",
+            "url": "http://localhost/posts/hello.html",
+            "replace": {"post-status": ["published"], "mp-slug": ["moved"]},
+        });
+        let request = Request::post("/micropub")
+            .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+
+        let stored = stored_post(&state.blog_manager, &post.id).await;
+        assert_eq!(stored.slug, "moved");
+        assert!(stored.published);
+    }
+}
@@ -1,45 +1,169 @@
+pub mod activitypub;
+pub mod admin;
 pub mod api;
 pub mod api_helpers;
+pub mod api_revisions;
 pub mod api_tags;
+pub mod api_write;
+pub mod comments;
+pub mod docs;
 pub mod handlers;
 pub mod handlers_helpers;
+pub mod micropub;
 pub mod server;
+#[cfg(test)]
+pub mod test_support;
+pub mod watch;
+pub mod webmention;
 
+use crate::auth::TokenStore;
 use crate::blog::BlogManager;
+use crate::comments::CommentStore;
+use crate::docs::DocsDatabase;
+use crate::site::templates::Templates;
 use crate::site::SiteConfig;
+use crate::webmention::WebmentionStore;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
 use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
 use tower_http::cors::CorsLayer;
 
 pub struct AppState {
     pub blog_manager: BlogManager,
+    pub docs_db: DocsDatabase,
     pub site_config: SiteConfig,
+    pub templates: Templates,
+    /// Notifies open pages to reload after content or templates change
+    pub reload_tx: broadcast::Sender<()>,
+    /// Bearer tokens accepted by the write API
+    pub tokens: TokenStore,
+    /// Logged in sessions of the `/admin` editor
+    pub admin_sessions: admin::AdminSessions,
+    /// Comments left through the comment form
+    pub comments: CommentStore,
+    /// Limits how often one address may comment
+    pub comment_limiter: comments::RateLimiter,
+    /// Received webmentions, verified in the background
+    pub webmentions: WebmentionStore,
+    /// Serialises writes through the API, which work on clones of `blog_manager`
+    pub write_lock: Mutex<()>,
 }
 
+impl AppState {
+    pub fn new(blog_manager: BlogManager, site_config: SiteConfig, templates: Templates) -> Self {
+        let (reload_tx, _) = broadcast::channel(16);
+        Self {
+            docs_db: blog_manager.docs_db(),
+            tokens: blog_manager.token_store(),
+            comments: blog_manager.comments(),
+            comment_limiter: comments::RateLimiter::default(),
+            webmentions: blog_manager.webmentions(),
+            blog_manager,
+            site_config,
+            templates,
+            reload_tx,
+            write_lock: Mutex::new(()),
+            admin_sessions: admin::AdminSessions::default(),
+        }
+    }
+}
+
+/// Request body limit for routes that take base64 encoded images
+const UPLOAD_BODY_LIMIT: usize = 32 * 1024 * 1024;
+
 pub fn create_app(state: Arc<AppState>) -> Router {
     Router::new()
         // Web UI routes
         .route("/", get(handlers::index))
         .route("/posts/:slug", get(handlers::post))
+        .route("/page/:page", get(handlers::index_page))
         .route("/archive", get(handlers::archive))
+        .route("/archive/page/:page", get(handlers::archive_page))
         .route("/search", get(handlers::search))
         .route("/tags", get(handlers::tags))
         .route("/tags/:tag", get(handlers::tag_posts))
-        .route("/docs", get(handlers::docs))
+        .route("/tags/:tag/page/:page", get(handlers::tag_posts_page))
+        .route("/tags/:tag/:feed", get(handlers::tag_feed))
+        .route("/categories/:category/:feed", get(handlers::category_feed))
+        .route("/docs", get(docs::docs_index))
+        .route("/docs/:category", get(docs::docs_category))
+        .route("/docs/detail/:slug", get(docs::docs_detail))
+        // Admin editor
+        .route("/admin", get(admin::admin_index))
+        .route("/admin/login", get(admin::login_page).post(admin::login))
+        .route("/admin/logout", post(admin::logout))
+        .route("/admin/posts", get(admin::posts))
+        .route(
+            "/admin/posts/new",
+            get(admin::new_post).post(admin::create_post),
+        )
+        .route(
+            "/admin/posts/:id",
+            get(admin::edit_post).post(admin::update_post),
+        )
+        .route("/admin/posts/:id/delete", post(admin::delete_post))
+        .route("/admin/preview", post(admin::preview))
+        .route(
+            "/admin/upload",
+            post(admin::upload).layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)),
+        )
+        // Micropub
+        .route("/micropub", get(micropub::query).post(micropub::micropub))
+        .route(
+            "/micropub/media",
+            post(micropub::media).layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)),
+        )
+        // ActivityPub
+        .route("/.well-known/webfinger", get(activitypub::webfinger))
+        .route("/activitypub/actor", get(activitypub::actor))
+        .route("/activitypub/outbox", get(activitypub::outbox))
+        .route("/activitypub/followers", get(activitypub::followers))
+        .route("/activitypub/inbox", post(activitypub::inbox))
+        .route("/activitypub/posts/:id", get(activitypub::article))
+        // Comments and webmentions
+        .route("/comments", post(comments::submit))
+        .route("/webmention", post(webmention::receive))
         // API routes
-        .route("/api/posts", get(api::list_posts))
-        .route("/api/posts/:id", get(api::get_post))
+        .route(
+            "/api/posts",
+            get(api::list_posts)
+                .post(api_write::create_post)
+                .layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)),
+        )
+        .route(
+            "/api/posts/:id",
+            get(api::get_post)
+                .put(api_write::update_post)
+                .delete(api_write::delete_post)
+                .layer(DefaultBodyLimit::max(UPLOAD_BODY_LIMIT)),
+        )
+        .route("/api/posts/:id/publish", post(api_write::publish_post))
+        .route(
+            "/api/posts/:id/revisions",
+            get(api_revisions::list_revisions),
+        )
         .route("/api/search", post(api::search_posts))
+        .route(
+            "/api/docs/:id",
+            get(docs::api_get_doc).put(docs::api_update_doc),
+        )
         .route("/api/tags", get(api_tags::list_tags))
         .route("/api/tags/:tag", get(api_tags::get_posts_by_tag))
         // Static files
         .route("/css/style.css", get(handlers::style_css))
         .route("/feed.xml", get(handlers::rss_feed))
+        .route("/atom.xml", get(handlers::atom_feed))
+        .route("/feed.json", get(handlers::json_feed))
+        // Live reload events for `serve --watch`
+        .route("/livereload", get(watch::live_reload))
         // Redirects for backward compatibility
         .route("/archive.html", get(handlers::redirect_archive))
+        // Theme static files
+        .fallback(handlers::theme_asset)
         // CORS for API access
         .layer(CorsLayer::permissive())
         // State
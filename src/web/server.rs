@@ -1,10 +1,14 @@
 use anyhow::Result;
+use axum::{extract::Request, ServiceExt};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tower::Layer;
+use tower_http::normalize_path::NormalizePathLayer;
 use tracing::info;
 
 use super::{create_app, AppState};
 use crate::blog::BlogManager;
+use crate::site::templates::Templates;
 use crate::site::SiteConfig;
 
 pub struct WebServer {
@@ -13,24 +17,40 @@ pub struct WebServer {
 }
 
 impl WebServer {
-    pub fn new(blog_manager: BlogManager, site_config: SiteConfig, port: u16) -> Self {
-        let app_state = Arc::new(AppState {
-            blog_manager,
-            site_config,
-        });
+    pub fn new(blog_manager: BlogManager, site_config: SiteConfig, port: u16) -> Result<Self> {
+        let templates = Templates::load(site_config.theme_templates_dir(), false)?;
+        Ok(Self::with_state(
+            AppState::new(blog_manager, site_config, templates),
+            port,
+        ))
+    }
 
+    pub fn with_state(app_state: AppState, port: u16) -> Self {
         let addr = SocketAddr::from(([127, 0, 0, 1], port));
 
-        Self { app_state, addr }
+        Self {
+            app_state: Arc::new(app_state),
+            addr,
+        }
+    }
+
+    pub fn state(&self) -> Arc<AppState> {
+        self.app_state.clone()
     }
 
     pub async fn run(self) -> Result<()> {
-        let app = create_app(self.app_state);
+        // /archive/ and /archive serve the same page, as on the static site
+        let app = NormalizePathLayer::trim_trailing_slash().layer(create_app(self.app_state));
 
         info!("🚀 Web server starting on http://{}", self.addr);
 
         let listener = tokio::net::TcpListener::bind(&self.addr).await?;
-        axum::serve(listener, app).await?;
+        // Peer addresses let the comment form rate-limit by client
+        axum::serve(
+            listener,
+            ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
+        )
+        .await?;
 
         Ok(())
     }
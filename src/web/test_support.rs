@@ -0,0 +1,31 @@
+//! Helpers for tests that send requests through the app's router
+
+use super::{create_app, AppState};
+use crate::blog::test_support::{blog_in, TempDir};
+use crate::site::templates::Templates;
+use crate::site::SiteConfig;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use std::sync::Arc;
+use tower::Service;
+
+/// Server state for a blog in `dir`, with the built-in templates
+pub async fn state(dir: &TempDir) -> Arc<AppState> {
+    let blog_manager = blog_in(dir).await;
+    let templates = Templates::builtin().unwrap();
+    Arc::new(AppState::new(
+        blog_manager,
+        SiteConfig::default(),
+        templates,
+    ))
+}
+
+/// Route `request` like `kpgb serve` would, returning the status and body
+pub async fn send(state: &Arc<AppState>, request: Request<Body>) -> (StatusCode, String) {
+    let response = create_app(state.clone()).call(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
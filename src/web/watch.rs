@@ -0,0 +1,248 @@
+use anyhow::Result;
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::stream::Stream;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info};
+
+use super::AppState;
+use crate::blog::BlogManager;
+use crate::storage::StorageManager;
+
+/// Server-sent events telling open pages to reload
+pub async fn live_reload(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.reload_tx.subscribe();
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(()) => return Some((Ok(Event::default().event("reload").data("reload")), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Watch the content directory and the template overrides. Changed markdown
+/// is re-imported, changed templates are reloaded, and open pages are told
+/// to refresh.
+pub async fn watch(
+    mut blog_manager: BlogManager,
+    storage_manager: StorageManager,
+    state: Arc<AppState>,
+    content_dir: PathBuf,
+    default_author: String,
+) -> Result<()> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        // Reads show up as access events; importing would trigger itself
+        if let Ok(event) = res {
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                let _ = tx.send(event);
+            }
+        }
+    })?;
+
+    // Event paths are absolute
+    let content_dir = content_dir.canonicalize().unwrap_or(content_dir);
+    let templates_dir = state
+        .templates
+        .dir()
+        .map(|dir| dir.canonicalize().unwrap_or(dir.to_path_buf()));
+
+    if content_dir.is_dir() {
+        let report = crate::import::import_directory(
+            &mut blog_manager,
+            &storage_manager,
+            &content_dir,
+            &default_author,
+        )
+        .await?;
+        println!("📥 Synced {}: {}", content_dir.display(), report.summary());
+
+        watcher.watch(&content_dir, RecursiveMode::Recursive)?;
+        println!("👀 Watching {}", content_dir.display());
+    }
+    if let Some(dir) = templates_dir.as_ref().filter(|dir| dir.is_dir()) {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+        println!("👀 Watching {}", dir.display());
+    }
+
+    while let Some(event) = rx.recv().await {
+        // Editors save in bursts of events; let them settle first
+        let mut paths: BTreeSet<PathBuf> = event.paths.into_iter().collect();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        while let Ok(event) = rx.try_recv() {
+            paths.extend(event.paths);
+        }
+
+        let markdown: Vec<PathBuf> = paths
+            .iter()
+            .filter(|p| p.starts_with(&content_dir))
+            .filter(|p| crate::import::is_markdown_file(p) && p.is_file())
+            .cloned()
+            .collect();
+        let templates_changed = templates_dir
+            .as_ref()
+            .is_some_and(|dir| paths.iter().any(|p| p.starts_with(dir)));
+
+        let mut changed = false;
+        if !markdown.is_empty() {
+            match crate::import::import_files(
+                &mut blog_manager,
+                &storage_manager,
+                markdown,
+                &default_author,
+            )
+            .await
+            {
+                Ok(report) => {
+                    info!("Re-imported content: {}", report.summary());
+                    changed |= !report.created.is_empty() || !report.updated.is_empty();
+                }
+                Err(e) => error!("Failed to re-import content: {}", e),
+            }
+        }
+
+        if templates_changed {
+            match state.templates.reload() {
+                Ok(()) => {
+                    println!("🎨 Templates reloaded");
+                    changed = true;
+                }
+                Err(e) => error!("Failed to reload templates: {}", e),
+            }
+        }
+
+        if changed {
+            // No receivers just means no page is open
+            let _ = state.reload_tx.send(());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::test_support::{blog_in, TempDir};
+    use crate::site::templates::Templates;
+    use crate::site::SiteConfig;
+    use tokio::sync::broadcast::Receiver;
+
+    async fn state(dir: &TempDir) -> Arc<AppState> {
+        let blog_manager = blog_in(dir).await;
+        let templates = Templates::load(Some(dir.path().join("templates")), true).unwrap();
+        Arc::new(AppState::new(
+            blog_manager,
+            SiteConfig::default(),
+            templates,
+        ))
+    }
+
+    /// Keep touching `path` until a reload goes out; the watcher may not be
+    /// registered yet when the first write happens
+    async fn reload_after_writing(rx: &mut Receiver<()>, path: &std::path::Path, content: &str) {
+        for attempt in 0..20 {
+            std::fs::write(path, format!("{}\n<!-- {} -->", content, attempt)).unwrap();
+            if let Ok(result) = tokio::time::timeout(Duration::from_millis(500), rx.recv()).await {
+                result.unwrap();
+                return;
+            }
+        }
+        panic!("no reload after writing {}", path.display());
+    }
+
+    #[tokio::test]
+    async fn test_changes_trigger_reload() {
+        let dir = TempDir::new("watch");
+        let content_dir = dir.path().join("content");
+        std::fs::create_dir_all(&content_dir).unwrap();
+        std::fs::create_dir_all(dir.path().join("templates")).unwrap();
+        let state = state(&dir).await;
+        let mut rx = state.reload_tx.subscribe();
+
+        let watcher = tokio::spawn(watch(
+            state.blog_manager.clone(),
+            state.blog_manager.storage_manager().clone(),
+            state.clone(),
+            content_dir.clone(),
+            "Me".to_string(),
+        ));
+
+        let post = content_dir.join("hello.md");
+        reload_after_writing(
+            &mut rx,
+            &post,
+            "---\ntitle: Hello\npublished: true\n---\nHi",
+        )
+        .await;
+        let posts = state.blog_manager.list_posts(false).await.unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].1.title, "Hello");
+
+        let template = dir.path().join("templates").join("tags.html");
+        reload_after_writing(&mut rx, &template, "<p>Tags</p>").await;
+        let rendered = state
+            .templates
+            .render("tags.html", &tera::Context::new())
+            .unwrap();
+        assert!(rendered.starts_with("<p>Tags</p>"));
+
+        watcher.abort();
+    }
+
+    #[tokio::test]
+    async fn test_live_reload_events() {
+        let dir = TempDir::new("sse");
+        let state = state(&dir).await;
+        let app = axum::Router::new()
+            .route("/livereload", axum::routing::get(live_reload))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut response = reqwest::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("http://{}/livereload", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()["content-type"].to_str().unwrap(),
+            "text/event-stream"
+        );
+
+        // The subscription exists once the response has started
+        state.reload_tx.send(()).unwrap();
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&chunk),
+            "event: reload\ndata: reload\n\n"
+        );
+    }
+}
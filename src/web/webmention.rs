@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Form, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use reqwest::Url;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::web::micropub::slug_from_url;
+use crate::web::AppState;
+use crate::webmention::receive::{self, VerifyOutcome};
+use crate::webmention::WebmentionStore;
+
+#[derive(Deserialize)]
+pub struct WebmentionForm {
+    source: String,
+    target: String,
+}
+
+fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, message.to_string()).into_response()
+}
+
+/// `host[:port]` of a URL, as it would appear in a `Host` header
+fn authority(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Whether `target` points at this site, either at the configured base URL
+/// or at the host the request was sent to
+fn is_own_url(state: &AppState, headers: &HeaderMap, target: &Url) -> bool {
+    let Some(target_authority) = authority(target) else {
+        return false;
+    };
+    let configured = Url::parse(&state.site_config.base_url)
+        .ok()
+        .and_then(|base| authority(&base));
+    let requested = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok());
+
+    configured.as_deref() == Some(target_authority.as_str())
+        || requested == Some(target_authority.as_str())
+}
+
+/// `POST /webmention`: accept a mention and verify its source in the
+/// background
+pub async fn receive(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Form(form): Form<WebmentionForm>,
+) -> Response {
+    let (Ok(source), Ok(target)) = (Url::parse(&form.source), Url::parse(&form.target)) else {
+        return bad_request("source and target must be URLs");
+    };
+    if !matches!(source.scheme(), "http" | "https") || !matches!(target.scheme(), "http" | "https")
+    {
+        return bad_request("source and target must be http(s) URLs");
+    }
+    if source == target {
+        return bad_request("source and target must differ");
+    }
+    if !is_own_url(&state, &headers, &target) {
+        return bad_request("target is not on this site");
+    }
+
+    let post = match slug_from_url(target.path()) {
+        Some(slug) => state.blog_manager.find_post_by_slug(slug).await,
+        None => Ok(None),
+    };
+    let post_id = match post {
+        Ok(Some((_, post))) if post.published => post.id,
+        Ok(_) => return bad_request("target is not a published post"),
+        Err(e) => {
+            warn!("Failed to look up webmention target {}: {}", target, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let id = match state
+        .webmentions
+        .receive(source.as_str(), target.as_str(), &post_id)
+        .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("Failed to store webmention: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    info!("Webmention received: {} -> {}", source, target);
+
+    let store = state.webmentions.clone();
+    let reload_tx = state.reload_tx.clone();
+    tokio::spawn(async move {
+        let verified = verify(&store, &id, source.as_str(), target.as_str()).await;
+        if verified {
+            let _ = reload_tx.send(());
+        }
+    });
+
+    (StatusCode::ACCEPTED, "Webmention accepted for verification").into_response()
+}
+
+/// Fetch the source and record the result. Returns whether the mention was
+/// verified.
+async fn verify(store: &WebmentionStore, id: &str, source: &str, target: &str) -> bool {
+    let outcome = match receive::verification_client() {
+        Ok(client) => receive::verify(&client, source, target).await,
+        Err(e) => Err(e),
+    };
+
+    let result = match outcome {
+        Ok(VerifyOutcome::Verified(details)) => {
+            info!("Webmention verified: {} ({})", source, details.kind);
+            store.mark_verified(id, &details).await.map(|_| true)
+        }
+        Ok(VerifyOutcome::Gone) => {
+            info!("Webmention source gone, deleting: {}", source);
+            store.delete(id).await.map(|_| false)
+        }
+        Ok(VerifyOutcome::Rejected(reason)) => {
+            info!("Webmention rejected: {}: {}", source, reason);
+            store.mark_rejected(id).await.map(|_| false)
+        }
+        Err(e) => {
+            info!("Webmention rejected: {}: {}", source, e);
+            store.mark_rejected(id).await.map(|_| false)
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        warn!("Failed to update webmention {}: {}", id, e);
+        false
+    })
+}
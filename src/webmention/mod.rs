@@ -0,0 +1,334 @@
+pub mod receive;
+pub mod send;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{query, Row, SqlitePool};
+
+/// A webmention another site sent for one of our posts
+#[derive(Debug, Clone, Serialize)]
+pub struct Webmention {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+    pub post_id: String,
+    /// `pending` until the source is fetched, then `verified` or `rejected`
+    pub status: String,
+    /// `reply`, `like`, `repost`, `bookmark` or `mention`
+    pub kind: String,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub content: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub verified_at: Option<DateTime<Utc>>,
+}
+
+/// What verifying a source found out about the mention
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MentionDetails {
+    pub kind: String,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub content: Option<String>,
+}
+
+/// A link from one of our posts to another site
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboundLink {
+    pub post_id: String,
+    pub source: String,
+    pub target: String,
+    /// `pending`, `sent`, `no_endpoint` or `failed`
+    pub status: String,
+    pub endpoint: Option<String>,
+    pub error: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// Received webmentions and outbound links, in the blog database
+#[derive(Clone)]
+pub struct WebmentionStore {
+    pool: SqlitePool,
+}
+
+impl WebmentionStore {
+    /// Tables are created by the migrations run in `Database::new`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a mention to verify. A mention sent again is verified again.
+    pub async fn receive(&self, source: &str, target: &str, post_id: &str) -> Result<String> {
+        let row = query(
+            r#"
+            INSERT INTO webmentions (id, source, target, post_id, status, created_at)
+            VALUES (?1, ?2, ?3, ?4, 'pending', ?5)
+            ON CONFLICT(source, target) DO UPDATE SET post_id = excluded.post_id
+            RETURNING id
+            "#,
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(source)
+        .bind(target)
+        .bind(post_id)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("id"))
+    }
+
+    pub async fn mark_verified(&self, id: &str, details: &MentionDetails) -> Result<()> {
+        query(
+            r#"
+            UPDATE webmentions
+            SET status = 'verified', kind = ?1, author_name = ?2, author_url = ?3,
+                author_photo = ?4, content = ?5, verified_at = ?6
+            WHERE id = ?7
+            "#,
+        )
+        .bind(&details.kind)
+        .bind(&details.author_name)
+        .bind(&details.author_url)
+        .bind(&details.author_photo)
+        .bind(&details.content)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn mark_rejected(&self, id: &str) -> Result<()> {
+        query("UPDATE webmentions SET status = 'rejected', verified_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a mention by ID or ID prefix. Returns whether one was deleted.
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = query("DELETE FROM webmentions WHERE id = ?1 OR id LIKE ?2")
+            .bind(id)
+            .bind(format!("{}%", id))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// All mentions, newest first, optionally only those with `status`
+    pub async fn list(&self, status: Option<&str>) -> Result<Vec<Webmention>> {
+        let rows = query(
+            r#"
+            SELECT * FROM webmentions
+            WHERE ?1 IS NULL OR status = ?1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_mention).collect())
+    }
+
+    /// Verified mentions of a post, oldest first
+    pub async fn verified_for_post(&self, post_id: &str) -> Result<Vec<Webmention>> {
+        let rows = query(
+            r#"
+            SELECT * FROM webmentions
+            WHERE post_id = ?1 AND status = 'verified'
+            ORDER BY created_at
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_mention).collect())
+    }
+
+    /// Remember the outbound links of a post. New links start out pending;
+    /// pending links the post no longer has are dropped.
+    pub async fn record_links(
+        &self,
+        post_id: &str,
+        source: &str,
+        targets: &[String],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for target in targets {
+            query(
+                r#"
+                INSERT INTO webmention_links (post_id, source, target, status, collected_at)
+                VALUES (?1, ?2, ?3, 'pending', ?4)
+                ON CONFLICT(post_id, target) DO UPDATE SET source = excluded.source
+                "#,
+            )
+            .bind(post_id)
+            .bind(source)
+            .bind(target)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let pending: Vec<String> =
+            query("SELECT target FROM webmention_links WHERE post_id = ?1 AND status = 'pending'")
+                .bind(post_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .iter()
+                .map(|row| row.get("target"))
+                .collect();
+        for target in pending.iter().filter(|t| !targets.contains(t)) {
+            query("DELETE FROM webmention_links WHERE post_id = ?1 AND target = ?2")
+                .bind(post_id)
+                .bind(target)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Links waiting to be sent. With `retry`, failed ones and those whose
+    /// site had no endpoint are included.
+    pub async fn pending_links(&self, retry: bool) -> Result<Vec<OutboundLink>> {
+        let rows = query(
+            r#"
+            SELECT * FROM webmention_links
+            WHERE status = 'pending' OR (?1 AND status IN ('failed', 'no_endpoint'))
+            ORDER BY collected_at, target
+            "#,
+        )
+        .bind(retry)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::row_to_link).collect())
+    }
+
+    pub async fn mark_link(
+        &self,
+        link: &OutboundLink,
+        status: &str,
+        endpoint: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        query(
+            r#"
+            UPDATE webmention_links
+            SET status = ?1, endpoint = ?2, error = ?3, sent_at = ?4
+            WHERE post_id = ?5 AND target = ?6
+            "#,
+        )
+        .bind(status)
+        .bind(endpoint)
+        .bind(error)
+        .bind(Utc::now())
+        .bind(&link.post_id)
+        .bind(&link.target)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    fn row_to_mention(row: &SqliteRow) -> Webmention {
+        Webmention {
+            id: row.get("id"),
+            source: row.get("source"),
+            target: row.get("target"),
+            post_id: row.get("post_id"),
+            status: row.get("status"),
+            kind: row.get("kind"),
+            author_name: row.get("author_name"),
+            author_url: row.get("author_url"),
+            author_photo: row.get("author_photo"),
+            content: row.get("content"),
+            created_at: row.get("created_at"),
+            verified_at: row.get("verified_at"),
+        }
+    }
+
+    fn row_to_link(row: &SqliteRow) -> OutboundLink {
+        OutboundLink {
+            post_id: row.get("post_id"),
+            source: row.get("source"),
+            target: row.get("target"),
+            status: row.get("status"),
+            endpoint: row.get("endpoint"),
+            error: row.get("error"),
+            sent_at: row.get("sent_at"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::BlogPost;
+
+    #[tokio::test]
+    async fn test_store() {
+        let path =
+            std::env::temp_dir().join(format!("kpgb-webmention-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let database = Database::new(&url).await.unwrap();
+        let post = BlogPost::new("Hello".to_string(), "Hi".to_string(), "me".to_string());
+        database.insert_post(&post, "storage-1").await.unwrap();
+        let store = WebmentionStore::new(database.pool().clone());
+
+        let id = store
+            .receive("https://a.example/1", "https://me/posts/hello", &post.id)
+            .await
+            .unwrap();
+        let again = store
+            .receive("https://a.example/1", "https://me/posts/hello", &post.id)
+            .await
+            .unwrap();
+        assert_eq!(id, again);
+        assert!(store.verified_for_post(&post.id).await.unwrap().is_empty());
+
+        let details = MentionDetails {
+            kind: "reply".to_string(),
+            author_name: Some("Ann".to_string()),
+            ..Default::default()
+        };
+        store.mark_verified(&id, &details).await.unwrap();
+        let verified = store.verified_for_post(&post.id).await.unwrap();
+        assert_eq!(verified[0].kind, "reply");
+        assert_eq!(verified[0].author_name.as_deref(), Some("Ann"));
+
+        let source = "https://me/posts/hello.html";
+        let links = vec!["https://x/a".to_string(), "https://x/b".to_string()];
+        store.record_links(&post.id, source, &links).await.unwrap();
+        let pending = store.pending_links(false).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        store
+            .mark_link(&pending[0], "no_endpoint", None, None)
+            .await
+            .unwrap();
+
+        // b was removed from the post; a already had its turn
+        store
+            .record_links(&post.id, source, &["https://x/a".to_string()])
+            .await
+            .unwrap();
+        assert!(store.pending_links(false).await.unwrap().is_empty());
+        assert_eq!(store.pending_links(true).await.unwrap().len(), 1);
+
+        assert!(store.delete(&id[..8]).await.unwrap());
+        assert!(store.list(None).await.unwrap().is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+}
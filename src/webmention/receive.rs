@@ -0,0 +1,368 @@
+use anyhow::Result;
+use regex::Regex;
+use reqwest::Url;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use super::MentionDetails;
+
+/// Sources larger than this aren't read
+const MAX_SOURCE_BYTES: usize = 1024 * 1024;
+
+/// Longest excerpt of a mention's content that is kept
+const MAX_CONTENT_CHARS: usize = 280;
+
+/// What fetching the source of a webmention showed
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyOutcome {
+    Verified(MentionDetails),
+    Rejected(String),
+    /// The source answered 410 Gone: the mention was deleted
+    Gone,
+}
+
+/// Whether a URL may be fetched on behalf of a sender: http(s) and not a
+/// loopback, private or link-local address. Host names aren't resolved, so
+/// this only keeps out the obvious internal targets.
+pub fn is_public_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+        Err(_) => {
+            let host = host.to_ascii_lowercase();
+            !(host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"))
+        }
+    }
+}
+
+/// HTTP client for fetching sources. Redirects are followed only to public URLs.
+pub fn verification_client() -> Result<reqwest::Client> {
+    let redirect = reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= 5 || !is_public_url(attempt.url()) {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    });
+
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .redirect(redirect)
+        .user_agent(concat!("kpgb/", env!("CARGO_PKG_VERSION"), " (webmention)"))
+        .build()?)
+}
+
+/// Fetch `source` and check that it links to `target`
+pub async fn verify(client: &reqwest::Client, source: &str, target: &str) -> Result<VerifyOutcome> {
+    let source_url = Url::parse(source)?;
+    if !is_public_url(&source_url) {
+        return Ok(VerifyOutcome::Rejected(
+            "Source is not a public URL".to_string(),
+        ));
+    }
+
+    let response = client.get(source_url.clone()).send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::GONE {
+        return Ok(VerifyOutcome::Gone);
+    }
+    if !status.is_success() {
+        return Ok(VerifyOutcome::Rejected(format!(
+            "Source returned {}",
+            status
+        )));
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > MAX_SOURCE_BYTES)
+    {
+        return Ok(VerifyOutcome::Rejected("Source is too large".to_string()));
+    }
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(true, |value| value.contains("html"));
+    let body = response.bytes().await?;
+    if body.len() > MAX_SOURCE_BYTES {
+        return Ok(VerifyOutcome::Rejected("Source is too large".to_string()));
+    }
+    let body = String::from_utf8_lossy(&body);
+
+    if is_html {
+        if !links_to(&body, &source_url, target) {
+            return Ok(VerifyOutcome::Rejected(
+                "Source doesn't link to target".to_string(),
+            ));
+        }
+        Ok(VerifyOutcome::Verified(parse_mention(
+            &body,
+            &source_url,
+            target,
+        )))
+    } else if body.contains(target) {
+        Ok(VerifyOutcome::Verified(MentionDetails {
+            kind: "mention".to_string(),
+            ..Default::default()
+        }))
+    } else {
+        Ok(VerifyOutcome::Rejected(
+            "Source doesn't mention target".to_string(),
+        ))
+    }
+}
+
+fn normalize(url: &str) -> &str {
+    let url = url.split('#').next().unwrap_or(url);
+    url.trim_end_matches('/')
+}
+
+fn same_url(href: &str, base: &Url, target: &str) -> bool {
+    base.join(href)
+        .is_ok_and(|url| normalize(url.as_str()) == normalize(target))
+}
+
+/// Whether any `href` of the page points at `target`
+pub fn links_to(html: &str, base: &Url, target: &str) -> bool {
+    let href_re = Regex::new(r#"(?i)\bhref\s*=\s*["']([^"']+)["']"#).unwrap();
+    let found = href_re
+        .captures_iter(html)
+        .any(|cap| same_url(&decode_entities(&cap[1]), base, target));
+    found
+}
+
+/// Read the kind of mention, its author and content from the source's
+/// microformats. This looks for the common class names rather than
+/// parsing microformats2 in full.
+pub fn parse_mention(html: &str, base: &Url, target: &str) -> MentionDetails {
+    let anchor_re = Regex::new(r#"(?is)<a\s[^>]*>"#).unwrap();
+    let kind = anchor_re
+        .find_iter(html)
+        .map(|m| m.as_str())
+        .filter(|tag| attr(tag, "href").is_some_and(|href| same_url(&href, base, target)))
+        .find_map(|tag| {
+            let classes = attr(tag, "class").unwrap_or_default();
+            let classes: Vec<&str> = classes.split_whitespace().collect();
+            [
+                ("u-in-reply-to", "reply"),
+                ("u-like-of", "like"),
+                ("u-repost-of", "repost"),
+                ("u-bookmark-of", "bookmark"),
+            ]
+            .into_iter()
+            .find(|(class, _)| classes.contains(class))
+            .map(|(_, kind)| kind)
+        })
+        .unwrap_or("mention");
+
+    let mut details = MentionDetails {
+        kind: kind.to_string(),
+        ..Default::default()
+    };
+
+    if let Some((tag, block)) = element_with_class(html, "p-author") {
+        details.author_name = class_text(block, "p-name").or_else(|| text_of(block));
+        details.author_url = attr(tag, "href")
+            .or_else(|| class_attr(block, "u-url", "href"))
+            .and_then(|href| base.join(&href).ok())
+            .map(String::from);
+        details.author_photo = class_attr(block, "u-photo", "src")
+            .or_else(|| first_attr(block, "img", "src"))
+            .and_then(|src| base.join(&src).ok())
+            .map(String::from);
+    }
+    if details.author_name.is_none() {
+        details.author_name = meta_content(html, "author");
+    }
+    if details.author_url.is_none() {
+        details.author_url = Some(base.origin().ascii_serialization());
+    }
+
+    details.content = element_with_class(html, "e-content")
+        .or_else(|| element_with_class(html, "p-content"))
+        .or_else(|| element_with_class(html, "p-summary"))
+        .and_then(|(_, block)| text_of(block))
+        .or_else(|| meta_content(html, "description"))
+        .or_else(|| {
+            let title_re = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+            title_re.captures(html).and_then(|cap| text_of(&cap[1]))
+        })
+        .map(|text| truncate_chars(&text, MAX_CONTENT_CHARS));
+
+    details
+}
+
+/// Value of an attribute in an opening tag
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(
+        r#"(?is)\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#,
+        regex::escape(name)
+    ))
+    .unwrap();
+    let cap = re.captures(tag)?;
+    let value = cap.get(1).or_else(|| cap.get(2))?.as_str();
+    Some(decode_entities(value))
+}
+
+/// The opening tag of the first element with `class`, and the markup from
+/// there to the first closing tag of the same name
+fn element_with_class<'a>(html: &'a str, class: &str) -> Option<(&'a str, &'a str)> {
+    let open_re = Regex::new(&format!(
+        r#"(?is)<(\w+)\s[^>]*class\s*=\s*["'][^"']*\b{}\b[^"']*["'][^>]*>"#,
+        regex::escape(class)
+    ))
+    .unwrap();
+    let cap = open_re.captures(html)?;
+    let open = cap.get(0)?;
+    let name = &cap[1];
+
+    let rest = &html[open.end()..];
+    let close = rest
+        .to_ascii_lowercase()
+        .find(&format!("</{}", name.to_ascii_lowercase()))
+        .unwrap_or(rest.len());
+    Some((open.as_str(), &html[open.start()..open.end() + close]))
+}
+
+fn class_text(html: &str, class: &str) -> Option<String> {
+    element_with_class(html, class).and_then(|(_, block)| text_of(block))
+}
+
+fn class_attr(html: &str, class: &str, name: &str) -> Option<String> {
+    element_with_class(html, class).and_then(|(tag, _)| attr(tag, name))
+}
+
+fn first_attr(html: &str, tag_name: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(r#"(?is)<{}\s[^>]*>"#, tag_name)).unwrap();
+    re.find(html).and_then(|tag| attr(tag.as_str(), name))
+}
+
+fn meta_content(html: &str, name: &str) -> Option<String> {
+    let meta_re = Regex::new(r#"(?is)<meta\s[^>]*>"#).unwrap();
+    let content = meta_re
+        .find_iter(html)
+        .map(|m| m.as_str())
+        .find(|tag| attr(tag, "name").is_some_and(|n| n.eq_ignore_ascii_case(name)))
+        .and_then(|tag| attr(tag, "content"))
+        .filter(|content| !content.trim().is_empty());
+    content
+}
+
+/// Markup as plain text with collapsed whitespace, `None` when empty
+pub fn text_of(html: &str) -> Option<String> {
+    let tag_re = Regex::new(r"(?s)<[^>]*>").unwrap();
+    let text = decode_entities(&tag_re.replace_all(html, " "));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "https://me.example/posts/hello";
+
+    fn base() -> Url {
+        Url::parse("https://them.example/notes/1").unwrap()
+    }
+
+    #[test]
+    fn test_is_public_url() {
+        let public = |url: &str| is_public_url(&Url::parse(url).unwrap());
+        assert!(public("https://example.com/a"));
+        assert!(public("http://8.8.8.8/"));
+        assert!(!public("http://localhost:9000/"));
+        assert!(!public("http://127.0.0.1/"));
+        assert!(!public("http://10.1.2.3/"));
+        assert!(!public("http://192.168.0.1/"));
+        assert!(!public("http://169.254.169.254/latest"));
+        assert!(!public("http://[::1]/"));
+        assert!(!public("file:///etc/passwd"));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let html = r#"<html><head><title>Note</title></head><body>
+            <article class="h-entry">
+              <a class="p-author h-card" href="/about"><img class="u-photo" src="/me.jpg"> Ann &amp; Co</a>
+              <a class="u-in-reply-to" href="https://me.example/posts/hello/">in reply to</a>
+              <div class="e-content"><p>Great   post, <b>thanks</b>!</p></div>
+            </article></body></html>"#;
+        assert!(links_to(html, &base(), TARGET));
+
+        let details = parse_mention(html, &base(), TARGET);
+        assert_eq!(details.kind, "reply");
+        assert_eq!(details.author_name.as_deref(), Some("Ann & Co"));
+        assert_eq!(
+            details.author_url.as_deref(),
+            Some("https://them.example/about")
+        );
+        assert_eq!(
+            details.author_photo.as_deref(),
+            Some("https://them.example/me.jpg")
+        );
+        assert_eq!(details.content.as_deref(), Some("Great post, thanks !"));
+    }
+
+    #[test]
+    fn test_parse_plain_mention() {
+        let html = r#"<html><head><title>My   links</title>
+            <meta name="author" content="Bob"></head>
+            <body><p>Read <a href="../../posts/hello">this</a></p></body></html>"#;
+        let base = Url::parse("https://me.example/a/b/c").unwrap();
+        assert!(links_to(html, &base, TARGET));
+
+        let details = parse_mention(html, &base, TARGET);
+        assert_eq!(details.kind, "mention");
+        assert_eq!(details.author_name.as_deref(), Some("Bob"));
+        assert_eq!(details.author_url.as_deref(), Some("https://me.example"));
+        assert_eq!(details.content.as_deref(), Some("My links"));
+
+        assert!(!links_to(
+            "<a href=\"https://me.example/posts/other\">x</a>",
+            &base,
+            TARGET
+        ));
+    }
+}
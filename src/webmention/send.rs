@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+use reqwest::header::LINK;
+use reqwest::Url;
+
+/// Absolute http(s) links of a markdown post, in order and without
+/// duplicates. Links to `own_base_url` are left out.
+pub fn outbound_links(content: &str, own_base_url: &str) -> Vec<String> {
+    let href_re = Regex::new(r#"href\s*=\s*["']([^"']+)["']"#).unwrap();
+    let mut links: Vec<String> = Vec::new();
+
+    for event in Parser::new(content) {
+        let found: Vec<String> = match event {
+            Event::Start(Tag::Link { dest_url, .. }) => vec![dest_url.to_string()],
+            Event::Html(html) | Event::InlineHtml(html) => href_re
+                .captures_iter(&html)
+                .map(|cap| cap[1].to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        for link in found {
+            let is_external = (link.starts_with("http://") || link.starts_with("https://"))
+                && (own_base_url.is_empty() || !link.starts_with(own_base_url));
+            if is_external && !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+
+    links
+}
+
+/// The webmention endpoint in an HTTP `Link` header, if any
+pub fn endpoint_from_link_header(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.trim().split_once(';')?;
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let is_webmention = params.split(';').any(|param| {
+            let Some((name, value)) = param.trim().split_once('=') else {
+                return false;
+            };
+            name.trim() == "rel"
+                && value
+                    .trim()
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|rel| rel == "webmention")
+        });
+        is_webmention.then_some(url)
+    })
+}
+
+/// The first `<link>` or `<a>` with `rel="webmention"` in a page
+pub fn endpoint_from_html(html: &str) -> Option<String> {
+    let tag_re = Regex::new(r#"(?is)<(?:link|a)\s[^>]*>"#).unwrap();
+    let rel_re = Regex::new(r#"(?i)\brel\s*=\s*["']([^"']*)["']"#).unwrap();
+    let href_re = Regex::new(r#"(?i)\bhref\s*=\s*["']([^"']*)["']"#).unwrap();
+
+    let endpoint = tag_re.find_iter(html).find_map(|tag| {
+        let tag = tag.as_str();
+        let rel = rel_re.captures(tag)?;
+        if !rel[1].split_whitespace().any(|r| r == "webmention") {
+            return None;
+        }
+        Some(href_re.captures(tag)?[1].to_string())
+    });
+    endpoint
+}
+
+/// Find the webmention endpoint of `target`, looking at the `Link` header
+/// first and the HTML second. Relative endpoints are resolved against the
+/// final URL of the page.
+pub async fn discover_endpoint(client: &reqwest::Client, target: &str) -> Result<Option<Url>> {
+    let response = client.get(target).send().await?.error_for_status()?;
+    let page_url = response.url().clone();
+
+    let from_header = response
+        .headers()
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| endpoint_from_link_header(value).map(str::to_string));
+
+    let endpoint = match from_header {
+        Some(endpoint) => Some(endpoint),
+        None => {
+            let is_html = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("html"));
+            if is_html {
+                endpoint_from_html(&response.text().await?)
+            } else {
+                None
+            }
+        }
+    };
+
+    // An empty href is the page itself
+    endpoint
+        .map(|endpoint| page_url.join(&endpoint).map_err(|e| anyhow!(e)))
+        .transpose()
+}
+
+/// Notify `endpoint` that `source` links to `target`
+pub async fn send_webmention(
+    client: &reqwest::Client,
+    endpoint: &Url,
+    source: &str,
+    target: &str,
+) -> Result<()> {
+    client
+        .post(endpoint.clone())
+        .form(&[("source", source), ("target", target)])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbound_links() {
+        let content = "See [a](https://a.example/post) and [b](https://b.example/).\n\n\
+            [self](https://me.example/posts/x.html) [rel](/posts/y.html) [a again](https://a.example/post)\n\n\
+            <p>Also <a href=\"https://c.example/\">c</a></p>\n";
+        assert_eq!(
+            outbound_links(content, "https://me.example"),
+            vec![
+                "https://a.example/post",
+                "https://b.example/",
+                "https://c.example/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_endpoint_from_link_header() {
+        assert_eq!(
+            endpoint_from_link_header(
+                r#"<https://x.example/style.css>; rel="stylesheet", <https://x.example/wm>; rel="other webmention""#
+            ),
+            Some("https://x.example/wm")
+        );
+        assert_eq!(
+            endpoint_from_link_header("</wm>; rel=webmention"),
+            Some("/wm")
+        );
+        assert_eq!(
+            endpoint_from_link_header("</wm>; rel=\"webmention-not\""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_endpoint_from_html() {
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="/style.css">
+            <link href="/webmention?x=1" rel="webmention">
+            </head><body><a rel="webmention" href="/other">x</a></body></html>"#;
+        assert_eq!(endpoint_from_html(html).as_deref(), Some("/webmention?x=1"));
+        assert_eq!(endpoint_from_html("<a href=\"/x\">x</a>"), None);
+
+        let page = Url::parse("https://x.example/posts/1").unwrap();
+        assert_eq!(
+            page.join("/webmention?x=1").unwrap().as_str(),
+            "https://x.example/webmention?x=1"
+        );
+        assert_eq!(page.join("").unwrap(), page);
+    }
+}